@@ -0,0 +1,108 @@
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use toslib::ipf::{decrypt_in_place, encrypt_in_place};
+
+const PASSWORD: [u8; 20] = [
+    0x6F, 0x66, 0x4F, 0x31, 0x61, 0x30, 0x75, 0x65, 0x58, 0x41, 0x3F, 0x20, 0x5B, 0xFF, 0x73, 0x20,
+    0x68, 0x20, 0x25, 0x3F,
+];
+
+/// The previous byte-at-a-time routine, kept as the baseline.
+fn decrypt_reference(buffer: &mut [u8]) {
+    fn crc32_byte(table: &[u32; 256], crc: u32, b: u8) -> u32 {
+        table[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    }
+    fn keys_update(table: &[u32; 256], keys: &mut [u32; 3], b: u8) {
+        keys[0] = crc32_byte(table, keys[0], b);
+        keys[1] = 0x8088405u32
+            .wrapping_mul((keys[0] as u8 as u32).wrapping_add(keys[1]))
+            .wrapping_add(1);
+        keys[2] = crc32_byte(table, keys[2], (keys[1] >> 24) as u8);
+    }
+
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+        *entry = crc;
+    }
+
+    let mut keys = [0x12345678, 0x23456789, 0x34567890];
+    for &byte in PASSWORD.iter() {
+        keys_update(&table, &mut keys, byte);
+    }
+    let buffer_size = (buffer.len() - 1) / 2 + 1;
+    for i in 0..buffer_size {
+        let v = (keys[2] & 0xFFFD) | 2;
+        let idx = i * 2;
+        if idx < buffer.len() {
+            buffer[idx] ^= ((v.wrapping_mul(v ^ 1)) >> 8) as u8;
+            keys_update(&table, &mut keys, buffer[idx]);
+        }
+    }
+}
+
+fn cipher(c: &mut Criterion) {
+    let mut encrypted: Vec<u8> = (0..4 << 20).map(|i| (i * 31 % 251) as u8).collect();
+    encrypt_in_place(&mut encrypted);
+
+    let mut check = encrypted.clone();
+    let mut expected = encrypted.clone();
+    decrypt_in_place(&mut check);
+    decrypt_reference(&mut expected);
+    assert_eq!(check, expected);
+
+    let mut group = c.benchmark_group("decrypt 4 MiB");
+    group.throughput(Throughput::Bytes(encrypted.len() as u64));
+    group.bench_function("reference", |b| {
+        b.iter(|| {
+            let mut data = encrypted.clone();
+            decrypt_reference(black_box(&mut data));
+            data
+        })
+    });
+    group.bench_function("decrypt_in_place", |b| {
+        b.iter(|| {
+            let mut data = encrypted.clone();
+            decrypt_in_place(black_box(&mut data));
+            data
+        })
+    });
+    group.finish();
+
+    // Most archive entries are small, so the per-entry key setup matters as much
+    let small: Vec<Vec<u8>> = encrypted
+        .chunks(64)
+        .take(4096)
+        .map(<[u8]>::to_vec)
+        .collect();
+    let mut group = c.benchmark_group("decrypt 4096 x 64 B");
+    group.throughput(Throughput::Bytes(64 * 4096));
+    group.bench_function("reference", |b| {
+        b.iter(|| {
+            let mut entries = small.clone();
+            for entry in &mut entries {
+                decrypt_reference(black_box(entry));
+            }
+            entries
+        })
+    });
+    group.bench_function("decrypt_in_place", |b| {
+        b.iter(|| {
+            let mut entries = small.clone();
+            for entry in &mut entries {
+                decrypt_in_place(black_box(entry));
+            }
+            entries
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cipher);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+/*
+ * C API of toslib. Link against libtoslib.a built with `cargo build --release`,
+ * passing -Wl,--gc-sections so the unused Python bindings are dropped, e.g.
+ *
+ *   cc tool.c -Iinclude target/release/libtoslib.a -Wl,--gc-sections -lm -ldl -lpthread
+ *
+ * libtoslib.so also works but needs libpython to resolve the Python bindings.
+ *
+ * Every function returning TosStatus reports failures through the status and
+ * tos_last_error(). Buffers filled in by the library belong to the caller and
+ * must be released with tos_buffer_free(). An archive handle must only be used
+ * from one thread at a time.
+ */
+#ifndef TOSLIB_H
+#define TOSLIB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum TosStatus {
+    TOS_OK = 0,
+    /* A null pointer, an index out of range or a path that is not UTF-8 */
+    TOS_INVALID_ARGUMENT = 1,
+    TOS_IO = 2,
+    /* The data is malformed or truncated */
+    TOS_FORMAT = 3,
+    /* No entry with the requested path */
+    TOS_NOT_FOUND = 4,
+    /* A bug in the library; the handle should not be used again */
+    TOS_PANIC = 5,
+} TosStatus;
+
+/* Bytes owned by the caller. data is followed by a NUL byte that len does not
+ * count, so entry names can be used as C strings directly. */
+typedef struct TosBuffer {
+    uint8_t *data;
+    size_t len;
+} TosBuffer;
+
+typedef struct TosIpf TosIpf;
+
+/* Message of the last failed call on this thread. Valid until the next failing
+ * call on the same thread; never NULL. */
+const char *tos_last_error(void);
+
+/* Opens the archive at path (UTF-8) and stores a handle in *out. */
+TosStatus tos_ipf_open(const char *path, TosIpf **out);
+
+/* Releases a handle from tos_ipf_open. NULL is ignored. */
+void tos_ipf_close(TosIpf *ipf);
+
+/* Number of entries in the archive, 0 for NULL. */
+size_t tos_ipf_entry_count(const TosIpf *ipf);
+
+/* Stores the path of entry index in *out. */
+TosStatus tos_ipf_entry_name(const TosIpf *ipf, size_t index, TosBuffer *out);
+
+/* Extracts entry index into *out. */
+TosStatus tos_ipf_read_index(TosIpf *ipf, size_t index, TosBuffer *out);
+
+/* Extracts the entry stored under path (e.g. "ies/item.ies") into *out. */
+TosStatus tos_ipf_read(TosIpf *ipf, const char *path, TosBuffer *out);
+
+/* Converts the XAC actor in data[0..len) to binary glTF (.glb) with the default
+ * options and stores it in *out. */
+TosStatus tos_xac_to_gltf(const uint8_t *data, size_t len, TosBuffer *out);
+
+/* Releases a buffer filled in by the library and resets it to empty.
+ * NULL and already freed (empty) buffers are ignored. */
+void tos_buffer_free(TosBuffer *buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TOSLIB_H */
@@ -0,0 +1,16 @@
+// Type declarations for the toslib N-API addon (src/node.rs).
+
+/** Directory names of every entry in an IPF archive. */
+export function ipfEntries(archive: string): string[];
+
+/** Extracted contents of the entry stored under `entry` (e.g. `ies/item.ies`). */
+export function ipfRead(archive: string, entry: string): Buffer;
+
+/** An IES table as JSON: an array of rows, each an object keyed by column name. */
+export function iesToJson(data: Buffer): string;
+
+/** An XAC model as binary glTF 2.0 (`.glb`) with its skeleton and skinning. */
+export function xacToGltf(data: Buffer): Buffer;
+
+/** The meshes of an XAC model as JSON, one object per mesh with its submeshes. */
+export function xacMeshesToJson(data: Buffer): string;
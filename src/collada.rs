@@ -0,0 +1,615 @@
+//! COLLADA 1.4.1 writing for `XACFile::export_collada`, from the scene the glTF export
+//! gathers: the skeleton as joint nodes, a geometry per mesh with a triangle list per
+//! submesh, and a skin controller for every skinned mesh.
+use crate::error::{Error, Result};
+use crate::gltf::{self, GltfMesh, GltfScene};
+use crate::ies::push_xml_escaped;
+use std::fmt::{self, Write};
+
+/// The scene as a `.dae` document, in the units of the scene, declared as centimeters.
+pub(crate) fn write_dae(scene: &GltfScene) -> Result<String> {
+    for mesh in &scene.meshes {
+        for primitive in &mesh.primitives {
+            let count = primitive.submesh.positions.len();
+            if let Some(&index) = primitive
+                .submesh
+                .indices
+                .iter()
+                .find(|&&index| index as usize >= count)
+            {
+                return Err(Error::Xac(format!(
+                    "Index {} beyond the {} vertices of its submesh",
+                    index, count
+                )));
+            }
+        }
+    }
+    let mut dae = String::new();
+    write_document(&mut dae, scene).map_err(|err| Error::Xac(err.to_string()))?;
+    Ok(dae)
+}
+
+fn write_document(dae: &mut String, scene: &GltfScene) -> fmt::Result {
+    writeln!(dae, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(
+        dae,
+        r#"<COLLADA xmlns="http://www.collada.org/2005/11/COLLADASchema" version="1.4.1">"#
+    )?;
+    writeln!(
+        dae,
+        r#"  <asset><contributor><authoring_tool>toslib</authoring_tool></contributor><unit name="centimeter" meter="0.01"/><up_axis>Y_UP</up_axis></asset>"#
+    )?;
+    write_materials(dae, scene)?;
+
+    writeln!(dae, "  <library_geometries>")?;
+    for (index, mesh) in scene.meshes.iter().enumerate() {
+        write_geometry(dae, index, mesh)?;
+    }
+    writeln!(dae, "  </library_geometries>")?;
+
+    let parents = gltf::acyclic_parents(&scene.nodes);
+    let skinned: Vec<bool> = scene
+        .meshes
+        .iter()
+        .map(|mesh| !scene.nodes.is_empty() && is_skinned(mesh))
+        .collect();
+    if skinned.contains(&true) {
+        let inverse_binds: Vec<[f32; 16]> = gltf::global_transforms(&scene.nodes, &parents)
+            .iter()
+            .map(gltf::inverse_affine)
+            .collect();
+        writeln!(dae, "  <library_controllers>")?;
+        for (index, mesh) in scene.meshes.iter().enumerate() {
+            if skinned[index] {
+                write_skin(dae, index, mesh, &inverse_binds)?;
+            }
+        }
+        writeln!(dae, "  </library_controllers>")?;
+    }
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); scene.nodes.len()];
+    let mut roots = Vec::new();
+    for (index, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(index),
+            None => roots.push(index),
+        }
+    }
+    // Static meshes hang from their node, skinned ones sit at the root
+    let mut attached: Vec<Vec<usize>> = vec![Vec::new(); scene.nodes.len()];
+    let mut root_meshes = Vec::new();
+    for (index, mesh) in scene.meshes.iter().enumerate() {
+        match mesh
+            .node
+            .filter(|&node| node < scene.nodes.len() && !skinned[index])
+        {
+            Some(node) => attached[node].push(index),
+            None => root_meshes.push(index),
+        }
+    }
+
+    writeln!(dae, "  <library_visual_scenes>")?;
+    writeln!(dae, r#"    <visual_scene id="scene" name="scene">"#)?;
+    let tree = NodeTree {
+        scene,
+        children: &children,
+        attached: &attached,
+    };
+    for &root in &roots {
+        tree.write_node(dae, root, 3)?;
+    }
+    for &index in &root_meshes {
+        let mesh = &scene.meshes[index];
+        write!(dae, r#"      <node id="mesh{}-node" name=""#, index)?;
+        push_xml_escaped(dae, &mesh.name);
+        writeln!(dae, r#"" type="NODE">"#)?;
+        if skinned[index] {
+            write!(
+                dae,
+                r##"        <instance_controller url="#mesh{}-skin">"##,
+                index
+            )?;
+            for root in &roots {
+                write!(dae, "<skeleton>#node{}</skeleton>", root)?;
+            }
+            write_bind_material(dae, mesh)?;
+            writeln!(dae, "</instance_controller>")?;
+        } else {
+            write!(dae, r##"        <instance_geometry url="#mesh{}">"##, index)?;
+            write_bind_material(dae, mesh)?;
+            writeln!(dae, "</instance_geometry>")?;
+        }
+        writeln!(dae, "      </node>")?;
+    }
+    writeln!(dae, "    </visual_scene>")?;
+    writeln!(dae, "  </library_visual_scenes>")?;
+    writeln!(
+        dae,
+        r##"  <scene><instance_visual_scene url="#scene"/></scene>"##
+    )?;
+    writeln!(dae, "</COLLADA>")
+}
+
+/// Images, Lambert effects and the materials over them.
+fn write_materials(dae: &mut String, scene: &GltfScene) -> fmt::Result {
+    if scene
+        .materials
+        .iter()
+        .any(|material| material.texture.is_some())
+    {
+        writeln!(dae, "  <library_images>")?;
+        for (index, material) in scene.materials.iter().enumerate() {
+            if let Some(uri) = &material.texture {
+                write!(dae, r#"    <image id="image{}"><init_from>"#, index)?;
+                push_xml_escaped(dae, uri);
+                writeln!(dae, "</init_from></image>")?;
+            }
+        }
+        writeln!(dae, "  </library_images>")?;
+    }
+
+    writeln!(dae, "  <library_effects>")?;
+    for (index, material) in scene.materials.iter().enumerate() {
+        writeln!(dae, r#"    <effect id="effect{}"><profile_COMMON>"#, index)?;
+        if material.texture.is_some() {
+            writeln!(
+                dae,
+                r#"      <newparam sid="image{0}-surface"><surface type="2D"><init_from>image{0}</init_from></surface></newparam>"#,
+                index
+            )?;
+            writeln!(
+                dae,
+                r#"      <newparam sid="image{0}-sampler"><sampler2D><source>image{0}-surface</source></sampler2D></newparam>"#,
+                index
+            )?;
+        }
+        write!(dae, r#"      <technique sid="common"><lambert><diffuse>"#)?;
+        if material.texture.is_some() {
+            write!(
+                dae,
+                r#"<texture texture="image{}-sampler" texcoord="UVMap"/>"#,
+                index
+            )?;
+        } else {
+            let [r, g, b, a] = material.base_color;
+            write!(dae, "<color>{} {} {} {}</color>", r, g, b, a)?;
+        }
+        write!(dae, "</diffuse>")?;
+        if material.base_color[3] < 1.0 {
+            write!(
+                dae,
+                "<transparency><float>{}</float></transparency>",
+                material.base_color[3]
+            )?;
+        }
+        writeln!(dae, "</lambert></technique>")?;
+        if material.double_sided {
+            writeln!(
+                dae,
+                r#"      <extra><technique profile="GOOGLEEARTH"><double_sided>1</double_sided></technique></extra>"#
+            )?;
+        }
+        writeln!(dae, "    </profile_COMMON></effect>")?;
+    }
+    writeln!(dae, "  </library_effects>")?;
+
+    writeln!(dae, "  <library_materials>")?;
+    for (index, material) in scene.materials.iter().enumerate() {
+        write!(dae, r#"    <material id="material{}" name=""#, index)?;
+        push_xml_escaped(dae, &material.name);
+        writeln!(
+            dae,
+            r##""><instance_effect url="#effect{}"/></material>"##,
+            index
+        )?;
+    }
+    writeln!(dae, "  </library_materials>")
+}
+
+/// One geometry over the vertices of every submesh, with a triangle list per submesh.
+/// Normals and UVs are only written when every submesh has them.
+fn write_geometry(dae: &mut String, index: usize, mesh: &GltfMesh) -> fmt::Result {
+    let submeshes = || mesh.primitives.iter().map(|primitive| &primitive.submesh);
+    let has_normals = submeshes().all(|submesh| submesh.normals.len() == submesh.positions.len());
+    let has_uvs = submeshes().all(|submesh| submesh.uvcoords.len() == submesh.positions.len());
+
+    write!(dae, r#"    <geometry id="mesh{}" name=""#, index)?;
+    push_xml_escaped(dae, &mesh.name);
+    writeln!(dae, r#""><mesh>"#)?;
+    let id = format!("mesh{}", index);
+    write_source(
+        dae,
+        &format!("{}-positions", id),
+        submeshes().flat_map(|submesh| submesh.positions.iter().flatten().copied()),
+        &["X", "Y", "Z"],
+    )?;
+    if has_normals {
+        write_source(
+            dae,
+            &format!("{}-normals", id),
+            submeshes().flat_map(|submesh| submesh.normals.iter().flatten().copied()),
+            &["X", "Y", "Z"],
+        )?;
+    }
+    if has_uvs {
+        // The UV origin of the actor is the top left, COLLADA's the bottom left as in OBJ
+        write_source(
+            dae,
+            &format!("{}-uvs", id),
+            submeshes()
+                .flat_map(|submesh| submesh.uvcoords.iter().flat_map(|[u, v]| [*u, 1.0 - v])),
+            &["S", "T"],
+        )?;
+    }
+    writeln!(
+        dae,
+        r##"      <vertices id="{0}-vertices"><input semantic="POSITION" source="#{0}-positions"/></vertices>"##,
+        id
+    )?;
+
+    let mut base = 0;
+    for primitive in &mesh.primitives {
+        let submesh = &primitive.submesh;
+        write!(
+            dae,
+            r##"      <triangles material="material{}" count="{}"><input semantic="VERTEX" source="#{}-vertices" offset="0"/>"##,
+            primitive.material,
+            submesh.indices.len() / 3,
+            id
+        )?;
+        if has_normals {
+            write!(
+                dae,
+                r##"<input semantic="NORMAL" source="#{}-normals" offset="0"/>"##,
+                id
+            )?;
+        }
+        if has_uvs {
+            write!(
+                dae,
+                r##"<input semantic="TEXCOORD" source="#{}-uvs" offset="0" set="0"/>"##,
+                id
+            )?;
+        }
+        write!(dae, "<p>")?;
+        // The mirrored geometry is inside out, so the winding is reversed like the
+        // OBJ and glTF exports do
+        let corners = submesh
+            .indices
+            .chunks_exact(3)
+            .flat_map(|face| [face[2], face[1], face[0]]);
+        for (corner, index) in corners.enumerate() {
+            if corner > 0 {
+                dae.push(' ');
+            }
+            write!(dae, "{}", base + index as usize)?;
+        }
+        writeln!(dae, "</p></triangles>")?;
+        base += submesh.positions.len();
+    }
+    writeln!(dae, "    </mesh></geometry>")
+}
+
+/// A skin binding the mesh to every node, each vertex to its non-zero weights.
+fn write_skin(
+    dae: &mut String,
+    index: usize,
+    mesh: &GltfMesh,
+    inverse_binds: &[[f32; 16]],
+) -> fmt::Result {
+    let id = format!("mesh{}-skin", index);
+    writeln!(
+        dae,
+        r##"    <controller id="{}"><skin source="#mesh{}">"##,
+        id, index
+    )?;
+    write!(dae, "      <bind_shape_matrix>")?;
+    push_matrix(dae, &gltf::IDENTITY)?;
+    writeln!(dae, "</bind_shape_matrix>")?;
+
+    write!(
+        dae,
+        r#"      <source id="{0}-joints"><Name_array id="{0}-joints-array" count="{1}">"#,
+        id,
+        inverse_binds.len()
+    )?;
+    for joint in 0..inverse_binds.len() {
+        if joint > 0 {
+            dae.push(' ');
+        }
+        write!(dae, "joint{}", joint)?;
+    }
+    writeln!(
+        dae,
+        r##"</Name_array><technique_common><accessor source="#{}-joints-array" count="{}" stride="1"><param name="JOINT" type="name"/></accessor></technique_common></source>"##,
+        id,
+        inverse_binds.len()
+    )?;
+
+    write!(
+        dae,
+        r#"      <source id="{0}-bind-poses"><float_array id="{0}-bind-poses-array" count="{1}">"#,
+        id,
+        inverse_binds.len() * 16
+    )?;
+    for (joint, matrix) in inverse_binds.iter().enumerate() {
+        if joint > 0 {
+            dae.push(' ');
+        }
+        push_matrix(dae, matrix)?;
+    }
+    writeln!(
+        dae,
+        r##"</float_array><technique_common><accessor source="#{}-bind-poses-array" count="{}" stride="16"><param name="TRANSFORM" type="float4x4"/></accessor></technique_common></source>"##,
+        id,
+        inverse_binds.len()
+    )?;
+
+    let influences: Vec<Vec<(u16, f32)>> = mesh
+        .primitives
+        .iter()
+        .flat_map(|primitive| {
+            let submesh = &primitive.submesh;
+            submesh.bone_indices.iter().zip(&submesh.bone_weights)
+        })
+        .map(|(joints, weights)| {
+            joints
+                .iter()
+                .zip(weights)
+                .filter(|&(_, &weight)| weight > 0.0)
+                .map(|(&joint, &weight)| (joint, weight))
+                .collect()
+        })
+        .collect();
+    write_source(
+        dae,
+        &format!("{}-weights", id),
+        influences.iter().flatten().map(|&(_, weight)| weight),
+        &["WEIGHT"],
+    )?;
+    writeln!(
+        dae,
+        r##"      <joints><input semantic="JOINT" source="#{0}-joints"/><input semantic="INV_BIND_MATRIX" source="#{0}-bind-poses"/></joints>"##,
+        id
+    )?;
+    write!(
+        dae,
+        r##"      <vertex_weights count="{1}"><input semantic="JOINT" source="#{0}-joints" offset="0"/><input semantic="WEIGHT" source="#{0}-weights" offset="1"/><vcount>"##,
+        id,
+        influences.len()
+    )?;
+    for (vertex, influence) in influences.iter().enumerate() {
+        if vertex > 0 {
+            dae.push(' ');
+        }
+        write!(dae, "{}", influence.len())?;
+    }
+    write!(dae, "</vcount><v>")?;
+    for (weight, &(joint, _)) in influences.iter().flatten().enumerate() {
+        if weight > 0 {
+            dae.push(' ');
+        }
+        write!(dae, "{} {}", joint, weight)?;
+    }
+    writeln!(dae, "</v></vertex_weights>")?;
+    writeln!(dae, "    </skin></controller>")
+}
+
+/// Binds the material symbols of the triangle lists, with their UVs as set 0.
+fn write_bind_material(dae: &mut String, mesh: &GltfMesh) -> fmt::Result {
+    write!(dae, "<bind_material><technique_common>")?;
+    let mut bound = Vec::new();
+    for primitive in &mesh.primitives {
+        if bound.contains(&primitive.material) {
+            continue;
+        }
+        bound.push(primitive.material);
+        write!(
+            dae,
+            r##"<instance_material symbol="material{0}" target="#material{0}"><bind_vertex_input semantic="UVMap" input_semantic="TEXCOORD" input_set="0"/></instance_material>"##,
+            primitive.material
+        )?;
+    }
+    write!(dae, "</technique_common></bind_material>")
+}
+
+/// The skeleton with the static meshes attached to its nodes.
+struct NodeTree<'a> {
+    scene: &'a GltfScene,
+    children: &'a [Vec<usize>],
+    attached: &'a [Vec<usize>],
+}
+
+impl NodeTree<'_> {
+    fn write_node(&self, dae: &mut String, index: usize, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let node = &self.scene.nodes[index];
+        write!(dae, r#"{}<node id="node{1}" name=""#, indent, index)?;
+        push_xml_escaped(dae, &node.name);
+        write!(
+            dae,
+            r#"" sid="joint{}" type="JOINT"><matrix sid="transform">"#,
+            index
+        )?;
+        push_matrix(dae, &gltf::trs(node))?;
+        writeln!(dae, "</matrix>")?;
+        for &mesh_index in &self.attached[index] {
+            let mesh = &self.scene.meshes[mesh_index];
+            write!(
+                dae,
+                r#"{}  <node id="mesh{}-node" name=""#,
+                indent, mesh_index
+            )?;
+            push_xml_escaped(dae, &mesh.name);
+            write!(
+                dae,
+                r##"" type="NODE"><instance_geometry url="#mesh{}">"##,
+                mesh_index
+            )?;
+            write_bind_material(dae, mesh)?;
+            writeln!(dae, "</instance_geometry></node>")?;
+        }
+        for &child in &self.children[index] {
+            self.write_node(dae, child, depth + 1)?;
+        }
+        writeln!(dae, "{}</node>", indent)
+    }
+}
+
+fn is_skinned(mesh: &GltfMesh) -> bool {
+    mesh.primitives
+        .iter()
+        .any(|primitive| !primitive.submesh.bone_indices.is_empty())
+        && mesh.primitives.iter().all(|primitive| {
+            let submesh = &primitive.submesh;
+            submesh.bone_indices.len() == submesh.positions.len()
+                && submesh.bone_weights.len() == submesh.positions.len()
+        })
+}
+
+/// A float source whose elements have one float per name in `params`.
+fn write_source(
+    dae: &mut String,
+    id: &str,
+    values: impl Iterator<Item = f32>,
+    params: &[&str],
+) -> fmt::Result {
+    let mut count = 0;
+    let mut array = String::new();
+    for value in values {
+        if count > 0 {
+            array.push(' ');
+        }
+        write!(array, "{}", value)?;
+        count += 1;
+    }
+    write!(
+        dae,
+        r##"      <source id="{0}"><float_array id="{0}-array" count="{1}">{2}</float_array><technique_common><accessor source="#{0}-array" count="{3}" stride="{4}">"##,
+        id,
+        count,
+        array,
+        count / params.len(),
+        params.len()
+    )?;
+    for param in params {
+        write!(dae, r#"<param name="{}" type="float"/>"#, param)?;
+    }
+    writeln!(dae, "</accessor></technique_common></source>")
+}
+
+/// Appends a column-major matrix in COLLADA's row-major order.
+fn push_matrix(dae: &mut String, matrix: &[f32; 16]) -> fmt::Result {
+    for row in 0..4 {
+        for column in 0..4 {
+            if row + column > 0 {
+                dae.push(' ');
+            }
+            write!(dae, "{}", matrix[column * 4 + row])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gltf::{GltfMaterial, GltfNode, GltfPrimitive};
+    use crate::xac::SubMesh;
+    use elementtree::Element;
+
+    #[test]
+    fn skinned_mesh_is_bound_to_the_joint_hierarchy() {
+        let node = |name: &str, parent, y| GltfNode {
+            name: name.into(),
+            parent,
+            translation: [0.0, y, 0.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+        };
+        let scene = GltfScene {
+            nodes: vec![node("root & co", None, 0.0), node("arm", Some(0), 10.0)],
+            materials: vec![GltfMaterial {
+                name: "skin".into(),
+                texture: Some("skin.png".into()),
+                base_color: [1.0; 4],
+                double_sided: false,
+            }],
+            meshes: vec![GltfMesh {
+                name: "body".into(),
+                node: Some(0),
+                primitives: vec![GltfPrimitive {
+                    submesh: SubMesh {
+                        positions: vec![[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                        uvcoords: vec![[0.0, 0.25]; 3],
+                        indices: vec![0, 1, 2],
+                        bone_indices: vec![[0, 0, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0]],
+                        bone_weights: vec![
+                            [1.0, 0.0, 0.0, 0.0],
+                            [0.75, 0.25, 0.0, 0.0],
+                            [1.0, 0.0, 0.0, 0.0],
+                        ],
+                        ..Default::default()
+                    },
+                    material: 0,
+                }],
+                morph_targets: Vec::new(),
+            }],
+        };
+
+        let dae = write_dae(&scene).unwrap();
+        let root = Element::from_reader(dae.as_bytes()).unwrap();
+        // Children by local name, `nth` of them
+        let find = |element: &Element, path: &[(&str, usize)]| -> Element {
+            let mut element = element.clone();
+            for &(name, nth) in path {
+                let child = element
+                    .children()
+                    .filter(|child| child.tag().name() == name)
+                    .nth(nth)
+                    .unwrap_or_else(|| panic!("no {} in {}", name, element.tag().name()))
+                    .clone();
+                element = child;
+            }
+            element
+        };
+        let text =
+            |element: &Element, path: &[(&str, usize)]| find(element, path).text().to_string();
+
+        let visual_scene = find(&root, &[("library_visual_scenes", 0), ("visual_scene", 0)]);
+        let joint = find(&visual_scene, &[("node", 0)]);
+        assert_eq!(joint.get_attr("name"), Some("root & co"));
+        let arm = find(&joint, &[("node", 0)]);
+        assert_eq!(arm.get_attr("sid"), Some("joint1"));
+        // Row-major, so the translation is the last column
+        assert_eq!(
+            text(&arm, &[("matrix", 0)]),
+            "1 0 0 0 0 1 0 10 0 0 1 0 0 0 0 1"
+        );
+        let instance = find(&visual_scene, &[("node", 1), ("instance_controller", 0)]);
+        assert_eq!(instance.get_attr("url"), Some("#mesh0-skin"));
+        assert_eq!(text(&instance, &[("skeleton", 0)]), "#node0");
+
+        let weights = find(
+            &root,
+            &[
+                ("library_controllers", 0),
+                ("controller", 0),
+                ("skin", 0),
+                ("vertex_weights", 0),
+            ],
+        );
+        assert_eq!(text(&weights, &[("vcount", 0)]), "1 2 1");
+        assert_eq!(text(&weights, &[("v", 0)]), "0 0 1 1 0 2 1 3");
+
+        let mesh = find(
+            &root,
+            &[("library_geometries", 0), ("geometry", 0), ("mesh", 0)],
+        );
+        assert_eq!(text(&mesh, &[("triangles", 0), ("p", 0)]), "2 1 0");
+        assert_eq!(
+            text(&mesh, &[("source", 1), ("float_array", 0)]),
+            "0 0.75 0 0.75 0 0.75"
+        );
+    }
+}
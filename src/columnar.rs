@@ -0,0 +1,282 @@
+use crate::ies::{IESFile, IESValue};
+use std::collections::BTreeMap;
+
+/// Column-major copy of an `IESFile` for analytics, made with `From<&IESFile>`.
+///
+/// Number columns hold one `f32` per row, the width they have on disk, and string
+/// columns share one text buffer, so a table takes a fraction of the memory of its
+/// rows of `IESValue`s. Empty cells are marked in a bitmask per column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IESColumnarTable {
+    columns: Vec<IESColumnData>,
+    row_count: usize,
+}
+
+/// The cells of one column, see `IESColumnarTable::column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IESColumnData {
+    name: String,
+    name_second: String,
+    values: ColumnValues,
+    /// One bit per row, set for empty cells
+    nulls: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnValues {
+    /// 0.0 for empty cells
+    Numbers(Vec<f32>),
+    /// Row `i` is `text[ends[i - 1]..ends[i]]`
+    Strings { text: String, ends: Vec<usize> },
+}
+
+/// Count, sum and range of the non-empty cells of a number column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    pub count: usize,
+    pub sum: f64,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl Aggregate {
+    fn add(&mut self, value: f32) {
+        self.count += 1;
+        self.sum += f64::from(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// `None` when no cell was counted.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+impl From<&IESFile> for IESColumnarTable {
+    /// Whole numbers beyond the exact range of `f32` round, which only happens for
+    /// values set by hand: every number read from a file is an `f32`.
+    fn from(ies: &IESFile) -> Self {
+        let row_count = ies.row_count();
+        let columns = ies
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                let cells = ies.rows().map(|row| row.value_at(index));
+                let mut nulls = vec![0u64; row_count.div_ceil(64)];
+                let mut mark = |row: usize| nulls[row / 64] |= 1 << (row % 64);
+                let values = if column.is_number() {
+                    let numbers = cells
+                        .enumerate()
+                        .map(|(row, cell)| {
+                            cell.as_f32().unwrap_or_else(|| {
+                                mark(row);
+                                0.0
+                            })
+                        })
+                        .collect();
+                    ColumnValues::Numbers(numbers)
+                } else {
+                    let mut text = String::new();
+                    let mut ends = Vec::with_capacity(row_count);
+                    for (row, cell) in cells.enumerate() {
+                        match cell {
+                            IESValue::Null => mark(row),
+                            cell => text.push_str(&cell.to_string()),
+                        }
+                        ends.push(text.len());
+                    }
+                    text.shrink_to_fit();
+                    ColumnValues::Strings { text, ends }
+                };
+                IESColumnData {
+                    name: column.name().to_string(),
+                    name_second: column.name_second().to_string(),
+                    values,
+                    nulls,
+                }
+            })
+            .collect();
+        Self { columns, row_count }
+    }
+}
+
+impl IESColumnarTable {
+    pub fn row_count(&self) -> usize {
+        self.row_count
+    }
+
+    /// Columns in the cell order of the table.
+    pub fn columns(&self) -> &[IESColumnData] {
+        &self.columns
+    }
+
+    /// The column called `name`, by its first or second name.
+    pub fn column(&self, name: &str) -> Option<&IESColumnData> {
+        self.columns
+            .iter()
+            .find(|column| column.name == name)
+            .or_else(|| {
+                self.columns
+                    .iter()
+                    .find(|column| column.name_second == name)
+            })
+    }
+
+    /// Aggregate of the number column `column`; `None` for a missing or string column.
+    pub fn aggregate(&self, column: &str) -> Option<Aggregate> {
+        let column = self.column(column)?;
+        let numbers = column.numbers()?;
+        let mut aggregate = Aggregate::default();
+        for (row, &value) in numbers.iter().enumerate() {
+            if !column.is_null(row) {
+                aggregate.add(value);
+            }
+        }
+        Some(aggregate)
+    }
+
+    pub fn sum(&self, column: &str) -> Option<f64> {
+        Some(self.aggregate(column)?.sum)
+    }
+
+    /// Smallest value, `None` also when every cell is empty.
+    pub fn min(&self, column: &str) -> Option<f32> {
+        let aggregate = self.aggregate(column)?;
+        (aggregate.count > 0).then_some(aggregate.min)
+    }
+
+    /// Largest value, `None` also when every cell is empty.
+    pub fn max(&self, column: &str) -> Option<f32> {
+        let aggregate = self.aggregate(column)?;
+        (aggregate.count > 0).then_some(aggregate.max)
+    }
+
+    /// Aggregates of the number column `value` per distinct text of the `key` column,
+    /// sorted by key; rows with an empty key cell group under "". `None` when either
+    /// column is missing or `value` holds strings.
+    pub fn group_by(&self, key: &str, value: &str) -> Option<BTreeMap<String, Aggregate>> {
+        let key = self.column(key)?;
+        let value = self.column(value)?;
+        let numbers = value.numbers()?;
+        let mut groups: BTreeMap<String, Aggregate> = BTreeMap::new();
+        for (row, &number) in numbers.iter().enumerate() {
+            let group = groups.entry(key.text(row)).or_default();
+            if !value.is_null(row) {
+                group.add(number);
+            }
+        }
+        Some(groups)
+    }
+}
+
+impl IESColumnData {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn name_second(&self) -> &str {
+        &self.name_second
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self.values, ColumnValues::Numbers(_))
+    }
+
+    pub fn is_null(&self, row: usize) -> bool {
+        self.nulls
+            .get(row / 64)
+            .is_some_and(|bits| bits & (1 << (row % 64)) != 0)
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.nulls
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+
+    /// Every value of a number column, 0.0 at empty cells; `None` for strings.
+    pub fn numbers(&self) -> Option<&[f32]> {
+        match &self.values {
+            ColumnValues::Numbers(numbers) => Some(numbers),
+            ColumnValues::Strings { .. } => None,
+        }
+    }
+
+    /// The number at `row`; `None` for empty cells, string columns and rows past the
+    /// end.
+    pub fn number(&self, row: usize) -> Option<f32> {
+        let value = *self.numbers()?.get(row)?;
+        (!self.is_null(row)).then_some(value)
+    }
+
+    /// The string at `row`; `None` for empty cells, number columns and rows past the
+    /// end.
+    pub fn string(&self, row: usize) -> Option<&str> {
+        let ColumnValues::Strings { text, ends } = &self.values else {
+            return None;
+        };
+        let end = *ends.get(row)?;
+        let start = row.checked_sub(1).map_or(0, |previous| ends[previous]);
+        (!self.is_null(row)).then(|| &text[start..end])
+    }
+
+    /// The cell at `row` as text, empty for empty cells.
+    fn text(&self, row: usize) -> String {
+        match &self.values {
+            ColumnValues::Numbers(_) => self.number(row).map(IESValue::from),
+            ColumnValues::Strings { .. } => self.string(row).map(IESValue::from),
+        }
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_aggregate_and_group() {
+        let ies = IESFile::from_json(
+            r#"[{"Type": "Sword", "Price": 100, "Note": "old"},
+                {"Type": "Bow", "Price": 2.5, "Note": null},
+                {"Type": "Sword", "Price": null, "Note": ""},
+                {"Type": "Sword", "Price": 300, "Note": "new"}]"#,
+        )
+        .unwrap();
+        let table = IESColumnarTable::from(&ies);
+        assert_eq!(table.row_count(), 4);
+
+        let price = table.column("Price").unwrap();
+        assert_eq!(price.numbers(), Some(&[100.0, 2.5, 0.0, 300.0][..]));
+        assert_eq!((price.number(2), price.null_count()), (None, 1));
+        let note = table.column("Note").unwrap();
+        let notes: Vec<Option<&str>> = (0..4).map(|row| note.string(row)).collect();
+        assert_eq!(notes, [Some("old"), None, None, Some("new")]);
+
+        assert_eq!(table.sum("Price"), Some(402.5));
+        assert_eq!(
+            (table.min("Price"), table.max("Price")),
+            (Some(2.5), Some(300.0))
+        );
+        assert_eq!(table.aggregate("Note"), None);
+        let groups = table.group_by("Type", "Price").unwrap();
+        assert_eq!(groups["Sword"].count, 2);
+        assert_eq!(groups["Sword"].mean(), Some(200.0));
+        assert_eq!(groups["Bow"].max, 2.5);
+    }
+}
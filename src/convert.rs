@@ -0,0 +1,111 @@
+use crate::error::{Error, Result};
+use crate::ies::IESFile;
+use crate::texture;
+use crate::xac::XACFile;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Conversions offered by the HTTP server and the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Conversion {
+    /// IES table as JSON
+    IesJson,
+    /// Meshes of an XAC model as JSON
+    XacJson,
+    /// DDS texture as PNG
+    Png,
+}
+
+impl Conversion {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ies" => Some(Conversion::IesJson),
+            "xac" => Some(Conversion::XacJson),
+            "png" => Some(Conversion::Png),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Conversion::IesJson => "ies",
+            Conversion::XacJson => "xac",
+            Conversion::Png => "png",
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Conversion::IesJson | Conversion::XacJson => "application/json",
+            Conversion::Png => "image/png",
+        }
+    }
+
+    pub(crate) fn apply(self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Conversion::IesJson => Ok(IESFile::load_from_bytes(data)?.to_json()?.into_bytes()),
+            Conversion::XacJson => {
+                let meshes = XACFile::load_from_bytes(data)?.export_all_meshes_into_struct()?;
+                serde_json::to_vec(&meshes).map_err(|err| Error::Serialization(err.to_string()))
+            }
+            Conversion::Png => texture::dds_to_png(&data),
+        }
+    }
+}
+
+/// Converted assets by key up to a byte budget, dropping the oldest first. Shared by
+/// the HTTP server and the daemon.
+pub(crate) struct ConversionCache {
+    budget: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, Arc<[u8]>>,
+    order: VecDeque<String>,
+    bytes: u64,
+}
+
+impl ConversionCache {
+    pub(crate) fn new(budget: u64) -> Self {
+        Self {
+            budget,
+            state: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn get_or_insert(
+        &self,
+        key: &str,
+        convert: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Arc<[u8]>> {
+        if let Some(body) = self.state.lock().unwrap().entries.get(key) {
+            return Ok(body.clone());
+        }
+        // Converted without the lock; two requests for the same key may both convert
+        let body: Arc<[u8]> = convert()?.into();
+        let size = body.len() as u64;
+        if size <= self.budget {
+            let mut state = self.state.lock().unwrap();
+            while state.bytes + size > self.budget {
+                let Some(oldest) = state.order.pop_front() else {
+                    break;
+                };
+                if let Some(evicted) = state.entries.remove(&oldest) {
+                    state.bytes -= evicted.len() as u64;
+                }
+            }
+            if state
+                .entries
+                .insert(key.to_string(), body.clone())
+                .is_none()
+            {
+                state.order.push_back(key.to_string());
+                state.bytes += size;
+            }
+        }
+        Ok(body)
+    }
+}
@@ -0,0 +1,418 @@
+use crate::convert::{Conversion, ConversionCache};
+use crate::error::Result;
+use crate::game::GameData;
+use crate::ies::{IESFile, IESRowView};
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The entry exists but could not be read or converted
+const ASSET_ERROR: i64 = -32000;
+/// No entry with the requested path
+const NOT_FOUND: i64 = -32001;
+
+/// Parsed IES tables kept for `table` queries.
+const TABLE_CACHE_SIZE: usize = 32;
+
+/// Settings for `Daemon`.
+#[derive(Debug, Clone, Copy)]
+pub struct DaemonOptions {
+    /// Upper bound on the bytes of converted assets kept in memory.
+    pub cache_budget: u64,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        Self {
+            cache_budget: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl DaemonOptions {
+    pub fn with_cache_budget(mut self, bytes: u64) -> Self {
+        self.cache_budget = bytes;
+        self
+    }
+}
+
+/// Long-running JSON-RPC 2.0 service over a client's archives for editor tooling.
+///
+/// Requests and responses are single-line JSON messages separated by newlines. Paths
+/// are `GameData` keys (`container/path`, any case). Methods:
+///
+/// - `list {prefix?}`: keys starting with `prefix`
+/// - `read {path}`: `{size, base64}` of the extracted entry
+/// - `convert {path, to}`: `to` is `"ies"` or `"xac"` for JSON, `"png"` for a base64 PNG
+/// - `table {path, columns?, where?, limit?}`: IES rows as objects keyed by column,
+///   keeping the rows whose cells equal every value in `where`
+pub struct Daemon {
+    game: GameData,
+    conversions: ConversionCache,
+    tables: Mutex<TableCache>,
+}
+
+#[derive(Default)]
+struct TableCache {
+    tables: HashMap<String, Arc<IESFile>>,
+    order: VecDeque<String>,
+}
+
+/// Failure of one request, sent back as a JSON-RPC error object.
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A result either built as a value or already serialized, as cached conversions are.
+enum Reply {
+    Value(Value),
+    Raw(Arc<[u8]>),
+}
+
+impl Daemon {
+    /// Reads the file tables of `client_dir`; entries are extracted on demand.
+    pub fn open<P: AsRef<Path>>(client_dir: P, options: &DaemonOptions) -> Result<Self> {
+        Ok(Self {
+            game: GameData::open(client_dir)?,
+            conversions: ConversionCache::new(options.cache_budget),
+            tables: Mutex::default(),
+        })
+    }
+
+    pub fn game(&self) -> &GameData {
+        &self.game
+    }
+
+    /// Answers connections on `listener` until the process exits, one thread per
+    /// connection.
+    pub fn listen_tcp(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = self.clone();
+            thread::spawn(move || {
+                let _ = daemon.serve_stream(&stream, &stream);
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `listen_tcp` for a Unix domain socket.
+    #[cfg(unix)]
+    pub fn listen_unix(self: Arc<Self>, listener: std::os::unix::net::UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = self.clone();
+            thread::spawn(move || {
+                let _ = daemon.serve_stream(&stream, &stream);
+            });
+        }
+        Ok(())
+    }
+
+    /// Answers every request line read from `input` on `output` until end of input.
+    pub fn serve_stream<R: Read, W: Write>(&self, input: R, mut output: W) -> io::Result<()> {
+        for line in BufReader::new(input).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_line(&line) {
+                output.write_all(&response)?;
+                output.write_all(b"\n")?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers one JSON-RPC request. Notifications (requests without an id) get no
+    /// response.
+    pub fn handle_line(&self, line: &str) -> Option<Vec<u8>> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(error_response(
+                    &Value::Null,
+                    RpcError::new(PARSE_ERROR, err.to_string()),
+                ));
+            }
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let err = RpcError::new(INVALID_REQUEST, "missing method");
+            return Some(error_response(&id.unwrap_or(Value::Null), err));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.call(method, &params);
+
+        let id = id?;
+        Some(match result {
+            Ok(Reply::Value(value)) => {
+                serde_json::to_vec(&json!({"jsonrpc": "2.0", "id": id, "result": value}))
+                    .unwrap_or_default()
+            }
+            Ok(Reply::Raw(result)) => {
+                let mut response =
+                    format!(r#"{{"jsonrpc":"2.0","id":{},"result":"#, id).into_bytes();
+                response.extend_from_slice(&result);
+                response.push(b'}');
+                response
+            }
+            Err(err) => error_response(&id, err),
+        })
+    }
+
+    fn call(&self, method: &str, params: &Value) -> std::result::Result<Reply, RpcError> {
+        match method {
+            "list" => {
+                let prefix = optional_str(params, "prefix")?
+                    .unwrap_or_default()
+                    .to_lowercase();
+                let paths: Vec<&str> = self
+                    .game
+                    .paths()
+                    .filter(|path| path.starts_with(&prefix))
+                    .collect();
+                Ok(Reply::Value(json!(paths)))
+            }
+            "read" => {
+                let path = self.path_param(params)?;
+                let data = self.game.read(path).map_err(asset_error)?;
+                Ok(Reply::Value(
+                    json!({"size": data.len(), "base64": base64(&data)}),
+                ))
+            }
+            "convert" => {
+                let path = self.path_param(params)?;
+                let to = optional_str(params, "to")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing 'to'"))?;
+                let conversion = Conversion::from_name(to).ok_or_else(|| {
+                    RpcError::new(INVALID_PARAMS, format!("unknown conversion '{}'", to))
+                })?;
+                let key = format!("{}/{}", conversion.name(), path.to_lowercase());
+                let converted = self
+                    .conversions
+                    .get_or_insert(&key, || {
+                        conversion
+                            .apply(self.game.read(path)?)
+                            .map_err(|err| err.in_entry(path))
+                    })
+                    .map_err(asset_error)?;
+                Ok(match conversion {
+                    Conversion::Png => Reply::Value(json!(base64(&converted))),
+                    Conversion::IesJson | Conversion::XacJson => Reply::Raw(converted),
+                })
+            }
+            "table" => self.table(params).map(Reply::Value),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        }
+    }
+
+    fn path_param<'a>(&self, params: &'a Value) -> std::result::Result<&'a str, RpcError> {
+        let path = optional_str(params, "path")?
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing 'path'"))?;
+        if !self.game.contains(path) {
+            return Err(RpcError::new(NOT_FOUND, format!("no entry '{}'", path)));
+        }
+        Ok(path)
+    }
+
+    fn table(&self, params: &Value) -> std::result::Result<Value, RpcError> {
+        let path = self.path_param(params)?;
+        let ies = self.load_table(path).map_err(asset_error)?;
+        let all_columns = ies.get_column_names();
+        let columns: Vec<&str> = match params.get("columns") {
+            None | Some(Value::Null) => all_columns.iter().map(|name| name.as_str()).collect(),
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|name| {
+                    name.as_str()
+                        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "columns must be strings"))
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            Some(_) => return Err(RpcError::new(INVALID_PARAMS, "columns must be an array")),
+        };
+        let filters = match params.get("where") {
+            None | Some(Value::Null) => Map::new(),
+            Some(Value::Object(filters)) => filters.clone(),
+            Some(_) => return Err(RpcError::new(INVALID_PARAMS, "where must be an object")),
+        };
+        let limit = params
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map_or(usize::MAX, |limit| limit as usize);
+
+        let cell = |column: &str, row: &IESRowView| {
+            row.value(column)
+                .map_or(Value::Null, |cell| cell.to_json_value())
+        };
+        let rows: Vec<Value> = ies
+            .rows()
+            .filter(|row| {
+                filters
+                    .iter()
+                    .all(|(column, expected)| cell_matches(&cell(column, row), expected))
+            })
+            .take(limit)
+            .map(|row| {
+                let object: Map<String, Value> = columns
+                    .iter()
+                    .map(|column| (column.to_string(), cell(column, &row)))
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        Ok(Value::Array(rows))
+    }
+
+    fn load_table(&self, path: &str) -> Result<Arc<IESFile>> {
+        let key = path.to_lowercase();
+        if let Some(table) = self.tables.lock().unwrap().tables.get(&key) {
+            return Ok(table.clone());
+        }
+        let table = Arc::new(
+            IESFile::load_from_slice_with_options(
+                &self.game.read(path)?,
+                &self.game.parse_options(path),
+            )
+            .map_err(|err| err.in_entry(path))?,
+        );
+        let mut cache = self.tables.lock().unwrap();
+        if cache.tables.insert(key.clone(), table.clone()).is_none() {
+            cache.order.push_back(key);
+        }
+        while cache.order.len() > TABLE_CACHE_SIZE {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.tables.remove(&oldest);
+            }
+        }
+        Ok(table)
+    }
+}
+
+fn optional_str<'a>(
+    params: &'a Value,
+    name: &str,
+) -> std::result::Result<Option<&'a str>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("'{}' must be a string", name),
+        )),
+    }
+}
+
+fn asset_error(err: crate::Error) -> RpcError {
+    RpcError::new(ASSET_ERROR, err.to_string())
+}
+
+fn error_response(id: &Value, err: RpcError) -> Vec<u8> {
+    let response = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": err.code, "message": err.message},
+    });
+    serde_json::to_vec(&response).unwrap_or_default()
+}
+
+/// Numbers compare by value, so `{"Level": 3}` matches a cell read as an integer or
+/// a float.
+fn cell_matches(cell: &Value, expected: &Value) -> bool {
+    match (cell.as_f64(), expected.as_f64()) {
+        (Some(cell), Some(expected)) => cell == expected,
+        _ => cell == expected,
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn answers_json_rpc_requests() {
+        let dir = std::env::temp_dir().join(format!("toslib-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        let mut writer = IPFWriter::new();
+        writer.add_file("xml.ipf", "xml/a.xml", b"<a/>".to_vec());
+        writer.add_file("xml.ipf", "xml/b.xml", b"hello".to_vec());
+        writer
+            .save_to_file(dir.join("data").join("xml.ipf"))
+            .unwrap();
+        let daemon = Daemon::open(&dir, &DaemonOptions::default()).unwrap();
+
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"list","params":{"prefix":"XML.ipf/xml/b"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"read","params":{"path":"xml.ipf/xml/b.xml"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"list"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"table","params":{"path":"xml.ipf/c.ies"}}"#,
+            "\n",
+            "not json\n",
+        );
+        let mut output = Vec::new();
+        daemon.serve_stream(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"], json!(["xml.ipf/xml/b.xml"]));
+        assert_eq!(
+            responses[1]["result"],
+            json!({"size": 5, "base64": "aGVsbG8="})
+        );
+        assert_eq!(responses[2]["error"]["code"], NOT_FOUND);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use crate::warning::ParseWarning;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Crate-wide error type returned by every public parsing and writing API.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("IPF error: {0}")]
+    Ipf(String),
+
+    #[error("IES error: {0}")]
+    Ies(String),
+
+    #[error("XAC error: {0}")]
+    Xac(String),
+
+    #[error("text error: {0}")]
+    Text(String),
+
+    #[error("XML error: {0}")]
+    Xml(String),
+
+    #[error("texture error: {0}")]
+    Texture(String),
+
+    #[error("serialization error: {0}")]
+    Serialization(String),
+
+    #[error("unsupported {format} version {version}")]
+    UnsupportedVersion { format: &'static str, version: u32 },
+
+    #[error("bad {format} magic: expected {expected:08x}, got {found:08x}")]
+    BadMagic {
+        format: &'static str,
+        expected: u32,
+        found: u32,
+    },
+
+    /// A payload that does not inflate, which is also how data deciphered with the
+    /// wrong keys shows.
+    #[error("decompression failed: {0}")]
+    Decompress(String),
+
+    #[error("CRC mismatch: expected {expected:08x}, got {actual:08x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+
+    #[error("input truncated: needed {needed} bytes but only {available} remain")]
+    Truncated { needed: u64, available: u64 },
+
+    #[error("operation cancelled")]
+    Cancelled,
+
+    #[error("strict mode: {0}")]
+    Strict(ParseWarning),
+
+    #[error("{context}: {source}")]
+    WithContext {
+        context: Box<ErrorContext>,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// Where in the input a parse error happened. Every field is optional and filled in
+/// as the error travels up from the failing read to the archive being processed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// File or archive on disk the data came from
+    pub path: Option<PathBuf>,
+    /// Entry inside the archive
+    pub entry: Option<String>,
+    /// Absolute byte offset within the parsed file
+    pub offset: Option<u64>,
+    /// XAC chunk id and version
+    pub chunk: Option<(u32, u32)>,
+    /// IES row index
+    pub row: Option<usize>,
+    /// IES column name
+    pub column: Option<String>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(path) = &self.path {
+            parts.push(path.display().to_string());
+        }
+        if let Some(entry) = &self.entry {
+            parts.push(entry.clone());
+        }
+        if let Some((chunk_id, version)) = self.chunk {
+            parts.push(format!("chunk {} v{}", chunk_id, version));
+        }
+        if let Some(row) = self.row {
+            parts.push(format!("row {}", row));
+        }
+        if let Some(column) = &self.column {
+            parts.push(format!("column {}", column));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {:#x}", offset));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Error {
+    /// The location attached to this error, if any.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The underlying error without any attached location.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::WithContext { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// Whether the input simply ended too early, as opposed to containing bad data.
+    pub fn is_truncation(&self) -> bool {
+        match self.root() {
+            Error::Truncated { .. } => true,
+            Error::Io(err) => err.kind() == io::ErrorKind::UnexpectedEof,
+            _ => false,
+        }
+    }
+
+    /// Fills in context fields that are still unset. Inner (more precise) locations
+    /// always win over outer ones.
+    fn with(self, update: impl FnOnce(&mut ErrorContext)) -> Self {
+        match self {
+            Error::WithContext {
+                mut context,
+                source,
+            } => {
+                update(&mut context);
+                Error::WithContext { context, source }
+            }
+            err => {
+                let mut context = Box::<ErrorContext>::default();
+                update(&mut context);
+                Error::WithContext {
+                    context,
+                    source: Box::new(err),
+                }
+            }
+        }
+    }
+
+    pub fn at_offset(self, offset: u64) -> Self {
+        self.with(|context| {
+            context.offset.get_or_insert(offset);
+        })
+    }
+
+    pub fn in_chunk(self, chunk_id: u32, version: u32) -> Self {
+        self.with(|context| {
+            context.chunk.get_or_insert((chunk_id, version));
+        })
+    }
+
+    pub fn in_row(self, row: usize) -> Self {
+        self.with(|context| {
+            context.row.get_or_insert(row);
+        })
+    }
+
+    pub fn in_column(self, column: &str) -> Self {
+        self.with(|context| {
+            context.column.get_or_insert_with(|| column.to_string());
+        })
+    }
+
+    pub fn in_entry(self, entry: &str) -> Self {
+        self.with(|context| {
+            context.entry.get_or_insert_with(|| entry.to_string());
+        })
+    }
+
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        self.with(|context| {
+            context.path.get_or_insert_with(|| path.into());
+        })
+    }
+}
+
+impl From<ParseWarning> for Error {
+    fn from(warning: ParseWarning) -> Self {
+        match warning {
+            ParseWarning::UnsupportedVersion {
+                format, version, ..
+            } => Error::UnsupportedVersion { format, version },
+            warning => Error::Strict(warning),
+        }
+    }
+}
+
+impl From<binrw::Error> for Error {
+    fn from(err: binrw::Error) -> Self {
+        let pos = match &err {
+            binrw::Error::Io(_) | binrw::Error::Backtrace(_) => None,
+            binrw::Error::BadMagic { pos, .. }
+            | binrw::Error::AssertFail { pos, .. }
+            | binrw::Error::Custom { pos, .. }
+            | binrw::Error::NoVariantMatch { pos }
+            | binrw::Error::EnumErrors { pos, .. } => Some(*pos),
+            _ => None,
+        };
+        let err = match err {
+            binrw::Error::Io(err) => Error::Io(err),
+            err => Error::Xac(err.to_string()),
+        };
+        match pos {
+            Some(pos) => err.at_offset(pos),
+            None => err,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,228 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Kind of long-running work an event belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Reading the archive tables of a client, `GameData::open`
+    Load,
+    /// Extracting entries through `scan`; progress counts entries
+    Extract,
+    /// Writing exported files; progress counts files
+    Convert,
+    /// Searching a client, `search::search`
+    Search,
+    /// Building a patch, `ModProject::build`; progress counts files
+    Build,
+}
+
+/// Something that happened during an operation. Events of one run share `task`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<'a> {
+    Started {
+        task: u64,
+        operation: Operation,
+        /// Client, archive or model being worked on
+        subject: &'a str,
+        /// Number of steps when known upfront
+        total: Option<u64>,
+    },
+    Progress {
+        task: u64,
+        operation: Operation,
+        done: u64,
+        total: Option<u64>,
+    },
+    /// A problem that did not stop the operation
+    Warning {
+        task: u64,
+        operation: Operation,
+        message: &'a str,
+    },
+    Finished {
+        task: u64,
+        operation: Operation,
+        /// The error when the operation failed
+        error: Option<&'a str>,
+    },
+}
+
+/// Receiver of events from every long operation in the process, see `subscribe`.
+/// Called on whichever thread does the work, so implementations should hand events
+/// off (e.g. into a channel) rather than block.
+pub trait EventListener: Send + Sync {
+    fn event(&self, event: &Event);
+}
+
+/// Returned by `subscribe`, for `unsubscribe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subscription(u64);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LISTENERS: RwLock<Vec<(u64, Arc<dyn EventListener>)>> = RwLock::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Adds `listener` for the whole process. With no listener subscribed, publishing
+/// costs a single relaxed load.
+pub fn subscribe(listener: Arc<dyn EventListener>) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    LISTENERS.write().unwrap().push((id, listener));
+    ENABLED.store(true, Ordering::Relaxed);
+    Subscription(id)
+}
+
+pub fn unsubscribe(subscription: Subscription) {
+    let mut listeners = LISTENERS.write().unwrap();
+    listeners.retain(|(id, _)| *id != subscription.0);
+    ENABLED.store(!listeners.is_empty(), Ordering::Relaxed);
+}
+
+fn publish(event: &Event) {
+    for (_, listener) in LISTENERS.read().unwrap().iter() {
+        listener.event(event);
+    }
+}
+
+/// Publisher for one run of an operation. Does nothing when nobody listens.
+pub(crate) struct Task {
+    id: u64,
+    operation: Operation,
+    total: Option<u64>,
+}
+
+impl Task {
+    pub(crate) fn start(operation: Operation, subject: &str, total: Option<u64>) -> Self {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Self {
+                id: 0,
+                operation,
+                total,
+            };
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        publish(&Event::Started {
+            task: id,
+            operation,
+            subject,
+            total,
+        });
+        Self {
+            id,
+            operation,
+            total,
+        }
+    }
+
+    pub(crate) fn progress(&self, done: u64) {
+        if self.id != 0 {
+            publish(&Event::Progress {
+                task: self.id,
+                operation: self.operation,
+                done,
+                total: self.total,
+            });
+        }
+    }
+
+    pub(crate) fn warning(&self, message: &dyn std::fmt::Display) {
+        if self.id != 0 {
+            publish(&Event::Warning {
+                task: self.id,
+                operation: self.operation,
+                message: &message.to_string(),
+            });
+        }
+    }
+
+    /// Publishes a successful finish.
+    pub(crate) fn done(&self) {
+        let _ = self.finish(Ok::<(), &str>(()));
+    }
+
+    /// Publishes the outcome and passes `result` through.
+    pub(crate) fn finish<T, E: std::fmt::Display>(&self, result: Result<T, E>) -> Result<T, E> {
+        if self.id != 0 {
+            let error = result.as_ref().err().map(ToString::to_string);
+            publish(&Event::Finished {
+                task: self.id,
+                operation: self.operation,
+                error: error.as_deref(),
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameData;
+    use crate::ipf::IPFWriter;
+    use std::sync::Mutex;
+
+    /// Load events as `(task, description)`.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(u64, String)>>);
+
+    impl EventListener for Recorder {
+        fn event(&self, event: &Event) {
+            let line = match *event {
+                Event::Started {
+                    task,
+                    operation: Operation::Load,
+                    subject,
+                    total,
+                } => (task, format!("started {} {:?}", subject, total)),
+                Event::Progress {
+                    task,
+                    operation: Operation::Load,
+                    done,
+                    ..
+                } => (task, format!("progress {}", done)),
+                Event::Finished {
+                    task,
+                    operation: Operation::Load,
+                    error,
+                } => (task, format!("finished {:?}", error)),
+                _ => return,
+            };
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    #[test]
+    fn loading_a_client_publishes_progress() {
+        let dir = std::env::temp_dir().join(format!("toslib-events-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        for name in ["a.ipf", "b.ipf"] {
+            let mut writer = IPFWriter::new();
+            writer.add_file(name, "a.xml", b"<a/>".to_vec());
+            writer.save_to_file(dir.join("data").join(name)).unwrap();
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let subscription = subscribe(recorder.clone());
+        GameData::open(&dir).unwrap();
+        unsubscribe(subscription);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Other tests may load clients at the same time
+        let events = recorder.0.lock().unwrap();
+        let started = format!("started {} Some(2)", dir.display());
+        let task = events.iter().find(|(_, line)| *line == started).unwrap().0;
+        let mine: Vec<&str> = events
+            .iter()
+            .filter(|(id, _)| *id == task)
+            .map(|(_, line)| line.as_str())
+            .collect();
+        assert_eq!(
+            mine,
+            [
+                started.as_str(),
+                "progress 1",
+                "progress 2",
+                "finished None"
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+/// Coordinate conventions for exported geometry.
+///
+/// XAC data is left-handed and Y-up. Units are taken to be centimeters, which is what
+/// the engine presets scale from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportOptions {
+    pub up_axis: UpAxis,
+    pub handedness: Handedness,
+    /// Factor applied to positions after the axis conversion.
+    pub scale: f32,
+    /// Which meshes to export depending on their collision flag.
+    pub collision: CollisionMeshes,
+    /// Moves every mesh by the bind pose transform of its node, so props made of
+    /// several nodes come out assembled rather than all at the origin.
+    pub world_space: bool,
+    /// Computes tangents and bitangents for submeshes without a tangent layer, see
+    /// `Mesh::compute_tangents`.
+    pub compute_tangents: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    /// Rotated so the XAC up direction becomes +Z.
+    Z,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Handedness {
+    /// Mirrored along X, as glTF, OBJ, Blender and Godot expect.
+    #[default]
+    Right,
+    /// The XAC coordinates as stored, as Unity and Unreal expect.
+    Left,
+}
+
+/// Selects meshes by the collision flag of their XAC mesh chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CollisionMeshes {
+    /// Render and collision meshes alike
+    #[default]
+    Include,
+    /// Only the render meshes
+    Exclude,
+    /// Only the collision meshes, e.g. for server-side hitboxes
+    Only,
+}
+
+impl CollisionMeshes {
+    /// Whether a mesh with the given collision flag is selected.
+    pub fn keeps(self, is_collision: bool) -> bool {
+        match self {
+            CollisionMeshes::Include => true,
+            CollisionMeshes::Exclude => !is_collision,
+            CollisionMeshes::Only => is_collision,
+        }
+    }
+}
+
+/// Conventions of the engines the exports are usually imported into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnginePreset {
+    /// Y-up, left-handed, meters
+    Unity,
+    /// Z-up, left-handed, centimeters
+    Unreal,
+    /// Y-up, right-handed, meters
+    Godot,
+}
+
+impl Default for ExportOptions {
+    /// Right-handed Y-up in the original units, matching the OBJ exporter.
+    fn default() -> Self {
+        Self {
+            up_axis: UpAxis::Y,
+            handedness: Handedness::Right,
+            scale: 1.0,
+            collision: CollisionMeshes::Include,
+            world_space: false,
+            compute_tangents: false,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn preset(preset: EnginePreset) -> Self {
+        let (up_axis, handedness, scale) = match preset {
+            EnginePreset::Unity => (UpAxis::Y, Handedness::Left, 0.01),
+            EnginePreset::Unreal => (UpAxis::Z, Handedness::Left, 1.0),
+            EnginePreset::Godot => (UpAxis::Y, Handedness::Right, 0.01),
+        };
+        Self {
+            up_axis,
+            handedness,
+            scale,
+            collision: CollisionMeshes::Include,
+            world_space: false,
+            compute_tangents: false,
+        }
+    }
+
+    pub fn with_up_axis(mut self, up_axis: UpAxis) -> Self {
+        self.up_axis = up_axis;
+        self
+    }
+
+    pub fn with_handedness(mut self, handedness: Handedness) -> Self {
+        self.handedness = handedness;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_collision(mut self, collision: CollisionMeshes) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    pub fn with_world_space(mut self, world_space: bool) -> Self {
+        self.world_space = world_space;
+        self
+    }
+
+    pub fn with_compute_tangents(mut self, compute_tangents: bool) -> Self {
+        self.compute_tangents = compute_tangents;
+        self
+    }
+
+    /// Converts a direction (normal, tangent, bitangent) from XAC space.
+    pub fn direction(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let x = match self.handedness {
+            Handedness::Right => -x,
+            Handedness::Left => x,
+        };
+        match self.up_axis {
+            UpAxis::Y => [x, y, z],
+            UpAxis::Z => [x, -z, y],
+        }
+    }
+
+    /// Converts a per-axis node scale from XAC space, which only follows the axes.
+    pub fn axis_scale(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match self.up_axis {
+            UpAxis::Y => [x, y, z],
+            UpAxis::Z => [x, z, y],
+        }
+    }
+
+    /// Converts a position from XAC space, including the scale.
+    pub fn position(&self, position: [f32; 3]) -> [f32; 3] {
+        self.direction(position).map(|value| value * self.scale)
+    }
+
+    /// Converts a rotation quaternion `[x, y, z, w]` from XAC space. Its axis is
+    /// mirrored like a direction, but as an axial vector it keeps the mirrored component
+    /// and flips the others.
+    pub fn rotation(&self, [x, y, z, w]: [f32; 4]) -> [f32; 4] {
+        let [x, y, z] = match self.handedness {
+            Handedness::Right => [x, -y, -z],
+            Handedness::Left => [x, y, z],
+        };
+        match self.up_axis {
+            UpAxis::Y => [x, y, z, w],
+            UpAxis::Z => [x, -z, y, w],
+        }
+    }
+
+    /// Converts a tangent whose `w` is the bitangent sign, which flips with the
+    /// handedness.
+    pub fn tangent(&self, [x, y, z, w]: [f32; 4]) -> [f32; 4] {
+        let [x, y, z] = self.direction([x, y, z]);
+        match self.handedness {
+            Handedness::Right => [x, y, z, -w],
+            Handedness::Left => [x, y, z, w],
+        }
+    }
+}
+
+/// Settings for `XACFile::export_gltf`. glTF is right-handed and Y-up, so only the
+/// scale of the geometry is free.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfOptions {
+    /// Factor applied to positions, 0.01 by default to turn XAC centimeters into the
+    /// meters glTF specifies
+    pub scale: f32,
+    /// Level of detail whose meshes are exported
+    pub lod: u32,
+    /// Export the node hierarchy as a skin with joint weights per vertex; without it
+    /// nodes are still written, but meshes are static
+    pub skinning: bool,
+    /// Prepended to `<texture stem>.png` in the image URIs of the materials, e.g. the
+    /// `output_prefix` plus `_` given to `export_all_meshes_with_textures`
+    pub texture_uri_prefix: String,
+}
+
+impl Default for GltfOptions {
+    fn default() -> Self {
+        Self {
+            scale: 0.01,
+            lod: 0,
+            skinning: true,
+            texture_uri_prefix: String::new(),
+        }
+    }
+}
+
+impl GltfOptions {
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_lod(mut self, lod: u32) -> Self {
+        self.lod = lod;
+        self
+    }
+
+    pub fn with_skinning(mut self, skinning: bool) -> Self {
+        self.skinning = skinning;
+        self
+    }
+
+    pub fn with_texture_uri_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.texture_uri_prefix = prefix.into();
+        self
+    }
+
+    /// The geometry conventions glTF requires, at `scale`.
+    pub(crate) fn export_options(&self) -> ExportOptions {
+        ExportOptions::default().with_scale(self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_convert_axes_and_units() {
+        let point = [1.0, 2.0, 3.0];
+        assert_eq!(ExportOptions::default().position(point), [-1.0, 2.0, 3.0]);
+        assert_eq!(
+            ExportOptions::preset(EnginePreset::Unreal).position(point),
+            [1.0, -3.0, 2.0]
+        );
+        assert_eq!(
+            ExportOptions::preset(EnginePreset::Unity).position([100.0, 200.0, 300.0]),
+            [1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            ExportOptions::preset(EnginePreset::Godot).tangent([1.0, 0.0, 0.0, 1.0]),
+            [-1.0, 0.0, 0.0, -1.0]
+        );
+        // A quarter turn about Y turns the other way once X is mirrored
+        let quarter = [0.0, 0.5f32.sqrt(), 0.0, 0.5f32.sqrt()];
+        assert_eq!(
+            ExportOptions::default().rotation(quarter),
+            [0.0, -quarter[1], 0.0, quarter[3]]
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! C API, declared in `include/toslib.h`.
+//!
+//! Every function returns a `TosStatus`; on failure `tos_last_error` describes the
+//! error. Buffers handed out by the library are owned by the caller and must be
+//! released with `tos_buffer_free`. Archive handles are not thread-safe: use one
+//! handle per thread.
+
+use crate::error::Error;
+use crate::export::GltfOptions;
+use crate::ipf::IPFFile;
+use crate::tosreader::BinaryReader;
+use crate::xac::XACFile;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TosStatus {
+    Ok = 0,
+    /// A null pointer, an index out of range or a path that is not UTF-8
+    InvalidArgument = 1,
+    Io = 2,
+    /// The data is malformed or truncated
+    Format = 3,
+    /// No entry with the requested path
+    NotFound = 4,
+    /// A bug in the library; the handle should not be used again
+    Panic = 5,
+}
+
+/// Bytes owned by the caller. `data` is followed by a NUL byte that `len` does not
+/// count, so names can be used as C strings directly.
+#[repr(C)]
+pub struct TosBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl TosBuffer {
+    fn from_vec(mut bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        bytes.push(0);
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        TosBuffer { data, len }
+    }
+}
+
+/// An opened archive: its file table and a reader for entry data.
+pub struct TosIpf {
+    ipf: IPFFile,
+    reader: BinaryReader<BufReader<File>>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+fn status_of(err: &Error) -> TosStatus {
+    match err.root() {
+        Error::Io(io) if io.kind() != std::io::ErrorKind::UnexpectedEof => TosStatus::Io,
+        _ => TosStatus::Format,
+    }
+}
+
+/// Runs `f`, turning errors and panics into a status and the last error message.
+fn guard(f: impl FnOnce() -> Result<(), (TosStatus, String)>) -> TosStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TosStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("internal error".to_string());
+            TosStatus::Panic
+        }
+    }
+}
+
+fn failed(err: Error) -> (TosStatus, String) {
+    (status_of(&err), err.to_string())
+}
+
+fn invalid(message: &str) -> (TosStatus, String) {
+    (TosStatus::InvalidArgument, message.to_string())
+}
+
+/// # Safety
+/// `path` must be null or point to a NUL-terminated string.
+unsafe fn path_arg<'a>(path: *const c_char) -> Result<&'a str, (TosStatus, String)> {
+    if path.is_null() {
+        return Err(invalid("path is null"));
+    }
+    unsafe { CStr::from_ptr(path) }
+        .to_str()
+        .map_err(|_| invalid("path is not valid UTF-8"))
+}
+
+/// Message of the last failed call on this thread. Valid until the next failing call
+/// on the same thread; never null.
+#[unsafe(no_mangle)]
+pub extern "C" fn tos_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Opens the archive at `path` and stores a handle in `*out`.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_open(path: *const c_char, out: *mut *mut TosIpf) -> TosStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(invalid("out is null"));
+        }
+        let path = unsafe { path_arg(path) }?;
+        let open = || -> crate::Result<TosIpf> {
+            let ipf = IPFFile::load_from_file(path)?;
+            let file = File::open(path).map_err(|err| Error::from(err).in_file(path))?;
+            let reader = BinaryReader::new(BufReader::new(file));
+            Ok(TosIpf { ipf, reader })
+        };
+        let handle = open().map_err(failed)?;
+        unsafe { *out = Box::into_raw(Box::new(handle)) };
+        Ok(())
+    })
+}
+
+/// Releases a handle from `tos_ipf_open`. Null is ignored.
+///
+/// # Safety
+/// `ipf` must be null or a handle that has not been closed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_close(ipf: *mut TosIpf) {
+    if !ipf.is_null() {
+        drop(unsafe { Box::from_raw(ipf) });
+    }
+}
+
+/// Number of entries in the archive, 0 for a null handle.
+///
+/// # Safety
+/// `ipf` must be null or an open handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_entry_count(ipf: *const TosIpf) -> usize {
+    unsafe { ipf.as_ref() }.map_or(0, |ipf| ipf.ipf.file_table().len())
+}
+
+/// Stores the path of entry `index` in `*out`.
+///
+/// # Safety
+/// `ipf` must be an open handle and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_entry_name(
+    ipf: *const TosIpf,
+    index: usize,
+    out: *mut TosBuffer,
+) -> TosStatus {
+    guard(|| {
+        let (Some(ipf), false) = (unsafe { ipf.as_ref() }, out.is_null()) else {
+            return Err(invalid("null argument"));
+        };
+        let entry = ipf
+            .ipf
+            .file_table()
+            .get(index)
+            .ok_or_else(|| invalid("entry index out of range"))?;
+        unsafe { out.write(TosBuffer::from_vec(entry.directory_name().into_bytes())) };
+        Ok(())
+    })
+}
+
+/// Extracts entry `index` into `*out`.
+///
+/// # Safety
+/// `ipf` must be an open handle and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_read_index(
+    ipf: *mut TosIpf,
+    index: usize,
+    out: *mut TosBuffer,
+) -> TosStatus {
+    guard(|| {
+        let (Some(ipf), false) = (unsafe { ipf.as_mut() }, out.is_null()) else {
+            return Err(invalid("null argument"));
+        };
+        let entry = ipf
+            .ipf
+            .file_table()
+            .get(index)
+            .ok_or_else(|| invalid("entry index out of range"))?;
+        let data = entry.extract(&mut ipf.reader).map_err(failed)?;
+        unsafe { out.write(TosBuffer::from_vec(data)) };
+        Ok(())
+    })
+}
+
+/// Extracts the entry stored under `path` (e.g. `ies/item.ies`) into `*out`.
+///
+/// # Safety
+/// `ipf` must be an open handle, `path` a NUL-terminated string and `out` a valid
+/// pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_ipf_read(
+    ipf: *mut TosIpf,
+    path: *const c_char,
+    out: *mut TosBuffer,
+) -> TosStatus {
+    guard(|| {
+        let (Some(ipf), false) = (unsafe { ipf.as_mut() }, out.is_null()) else {
+            return Err(invalid("null argument"));
+        };
+        let path = unsafe { path_arg(path) }?;
+        let entry = ipf
+            .ipf
+            .file_table()
+            .iter()
+            .find(|entry| entry.directory_name_str() == path)
+            .ok_or_else(|| (TosStatus::NotFound, format!("no entry {}", path)))?;
+        let data = entry.extract(&mut ipf.reader).map_err(failed)?;
+        unsafe { out.write(TosBuffer::from_vec(data)) };
+        Ok(())
+    })
+}
+
+/// Converts the XAC actor in `data` to binary glTF (`.glb`) with the default
+/// `GltfOptions` and stores it in `*out`.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes, or be null when `len` is 0, and `out`
+/// must be a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_xac_to_gltf(
+    data: *const u8,
+    len: usize,
+    out: *mut TosBuffer,
+) -> TosStatus {
+    guard(|| {
+        if out.is_null() || (data.is_null() && len > 0) {
+            return Err(invalid("null argument"));
+        }
+        let data = if len == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(data, len) }
+        };
+        let glb = XACFile::load_from_slice(data)
+            .and_then(|xac| xac.to_glb(&GltfOptions::default()))
+            .map_err(failed)?;
+        unsafe { out.write(TosBuffer::from_vec(glb)) };
+        Ok(())
+    })
+}
+
+/// Releases a buffer returned by the library and resets it to empty.
+///
+/// # Safety
+/// `buffer` must be null or point to a buffer filled in by this library that has not
+/// been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tos_buffer_free(buffer: *mut TosBuffer) {
+    let Some(buffer) = (unsafe { buffer.as_mut() }) else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len + 1);
+        drop(unsafe { Box::from_raw(slice) });
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn read_entries_through_the_c_api() {
+        let path = std::env::temp_dir().join(format!("toslib-ffi-{}.ipf", std::process::id()));
+        let mut writer = IPFWriter::new();
+        writer.add_file("patch.ipf", "xml/a.xml", b"<root/>".to_vec());
+        writer.save_to_file(&path).unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut ipf = ptr::null_mut();
+            assert_eq!(tos_ipf_open(c_path.as_ptr(), &mut ipf), TosStatus::Ok);
+            assert_eq!(tos_ipf_entry_count(ipf), 1);
+
+            let mut buffer = TosBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(tos_ipf_entry_name(ipf, 0, &mut buffer), TosStatus::Ok);
+            assert_eq!(CStr::from_ptr(buffer.data as *const c_char), c"xml/a.xml");
+            tos_buffer_free(&mut buffer);
+
+            assert_eq!(
+                tos_ipf_read(ipf, c"xml/a.xml".as_ptr(), &mut buffer),
+                TosStatus::Ok
+            );
+            assert_eq!(
+                std::slice::from_raw_parts(buffer.data, buffer.len),
+                b"<root/>"
+            );
+            tos_buffer_free(&mut buffer);
+            assert!(buffer.data.is_null());
+
+            assert_eq!(
+                tos_ipf_read(ipf, c"missing".as_ptr(), &mut buffer),
+                TosStatus::NotFound
+            );
+            assert_eq!(CStr::from_ptr(tos_last_error()), c"no entry missing");
+            assert_eq!(
+                tos_ipf_read_index(ipf, 5, &mut buffer),
+                TosStatus::InvalidArgument
+            );
+            tos_ipf_close(ipf);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn convert_actors_to_gltf_through_the_c_api() {
+        // A little-endian actor with one triangle mesh
+        let words = |values: &[u32]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut mesh: Vec<u8> = words(&[0, 3, 3, 3, 1, 1]);
+        mesh.extend([0; 4]);
+        mesh.extend(words(&[0, 12])); // Positions layer
+        mesh.extend([0; 4]);
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            mesh.extend(value.to_le_bytes());
+        }
+        mesh.extend(words(&[3, 3, 0, 0, 0, 1, 2]));
+        let mut actor = b"XAC \x01\x00\x00\x00".to_vec();
+        actor.extend(words(&[1, mesh.len() as u32, 1]));
+        actor.extend(mesh);
+        let expected = XACFile::load_from_slice(&actor)
+            .unwrap()
+            .to_glb(&GltfOptions::default())
+            .unwrap();
+
+        unsafe {
+            let mut buffer = TosBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(
+                tos_xac_to_gltf(actor.as_ptr(), actor.len(), &mut buffer),
+                TosStatus::Ok
+            );
+            let glb = std::slice::from_raw_parts(buffer.data, buffer.len);
+            assert_eq!(glb, expected);
+            assert_eq!(&glb[..4], b"glTF");
+            let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+            let json: serde_json::Value =
+                serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+            assert_eq!(json["meshes"].as_array().map(Vec::len), Some(1));
+            tos_buffer_free(&mut buffer);
+
+            assert_eq!(
+                tos_xac_to_gltf(ptr::null(), 4, &mut buffer),
+                TosStatus::InvalidArgument
+            );
+            assert_eq!(
+                tos_xac_to_gltf(actor.as_ptr(), 6, &mut buffer),
+                TosStatus::Format
+            );
+            assert!(buffer.data.is_null());
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use crate::error::{Error, Result};
+use crate::events::{Operation, Task};
+use crate::ipf::{IPFFile, IPFFileTable, IpfCrypto};
+use crate::mount::{EntrySource, IpfMount};
+use crate::options::ParseOptions;
+use crate::text::TextDecoding;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Settings for `GameData::open_with`.
+#[derive(Debug, Clone, Default)]
+pub struct GameOptions {
+    /// Decoding of entry names and entry text, e.g. `TextDecoding::Detect` for clients
+    /// of other regions.
+    pub text: TextDecoding,
+    /// Decodings for single archives, keyed by lowercase file name (`xml_tr.ipf`),
+    /// for archives known to use another code page than the rest.
+    pub text_overrides: HashMap<String, TextDecoding>,
+    /// Payload encryption of every archive, for builds without the client cipher.
+    pub crypto: IpfCrypto,
+}
+
+impl GameOptions {
+    pub fn with_text(mut self, text: TextDecoding) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn with_text_override(mut self, archive_name: &str, text: TextDecoding) -> Self {
+        self.text_overrides
+            .insert(archive_name.to_lowercase(), text);
+        self
+    }
+
+    pub fn with_crypto(mut self, crypto: IpfCrypto) -> Self {
+        self.crypto = crypto;
+        self
+    }
+
+    fn text_for(&self, archive: &Path) -> TextDecoding {
+        archive
+            .file_name()
+            .and_then(|name| {
+                self.text_overrides
+                    .get(&name.to_string_lossy().to_lowercase())
+            })
+            .copied()
+            .unwrap_or(self.text)
+    }
+}
+
+/// The archives of a client installation seen as one set of entries.
+///
+/// Entries are keyed by their lowercase `container/path`. Archives are mounted in
+/// order, `data/` before `patch/` and each sorted by file name, so entries of later
+/// archives replace earlier ones as the game's patcher does.
+#[derive(Debug)]
+pub struct GameData {
+    root: PathBuf,
+    archives: Vec<PathBuf>,
+    mount: IpfMount,
+}
+
+/// Footer versions of one archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveVersion {
+    pub path: PathBuf,
+    pub version_to_patch: u32,
+    pub new_version: u32,
+}
+
+/// Build of a client installation as reported by `GameData::detect_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientVersion {
+    /// Highest `new_version` of any archive, the revision the client is patched to.
+    pub version: u32,
+    /// Archives in the order they are applied.
+    pub archives: Vec<ArchiveVersion>,
+    /// Archives patching a version no other archive produces, meaning a patch in
+    /// between is missing.
+    pub gaps: Vec<PathBuf>,
+    /// Numbers from the launcher's `release/*.revision.txt` files, keyed by file stem
+    /// (`data`, `release`).
+    pub revisions: BTreeMap<String, u32>,
+}
+
+impl ClientVersion {
+    /// Fails unless the client is patched to a version within `min..=max`, for tools
+    /// that only understand data of known builds.
+    pub fn ensure(&self, min: u32, max: u32) -> Result<()> {
+        if (min..=max).contains(&self.version) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedVersion {
+                format: "client",
+                version: self.version,
+            })
+        }
+    }
+}
+
+impl GameData {
+    /// Reads the file table of every archive under `client_dir`.
+    pub fn open<P: AsRef<Path>>(client_dir: P) -> Result<Self> {
+        Self::open_with(client_dir, &GameOptions::default())
+    }
+
+    /// Like `open`, decoding the text of each archive as `options` says.
+    pub fn open_with<P: AsRef<Path>>(client_dir: P, options: &GameOptions) -> Result<Self> {
+        let root = client_dir.as_ref().to_path_buf();
+        let mut archives = Vec::new();
+        for sub in ["data", "patch"] {
+            let dir = root.join(sub);
+            if dir.is_dir() {
+                archives.extend(list_archives(&dir)?);
+            }
+        }
+        if archives.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no .ipf archives found in '{}'", root.display()),
+            )
+            .into());
+        }
+
+        let task = Task::start(
+            Operation::Load,
+            &root.display().to_string(),
+            Some(archives.len() as u64),
+        );
+        let mut mount = IpfMount::new();
+        let mut load = || -> Result<()> {
+            for (index, archive) in archives.iter().enumerate() {
+                let text = options.text_for(archive);
+                let parse = ParseOptions::default()
+                    .with_text(text)
+                    .with_crypto(options.crypto);
+                let ipf = IPFFile::load_from_file_with_options(archive, &parse)?;
+                for warning in ipf.warnings() {
+                    task.warning(&format_args!("{}: {}", archive.display(), warning));
+                }
+                mount.insert(archive.clone(), &ipf, text);
+                task.progress(index as u64 + 1);
+            }
+            Ok(())
+        };
+        task.finish(load())?;
+        Ok(Self {
+            root,
+            archives,
+            mount,
+        })
+    }
+
+    /// Reports the client build from the archive footers and the launcher's revision
+    /// files.
+    pub fn detect_version(&self) -> Result<ClientVersion> {
+        let archives: Vec<ArchiveVersion> = self
+            .mount
+            .mounted()
+            .iter()
+            .map(|archive| ArchiveVersion {
+                path: archive.path.clone(),
+                version_to_patch: archive.version_to_patch,
+                new_version: archive.new_version,
+            })
+            .collect();
+
+        // The oldest base version has nothing before it in the client by design
+        let produced: HashSet<u32> = archives.iter().map(|a| a.new_version).collect();
+        let base = archives
+            .iter()
+            .map(|a| a.version_to_patch)
+            .min()
+            .unwrap_or(0);
+        let gaps = archives
+            .iter()
+            .filter(|a| a.version_to_patch != 0 && a.version_to_patch != base)
+            .filter(|a| !produced.contains(&a.version_to_patch))
+            .map(|a| a.path.clone())
+            .collect();
+
+        let mut revisions = BTreeMap::new();
+        let release = self.root.join("release");
+        if release.is_dir() {
+            for entry in std::fs::read_dir(&release)? {
+                let path = entry?.path();
+                let Some(stem) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".revision.txt"))
+                else {
+                    continue;
+                };
+                let text = std::fs::read_to_string(&path)?;
+                if let Some(revision) = text
+                    .split_whitespace()
+                    .next()
+                    .and_then(|token| token.parse().ok())
+                {
+                    revisions.insert(stem.to_string(), revision);
+                }
+            }
+        }
+
+        Ok(ClientVersion {
+            version: produced.iter().copied().max().unwrap_or(0),
+            archives,
+            gaps,
+            revisions,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Archives in the order they are applied.
+    pub fn archives(&self) -> &[PathBuf] {
+        &self.archives
+    }
+
+    /// The archives as one file system.
+    pub fn mount(&self) -> &IpfMount {
+        &self.mount
+    }
+
+    /// Keys of every entry, sorted.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.mount.paths()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mount.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mount.is_empty()
+    }
+
+    /// The winning entry for `path` (`container/path`, any case) and the archive that
+    /// holds it.
+    pub fn entry(&self, path: &str) -> Option<(&Path, &IPFFileTable)> {
+        self.mount.entry(path)
+    }
+
+    /// Options for parsing the entry at `path`, with the text decoding of the archive
+    /// that holds it.
+    pub fn parse_options(&self, path: &str) -> ParseOptions {
+        let text = self.mount.text(path).unwrap_or_default();
+        ParseOptions::default().with_text(text)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.mount.contains(path)
+    }
+
+    /// Extracts the winning entry for `path`.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        if !self.mount.contains(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no entry '{}' in '{}'", path, self.root.display()),
+            )
+            .into());
+        }
+        self.mount.open(path)
+    }
+}
+
+impl EntrySource for GameData {
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>> {
+        self.read(path)
+    }
+
+    fn parse_options(&self, path: &str) -> ParseOptions {
+        GameData::parse_options(self, path)
+    }
+}
+
+/// Key of an entry: lowercase `container/path`.
+pub(crate) fn entry_key(container_name: &str, directory_name: &str) -> String {
+    format!("{}/{}", container_name, directory_name).to_lowercase()
+}
+
+/// The `.ipf` files directly inside `dir`, sorted by file name so numbered patches
+/// come in order.
+pub(crate) fn list_archives(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_ipf = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ipf"));
+        if path.is_file() && is_ipf {
+            archives.push(path);
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn detects_version_and_missing_patches() {
+        let dir = std::env::temp_dir().join(format!("toslib-game-{}", std::process::id()));
+        let archives = [
+            ("data", "xml.ipf", 0, 100),
+            ("patch", "101.ipf", 100, 101),
+            ("patch", "103.ipf", 102, 103),
+        ];
+        for (sub, name, version_to_patch, new_version) in archives {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+            let mut writer = IPFWriter::new();
+            writer.versions(version_to_patch, new_version);
+            writer.add_file(name, "a.xml", b"<a/>".to_vec());
+            writer.save_to_file(dir.join(sub).join(name)).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("release")).unwrap();
+        std::fs::write(dir.join("release").join("data.revision.txt"), "103\n").unwrap();
+
+        let version = GameData::open(&dir).unwrap().detect_version().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(version.version, 103);
+        assert_eq!(version.gaps, [dir.join("patch").join("103.ipf")]);
+        assert_eq!(version.revisions.get("data"), Some(&103));
+        assert!(version.ensure(100, 103).is_ok());
+        assert!(version.ensure(104, u32::MAX).is_err());
+    }
+}
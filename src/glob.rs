@@ -0,0 +1,171 @@
+/// Shell-style pattern over `/`-separated entry paths, matched ignoring case like
+/// `GameData` keys.
+///
+/// `*` matches within one path component, `**` across components (`**/` also
+/// matches no directory at all), `?` one character other than `/`, and `[abc]`,
+/// `[a-z]` or `[!abc]` one character from a set. `\` in patterns and paths is read
+/// as `/`. A `[` without a closing `]` is a literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    DoubleStar,
+    /// `**/`, any run of whole directories including none
+    Directories,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = normalize(pattern).chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    if chars.get(i + 2) == Some(&'/') {
+                        i += 2;
+                        Token::Directories
+                    } else {
+                        i += 1;
+                        Token::DoubleStar
+                    }
+                }
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => match parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        i += len;
+                        token
+                    }
+                    None => Token::Char('['),
+                },
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Self {
+            pattern: pattern.to_string(),
+            tokens,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the whole of `path` matches.
+    pub fn matches(&self, path: &str) -> bool {
+        let chars: Vec<char> = normalize(path).chars().collect();
+        let mut memo = vec![None; (self.tokens.len() + 1) * (chars.len() + 1)];
+        self.match_from(0, 0, &chars, &mut memo)
+    }
+
+    fn match_from(
+        &self,
+        token: usize,
+        at: usize,
+        path: &[char],
+        memo: &mut [Option<bool>],
+    ) -> bool {
+        let slot = token * (path.len() + 1) + at;
+        if let Some(known) = memo[slot] {
+            return known;
+        }
+        let next = path.get(at).copied();
+        let matched = match self.tokens.get(token) {
+            None => next.is_none(),
+            Some(Token::Char(c)) => {
+                next == Some(*c) && self.match_from(token + 1, at + 1, path, memo)
+            }
+            Some(Token::Any) => {
+                next.is_some_and(|c| c != '/') && self.match_from(token + 1, at + 1, path, memo)
+            }
+            Some(Token::Class { negated, ranges }) => {
+                next.is_some_and(|c| {
+                    c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+                }) && self.match_from(token + 1, at + 1, path, memo)
+            }
+            Some(Token::Star) => {
+                self.match_from(token + 1, at, path, memo)
+                    || (next.is_some_and(|c| c != '/')
+                        && self.match_from(token, at + 1, path, memo))
+            }
+            Some(Token::DoubleStar) => {
+                self.match_from(token + 1, at, path, memo)
+                    || (next.is_some() && self.match_from(token, at + 1, path, memo))
+            }
+            Some(Token::Directories) => {
+                self.match_from(token + 1, at, path, memo)
+                    || (at..path.len())
+                        .filter(|&i| path[i] == '/')
+                        .any(|i| self.match_from(token + 1, i + 1, path, memo))
+            }
+        };
+        memo[slot] = Some(matched);
+        matched
+    }
+}
+
+/// A class after its `[`, and the number of characters it spans including `]`.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    let mut i = negated as usize;
+    let mut ranges = Vec::new();
+    // A `]` right after the opening bracket is part of the set
+    while let Some(&c) = chars.get(i) {
+        if c == ']' && i > negated as usize {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&hi| hi != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+/// `text` with `/` separators in lower case, the form paths are compared in.
+pub(crate) fn normalize(text: &str) -> String {
+    text.replace('\\', "/").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_components_and_directory_runs() {
+        let models = Glob::new("**/*.xac");
+        assert!(models.matches("char_hi/npc/Guard.XAC"));
+        assert!(models.matches("guard.xac"));
+        assert!(!models.matches("char_hi/guard.xac.bak"));
+
+        let direct = Glob::new("xml/*.xml");
+        assert!(direct.matches("XML\\item.xml"));
+        assert!(!direct.matches("xml/sub/item.xml"));
+
+        let anywhere = Glob::new("ies/**");
+        assert!(anywhere.matches("ies/a/b/c.ies"));
+        assert!(!Glob::new("**/a.xac").matches("xa.xac"));
+
+        let class = Glob::new("item_[0-9][!a-c]?.ies");
+        assert!(class.matches("item_1dx.ies"));
+        assert!(!class.matches("item_1bx.ies"));
+        assert!(Glob::new("a[b.txt").matches("a[b.txt"));
+    }
+}
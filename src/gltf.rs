@@ -0,0 +1,674 @@
+//! Binary glTF 2.0 writing for `XACFile::export_gltf`. The actor is first gathered into
+//! the plain structures below, already in glTF conventions, then written as one
+//! `.glb` holding a single buffer.
+use crate::error::{Error, Result};
+use crate::xac::{MorphTarget, SubMesh};
+use serde_json::{Map, Value, json};
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const UNSIGNED_SHORT: u32 = 5123;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+pub(crate) const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+/// A node of the skeleton in its bind pose, relative to its parent.
+pub(crate) struct GltfNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+pub(crate) struct GltfMaterial {
+    pub name: String,
+    /// URI of the base color image
+    pub texture: Option<String>,
+    pub base_color: [f32; 4],
+    pub double_sided: bool,
+}
+
+/// A submesh; its bone indices, when present, are joints of a skin over every node.
+pub(crate) struct GltfPrimitive {
+    pub submesh: SubMesh,
+    pub material: usize,
+}
+
+pub(crate) struct GltfMesh {
+    pub name: String,
+    /// Node a static mesh hangs from; skinned meshes sit at the root
+    pub node: Option<usize>,
+    pub primitives: Vec<GltfPrimitive>,
+    /// Blend shapes, with deltas for every primitive
+    pub morph_targets: Vec<MorphTarget>,
+}
+
+#[derive(Default)]
+pub(crate) struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+    pub materials: Vec<GltfMaterial>,
+    pub meshes: Vec<GltfMesh>,
+}
+
+/// The binary chunk with its buffer views and accessors.
+#[derive(Default)]
+struct Buffer {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffer {
+    /// Appends `bytes` as a buffer view with one accessor over it, and returns the
+    /// accessor index. `extra` holds further accessor fields such as `min` and `max`.
+    fn accessor(
+        &mut self,
+        bytes: Vec<u8>,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        target: Option<u32>,
+        extra: Map<String, Value>,
+    ) -> usize {
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bin.extend(bytes);
+        self.views.push(view);
+
+        let mut accessor = Map::new();
+        accessor.insert("bufferView".into(), json!(self.views.len() - 1));
+        accessor.insert("componentType".into(), json!(component_type));
+        accessor.insert("count".into(), json!(count));
+        accessor.insert("type".into(), json!(kind));
+        accessor.extend(extra);
+        self.accessors.push(Value::Object(accessor));
+        self.accessors.len() - 1
+    }
+
+    fn floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+        let bytes = values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.accessor(
+            bytes,
+            FLOAT,
+            values.len(),
+            kind,
+            Some(ARRAY_BUFFER),
+            Map::new(),
+        )
+    }
+}
+
+/// The scene as a `.glb` file.
+pub(crate) fn write_glb(scene: &GltfScene) -> Result<Vec<u8>> {
+    let mut buffer = Buffer::default();
+    let parents = acyclic_parents(&scene.nodes);
+
+    let mut nodes: Vec<Value> = scene
+        .nodes
+        .iter()
+        .map(|node| {
+            json!({
+                "name": node.name,
+                "translation": node.translation,
+                "rotation": node.rotation,
+                "scale": node.scale,
+            })
+        })
+        .collect();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); scene.nodes.len()];
+    let mut roots = Vec::new();
+    for (index, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(index),
+            None => roots.push(index),
+        }
+    }
+
+    let skinned = scene.meshes.iter().any(|mesh| {
+        mesh.primitives
+            .iter()
+            .any(|p| !p.submesh.bone_indices.is_empty())
+    });
+    let mut skins = Vec::new();
+    if skinned && !scene.nodes.is_empty() {
+        let matrices: Vec<[f32; 16]> = global_transforms(&scene.nodes, &parents)
+            .iter()
+            .map(inverse_affine)
+            .collect();
+        let inverse_bind_matrices = buffer.accessor(
+            matrices
+                .iter()
+                .flatten()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            FLOAT,
+            matrices.len(),
+            "MAT4",
+            None,
+            Map::new(),
+        );
+        skins.push(json!({
+            "joints": (0..scene.nodes.len()).collect::<Vec<_>>(),
+            "inverseBindMatrices": inverse_bind_matrices,
+        }));
+    }
+
+    let mut meshes = Vec::new();
+    for mesh in &scene.meshes {
+        let mut primitives = Vec::new();
+        for (index, primitive) in mesh.primitives.iter().enumerate() {
+            if let Some(primitive) =
+                write_primitive(&mut buffer, primitive, &mesh.morph_targets, index)?
+            {
+                primitives.push(primitive);
+            }
+        }
+        if primitives.is_empty() {
+            continue;
+        }
+        let mut node = json!({ "name": mesh.name, "mesh": meshes.len() });
+        let mesh_skinned = mesh
+            .primitives
+            .iter()
+            .any(|p| !p.submesh.bone_indices.is_empty());
+        if mesh_skinned && !skins.is_empty() {
+            node["skin"] = json!(0);
+        }
+        let mut value = json!({ "name": mesh.name, "primitives": primitives });
+        if !mesh.morph_targets.is_empty() {
+            value["weights"] = json!(vec![0.0; mesh.morph_targets.len()]);
+            value["extras"] = json!({
+                "targetNames": mesh
+                    .morph_targets
+                    .iter()
+                    .map(|target| target.name.as_str())
+                    .collect::<Vec<_>>(),
+            });
+        }
+        meshes.push(value);
+        let index = nodes.len();
+        nodes.push(node);
+        children.push(Vec::new());
+        match mesh
+            .node
+            .filter(|&node| node < scene.nodes.len() && !mesh_skinned)
+        {
+            Some(parent) => children[parent].push(index),
+            None => roots.push(index),
+        }
+    }
+    for (node, children) in nodes.iter_mut().zip(children) {
+        if !children.is_empty() {
+            node["children"] = json!(children);
+        }
+    }
+
+    let mut images = Vec::new();
+    let mut textures = Vec::new();
+    let materials: Vec<Value> = scene
+        .materials
+        .iter()
+        .map(|material| {
+            let mut pbr = json!({
+                "baseColorFactor": material.base_color,
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            });
+            if let Some(uri) = &material.texture {
+                pbr["baseColorTexture"] = json!({ "index": textures.len() });
+                textures.push(json!({ "source": images.len() }));
+                images.push(json!({ "uri": uri }));
+            }
+            let mut value = json!({
+                "name": material.name,
+                "pbrMetallicRoughness": pbr,
+                "doubleSided": material.double_sided,
+            });
+            if material.base_color[3] < 1.0 {
+                value["alphaMode"] = json!("BLEND");
+            }
+            value
+        })
+        .collect();
+
+    let mut root = Map::new();
+    root.insert(
+        "asset".into(),
+        json!({ "version": "2.0", "generator": "toslib" }),
+    );
+    root.insert("scene".into(), json!(0));
+    root.insert("scenes".into(), json!([{ "nodes": roots }]));
+    for (key, values) in [
+        ("nodes", nodes),
+        ("meshes", meshes),
+        ("skins", skins),
+        ("materials", materials),
+        ("textures", textures),
+        ("images", images),
+        ("accessors", buffer.accessors),
+        ("bufferViews", buffer.views),
+    ] {
+        if !values.is_empty() {
+            root.insert(key.into(), Value::Array(values));
+        }
+    }
+    if !buffer.bin.is_empty() {
+        root.insert(
+            "buffers".into(),
+            json!([{ "byteLength": buffer.bin.len() }]),
+        );
+    }
+
+    let mut json =
+        serde_json::to_vec(&root).map_err(|err| Error::Serialization(err.to_string()))?;
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = buffer.bin;
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let mut length = 12 + 8 + json.len();
+    if !bin.is_empty() {
+        length += 8 + bin.len();
+    }
+    let length = u32::try_from(length)
+        .map_err(|_| Error::Xac(format!("glTF file of {} bytes is too large", length)))?;
+    let mut glb = Vec::with_capacity(length as usize);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&length.to_le_bytes());
+    for (kind, chunk) in [(b"JSON", json), (b"BIN\0", bin)] {
+        if chunk.is_empty() {
+            continue;
+        }
+        glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(kind);
+        glb.extend(chunk);
+    }
+    Ok(glb)
+}
+
+/// The primitive's accessors and attributes, or `None` when it has no positions.
+/// Attributes whose count differs from the positions are left out. `index` picks the
+/// primitive's deltas out of the morph targets of its mesh.
+fn write_primitive(
+    buffer: &mut Buffer,
+    primitive: &GltfPrimitive,
+    morph_targets: &[MorphTarget],
+    index: usize,
+) -> Result<Option<Value>> {
+    let submesh = &primitive.submesh;
+    let count = submesh.positions.len();
+    if count == 0 {
+        return Ok(None);
+    }
+    if let Some(&index) = submesh
+        .indices
+        .iter()
+        .find(|&&index| index as usize >= count)
+    {
+        return Err(Error::Xac(format!(
+            "Index {} beyond the {} vertices of its submesh",
+            index, count
+        )));
+    }
+
+    let mut attributes = Map::new();
+    let positions = buffer.accessor(
+        submesh
+            .positions
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect(),
+        FLOAT,
+        count,
+        "VEC3",
+        Some(ARRAY_BUFFER),
+        bounds(&submesh.positions),
+    );
+    attributes.insert("POSITION".into(), json!(positions));
+    if submesh.normals.len() == count {
+        attributes.insert(
+            "NORMAL".into(),
+            json!(buffer.floats(&submesh.normals, "VEC3")),
+        );
+    }
+    if submesh.tangents.len() == count {
+        attributes.insert(
+            "TANGENT".into(),
+            json!(buffer.floats(&submesh.tangents, "VEC4")),
+        );
+    }
+    if submesh.uvcoords.len() == count {
+        attributes.insert(
+            "TEXCOORD_0".into(),
+            json!(buffer.floats(&submesh.uvcoords, "VEC2")),
+        );
+    }
+    let colors: Vec<[f32; 4]> = if submesh.colors128.len() == count {
+        submesh.colors128.clone()
+    } else if submesh.colors32.len() == count {
+        submesh
+            .colors32
+            .iter()
+            .map(|color| {
+                color
+                    .to_le_bytes()
+                    .map(|channel| f32::from(channel) / 255.0)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if !colors.is_empty() {
+        attributes.insert("COLOR_0".into(), json!(buffer.floats(&colors, "VEC4")));
+    }
+    if submesh.bone_indices.len() == count && submesh.bone_weights.len() == count {
+        let joints = buffer.accessor(
+            submesh
+                .bone_indices
+                .iter()
+                .flatten()
+                .flat_map(|joint| joint.to_le_bytes())
+                .collect(),
+            UNSIGNED_SHORT,
+            count,
+            "VEC4",
+            Some(ARRAY_BUFFER),
+            Map::new(),
+        );
+        attributes.insert("JOINTS_0".into(), json!(joints));
+        attributes.insert(
+            "WEIGHTS_0".into(),
+            json!(buffer.floats(&submesh.bone_weights, "VEC4")),
+        );
+    }
+
+    // Mirroring the geometry turns the triangles inside out, so their winding is
+    // reversed like the OBJ export does
+    let indices: Vec<u8> = submesh
+        .indices
+        .chunks_exact(3)
+        .flat_map(|face| [face[2], face[1], face[0]])
+        .flat_map(|index| index.to_le_bytes())
+        .collect();
+    let index_count = indices.len() / 4;
+    // Every primitive of a mesh needs the same targets, so missing deltas are zeros
+    let deltas = |deltas: &[Vec<[f32; 3]>]| {
+        let mut deltas = deltas.get(index).cloned().unwrap_or_default();
+        deltas.resize(count, [0.0; 3]);
+        deltas
+    };
+    let targets: Vec<Value> = morph_targets
+        .iter()
+        .map(|target| {
+            let positions = deltas(&target.position_deltas);
+            let mut attributes = Map::new();
+            let accessor = buffer.accessor(
+                positions
+                    .iter()
+                    .flatten()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect(),
+                FLOAT,
+                count,
+                "VEC3",
+                Some(ARRAY_BUFFER),
+                bounds(&positions),
+            );
+            attributes.insert("POSITION".into(), json!(accessor));
+            if submesh.normals.len() == count {
+                let normals = deltas(&target.normal_deltas);
+                attributes.insert("NORMAL".into(), json!(buffer.floats(&normals, "VEC3")));
+            }
+            if submesh.tangents.len() == count {
+                // Morph target tangents carry no handedness
+                let tangents = deltas(&target.tangent_deltas);
+                attributes.insert("TANGENT".into(), json!(buffer.floats(&tangents, "VEC3")));
+            }
+            Value::Object(attributes)
+        })
+        .collect();
+
+    let mut value = json!({ "attributes": attributes, "material": primitive.material });
+    if !targets.is_empty() {
+        value["targets"] = json!(targets);
+    }
+    if index_count > 0 {
+        value["indices"] = json!(buffer.accessor(
+            indices,
+            UNSIGNED_INT,
+            index_count,
+            "SCALAR",
+            Some(ELEMENT_ARRAY_BUFFER),
+            Map::new(),
+        ));
+    }
+    Ok(Some(value))
+}
+
+/// The `min` and `max` of an accessor over positions, which glTF requires.
+fn bounds(positions: &[[f32; 3]]) -> Map<String, Value> {
+    let (min, max) = positions.iter().fold(
+        ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+        |(min, max), position| {
+            (
+                std::array::from_fn(|i| min[i].min(position[i])),
+                std::array::from_fn(|i| max[i].max(position[i])),
+            )
+        },
+    );
+    let mut bounds = Map::new();
+    bounds.insert("min".into(), json!(min));
+    bounds.insert("max".into(), json!(max));
+    bounds
+}
+
+/// Parents of the nodes with out of range parents and cycles cut, so every node
+/// leads up to a root.
+pub(crate) fn acyclic_parents(nodes: &[GltfNode]) -> Vec<Option<usize>> {
+    let mut parents: Vec<Option<usize>> = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| node.parent.filter(|&p| p < nodes.len() && p != index))
+        .collect();
+    for index in 0..parents.len() {
+        let mut steps = 0;
+        let mut next = parents[index];
+        while let Some(parent) = next {
+            steps += 1;
+            if steps > parents.len() {
+                parents[index] = None;
+                break;
+            }
+            next = parents[parent];
+        }
+    }
+    parents
+}
+
+/// Column-major transform of every node relative to the scene root.
+pub(crate) fn global_transforms(nodes: &[GltfNode], parents: &[Option<usize>]) -> Vec<[f32; 16]> {
+    let mut globals: Vec<Option<[f32; 16]>> = vec![None; nodes.len()];
+    for index in 0..nodes.len() {
+        let mut chain = Vec::new();
+        let mut next = Some(index);
+        while let Some(node) = next.filter(|&node| globals[node].is_none()) {
+            chain.push(node);
+            next = parents[node];
+        }
+        for node in chain.into_iter().rev() {
+            let local = trs(&nodes[node]);
+            let parent = parents[node].and_then(|parent| globals[parent]);
+            globals[node] = Some(match parent {
+                Some(parent) => multiply(&parent, &local),
+                None => local,
+            });
+        }
+    }
+    globals
+        .into_iter()
+        .map(|global| global.unwrap_or(IDENTITY))
+        .collect()
+}
+
+pub(crate) fn trs(node: &GltfNode) -> [f32; 16] {
+    let [x, y, z, w] = node.rotation;
+    let length = (x * x + y * y + z * z + w * w).sqrt();
+    let [x, y, z, w] = if length > 0.0 {
+        node.rotation.map(|value| value / length)
+    } else {
+        [0.0, 0.0, 0.0, 1.0]
+    };
+    let [sx, sy, sz] = node.scale;
+    let [tx, ty, tz] = node.translation;
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * sx,
+        2.0 * (x * y + z * w) * sx,
+        2.0 * (x * z - y * w) * sx,
+        0.0,
+        2.0 * (x * y - z * w) * sy,
+        (1.0 - 2.0 * (x * x + z * z)) * sy,
+        2.0 * (y * z + x * w) * sy,
+        0.0,
+        2.0 * (x * z + y * w) * sz,
+        2.0 * (y * z - x * w) * sz,
+        (1.0 - 2.0 * (x * x + y * y)) * sz,
+        0.0,
+        tx,
+        ty,
+        tz,
+        1.0,
+    ]
+}
+
+fn multiply(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+    std::array::from_fn(|i| {
+        let (column, row) = (i / 4, i % 4);
+        (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum()
+    })
+}
+
+pub(crate) fn transform_point(m: &[f32; 16], [x, y, z]: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|row| m[row] * x + m[4 + row] * y + m[8 + row] * z + m[12 + row])
+}
+
+/// Applies the linear part of `m`, leaving out the translation.
+pub(crate) fn transform_vector(m: &[f32; 16], [x, y, z]: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|row| m[row] * x + m[4 + row] * y + m[8 + row] * z)
+}
+
+/// The matrix normals are transformed by, the inverse transpose of `m`, which keeps
+/// them perpendicular to the surface under non-uniform scale.
+pub(crate) fn normal_matrix(m: &[f32; 16]) -> [f32; 16] {
+    let inverse = inverse_affine(m);
+    std::array::from_fn(|i| {
+        let (column, row) = (i / 4, i % 4);
+        match (column, row) {
+            (3, 3) => 1.0,
+            (3, _) | (_, 3) => 0.0,
+            (column, row) => inverse[row * 4 + column],
+        }
+    })
+}
+
+pub(crate) fn normalized(v: [f32; 3]) -> [f32; 3] {
+    let length = v.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if length > 0.0 {
+        v.map(|value| value / length)
+    } else {
+        v
+    }
+}
+
+/// Inverse of a transform without projection; the identity for singular ones.
+pub(crate) fn inverse_affine(m: &[f32; 16]) -> [f32; 16] {
+    let a = |row: usize, column: usize| m[column * 4 + row];
+    let cofactor = |row: usize, column: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        a(r0, c0) * a(r1, c1) - a(r0, c1) * a(r1, c0)
+    };
+    let det: f32 = (0..3)
+        .map(|column| a(0, column) * cofactor(0, column))
+        .sum();
+    if det.abs() <= f32::EPSILON {
+        return IDENTITY;
+    }
+    // The inverse is the transposed cofactor matrix over the determinant
+    let inverse = |row: usize, column: usize| cofactor(column, row) / det;
+    let translation: [f32; 3] =
+        std::array::from_fn(|row| -(0..3).map(|k| inverse(row, k) * m[12 + k]).sum::<f32>());
+    std::array::from_fn(|i| {
+        let (column, row) = (i / 4, i % 4);
+        match (column, row) {
+            (3, 3) => 1.0,
+            (3, row) => translation[row],
+            (_, 3) => 0.0,
+            (column, row) => inverse(row, column),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_bind_matrices_undo_the_bind_pose() {
+        let nodes = [
+            GltfNode {
+                name: "root".into(),
+                parent: None,
+                translation: [1.0, 2.0, 3.0],
+                rotation: [0.0, 0.5f32.sqrt(), 0.0, 0.5f32.sqrt()],
+                scale: [2.0, 2.0, 2.0],
+            },
+            GltfNode {
+                name: "child".into(),
+                parent: Some(0),
+                translation: [0.0, 1.0, 0.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [1.0, 1.0, 1.0],
+            },
+        ];
+        let parents = acyclic_parents(&nodes);
+        let globals = global_transforms(&nodes, &parents);
+        assert_eq!(&globals[1][12..15], &[1.0, 4.0, 3.0]);
+        for global in &globals {
+            let product = multiply(global, &inverse_affine(global));
+            for (value, expected) in product.iter().zip(IDENTITY) {
+                assert!((value - expected).abs() < 1e-5, "{:?}", product);
+            }
+        }
+
+        let cyclic = [
+            GltfNode {
+                parent: Some(1),
+                ..nodes.into_iter().next().unwrap()
+            },
+            GltfNode {
+                name: "loop".into(),
+                parent: Some(0),
+                translation: [0.0; 3],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [1.0; 3],
+            },
+        ];
+        assert_eq!(acyclic_parents(&cyclic), [None, Some(0)]);
+    }
+}
@@ -1,23 +1,49 @@
 #![allow(dead_code)]
+use crate::error::{Error, Result};
+use crate::mount::EntrySource;
+use crate::options::{ConflictPolicy, ParseOptions};
+use crate::partial::Parsed;
+use crate::query::{IESIndex, IESQuery};
+use crate::serial;
+use crate::text::TextDecoding;
 use crate::tosreader::BinaryReader;
+use crate::warning::ParseWarning;
+use rayon::prelude::*;
+use serde::de::value::{Error as DeError, MapDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 const HEADER_NAME: usize = 128;
 const DATA_NAME: usize = 64;
+// Two encrypted names, type, flags and position
+const COLUMN_SIZE: usize = DATA_NAME * 2 + 2 + 4 + 2;
+// Name, three offsets and the file size, six counts
+const HEADER_SIZE: usize = HEADER_NAME + 4 * 4 + 6 * 2;
+// Below this, spreading rows over threads costs more than it saves
+const PARALLEL_MIN_ROWS: usize = 256;
 
-#[derive(Debug, Serialize, Deserialize, Ord, PartialOrd, PartialEq, Eq)]
-enum IESColumnType {
+/// Storage kind of a column: numbers, or strings in one of two sections.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Ord, PartialOrd, PartialEq, Eq)]
+pub enum IESColumnType {
     Float,
     String,
     StringSecond,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 struct IESHeader {
     name: String,
+    /// Fields of unknown meaning, written back as read: the word after the name and
+    /// the half-words before the row count and after the string column count
+    #[serde(default)]
+    reserved: (u32, u16, u16),
     data_offset: u32,
     resource_offset: u32,
     file_size: u32,
@@ -27,11 +53,15 @@ struct IESHeader {
     string_column_count: u16,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq)]
-struct IESColumn {
+/// Definition of a column, see `IESFile::column`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq)]
+pub struct IESColumn {
     name: String,
     name_second: String,
     column_type: IESColumnType,
+    /// Access and sync flags, kept for writing the column back
+    #[serde(default)]
+    flags: u32,
     position: u16,
 }
 
@@ -41,10 +71,30 @@ impl Default for IESColumn {
             name: "".to_string(),
             name_second: "".to_string(),
             column_type: IESColumnType::Float,
+            flags: 0,
             position: 0,
         }
     }
 }
+impl IESColumn {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The alternative name, which also finds the column.
+    pub fn name_second(&self) -> &str {
+        &self.name_second
+    }
+
+    pub fn column_type(&self) -> IESColumnType {
+        self.column_type
+    }
+
+    pub fn is_number(&self) -> bool {
+        self.column_type == IESColumnType::Float
+    }
+}
+
 impl Ord for IESColumn {
     /// Implements ordering for `IESColumn` based on column type and position.
     /// This is used for sorting columns, making it easier to navigate when viewing data in tables.
@@ -79,179 +129,1250 @@ impl PartialEq for IESColumn {
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-pub struct IESRow {
-    value_float: Option<f32>,
-    value_int: Option<u32>,
-    value_string: Option<String>,
+/// One cell of a table.
+///
+/// Number columns hold `f32`s on disk; whole numbers read as `Int` and everything
+/// else, fractions and non-finite values included, as `Float` with its exact bits.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum IESValue {
+    Float(f32),
+    Int(i64),
+    Str(String),
+    /// Empty string cell
+    #[default]
+    Null,
+}
+
+impl IESValue {
+    /// Classifies a number cell as read from disk.
+    fn from_f32(value: f32) -> Self {
+        let whole = value as i64;
+        // Comparing bits also keeps -0.0, NaN and saturated values as floats
+        if (whole as f32).to_bits() == value.to_bits() {
+            IESValue::Int(whole)
+        } else {
+            IESValue::Float(value)
+        }
+    }
+
+    /// Numbers of either kind; `None` for strings and empty cells.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            IESValue::Float(value) => Some(*value),
+            IESValue::Int(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+
+    /// Whole numbers only; fractions are not rounded.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            IESValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            IESValue::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, IESValue::Null)
+    }
+
+    /// The cell as text, numbers formatted as written; `None` for empty cells.
+    pub(crate) fn text(&self) -> Option<String> {
+        match self {
+            IESValue::Float(value) => Some(value.to_string()),
+            IESValue::Int(value) => Some(value.to_string()),
+            IESValue::Str(value) => Some(value.clone()),
+            IESValue::Null => None,
+        }
+    }
+
+    /// The cell as a JSON number or string; empty cells are null.
+    #[cfg(feature = "daemon")]
+    pub(crate) fn to_json_value(&self) -> serde_json::Value {
+        match self {
+            IESValue::Float(value) => (*value).into(),
+            IESValue::Int(value) => (*value).into(),
+            IESValue::Str(value) => value.as_str().into(),
+            IESValue::Null => serde_json::Value::Null,
+        }
+    }
+}
+
+/// Which of a column's two names heads it in CSV.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvHeader {
+    #[default]
+    Name,
+    NameSecond,
+}
+
+/// Settings of `IESFile::to_csv` and `IESFile::from_csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Field separator, `,` by default; `;` suits spreadsheets of locales with a
+    /// decimal comma
+    pub delimiter: char,
+    pub header: CsvHeader,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: CsvHeader::Name,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_header(mut self, header: CsvHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    fn column_name<'a>(&self, column: &'a IESColumn) -> &'a str {
+        match self.header {
+            CsvHeader::Name => &column.name,
+            CsvHeader::NameSecond => &column.name_second,
+        }
+    }
+}
+
+/// Numbers as written and strings as they are; empty cells print nothing.
+impl std::fmt::Display for IESValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IESValue::Float(value) => write!(f, "{}", value),
+            IESValue::Int(value) => write!(f, "{}", value),
+            IESValue::Str(value) => f.write_str(value),
+            IESValue::Null => Ok(()),
+        }
+    }
+}
+
+impl From<i64> for IESValue {
+    fn from(value: i64) -> Self {
+        IESValue::Int(value)
+    }
+}
+
+impl From<i32> for IESValue {
+    fn from(value: i32) -> Self {
+        IESValue::Int(value.into())
+    }
+}
+
+impl From<u32> for IESValue {
+    fn from(value: u32) -> Self {
+        IESValue::Int(value.into())
+    }
+}
+
+impl From<f32> for IESValue {
+    fn from(value: f32) -> Self {
+        IESValue::Float(value)
+    }
+}
+
+impl From<f64> for IESValue {
+    fn from(value: f64) -> Self {
+        IESValue::Float(value as f32)
+    }
+}
+
+impl From<&str> for IESValue {
+    fn from(value: &str) -> Self {
+        IESValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for IESValue {
+    fn from(value: String) -> Self {
+        IESValue::Str(value)
+    }
+}
+
+/// Conversion of a cell for `IESRowView::get`. Numbers convert only when the value
+/// fits the type; strings convert to `String` only.
+pub trait FromIESValue: Sized {
+    fn from_ies_value(value: &IESValue) -> Option<Self>;
+}
+
+impl FromIESValue for f32 {
+    fn from_ies_value(value: &IESValue) -> Option<Self> {
+        value.as_f32()
+    }
+}
+
+impl FromIESValue for f64 {
+    fn from_ies_value(value: &IESValue) -> Option<Self> {
+        value.as_f32().map(f64::from)
+    }
+}
+
+macro_rules! from_ies_integer {
+    ($($ty:ty),*) => {$(
+        impl FromIESValue for $ty {
+            fn from_ies_value(value: &IESValue) -> Option<Self> {
+                value.as_i64().and_then(|value| <$ty>::try_from(value).ok())
+            }
+        }
+    )*};
+}
+
+from_ies_integer!(i64, i32, u32, u16, u8);
+
+impl FromIESValue for String {
+    fn from_ies_value(value: &IESValue) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+/// One row of an `IESFile`, with its cells found by column name.
+#[derive(Debug, Clone, Copy)]
+pub struct IESRowView<'a> {
+    file: &'a IESFile,
+    index: usize,
+}
+
+impl<'a> IESRowView<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Class id stored in front of the row's cells.
+    pub fn class_id(&self) -> u32 {
+        self.key().map_or(0, |key| key.class_id)
+    }
+
+    /// Class name stored in front of the row's cells.
+    pub fn class_name(&self) -> &'a str {
+        self.key().map_or("", |key| &key.class_name)
+    }
+
+    fn key(&self) -> Option<&'a IESRowKey> {
+        self.file.row_keys.get(self.index)
+    }
+
+    /// The cell of `column`, by its first or second name.
+    pub fn value(&self, column: &str) -> Option<&'a IESValue> {
+        let column = self.file.get_column_index_by_name(column)?;
+        Some(&self.file.rows[self.index][column])
+    }
+
+    pub(crate) fn value_at(&self, column: usize) -> &'a IESValue {
+        &self.file.rows[self.index][column]
+    }
+
+    /// The cell of `column` as a `T`, e.g. `row.get::<f32>("Level")`; `None` when the
+    /// column is missing, the cell is empty or it does not convert.
+    pub fn get<T: FromIESValue>(&self, column: &str) -> Option<T> {
+        T::from_ies_value(self.value(column)?)
+    }
+
+    /// Columns and their cells, in cell order.
+    pub fn cells(&self) -> impl Iterator<Item = (&'a IESColumn, &'a IESValue)> {
+        self.file.columns.iter().zip(&self.file.rows[self.index])
+    }
+
+    /// The string cell of `column` as the bytes stored in the file, decrypted but not
+    /// decoded, so CP949 text comes back as CP949 whatever `TextDecoding` read it.
+    /// Cells that were UTF-8 or were set after loading give their UTF-8 text; `None`
+    /// for a missing or number column.
+    pub fn raw(&self, column: &str) -> Option<Cow<'a, [u8]>> {
+        let index = self.file.get_column_index_by_name(column)?;
+        let key = self.key().unwrap_or(&EMPTY_KEY);
+        key.raw_bytes(index, &self.file.columns[index], self.value_at(index))
+    }
+}
+
+/// A table whose rows stay on disk until iterated, see `IESFile::open_lazy`. Only the
+/// header and column definitions are held in memory.
+pub struct LazyIESFile<R: Read + Seek = BufReader<std::fs::File>> {
+    /// Header and columns, no rows
+    table: IESFile,
+    reader: BinaryReader<R>,
+    rows_start: u64,
+    text: TextDecoding,
+}
+
+impl<R: Read + Seek> LazyIESFile<R> {
+    /// Reads the header and columns of the table `reader` holds.
+    pub fn from_reader(reader: R, options: &ParseOptions) -> Result<Self> {
+        let mut reader = BinaryReader::new(reader);
+        let mut table = IESFile::default();
+        table.read_header(&mut reader, options)?;
+        table.read_columns(&mut reader, options)?;
+        let rows_start = table.data_end(reader.file_size()?) - table.header.resource_offset as u64;
+        Ok(Self {
+            table,
+            reader,
+            rows_start,
+            text: options.text,
+        })
+    }
+
+    /// Rows the header declares.
+    pub fn row_count(&self) -> usize {
+        self.table.header.row_count as usize
+    }
+
+    pub fn column(&self, name: &str) -> Option<&IESColumn> {
+        self.table.column(name)
+    }
+
+    pub fn columns(&self) -> &[IESColumn] {
+        &self.table.columns
+    }
+
+    /// Problems with the header that were recovered from in lenient mode.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.table.warnings
+    }
+
+    /// Reads the rows from the first one on, one per step. Iteration ends after the
+    /// first error, which carries the row and offset like an eager load reports them.
+    pub fn rows(&mut self) -> LazyRows<'_, R> {
+        LazyRows {
+            table: &self.table,
+            reader: &mut self.reader,
+            start: Some(self.rows_start),
+            text: self.text,
+            index: 0,
+        }
+    }
+}
+
+/// Iterator of `LazyIESFile::rows`.
+pub struct LazyRows<'a, R: Read + Seek> {
+    table: &'a IESFile,
+    reader: &'a mut BinaryReader<R>,
+    /// Where to seek before the first row
+    start: Option<u64>,
+    text: TextDecoding,
+    index: usize,
+}
+
+impl<'a, R: Read + Seek> Iterator for LazyRows<'a, R> {
+    type Item = Result<LazyRow<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.table.header.row_count as usize {
+            return None;
+        }
+        let index = self.index;
+        let row = (|| {
+            if let Some(start) = self.start.take() {
+                self.reader.seek(SeekFrom::Start(start))?;
+            }
+            self.table.read_row(self.reader, index, 0, self.text)
+        })();
+        self.index = match row {
+            Ok(_) => index + 1,
+            Err(_) => usize::MAX,
+        };
+        Some(row.map(|(key, cells)| LazyRow {
+            table: self.table,
+            index,
+            key,
+            cells,
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.table.header.row_count as usize).saturating_sub(self.index);
+        (0, Some(left))
+    }
+}
+
+/// A row read by `LazyRows`, owning its cells.
+#[derive(Debug, Clone)]
+pub struct LazyRow<'a> {
+    table: &'a IESFile,
+    index: usize,
+    key: IESRowKey,
+    cells: Vec<IESValue>,
+}
+
+impl LazyRow<'_> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn class_id(&self) -> u32 {
+        self.key.class_id
+    }
+
+    pub fn class_name(&self) -> &str {
+        &self.key.class_name
+    }
+
+    /// The cell of `column`, by its first or second name.
+    pub fn value(&self, column: &str) -> Option<&IESValue> {
+        self.cells.get(self.table.get_column_index_by_name(column)?)
+    }
+
+    /// The cell of `column` as a `T`, see `IESRowView::get`.
+    pub fn get<T: FromIESValue>(&self, column: &str) -> Option<T> {
+        T::from_ies_value(self.value(column)?)
+    }
+
+    /// Columns and their cells, in cell order.
+    pub fn cells(&self) -> impl Iterator<Item = (&IESColumn, &IESValue)> {
+        self.table.columns.iter().zip(&self.cells)
+    }
+
+    /// The string cell of `column` as stored in the file, see `IESRowView::raw`.
+    pub fn raw(&self, column: &str) -> Option<Cow<'_, [u8]>> {
+        let index = self.table.get_column_index_by_name(column)?;
+        self.key
+            .raw_bytes(index, &self.table.columns[index], &self.cells[index])
+    }
+
+    /// The cells in column order.
+    pub fn into_values(self) -> Vec<IESValue> {
+        self.cells
+    }
+}
+
+/// What precedes and follows the cells of a row on disk.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IESRowKey {
+    class_id: u32,
+    class_name: String,
+    /// One byte per string column after the cells
+    flags: Vec<u8>,
+    /// Decrypted bytes of the string cells that do not read back as their text, such
+    /// as CP949 or invalid UTF-8, by column index. Written back in place of the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    raw: Vec<(usize, Vec<u8>)>,
+}
+
+static EMPTY_KEY: IESRowKey = IESRowKey {
+    class_id: 0,
+    class_name: String::new(),
+    flags: Vec::new(),
+    raw: Vec::new(),
+};
+
+impl IESRowKey {
+    fn raw_cell(&self, column: usize) -> Option<&[u8]> {
+        self.raw
+            .iter()
+            .find(|(index, _)| *index == column)
+            .map(|(_, bytes)| bytes.as_slice())
+    }
+
+    /// The bytes of a string cell as stored in the file; `None` for number columns.
+    fn raw_bytes<'a>(
+        &'a self,
+        index: usize,
+        column: &IESColumn,
+        cell: &'a IESValue,
+    ) -> Option<Cow<'a, [u8]>> {
+        if column.is_number() {
+            return None;
+        }
+        Some(match (self.raw_cell(index), cell) {
+            (Some(bytes), _) => Cow::Borrowed(bytes),
+            (None, IESValue::Str(text)) => Cow::Borrowed(text.as_bytes()),
+            (None, cell) => Cow::Owned(cell.text().unwrap_or_default().into_bytes()),
+        })
+    }
+
+    /// Forgets the stored bytes of a cell whose value changed.
+    fn clear_raw(&mut self, column: usize) {
+        self.raw.retain(|(index, _)| *index != column);
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct IESFile {
     header: IESHeader,
     columns: Vec<IESColumn>,
-    rows: Vec<Vec<IESRow>>,
+    rows: Vec<Vec<IESValue>>,
+    /// Parallel to `rows`
+    #[serde(default)]
+    row_keys: Vec<IESRowKey>,
+    #[serde(skip)]
+    warnings: Vec<ParseWarning>,
 }
 
 impl IESFile {
-    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> io::Result<Self> {
-        let file = std::fs::File::open(file_path)?;
-        let mut buf_reader = BufReader::new(file);
-        let mut binary_reader = BinaryReader::new(&mut buf_reader);
-        Self::load_from_reader(&mut binary_reader)
+    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        Self::load_from_file_with_options(file_path, &ParseOptions::default())
     }
 
-    pub fn load_from_bytes(mut bytes: Vec<u8>) -> io::Result<Self> {
+    pub fn load_from_bytes(mut bytes: Vec<u8>) -> Result<Self> {
         let cursor = Cursor::new(&mut bytes);
         let mut binary_reader = BinaryReader::new(cursor);
         Self::load_from_reader(&mut binary_reader)
     }
 
-    fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> io::Result<Self> {
+    /// Parses a table from a borrowed buffer. Any byte slice is accepted and
+    /// malformed input yields an error, which makes this the entry point for fuzzing.
+    pub fn load_from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_from_reader(&mut binary_reader)
+    }
+
+    /// Reads the table stored under `path` in an archive on disk, an `IpfMount` or a
+    /// `GameData`, e.g. `IESFile::from_ipf(Path::new("data/ies.ipf"),
+    /// "ies.ipf/item.ies")`. Errors name the entry, and the archive when reading it
+    /// fails.
+    pub fn from_ipf<S: EntrySource + ?Sized>(source: &S, path: &str) -> Result<Self> {
+        let bytes = source.read_entry(path)?;
+        Self::load_from_slice_with_options(&bytes, &source.parse_options(path))
+            .map_err(|err| err.in_entry(path))
+    }
+
+    /// Checks a table without parsing it into rows: the header counts and sizes against
+    /// what the column and row sections hold, column types and names, and every cell
+    /// length against the end of the data. Never fails; a damaged table gives a report
+    /// naming each problem and where it is, instead of the first error of a load.
+    pub fn validate(bytes: &[u8]) -> IesValidation {
+        let mut report = IesValidation::default();
+        let size = bytes.len() as u64;
+        let u16_at = |pos: usize| {
+            bytes
+                .get(pos..pos + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        let u32_at = |pos: usize| {
+            bytes
+                .get(pos..pos + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        if bytes.len() < HEADER_SIZE {
+            report.issues.push(IesIssue::TruncatedHeader { size });
+            return report;
+        }
+        let mut pos = HEADER_NAME + 4;
+        let mut next_u32 = || {
+            pos += 4;
+            u32_at(pos - 4).unwrap_or_default()
+        };
+        let (data_offset, resource_offset, file_size) = (next_u32(), next_u32(), next_u32());
+        let counts: Vec<u16> = (0..4)
+            .map(|i| u16_at(HEADER_NAME + 18 + 2 * i).unwrap_or_default())
+            .collect();
+        let (row_count, column_count) = (counts[0] as usize, counts[1] as usize);
+        let (number_count, string_count) = (counts[2] as u64, counts[3] as u64);
+        report.declared_rows = row_count;
+
+        let mismatch = |report: &mut IesValidation, field, declared: u64, actual: u64| {
+            if declared != actual {
+                report.issues.push(IesIssue::CountMismatch {
+                    field,
+                    declared,
+                    actual,
+                });
+            }
+        };
+        mismatch(&mut report, "file size", file_size as u64, size);
+        mismatch(
+            &mut report,
+            "column section size",
+            data_offset as u64,
+            column_count as u64 * COLUMN_SIZE as u64,
+        );
+        let end = size.max(file_size as u64);
+        let Some(columns_start) = end.checked_sub(resource_offset as u64 + data_offset as u64)
+        else {
+            report.issues.push(IesIssue::SectionOutOfBounds {
+                data_offset,
+                resource_offset,
+                size,
+            });
+            return report;
+        };
+
+        // Columns, as (type, position, name) in the order the parser sorts them
+        let mut columns = Vec::with_capacity(column_count);
+        for index in 0..column_count {
+            let start = columns_start as usize + index * COLUMN_SIZE;
+            let (Some(name), Some(column_type)) = (
+                bytes.get(start..start + DATA_NAME),
+                u16_at(start + 2 * DATA_NAME),
+            ) else {
+                report.issues.push(IesIssue::CountMismatch {
+                    field: "column count",
+                    declared: column_count as u64,
+                    actual: index as u64,
+                });
+                break;
+            };
+            if column_type > 2 {
+                report.issues.push(IesIssue::BadColumnType {
+                    index,
+                    offset: start as u64,
+                    value: column_type,
+                });
+            }
+            let position = u16_at(start + 2 * DATA_NAME + 6).unwrap_or_default();
+            let name = Self::decrypt_string(name, TextDecoding::default()).unwrap_or_default();
+            columns.push((column_type, position, name));
+        }
+        let numbers = columns.iter().filter(|column| column.0 == 0).count() as u64;
+        let strings = columns.iter().filter(|column| column.0 != 0).count() as u64;
+        mismatch(&mut report, "number column count", number_count, numbers);
+        mismatch(&mut report, "string column count", string_count, strings);
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, _, name) in &columns {
+            *names.entry(name).or_default() += 1;
+        }
+        for (name, count) in names {
+            if count > 1 {
+                report.issues.push(IesIssue::DuplicateColumn {
+                    name: name.to_string(),
+                    count,
+                });
+            }
+        }
+        columns.sort_by_key(|column| (column.0, column.1));
+
+        // Rows, following the length prefixes up to the end of the file
+        let rows_start = (end - resource_offset as u64) as usize;
+        let mut pos = rows_start;
+        'rows: for row in 0..row_count {
+            let row_start = pos;
+            let cut = |report: &mut IesValidation| {
+                report.issues.push(IesIssue::TruncatedRow {
+                    row,
+                    offset: row_start as u64,
+                })
+            };
+            let Some(name_length) = u16_at(pos + 4) else {
+                cut(&mut report);
+                break;
+            };
+            pos += 6 + name_length as usize;
+            for (column_type, _, name) in &columns {
+                if *column_type == 0 {
+                    pos += 4;
+                    continue;
+                }
+                let Some(length) = u16_at(pos) else {
+                    cut(&mut report);
+                    break 'rows;
+                };
+                let available = bytes.len().saturating_sub(pos + 2);
+                if length as usize > available {
+                    report.issues.push(IesIssue::TruncatedCell {
+                        row,
+                        column: name.clone(),
+                        offset: pos as u64,
+                        length: length as u64,
+                        available: available as u64,
+                    });
+                    break 'rows;
+                }
+                pos += 2 + length as usize;
+            }
+            pos += strings as usize;
+            if pos > bytes.len() {
+                cut(&mut report);
+                break;
+            }
+            report.rows_read += 1;
+        }
+
+        if report.rows_read == row_count {
+            let consumed = (pos - rows_start) as u64;
+            if consumed != resource_offset as u64 {
+                report.issues.push(IesIssue::CountMismatch {
+                    field: "row section size",
+                    declared: resource_offset as u64,
+                    actual: consumed,
+                });
+            }
+            if (pos as u64) < size {
+                report.issues.push(IesIssue::TrailingBytes {
+                    offset: pos as u64,
+                    count: size - pos as u64,
+                });
+            }
+        }
+        report
+    }
+
+    pub fn validate_file<P: AsRef<Path>>(file_path: P) -> Result<IesValidation> {
+        let file_path = file_path.as_ref();
+        let bytes = std::fs::read(file_path).map_err(|err| Error::from(err).in_file(file_path))?;
+        Ok(Self::validate(&bytes))
+    }
+
+    /// Opens a table for streaming its rows with `LazyIESFile::rows` instead of
+    /// loading them all, for tables with hundreds of thousands of rows.
+    pub fn open_lazy<P: AsRef<Path>>(file_path: P) -> Result<LazyIESFile> {
+        Self::open_lazy_with_options(file_path, &ParseOptions::default())
+    }
+
+    pub fn open_lazy_with_options<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<LazyIESFile> {
+        let file_path = file_path.as_ref();
+        let open = || {
+            let file = std::fs::File::open(file_path)?;
+            LazyIESFile::from_reader(BufReader::new(file), options)
+        };
+        open().map_err(|err| err.in_file(file_path))
+    }
+
+    pub fn load_from_file_with_options<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        let load = || {
+            let file = std::fs::File::open(file_path)?;
+            let mut binary_reader = BinaryReader::new(BufReader::new(file));
+            Self::load_from_reader_with_options(&mut binary_reader, options)
+        };
+        load().map_err(|err| err.in_file(file_path))
+    }
+
+    pub fn load_from_slice_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Self> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_from_reader_with_options(&mut binary_reader, options)
+    }
+
+    /// Loads as much of a possibly truncated table as is present. Rows after the end
+    /// of the data are dropped, everything before them is kept. Strict options turn
+    /// truncation into an error like any other.
+    pub fn load_partial_from_file<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Parsed<Self>> {
+        let file_path = file_path.as_ref();
+        let load = || {
+            let file = std::fs::File::open(file_path)?;
+            let mut binary_reader = BinaryReader::new(BufReader::new(file));
+            Self::load_partial_from_reader(&mut binary_reader, options)
+        };
+        load().map_err(|err| err.in_file(file_path))
+    }
+
+    pub fn load_partial_from_slice(bytes: &[u8], options: &ParseOptions) -> Result<Parsed<Self>> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_partial_from_reader(&mut binary_reader, options)
+    }
+
+    fn load_partial_from_reader<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<Parsed<Self>> {
+        let mut ies_data = IESFile::default();
+        ies_data.read_header(reader, options)?;
+        let outcome = ies_data
+            .read_columns(reader, options)
+            .and_then(|ies_data| ies_data.read_rows(reader, options))
+            .map(|_| ());
+        Parsed::from_outcome(ies_data, outcome, options)
+    }
+
+    fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        Self::load_from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    fn load_from_reader_with_options<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let mut ies_data = IESFile::default();
-        ies_data.read_header(reader)?;
-        ies_data.read_columns(reader)?;
-        ies_data.read_rows(reader)?;
+        ies_data.read_header(reader, options)?;
+        ies_data.read_columns(reader, options)?;
+        ies_data.read_rows(reader, options)?;
         Ok(ies_data)
     }
 
+    /// Problems that were recovered from while loading in lenient mode.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// The rows as a JSON array of objects keyed by column name, columns in their
+    /// order in the file: `[{"ClassID": 1, "ClassName": "Sword", ...}, ...]`. Empty
+    /// cells are null. See `from_json`.
+    pub fn to_json(&self) -> Result<String> {
+        serial::to_json(&JsonRows(self))
+    }
+
+    /// Rebuilds a table from an array of objects like `to_json` writes. Columns come in
+    /// order of first appearance; a column whose values are all numbers or null is a
+    /// number column, any other a string column. `ClassID` and `ClassName` cells also
+    /// become the class id and name stored in front of each row. The table name and
+    /// column flags are not part of the JSON, `to_cbor` keeps everything.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let objects: Vec<JsonObject> = serial::from_json(json)?;
+
+        let mut names: Vec<&str> = Vec::new();
+        for object in &objects {
+            for (name, _) in &object.0 {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        let mut columns: Vec<IESColumn> = Vec::with_capacity(names.len());
+        for name in &names {
+            let numbers = objects.iter().all(|object| {
+                object
+                    .get(name)
+                    .is_none_or(|value| value.is_number() || value.is_null())
+            });
+            let column_type = if numbers {
+                IESColumnType::Float
+            } else {
+                IESColumnType::String
+            };
+            let position = columns
+                .iter()
+                .filter(|column| column.column_type == column_type)
+                .count() as u16;
+            columns.push(IESColumn {
+                name: name.to_string(),
+                name_second: name.to_string(),
+                column_type,
+                flags: 0,
+                position,
+            });
+        }
+
+        let mut rows = Vec::with_capacity(objects.len());
+        let mut row_keys = Vec::with_capacity(objects.len());
+        for (row_index, object) in objects.iter().enumerate() {
+            let mut row = Vec::with_capacity(columns.len());
+            for column in &columns {
+                let cell = match object.get(&column.name) {
+                    None | Some(serde_json::Value::Null) => IESValue::Null,
+                    Some(serde_json::Value::Number(number)) => match number.as_i64() {
+                        _ if column.column_type != IESColumnType::Float => {
+                            IESValue::Str(number.to_string())
+                        }
+                        Some(value) => IESValue::Int(value),
+                        None => IESValue::Float(number.as_f64().unwrap_or_default() as f32),
+                    },
+                    Some(serde_json::Value::String(text)) if text.is_empty() => IESValue::Null,
+                    Some(serde_json::Value::String(text)) => IESValue::Str(text.clone()),
+                    Some(other) => {
+                        return Err(Error::Ies(format!(
+                            "Expected a number or string cell, found {}",
+                            other
+                        ))
+                        .in_row(row_index)
+                        .in_column(&column.name));
+                    }
+                };
+                row.push(cell);
+            }
+            let key = |name: &str| {
+                let index = columns.iter().position(|column| column.name == name)?;
+                Some(&row[index])
+            };
+            row_keys.push(IESRowKey {
+                class_id: key("ClassID")
+                    .and_then(IESValue::as_i64)
+                    .and_then(|id| u32::try_from(id).ok())
+                    .unwrap_or_default(),
+                class_name: key("ClassName")
+                    .and_then(IESValue::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                ..Default::default()
+            });
+            rows.push(row);
+        }
+
+        let number_columns = columns
+            .iter()
+            .filter(|column| column.column_type == IESColumnType::Float)
+            .count();
+        Ok(Self {
+            header: IESHeader {
+                row_count: rows.len() as u16,
+                column_count: columns.len() as u16,
+                number_column_count: number_columns as u16,
+                string_column_count: (columns.len() - number_columns) as u16,
+                ..Default::default()
+            },
+            columns,
+            rows,
+            row_keys,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// The rows as the `<idspace>` XML IESTool exports, one `<Class>` element per row
+    /// with an attribute per column in file order:
+    /// `<idspace id="Item"><Class ClassID="1" ClassName="Sword" .../></idspace>`. The
+    /// idspace id is the table name without its `.ies` extension. Empty cells are left
+    /// out, as in the client's own XML.
+    pub fn to_xml(&self) -> String {
+        let id = self
+            .header
+            .name
+            .strip_suffix(".ies")
+            .unwrap_or(&self.header.name);
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<idspace id=\"");
+        push_xml_escaped(&mut xml, id);
+        xml.push_str("\">\n");
+        for row in self.rows() {
+            xml.push_str("\t<Class");
+            for (column, cell) in row.cells() {
+                if cell.is_null() {
+                    continue;
+                }
+                xml.push(' ');
+                xml.push_str(&column.name);
+                xml.push_str("=\"");
+                push_xml_escaped(&mut xml, &cell.to_string());
+                xml.push('"');
+            }
+            xml.push_str("/>\n");
+        }
+        xml.push_str("</idspace>\n");
+        xml
+    }
+
+    /// Dumps the parsed table as compact binary CBOR, see `from_cbor`.
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        serial::to_cbor(self)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        serial::from_cbor(bytes)
+    }
+
     fn read_header<R: Read + Seek>(
         &mut self,
         reader: &mut BinaryReader<R>,
-    ) -> io::Result<&mut Self> {
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
         let name = reader.read_bytes(HEADER_NAME)?;
-        // Convert to UTF-8 and trim trailing null characters
-        self.header.name = String::from_utf8_lossy(&name)
-            .trim_end_matches('\0') // Trim trailing null characters
-            .to_string(); // Convert to String
+        // Trim trailing null characters, then decode
+        self.header.name = options.text.decode_vec(trim_end_bytes(name, 0))?;
 
-        reader.read_u32()?; // Padding
+        self.header.reserved.0 = reader.read_u32()?;
         self.header.data_offset = reader.read_u32()?;
         self.header.resource_offset = reader.read_u32()?;
         self.header.file_size = reader.read_u32()?;
-        reader.read_u16()?; // Padding
+        self.header.reserved.1 = reader.read_u16()?;
         self.header.row_count = reader.read_u16()?;
         self.header.column_count = reader.read_u16()?;
         self.header.number_column_count = reader.read_u16()?;
         self.header.string_column_count = reader.read_u16()?;
-        reader.read_u16()?; // Padding
+        self.header.reserved.2 = reader.read_u16()?;
+
+        // Columns and rows are located relative to the end of the file, so the
+        // offsets must stay inside it
+        let file_size = reader.file_size()?;
+        let data_start = self.header.resource_offset as u64 + self.header.data_offset as u64;
+        if data_start > self.data_end(file_size) {
+            return Err(Error::Ies(format!(
+                "Data offsets {} + {} exceed the file size {}",
+                self.header.data_offset, self.header.resource_offset, file_size
+            )));
+        }
+        if self.header.column_count as u64 * (DATA_NAME as u64 * 2 + 8) > data_start {
+            return Err(Error::Ies(format!(
+                "Column count {} does not fit in the column section",
+                self.header.column_count
+            )));
+        }
+        let typed_columns =
+            self.header.number_column_count as u32 + self.header.string_column_count as u32;
+        if typed_columns != self.header.column_count as u32 {
+            options.report(
+                &mut self.warnings,
+                ParseWarning::SuspiciousValue {
+                    field: "column count",
+                    declared: self.header.column_count as u64,
+                    actual: typed_columns as u64,
+                },
+            )?;
+        }
+        if self.header.file_size as u64 != file_size {
+            options.report(
+                &mut self.warnings,
+                ParseWarning::SuspiciousValue {
+                    field: "file size",
+                    declared: self.header.file_size as u64,
+                    actual: file_size,
+                },
+            )?;
+        }
         Ok(self)
     }
 
+    /// End of the table data that the offsets are relative to. A file shorter than its
+    /// header declares is truncated, so the declared size still locates the sections.
+    fn data_end(&self, file_size: u64) -> u64 {
+        file_size.max(self.header.file_size as u64)
+    }
+
     fn read_columns<R: Read + Seek>(
         &mut self,
         reader: &mut BinaryReader<R>,
-    ) -> io::Result<&mut Self> {
-        reader.seek(SeekFrom::End(
-            -((self.header.resource_offset as i64) + (self.header.data_offset as i64)),
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
+        let data_end = self.data_end(reader.file_size()?);
+        reader.seek(SeekFrom::Start(
+            data_end - self.header.resource_offset as u64 - self.header.data_offset as u64,
         ))?;
-        for _ in 0..self.header.column_count {
-            let mut column = IESColumn::default();
-
-            let name = reader.read_bytes(DATA_NAME)?;
-            column.name = Self::decrypt_string(&name)?;
-
-            let name_second = reader.read_bytes(DATA_NAME)?;
-            column.name_second = Self::decrypt_string(&name_second)?;
-            let num = reader.read_u16()?;
-            column.column_type = match num {
-                0 => IESColumnType::Float,
-                1 => IESColumnType::String,
-                2 => IESColumnType::StringSecond,
-                _ => panic!("Invalid column type"),
-            };
-            reader.read_u32()?; // Padding
-            column.position = reader.read_u16()?;
+        for index in 0..self.header.column_count {
+            let offset = reader.tell()?;
+            let column = Self::read_column(reader, options.text)
+                .map_err(|err| err.in_column(&format!("#{}", index)).at_offset(offset))?;
             self.columns.push(column);
         }
         self.columns.sort();
         Ok(self)
     }
 
-    fn read_rows<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> io::Result<&mut Self> {
-        reader.seek(SeekFrom::End(-(self.header.resource_offset as i64)))?;
+    fn read_column<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        text: TextDecoding,
+    ) -> Result<IESColumn> {
+        let mut column = IESColumn::default();
 
-        for _ in 0..self.header.row_count {
-            reader.read_u32()?; // Padding
+        let name = reader.read_bytes(DATA_NAME)?;
+        column.name = Self::decrypt_string(&name, text)?;
 
-            let count = reader.read_u16()?;
-            let _buffer = reader.read_bytes(count as usize)?;
-            let mut row = Vec::with_capacity(self.header.row_count as usize);
+        let name_second = reader.read_bytes(DATA_NAME)?;
+        column.name_second = Self::decrypt_string(&name_second, text)?;
+        let num = reader.read_u16()?;
+        column.column_type = match num {
+            0 => IESColumnType::Float,
+            1 => IESColumnType::String,
+            2 => IESColumnType::StringSecond,
+            _ => return Err(Error::Ies(format!("Invalid column type {}", num))),
+        };
+        column.flags = reader.read_u32()?;
+        column.position = reader.read_u16()?;
+        Ok(column)
+    }
 
-            for (_, column) in self.columns.iter().enumerate() {
-                let value = if column.column_type == IESColumnType::Float {
-                    let nan = reader.read_f32()?;
-                    let max_value = f32::from_bits(u32::MAX);
-                    if (nan - max_value).abs() < f32::EPSILON {
-                        IESRow {
-                            value_float: Some(max_value),
-                            value_int: None,
-                            value_string: None,
-                        }
-                    } else {
-                        IESRow {
-                            value_float: None,
-                            value_int: Some(nan as u32),
-                            value_string: None,
-                        }
-                    }
-                } else {
-                    let length = reader.read_u16()?;
-                    let string_buffer = reader.read_bytes(length as usize)?;
-                    let string_value = Self::decrypt_string(&string_buffer)?;
-                    if !string_value.is_empty() {
-                        IESRow {
-                            value_float: None,
-                            value_int: None,
-                            value_string: Some(string_value),
-                        }
-                    } else {
-                        IESRow {
-                            value_float: None,
-                            value_int: None,
-                            value_string: None,
-                        }
-                    }
-                };
-                row.push(value);
+    fn read_rows<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
+        let data_end = self.data_end(reader.file_size()?);
+        reader.seek(SeekFrom::Start(
+            data_end - self.header.resource_offset as u64,
+        ))?;
+
+        if options.parallel && self.header.row_count as usize >= PARALLEL_MIN_ROWS {
+            self.read_rows_parallel(reader, options)?;
+        } else {
+            for row_index in 0..self.header.row_count as usize {
+                let (key, row) = self.read_row(reader, row_index, 0, options.text)?;
+                self.row_keys.push(key);
+                self.rows.push(row);
             }
+        }
 
-            self.rows.push(row);
-            reader.seek(SeekFrom::Current(self.header.string_column_count as i64))?;
+        let offset = reader.tell()?;
+        let file_size = reader.file_size()?;
+        if offset < file_size {
+            options.report(
+                &mut self.warnings,
+                ParseWarning::SkippedBytes {
+                    offset,
+                    count: file_size - offset,
+                },
+            )?;
         }
         Ok(self)
     }
 
+    /// Same result as the sequential pass, but rows are decoded on the rayon pool. A
+    /// first pass only follows the length prefixes to find where every row starts.
+    fn read_rows_parallel<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        let base = reader.tell()?;
+        let mut bytes = Vec::new();
+        reader.reader.read_to_end(&mut bytes)?;
+
+        let mut row_offsets = Vec::with_capacity(self.header.row_count as usize);
+        let mut offset = 0;
+        while row_offsets.len() < self.header.row_count as usize {
+            let Some(end) = self.row_end(&bytes, offset) else {
+                break;
+            };
+            row_offsets.push(offset);
+            offset = end;
+        }
+
+        let rows: Vec<Result<(IESRowKey, Vec<IESValue>)>> = row_offsets
+            .par_iter()
+            .enumerate()
+            .map(|(row_index, &offset)| {
+                let mut row_reader = BinaryReader::new(Cursor::new(&bytes[offset..]));
+                self.read_row(
+                    &mut row_reader,
+                    row_index,
+                    base + offset as u64,
+                    options.text,
+                )
+            })
+            .collect();
+        for row in rows {
+            let (key, row) = row?;
+            self.row_keys.push(key);
+            self.rows.push(row);
+        }
+
+        // The row the scan stopped at is read the sequential way, which fails with
+        // the same error a sequential load reports
+        if row_offsets.len() < self.header.row_count as usize {
+            let row_index = row_offsets.len();
+            let mut row_reader = BinaryReader::new(Cursor::new(&bytes[offset.min(bytes.len())..]));
+            let (key, row) = self.read_row(
+                &mut row_reader,
+                row_index,
+                base + offset as u64,
+                options.text,
+            )?;
+            self.row_keys.push(key);
+            self.rows.push(row);
+        }
+
+        reader.seek(SeekFrom::Start(base + offset as u64))?;
+        Ok(())
+    }
+
+    /// Offset just past the row that starts at `start`, or `None` when it does not
+    /// fit in `bytes`. Only length prefixes are read.
+    fn row_end(&self, bytes: &[u8], start: usize) -> Option<usize> {
+        let length_at = |pos: usize| {
+            bytes
+                .get(pos..pos + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        };
+        let mut pos = start + 4; // Padding
+        pos += 2 + length_at(pos)?;
+        for column in &self.columns {
+            pos += match column.column_type {
+                IESColumnType::Float => 4,
+                _ => 2 + length_at(pos)?,
+            };
+        }
+        let end = pos + self.header.string_column_count as usize;
+        (end <= bytes.len()).then_some(end)
+    }
+
+    /// Reads one row and the per-string-column bytes that follow it. `base` is added
+    /// to reader positions in errors, for readers that start inside the file.
+    fn read_row<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        row_index: usize,
+        base: u64,
+        text: TextDecoding,
+    ) -> Result<(IESRowKey, Vec<IESValue>)> {
+        let row_offset = base + reader.tell()?;
+        let mut key = Self::read_row_header(reader, text)
+            .map_err(|err| err.in_row(row_index).at_offset(row_offset))?;
+        let mut row = Vec::with_capacity(self.columns.len());
+
+        for (index, column) in self.columns.iter().enumerate() {
+            let offset = base + reader.tell()?;
+            let (value, raw) = Self::read_value(reader, column, text).map_err(|err| {
+                err.in_row(row_index)
+                    .in_column(&column.name)
+                    .at_offset(offset)
+            })?;
+            if let Some(raw) = raw {
+                key.raw.push((index, raw));
+            }
+            row.push(value);
+        }
+
+        let offset = base + reader.tell()?;
+        key.flags = reader
+            .read_bytes(self.header.string_column_count as usize)
+            .map_err(|err| Error::from(err).in_row(row_index).at_offset(offset))?;
+        Ok((key, row))
+    }
+
+    /// Reads the class id and class name that precede every row.
+    fn read_row_header<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        text: TextDecoding,
+    ) -> Result<IESRowKey> {
+        let class_id = reader.read_u32()?;
+        let count = reader.read_u16()?;
+        let class_name = Self::decrypt_string(&reader.read_bytes(count as usize)?, text)?;
+        Ok(IESRowKey {
+            class_id,
+            class_name,
+            ..Default::default()
+        })
+    }
+
+    /// Reads one cell, and for string cells that do not re-encode to the bytes they
+    /// were read from, those bytes.
+    fn read_value<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        column: &IESColumn,
+        text: TextDecoding,
+    ) -> Result<(IESValue, Option<Vec<u8>>)> {
+        if column.column_type == IESColumnType::Float {
+            return Ok((IESValue::from_f32(reader.read_f32()?), None));
+        }
+        let length = reader.read_u16()?;
+        let string_buffer = reader.read_bytes(length as usize)?;
+        let bytes = decrypt_bytes(&string_buffer);
+        let string_value = text.decode_cow(&bytes)?;
+        let raw = (string_value.as_bytes() != bytes.as_slice()).then(|| bytes.clone());
+        let value = if string_value.is_empty() {
+            IESValue::Null
+        } else {
+            IESValue::Str(string_value.into_owned())
+        };
+        Ok((value, raw))
+    }
+
     /// Decrypts a byte array using a simple XOR operation.
     /// The function applIES a XOR operation using a predefined key (xor_key = 1) to each byte in the input data array.
-    /// The decrypted byte array is then converted into a UTF-8 string, removing trailing null characters ('\u{1}'),
+    /// The decrypted byte array is then decoded with the given text policy, removing trailing null characters ('\u{1}'),
     /// and returning the resulting string.
-    fn decrypt_string(data: &[u8]) -> io::Result<String> {
-        let xor_key = 1;
-
-        // Apply XOR operation to each byte in the input data array to decrypt it.
-        let decrypted_data: Vec<u8> = data.iter().map(|&byte| byte ^ xor_key).collect();
-
-        // Convert the decrypted byte array into a UTF-8 string.
-        // Trim trailing null characters ('\u{1}') and return the resulting string.
-        Ok(String::from_utf8(decrypted_data)
-            .unwrap()
-            .trim_end_matches('\u{1}')
-            .to_string())
+    fn decrypt_string(data: &[u8], text: TextDecoding) -> Result<String> {
+        text.decode_vec(decrypt_bytes(data))
     }
 
-    pub fn get_columns_length(&self) -> io::Result<usize> {
+    pub fn get_columns_length(&self) -> Result<usize> {
         Ok(self.columns.len())
     }
-    pub fn get_rows_length(&self) -> io::Result<usize> {
+    pub fn get_rows_length(&self) -> Result<usize> {
         Ok(self.rows.len())
     }
 
+    #[deprecated(note = "use `IESFile::row` and `IESRowView::value`")]
     pub fn get_data_by_column_name_and_index(
         &self,
         column_name: &str,
         row_index: usize,
-    ) -> Option<&IESRow> {
+    ) -> Option<&IESValue> {
         if let Some(column_index) = self.get_column_index_by_name(column_name) {
             if row_index < self.rows.len() {
                 Some(&self.rows[row_index][column_index])
@@ -263,7 +1384,187 @@ impl IESFile {
         }
     }
 
-    fn get_column_index_by_name(&self, column_name: &str) -> Option<usize> {
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Every row in file order.
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = IESRowView<'_>> {
+        (0..self.rows.len()).map(|index| IESRowView { file: self, index })
+    }
+
+    pub fn row(&self, index: usize) -> Option<IESRowView<'_>> {
+        (index < self.rows.len()).then_some(IESRowView { file: self, index })
+    }
+
+    /// The column called `name`, by its first or second name.
+    pub fn column(&self, name: &str) -> Option<&IESColumn> {
+        Some(&self.columns[self.get_column_index_by_name(name)?])
+    }
+
+    /// Columns in cell order.
+    pub fn columns(&self) -> &[IESColumn] {
+        &self.columns
+    }
+
+    /// Upserts the rows of `overlay` into this table the way the client applies addon
+    /// tables: rows are matched by the text of their `key_column` cell, new keys are
+    /// appended and columns only the overlay has are added, empty in the existing rows.
+    /// A row under an existing key that differs in a shared column is a conflict:
+    /// `FirstWins` keeps the row as it is, `LastWins` takes the overlay's cells and
+    /// `Error` fails before anything is changed. Columns the overlay lacks keep their
+    /// cells either way.
+    pub fn merge(
+        &mut self,
+        overlay: &IESFile,
+        key_column: &str,
+        policy: ConflictPolicy,
+    ) -> Result<IesMergeSummary> {
+        let mut old_rows: HashMap<String, usize> = keyed_rows(self, key_column)?
+            .into_iter()
+            .map(|(key, row)| (key, row.index()))
+            .collect();
+        let new_rows = keyed_rows(overlay, key_column)?;
+        let differs = |index: usize, row: &IESRowView| {
+            row.cells().any(|(column, cell)| {
+                self.get_column_index_by_name(&column.name)
+                    .is_some_and(|column| !same_value(&self.rows[index][column], cell))
+            })
+        };
+        let conflicts: Vec<bool> = new_rows
+            .iter()
+            .map(|(key, row)| old_rows.get(key).is_some_and(|&index| differs(index, row)))
+            .collect();
+        if policy == ConflictPolicy::Error
+            && let Some(conflict) = conflicts.iter().position(|&conflict| conflict)
+        {
+            return Err(Error::Ies(format!(
+                "{} '{}' differs in the overlay",
+                key_column, new_rows[conflict].0
+            ))
+            .in_row(new_rows[conflict].1.index()));
+        }
+
+        for column in &overlay.columns {
+            if self.get_column_index_by_name(&column.name).is_none() {
+                self.insert_column(column);
+            }
+        }
+
+        let mut summary = IesMergeSummary::default();
+        for ((key, row), conflict) in new_rows.iter().zip(conflicts) {
+            let cells = self
+                .columns
+                .iter()
+                .map(|column| row.value(&column.name).cloned())
+                .collect::<Vec<_>>();
+            match old_rows.get(key) {
+                Some(_) if !conflict => {}
+                Some(_) if policy == ConflictPolicy::FirstWins => summary.kept += 1,
+                Some(&index) => {
+                    for (column, (slot, cell)) in self.rows[index].iter_mut().zip(cells).enumerate()
+                    {
+                        if let Some(cell) = cell {
+                            if !same_value(slot, &cell)
+                                && let Some(key) = self.row_keys.get_mut(index)
+                            {
+                                key.clear_raw(column);
+                            }
+                            *slot = cell;
+                        }
+                    }
+                    summary.updated += 1;
+                }
+                None => {
+                    old_rows.insert(key.clone(), self.rows.len());
+                    self.rows
+                        .push(cells.into_iter().map(Option::unwrap_or_default).collect());
+                    let mut row_key = overlay
+                        .row_keys
+                        .get(row.index())
+                        .cloned()
+                        .unwrap_or_default();
+                    row_key.flags.clear();
+                    row_key.raw = std::mem::take(&mut row_key.raw)
+                        .into_iter()
+                        .filter_map(|(column, bytes)| {
+                            let name = &overlay.columns[column].name;
+                            Some((self.get_column_index_by_name(name)?, bytes))
+                        })
+                        .collect();
+                    self.row_keys
+                        .resize(self.rows.len() - 1, IESRowKey::default());
+                    self.row_keys.push(row_key);
+                    summary.added += 1;
+                }
+            }
+        }
+        self.header.row_count = self.rows.len() as u16;
+        Ok(summary)
+    }
+
+    /// Adds a copy of `column` after the columns of its type, empty in every row, and
+    /// keeps the header counts in step.
+    fn insert_column(&mut self, column: &IESColumn) {
+        let same_type = self
+            .columns
+            .iter()
+            .filter(|existing| existing.column_type == column.column_type);
+        let position = same_type
+            .map(|existing| existing.position + 1)
+            .max()
+            .unwrap_or(0);
+        let column = IESColumn {
+            position,
+            ..column.clone()
+        };
+        let index = self.columns.partition_point(|existing| existing < &column);
+        self.columns.insert(index, column);
+        for row in &mut self.rows {
+            row.insert(index, IESValue::Null);
+        }
+        for key in &mut self.row_keys {
+            for (column, _) in &mut key.raw {
+                if *column >= index {
+                    *column += 1;
+                }
+            }
+        }
+        self.header.column_count += 1;
+        if self.columns[index].is_number() {
+            self.header.number_column_count += 1;
+        } else {
+            self.header.string_column_count += 1;
+        }
+    }
+
+    /// Rows matching filters, see `IESQuery`: `ies.query().filter("ClassName",
+    /// query::eq("Sword"))`.
+    pub fn query(&self) -> IESQuery<'_> {
+        IESQuery::new(self)
+    }
+
+    /// Rows by the value of `column`, for repeated lookups; `None` when there is no
+    /// such column.
+    pub fn index(&self, column: &str) -> Option<IESIndex<'_>> {
+        Some(IESIndex::new(self, self.get_column_index_by_name(column)?))
+    }
+
+    /// Deserializes every row into a `T`, typically a struct whose fields are named
+    /// after columns: `let items: Vec<Item> = ies.deserialize_rows()?`. Columns match
+    /// fields ignoring ASCII case, and columns without a field are skipped unless `T`
+    /// denies unknown fields. Empty cells read as `None` for `Option` fields and as an
+    /// empty string for `String` fields; numbers also read into string fields.
+    pub fn deserialize_rows<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.rows()
+            .map(|row| {
+                T::deserialize(RowDeserializer(row))
+                    .map_err(|err| Error::Serialization(err.to_string()).in_row(row.index()))
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_column_index_by_name(&self, column_name: &str) -> Option<usize> {
         if let Some(index) = self.columns.iter().position(|col| col.name == column_name) {
             Some(index)
         } else {
@@ -276,4 +1577,1406 @@ impl IESFile {
     pub fn get_column_names(&self) -> Vec<&String> {
         self.columns.iter().map(|col| &col.name).collect()
     }
+
+    /// Replaces a cell with `text`, parsed as a number in number columns. Empty text
+    /// clears the cell.
+    pub fn set_data_by_column_name_and_index(
+        &mut self,
+        column_name: &str,
+        row_index: usize,
+        text: &str,
+    ) -> Result<()> {
+        let column_index = self
+            .get_column_index_by_name(column_name)
+            .ok_or_else(|| Error::Ies(format!("No column '{}'", column_name)))?;
+        let cell = self
+            .rows
+            .get_mut(row_index)
+            .and_then(|row| row.get_mut(column_index))
+            .ok_or_else(|| Error::Ies(format!("No row {}", row_index)))?;
+        *cell = Self::parse_cell(&self.columns[column_index], text)?;
+        if let Some(key) = self.row_keys.get_mut(row_index) {
+            key.clear_raw(column_index);
+        }
+        Ok(())
+    }
+
+    /// `text` as a value of `column`; empty text is an empty cell.
+    fn parse_cell(column: &IESColumn, text: &str) -> Result<IESValue> {
+        Ok(match column.column_type {
+            _ if text.is_empty() => IESValue::Null,
+            IESColumnType::Float => match (text.parse::<i64>(), text.parse::<f32>()) {
+                (Ok(value), _) => IESValue::Int(value),
+                (_, Ok(value)) => IESValue::Float(value),
+                _ => {
+                    return Err(Error::Ies(format!(
+                        "'{}' is not a number for column '{}'",
+                        text, column.name
+                    )));
+                }
+            },
+            _ => IESValue::Str(text.to_string()),
+        })
+    }
+
+    /// Writes the table as CSV: a header line of column names, then one line per row.
+    /// Numbers are written in full precision and empty cells as empty fields.
+    pub fn to_csv<W: Write>(&self, mut writer: W, options: &CsvOptions) -> Result<()> {
+        let mut line = String::new();
+        let header = self
+            .columns
+            .iter()
+            .map(|column| options.column_name(column));
+        write_csv_line(&mut line, header, options.delimiter);
+        writer.write_all(line.as_bytes())?;
+        for row in &self.rows {
+            line.clear();
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| cell.text().unwrap_or_default())
+                .collect();
+            write_csv_line(
+                &mut line,
+                cells.iter().map(String::as_str),
+                options.delimiter,
+            );
+            writer.write_all(line.as_bytes())?;
+        }
+        Ok(writer.flush()?)
+    }
+
+    /// Reads CSV written by `to_csv`, possibly edited since, into a table shaped like
+    /// `schema`: its header, column types and order come from there and the CSV columns
+    /// are matched to them by name, in any order. Columns missing from the CSV are
+    /// left empty. A row keeps the class id, class name and flags of the schema row at
+    /// the same index; added rows start without them.
+    pub fn from_csv<R: Read>(
+        mut reader: R,
+        schema: &IESFile,
+        options: &CsvOptions,
+    ) -> Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        // Spreadsheets tend to save UTF-8 with a byte order mark
+        let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+        let mut lines = parse_csv(text, options.delimiter)?.into_iter();
+        let header = lines.next().unwrap_or_default();
+
+        let mut sources = vec![None; schema.columns.len()];
+        for (field, name) in header.iter().enumerate() {
+            let column = schema
+                .columns
+                .iter()
+                .position(|column| options.column_name(column) == name)
+                .ok_or_else(|| Error::Ies(format!("No column '{}' in the schema", name)))?;
+            sources[column] = Some(field);
+        }
+
+        let mut rows = Vec::new();
+        let mut row_keys = Vec::new();
+        for (row_index, fields) in lines.enumerate() {
+            if fields.len() != header.len() {
+                return Err(Error::Ies(format!(
+                    "Expected {} fields, found {}",
+                    header.len(),
+                    fields.len()
+                ))
+                .in_row(row_index));
+            }
+            let mut row = Vec::with_capacity(schema.columns.len());
+            for (column, source) in schema.columns.iter().zip(&sources) {
+                let text = source.map_or("", |field| fields[field].as_str());
+                let cell = Self::parse_cell(column, text)
+                    .map_err(|err| err.in_row(row_index).in_column(&column.name))?;
+                row.push(cell);
+            }
+            rows.push(row);
+            row_keys.push(schema.row_keys.get(row_index).cloned().unwrap_or_default());
+        }
+
+        Ok(Self {
+            header: schema.header.clone(),
+            columns: schema.columns.clone(),
+            rows,
+            row_keys,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Writes the table back to disk, see `to_bytes`.
+    pub fn save_to_file<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        let file_path = file_path.as_ref();
+        let save = || -> Result<()> { Ok(std::fs::write(file_path, self.to_bytes()?)?) };
+        save().map_err(|err| err.in_file(file_path))
+    }
+
+    /// The table in the on-disk layout: header, column definitions and rows, with the
+    /// section offsets and counts rebuilt and every string XOR-encrypted again. Text is
+    /// written as UTF-8; columns come out in parsed order, which keeps their positions.
+    /// Whole numbers an `f32` cannot hold exactly, past 2^24, and cells of the wrong
+    /// kind for their column fail.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.rows.len() > u16::MAX as usize || self.columns.len() > u16::MAX as usize {
+            return Err(Error::Ies(format!(
+                "Too many rows ({}) or columns ({}) for one table",
+                self.rows.len(),
+                self.columns.len()
+            )));
+        }
+        let string_columns = self
+            .columns
+            .iter()
+            .filter(|column| column.column_type != IESColumnType::Float)
+            .count();
+
+        let mut columns = Vec::new();
+        for column in &self.columns {
+            columns.extend(Self::encrypt_field(&column.name, DATA_NAME)?);
+            columns.extend(Self::encrypt_field(&column.name_second, DATA_NAME)?);
+            let column_type: u16 = match column.column_type {
+                IESColumnType::Float => 0,
+                IESColumnType::String => 1,
+                IESColumnType::StringSecond => 2,
+            };
+            columns.extend_from_slice(&column_type.to_le_bytes());
+            columns.extend_from_slice(&column.flags.to_le_bytes());
+            columns.extend_from_slice(&column.position.to_le_bytes());
+        }
+
+        let mut rows = Vec::new();
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let key = self.row_keys.get(row_index).unwrap_or(&EMPTY_KEY);
+            rows.extend_from_slice(&key.class_id.to_le_bytes());
+            Self::write_string(&mut rows, &key.class_name)?;
+            for (index, (column, cell)) in self.columns.iter().zip(row).enumerate() {
+                let mismatch = match (column.is_number(), cell) {
+                    (true, IESValue::Str(text)) => Some(format!("'{}' is not a number", text)),
+                    (false, IESValue::Int(_) | IESValue::Float(_)) => Some(format!(
+                        "{} is not a string",
+                        cell.text().unwrap_or_default()
+                    )),
+                    _ => None,
+                };
+                if let Some(message) = mismatch {
+                    return Err(
+                        Error::Ies(format!("{} for column '{}'", message, column.name))
+                            .in_row(row_index)
+                            .in_column(&column.name),
+                    );
+                }
+                match key.raw_bytes(index, column, cell) {
+                    None => {
+                        let value = match cell {
+                            IESValue::Int(int) if (*int as f32) as i128 != *int as i128 => {
+                                return Err(Error::Ies(format!(
+                                    "{} cannot be stored exactly in a number column",
+                                    int
+                                ))
+                                .in_row(row_index)
+                                .in_column(&column.name));
+                            }
+                            _ => cell.as_f32().unwrap_or(0.0),
+                        };
+                        rows.extend_from_slice(&value.to_le_bytes());
+                    }
+                    Some(bytes) => Self::write_bytes(&mut rows, &bytes)
+                        .map_err(|err| err.in_row(row_index).in_column(&column.name))?,
+                }
+            }
+            let mut flags = key.flags.clone();
+            flags.resize(string_columns, 0);
+            rows.extend(flags);
+        }
+
+        let mut bytes = self.header.name.as_bytes().to_vec();
+        if bytes.len() > HEADER_NAME {
+            return Err(Error::Ies(format!(
+                "Table name '{}' is longer than {} bytes",
+                self.header.name, HEADER_NAME
+            )));
+        }
+        bytes.resize(HEADER_NAME, 0);
+        let file_size = HEADER_SIZE + columns.len() + rows.len();
+        for value in [
+            self.header.reserved.0,
+            columns.len() as u32,
+            rows.len() as u32,
+            file_size as u32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            self.header.reserved.1,
+            self.rows.len() as u16,
+            self.columns.len() as u16,
+            (self.columns.len() - string_columns) as u16,
+            string_columns as u16,
+            self.header.reserved.2,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend(columns);
+        bytes.extend(rows);
+        Ok(bytes)
+    }
+
+    /// `text` XOR-encrypted into a zero-padded field of `len` bytes.
+    fn encrypt_field(text: &str, len: usize) -> Result<Vec<u8>> {
+        if text.len() > len {
+            return Err(Error::Ies(format!(
+                "Column name '{}' is longer than {} bytes",
+                text, len
+            )));
+        }
+        let mut field: Vec<u8> = text.bytes().map(|b| b ^ 1).collect();
+        field.resize(len, 0);
+        Ok(field)
+    }
+
+    /// Appends `text` XOR-encrypted with its length in front.
+    fn write_string(out: &mut Vec<u8>, text: &str) -> Result<()> {
+        Self::write_bytes(out, text.as_bytes())
+    }
+
+    fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+        let length = u16::try_from(bytes.len())
+            .map_err(|_| Error::Ies(format!("String of {} bytes is too long", bytes.len())))?;
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend(bytes.iter().map(|b| b ^ 1));
+        Ok(())
+    }
+}
+
+/// XORs `data` with the key 1 and trims the trailing '\u{1}' padding, giving the
+/// undecoded text.
+fn decrypt_bytes(data: &[u8]) -> Vec<u8> {
+    let xor_key = 1;
+    let decrypted_data: Vec<u8> = data.iter().map(|&byte| byte ^ xor_key).collect();
+    trim_end_bytes(decrypted_data, 1)
+}
+
+/// Drops trailing `pad` bytes. Pad values below 0x20 never occur inside a UTF-8 or
+/// CP949 character, so this trims the same text as trimming after decoding.
+fn trim_end_bytes(mut bytes: Vec<u8>, pad: u8) -> Vec<u8> {
+    let len = bytes.iter().rposition(|&b| b != pad).map_or(0, |i| i + 1);
+    bytes.truncate(len);
+    bytes
+}
+
+/// Rows changed by `IESFile::merge`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IesMergeSummary {
+    /// Rows under keys the table did not have
+    pub added: usize,
+    /// Conflicting rows that took the overlay's cells
+    pub updated: usize,
+    /// Conflicting rows left as they were
+    pub kept: usize,
+}
+
+/// Findings of `IESFile::validate`, in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IesValidation {
+    /// Rows the header declares
+    pub declared_rows: usize,
+    /// Rows that are complete in the data
+    pub rows_read: usize,
+    pub issues: Vec<IesIssue>,
+}
+
+impl IesValidation {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// One inconsistency found by `IESFile::validate`. Offsets are from the start of the
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+pub enum IesIssue {
+    #[error("file of {size} bytes is shorter than the header")]
+    TruncatedHeader { size: u64 },
+
+    #[error("{field} declares {declared}, but the data has {actual}")]
+    CountMismatch {
+        field: &'static str,
+        declared: u64,
+        actual: u64,
+    },
+
+    #[error("data offsets {data_offset} + {resource_offset} exceed the file size {size}")]
+    SectionOutOfBounds {
+        data_offset: u32,
+        resource_offset: u32,
+        size: u64,
+    },
+
+    #[error("column #{index} at offset {offset} has unknown type {value}")]
+    BadColumnType {
+        index: usize,
+        offset: u64,
+        value: u16,
+    },
+
+    #[error("column name '{name}' appears {count} times")]
+    DuplicateColumn { name: String, count: usize },
+
+    #[error(
+        "row {row}, column '{column}': string cell at offset {offset} declares {length} bytes, {available} remain"
+    )]
+    TruncatedCell {
+        row: usize,
+        column: String,
+        offset: u64,
+        length: u64,
+        available: u64,
+    },
+
+    #[error("row {row} at offset {offset} is cut off by the end of the file")]
+    TruncatedRow { row: usize, offset: u64 },
+
+    #[error("{count} bytes after the last row at offset {offset}")]
+    TrailingBytes { offset: u64, count: u64 },
+}
+
+/// Rows that differ between two versions of a table, see `diff`. Rows are named by
+/// the text of their key cell.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IesDiff {
+    /// Column the rows were matched by
+    pub key_column: String,
+    /// Columns in the new table only; their cells are not reported per row
+    pub added_columns: Vec<String>,
+    /// Columns in the old table only
+    pub removed_columns: Vec<String>,
+    /// Keys of rows in the new table only, in new table order
+    pub added: Vec<String>,
+    /// Keys of rows in the old table only, in old table order
+    pub removed: Vec<String>,
+    /// Rows in both with different cells, in new table order
+    pub changed: Vec<IesRowChange>,
+}
+
+/// Cells of one row that differ, in new table column order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IesRowChange {
+    pub key: String,
+    pub cells: Vec<IesCellChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IesCellChange {
+    pub column: String,
+    pub old: IESValue,
+    pub new: IESValue,
+}
+
+impl IesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Output formats of `convert_batch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IesFormat {
+    /// `IESFile::to_json`
+    Json,
+    /// `IESFile::to_csv` with the default `CsvOptions`
+    Csv,
+    /// `IESFile::to_xml`
+    Xml,
+}
+
+impl IesFormat {
+    /// `json`, `csv` or `xml`, as returned by `extension`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(IesFormat::Json),
+            "csv" => Some(IesFormat::Csv),
+            "xml" => Some(IesFormat::Xml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            IesFormat::Json => "json",
+            IesFormat::Csv => "csv",
+            IesFormat::Xml => "xml",
+        }
+    }
+
+    fn convert(self, ies: &IESFile) -> Result<Vec<u8>> {
+        Ok(match self {
+            IesFormat::Json => ies.to_json()?.into_bytes(),
+            IesFormat::Csv => {
+                let mut csv = Vec::new();
+                ies.to_csv(&mut csv, &CsvOptions::default())?;
+                csv
+            }
+            IesFormat::Xml => ies.to_xml().into_bytes(),
+        })
+    }
+}
+
+/// Loads every table of `inputs` and writes it as `format` to `out_dir/<file
+/// stem>.<extension>`, converting many at once on the rayon pool. Returns each input
+/// with the file written for it or the error that stopped it, in input order; a
+/// failing table does not stop the others. An input whose file stem an earlier input
+/// already has fails instead of overwriting its output. Only failing to create
+/// `out_dir` fails the whole batch.
+pub fn convert_batch<P: AsRef<Path> + Sync>(
+    inputs: &[P],
+    format: IesFormat,
+    out_dir: &Path,
+) -> Result<Vec<(PathBuf, Result<PathBuf>)>> {
+    std::fs::create_dir_all(out_dir).map_err(|err| Error::from(err).in_file(out_dir))?;
+    let mut stems: HashMap<OsString, &Path> = HashMap::new();
+    let jobs: Vec<(&Path, Result<PathBuf>)> = inputs
+        .iter()
+        .map(|input| {
+            let input = input.as_ref();
+            let stem = input.file_stem().unwrap_or(input.as_os_str()).to_owned();
+            let target = match stems.get(&stem) {
+                Some(earlier) => Err(Error::Ies(format!(
+                    "{} is already written from {}",
+                    stem.to_string_lossy(),
+                    earlier.display()
+                ))
+                .in_file(input)),
+                None => {
+                    let mut name = stem.clone();
+                    name.push(".");
+                    name.push(format.extension());
+                    Ok(out_dir.join(name))
+                }
+            };
+            stems.entry(stem).or_insert(input);
+            (input, target)
+        })
+        .collect();
+
+    Ok(jobs
+        .into_par_iter()
+        .map(|(input, target)| {
+            let result = target.and_then(|target| {
+                let ies = IESFile::load_from_file(input)?;
+                let bytes = format.convert(&ies).map_err(|err| err.in_file(input))?;
+                std::fs::write(&target, bytes).map_err(|err| Error::from(err).in_file(&target))?;
+                Ok(target)
+            });
+            (input.to_path_buf(), result)
+        })
+        .collect())
+}
+
+/// Compares `old` and `new` row by row, matching rows by the cell of `key_column`
+/// (first or second name) and cells by column name. When a key repeats, the last row
+/// counts. Numbers compare by value. Fails when either table lacks the key column.
+pub fn diff(old: &IESFile, new: &IESFile, key_column: &str) -> Result<IesDiff> {
+    let old_rows = keyed_rows(old, key_column)?;
+    let new_rows = keyed_rows(new, key_column)?;
+    let old_by_key: HashMap<&str, IESRowView> = old_rows
+        .iter()
+        .map(|(key, row)| (key.as_str(), *row))
+        .collect();
+    let new_by_key: HashMap<&str, IESRowView> = new_rows
+        .iter()
+        .map(|(key, row)| (key.as_str(), *row))
+        .collect();
+
+    let mut result = IesDiff {
+        key_column: key_column.to_string(),
+        ..Default::default()
+    };
+    for column in &new.columns {
+        if old.get_column_index_by_name(&column.name).is_none() {
+            result.added_columns.push(column.name.clone());
+        }
+    }
+    for column in &old.columns {
+        if new.get_column_index_by_name(&column.name).is_none() {
+            result.removed_columns.push(column.name.clone());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for (key, row) in new_rows.iter().rev() {
+        // The last row of a repeated key counts
+        if !seen.insert(key.as_str()) {
+            continue;
+        }
+        let Some(before) = old_by_key.get(key.as_str()) else {
+            result.added.push(key.clone());
+            continue;
+        };
+        let cells: Vec<IesCellChange> = row
+            .cells()
+            .filter_map(|(column, cell)| {
+                let old_cell = before.value(&column.name)?;
+                (!same_value(old_cell, cell)).then(|| IesCellChange {
+                    column: column.name.clone(),
+                    old: old_cell.clone(),
+                    new: cell.clone(),
+                })
+            })
+            .collect();
+        if !cells.is_empty() {
+            result.changed.push(IesRowChange {
+                key: key.clone(),
+                cells,
+            });
+        }
+    }
+    result.added.reverse();
+    result.changed.reverse();
+
+    let mut seen = HashSet::new();
+    for (key, _) in &old_rows {
+        if !new_by_key.contains_key(key.as_str()) && seen.insert(key.as_str()) {
+            result.removed.push(key.clone());
+        }
+    }
+    Ok(result)
+}
+
+/// Rows with the text of their `key_column` cell.
+fn keyed_rows<'a>(file: &'a IESFile, key_column: &str) -> Result<Vec<(String, IESRowView<'a>)>> {
+    let column = file
+        .get_column_index_by_name(key_column)
+        .ok_or_else(|| Error::Ies(format!("No key column '{}'", key_column)))?;
+    Ok(file
+        .rows()
+        .map(|row| (row.value_at(column).text().unwrap_or_default(), row))
+        .collect())
+}
+
+/// Equal cells, numbers by value and floats by bits so NaN equals itself.
+fn same_value(a: &IESValue, b: &IESValue) -> bool {
+    match (a, b) {
+        (IESValue::Float(a), IESValue::Float(b)) => a.to_bits() == b.to_bits(),
+        (IESValue::Float(_), IESValue::Int(_)) | (IESValue::Int(_), IESValue::Float(_)) => {
+            a.as_f32() == b.as_f32()
+        }
+        _ => a == b,
+    }
+}
+
+/// A row as a map from column names to cells, for `IESFile::deserialize_rows`.
+struct RowDeserializer<'a>(IESRowView<'a>);
+
+impl<'de> RowDeserializer<'de> {
+    fn visit<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        let entries = self.0.cells().map(|(column, cell)| {
+            let name = fields
+                .iter()
+                .find(|field| field.eq_ignore_ascii_case(&column.name))
+                .copied()
+                .unwrap_or(&column.name);
+            (name, CellDeserializer(cell))
+        });
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for RowDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.visit(&[], visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.visit(fields, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// One cell for `RowDeserializer`.
+struct CellDeserializer<'a>(&'a IESValue);
+
+impl<'de> IntoDeserializer<'de, DeError> for CellDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for CellDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.0 {
+            IESValue::Float(value) => visitor.visit_f32(*value),
+            IESValue::Int(value) => visitor.visit_i64(*value),
+            IESValue::Str(value) => visitor.visit_borrowed_str(value),
+            IESValue::Null => visitor.visit_none(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.0 {
+            IESValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.0 {
+            IESValue::Int(value) => visitor.visit_bool(*value != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.0 {
+            IESValue::Str(value) => visitor.visit_borrowed_str(value),
+            IESValue::Null => visitor.visit_borrowed_str(""),
+            number => visitor.visit_string(number.text().unwrap_or_default()),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf unit unit_struct
+        newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// `to_json` shape of a table: one object per row.
+struct JsonRows<'a>(&'a IESFile);
+
+/// Cells keyed by the names of their columns, in column order.
+struct JsonRow<'a>(&'a [IESColumn], &'a [IESValue]);
+
+impl Serialize for JsonRows<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let mut rows = serializer.serialize_seq(Some(self.0.rows.len()))?;
+        for row in &self.0.rows {
+            rows.serialize_element(&JsonRow(&self.0.columns, row))?;
+        }
+        rows.end()
+    }
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let mut row = serializer.serialize_map(Some(self.0.len()))?;
+        for (column, cell) in self.0.iter().zip(self.1) {
+            match cell {
+                IESValue::Float(value) => row.serialize_entry(&column.name, value)?,
+                IESValue::Int(value) => row.serialize_entry(&column.name, value)?,
+                IESValue::Str(value) => row.serialize_entry(&column.name, value)?,
+                IESValue::Null => row.serialize_entry(&column.name, &())?,
+            }
+        }
+        row.end()
+    }
+}
+
+/// A JSON object with its keys in document order.
+struct JsonObject(Vec<(String, serde_json::Value)>);
+
+impl JsonObject {
+    fn get(&self, name: &str) -> Option<&serde_json::Value> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonObject {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = JsonObject;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an object of cells")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<JsonObject, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(JsonObject(entries))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// Appends `text` escaped for an XML attribute value; line breaks and tabs become
+/// character references so they survive attribute normalization.
+pub(crate) fn push_xml_escaped(xml: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            '\n' => xml.push_str("&#10;"),
+            '\r' => xml.push_str("&#13;"),
+            '\t' => xml.push_str("&#9;"),
+            c => xml.push(c),
+        }
+    }
+}
+
+/// Appends `fields` to `line` as one CSV line, quoting fields that need it.
+fn write_csv_line<'a>(line: &mut String, fields: impl Iterator<Item = &'a str>, delimiter: char) {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            line.push(delimiter);
+        }
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(field);
+        }
+    }
+    line.push_str("\r\n");
+}
+
+/// Splits CSV into lines of fields. Quoted fields may hold delimiters, doubled quotes
+/// and line breaks; lines end with `\n` or `\r\n`, and blank lines are skipped.
+fn parse_csv(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut lines = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            _ if quoted => field.push(c),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.len() > 1 || !fields[0].is_empty() {
+                    lines.push(std::mem::take(&mut fields));
+                }
+                fields.clear();
+            }
+            _ if c == delimiter => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::Ies("Unterminated quoted CSV field".to_string()));
+    }
+    if !fields.is_empty() || !field.is_empty() {
+        fields.push(field);
+        lines.push(fields);
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table with a float and a string column and `rows` rows.
+    fn table(rows: usize) -> Vec<u8> {
+        let mut columns = Vec::new();
+        for (name, column_type) in [("Level", 0u16), ("ClassName", 1)] {
+            for _ in 0..2 {
+                let mut field = [0u8; DATA_NAME];
+                for (slot, b) in field.iter_mut().zip(name.bytes()) {
+                    *slot = b ^ 1;
+                }
+                columns.extend_from_slice(&field);
+            }
+            columns.extend_from_slice(&column_type.to_le_bytes());
+            columns.extend_from_slice(&[0; 4]);
+            columns.extend_from_slice(&0u16.to_le_bytes());
+        }
+
+        let mut data = Vec::new();
+        for row in 0..rows {
+            let name = format!("Item_{}", row);
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(&(row as f32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend(name.bytes().map(|b| b ^ 1));
+            data.push(0); // One string column
+        }
+
+        let mut bytes = vec![0u8; HEADER_NAME];
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(columns.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        let file_size = bytes.len() + 4 + 12 + columns.len() + data.len();
+        bytes.extend_from_slice(&(file_size as u32).to_le_bytes());
+        for value in [0, rows as u16, 2, 1, 1, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&columns);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn parallel_load_matches_sequential_load() {
+        let bytes = table(PARALLEL_MIN_ROWS + 10);
+        let sequential = IESFile::load_from_slice(&bytes).unwrap();
+        let parallel = IESFile::load_from_slice_with_options(
+            &bytes,
+            &ParseOptions::strict().with_parallel(true),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&parallel).unwrap(),
+            serde_json::to_string(&sequential).unwrap()
+        );
+        let row = parallel
+            .row(265)
+            .and_then(|row| row.value("ClassName"))
+            .unwrap();
+        assert_eq!(row.as_str(), Some("Item_265"));
+
+        // Cut into the last row: both keep the rows before it and fail the same way
+        let truncated = &bytes[..bytes.len() - 3];
+        let options = ParseOptions::lenient();
+        let sequential = IESFile::load_partial_from_slice(truncated, &options).unwrap();
+        let parallel =
+            IESFile::load_partial_from_slice(truncated, &options.with_parallel(true)).unwrap();
+        assert_eq!(parallel.value().rows.len(), PARALLEL_MIN_ROWS + 9);
+        assert_eq!(
+            parallel.truncation().map(ToString::to_string),
+            sequential.truncation().map(ToString::to_string)
+        );
+    }
+
+    #[test]
+    fn written_tables_match_the_original_bytes() {
+        let bytes = table(3);
+        let mut ies = IESFile::load_from_slice(&bytes).unwrap();
+        assert_eq!(ies.to_bytes().unwrap(), bytes);
+
+        ies.set_data_by_column_name_and_index("ClassName", 1, "Sword_Ä")
+            .unwrap();
+        ies.set_data_by_column_name_and_index("Level", 2, "40")
+            .unwrap();
+        ies.set_data_by_column_name_and_index("Level", 1, "-2.5")
+            .unwrap();
+        assert!(
+            ies.set_data_by_column_name_and_index("Level", 0, "high")
+                .is_err()
+        );
+        let edited = IESFile::load_from_slice(&ies.to_bytes().unwrap()).unwrap();
+        let name = edited
+            .row(1)
+            .and_then(|row| row.value("ClassName"))
+            .unwrap();
+        assert_eq!(name.as_str(), Some("Sword_Ä"));
+        let level = edited.row(2).and_then(|row| row.value("Level")).unwrap();
+        assert_eq!(level.as_i64(), Some(40));
+        let fraction = edited.row(1).and_then(|row| row.value("Level")).unwrap();
+        assert_eq!(fraction, &IESValue::Float(-2.5));
+        assert_eq!((fraction.as_f32(), fraction.as_i64()), (Some(-2.5), None));
+        assert_eq!(edited.header.file_size as usize, bytes.len() + 2);
+    }
+
+    #[test]
+    fn whole_numbers_past_f32_precision_are_not_written() {
+        let mut ies = IESFile::load_from_slice(&table(2)).unwrap();
+        ies.set_data_by_column_name_and_index("Level", 1, "16777216")
+            .unwrap();
+        let written = IESFile::load_from_slice(&ies.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            written.row(1).unwrap().get::<i64>("Level"),
+            Some(16_777_216)
+        );
+
+        ies.set_data_by_column_name_and_index("Level", 1, "16777217")
+            .unwrap();
+        let err = ies.to_bytes().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("16777217 cannot be stored exactly in a number column")
+        );
+        let context = err.context().unwrap();
+        assert_eq!(
+            (context.row, context.column.as_deref()),
+            (Some(1), Some("Level"))
+        );
+    }
+
+    #[test]
+    fn text_in_number_columns_is_not_written() {
+        let mut ies = IESFile::load_from_slice(&table(2)).unwrap();
+        ies.rows[1][0] = IESValue::Str("high".into());
+        let err = ies.to_bytes().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'high' is not a number for column 'Level'")
+        );
+        assert_eq!(err.context().unwrap().row, Some(1));
+    }
+
+    #[test]
+    fn numbers_in_string_columns_are_not_written() {
+        let mut ies = IESFile::load_from_slice(&table(2)).unwrap();
+        ies.rows[0][1] = IESValue::Int(5);
+        ies.row_keys[0].clear_raw(1);
+        let err = ies.to_bytes().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("5 is not a string for column 'ClassName'")
+        );
+        assert_eq!(err.context().unwrap().row, Some(0));
+
+        // Empty cells stay allowed in either kind of column
+        ies.rows[0][1] = IESValue::Null;
+        ies.rows[1][0] = IESValue::Null;
+        assert!(ies.to_bytes().is_ok());
+    }
+
+    #[test]
+    fn csv_round_trips_through_the_schema() {
+        let ies = IESFile::load_from_slice(&table(2)).unwrap();
+        let options = CsvOptions::default().with_delimiter(';');
+        let mut csv = Vec::new();
+        ies.to_csv(&mut csv, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap(),
+            "Level;ClassName\r\n0;Item_0\r\n1;Item_1\r\n"
+        );
+        let same = IESFile::from_csv(csv.as_slice(), &ies, &options).unwrap();
+        assert_eq!(same.to_bytes().unwrap(), table(2));
+
+        let edited = "\u{feff}ClassName;Level\n\"Sword; \"\"Old\"\"\";2.5\n;\nNew;\n\n";
+        let edited = IESFile::from_csv(edited.as_bytes(), &ies, &options).unwrap();
+        let cell = |column, index| edited.row(index).and_then(|row| row.value(column));
+        assert_eq!(
+            cell("ClassName", 0).unwrap().as_str(),
+            Some("Sword; \"Old\"")
+        );
+        assert_eq!(cell("Level", 0), Some(&IESValue::Float(2.5)));
+        assert!(cell("ClassName", 1).unwrap().is_null());
+        assert_eq!(edited.get_rows_length().unwrap(), 3);
+
+        let bad = IESFile::from_csv("Level\nhigh\n".as_bytes(), &ies, &options);
+        assert_eq!(bad.unwrap_err().context().and_then(|c| c.row), Some(0));
+        assert!(IESFile::from_csv("Price\n".as_bytes(), &ies, &options).is_err());
+    }
+
+    #[test]
+    fn json_rows_are_keyed_by_column() {
+        let ies = IESFile::load_from_slice(&table(2)).unwrap();
+        let json = ies.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"[{"Level":0,"ClassName":"Item_0"},{"Level":1,"ClassName":"Item_1"}]"#
+        );
+        assert_eq!(IESFile::from_json(&json).unwrap().to_json().unwrap(), json);
+
+        let edited = r#"[{"ClassID": 7, "ClassName": "Sword", "Weight": 2.5}, {"ClassID": 8, "Weight": "heavy"}]"#;
+        let rebuilt = IESFile::from_json(edited).unwrap();
+        let bytes = rebuilt.to_bytes().unwrap();
+        let reloaded = IESFile::load_from_slice(&bytes).unwrap();
+        assert_eq!(reloaded.row_keys[0].class_id, 7);
+        assert_eq!(reloaded.row_keys[0].class_name, "Sword");
+        let weight = reloaded.row(0).and_then(|row| row.value("Weight"));
+        assert_eq!(weight.and_then(IESValue::as_str), Some("2.5"));
+        assert!(IESFile::from_json(r#"[{"Level": [1]}]"#).is_err());
+    }
+
+    #[test]
+    fn rows_give_typed_cells() {
+        let ies = IESFile::load_from_slice(&table(3)).unwrap();
+        assert_eq!(ies.row_count(), 3);
+        let levels: Vec<f32> = ies.rows().filter_map(|row| row.get("Level")).collect();
+        assert_eq!(levels, [0.0, 1.0, 2.0]);
+        let row = ies.row(2).unwrap();
+        assert_eq!(row.get::<u8>("Level"), Some(2));
+        assert_eq!(row.get::<String>("ClassName").as_deref(), Some("Item_2"));
+        assert_eq!(row.get::<f32>("ClassName"), None);
+        assert!(ies.row(3).is_none());
+
+        let column = ies.column("ClassName").unwrap();
+        assert_eq!(column.column_type(), IESColumnType::String);
+        assert!(!column.is_number() && ies.column("Price").is_none());
+        let names: Vec<&str> = row.cells().map(|(column, _)| column.name()).collect();
+        assert_eq!(names, ["Level", "ClassName"]);
+    }
+
+    #[test]
+    fn rows_deserialize_into_structs() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Item {
+            level: f32,
+            #[serde(rename = "classname")]
+            name: String,
+            price: Option<u32>,
+        }
+
+        let mut ies = IESFile::load_from_slice(&table(2)).unwrap();
+        ies.set_data_by_column_name_and_index("ClassName", 1, "")
+            .unwrap();
+        let items: Vec<Item> = ies.deserialize_rows().unwrap();
+        assert_eq!(
+            items,
+            [
+                Item {
+                    level: 0.0,
+                    name: "Item_0".to_string(),
+                    price: None
+                },
+                Item {
+                    level: 1.0,
+                    name: String::new(),
+                    price: None
+                },
+            ]
+        );
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Levels {
+            level: u8,
+            classname: u8,
+        }
+        let err = ies.deserialize_rows::<Levels>().unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.row), Some(0));
+    }
+
+    #[test]
+    fn diff_reports_rows_and_cells_by_key() {
+        let old = IESFile::from_json(
+            r#"[{"ClassID": 1, "Name": "Sword", "Price": 100, "Old": 1},
+                {"ClassID": 2, "Name": "Shield", "Price": 50, "Old": 1},
+                {"ClassID": 3, "Name": "Bow", "Price": 70, "Old": 1}]"#,
+        )
+        .unwrap();
+        let new = IESFile::from_json(
+            r#"[{"ClassID": 4, "Name": "Staff", "Price": 80, "Weight": 2},
+                {"ClassID": 3, "Name": "Bow", "Price": 70.0, "Weight": 3},
+                {"ClassID": 1, "Name": "Sword", "Price": 120.5, "Weight": 5}]"#,
+        )
+        .unwrap();
+
+        let changes = diff(&old, &new, "ClassID").unwrap();
+        assert_eq!(changes.added, ["4"]);
+        assert_eq!(changes.removed, ["2"]);
+        assert_eq!(changes.added_columns, ["Weight"]);
+        assert_eq!(changes.removed_columns, ["Old"]);
+        assert_eq!(
+            changes.changed,
+            [IesRowChange {
+                key: "1".to_string(),
+                cells: vec![IesCellChange {
+                    column: "Price".to_string(),
+                    old: IESValue::Int(100),
+                    new: IESValue::Float(120.5),
+                }],
+            }]
+        );
+        assert!(diff(&old, &old, "ClassID").unwrap().is_empty());
+        assert!(diff(&old, &new, "Missing").is_err());
+    }
+
+    #[test]
+    fn lazy_rows_match_an_eager_load() {
+        let bytes = table(300);
+        let eager = IESFile::load_from_slice(&bytes).unwrap();
+        let options = ParseOptions::default();
+        let mut lazy = LazyIESFile::from_reader(Cursor::new(&bytes), &options).unwrap();
+        assert_eq!(lazy.row_count(), 300);
+        assert!(lazy.column("Level").unwrap().is_number());
+        for (lazy_row, row) in lazy.rows().zip(eager.rows()) {
+            let lazy_row = lazy_row.unwrap();
+            assert_eq!(lazy_row.index(), row.index());
+            assert_eq!(lazy_row.value("ClassName"), row.value("ClassName"));
+        }
+        // A second pass starts over
+        let last = lazy.rows().last().unwrap().unwrap();
+        assert_eq!(last.get::<u32>("Level"), Some(299));
+
+        let truncated = &bytes[..bytes.len() - 3];
+        let mut lazy = LazyIESFile::from_reader(Cursor::new(truncated), &options).unwrap();
+        let rows: Vec<Result<LazyRow>> = lazy.rows().collect();
+        assert_eq!(rows.len(), 300);
+        let err = rows.last().unwrap().as_ref().unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.row), Some(299));
+    }
+
+    #[test]
+    fn validation_reports_every_inconsistency() {
+        let bytes = table(3);
+        let report = IESFile::validate(&bytes);
+        assert!(report.is_valid(), "{:?}", report.issues);
+        assert_eq!(report.rows_read, 3);
+
+        let truncated = IESFile::validate(&bytes[..bytes.len() - 3]);
+        assert_eq!(truncated.rows_read, 2);
+        assert!(matches!(
+            truncated.issues.as_slice(),
+            [
+                IesIssue::CountMismatch {
+                    field: "file size",
+                    ..
+                },
+                IesIssue::TruncatedCell {
+                    row: 2,
+                    length: 6,
+                    available: 4,
+                    ..
+                },
+            ]
+        ));
+
+        // Two columns declared as numbers and one extra row that is not there
+        let mut tampered = bytes.clone();
+        tampered[HEADER_NAME + 18..HEADER_NAME + 20].copy_from_slice(&4u16.to_le_bytes());
+        tampered[HEADER_NAME + 22..HEADER_NAME + 24].copy_from_slice(&2u16.to_le_bytes());
+        let report = IESFile::validate(&tampered);
+        let messages: Vec<String> = report.issues.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "number column count declares 2, but the data has 1".to_string(),
+                format!(
+                    "row 3 at offset {} is cut off by the end of the file",
+                    bytes.len()
+                ),
+            ]
+        );
+        assert!(!IESFile::validate(&bytes[..10]).is_valid());
+    }
+
+    #[test]
+    fn tables_load_from_archives_and_mounts() {
+        let dir = std::env::temp_dir().join(format!("toslib-ies-ipf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("ies.ipf");
+        let mut writer = crate::ipf::IPFWriter::new();
+        writer.add_file("ies.ipf", "item.ies", table(2));
+        writer.save_to_file(&archive).unwrap();
+
+        let by_full_path = IESFile::from_ipf(archive.as_path(), "ies.ipf/item.ies");
+        let by_inner_path = IESFile::from_ipf(&archive, "ITEM.ies");
+        let mount = crate::mount::IpfMount::from_archives([&archive]).unwrap();
+        let mounted = IESFile::from_ipf(&mount, "ies.ipf/item.ies");
+        let missing = IESFile::from_ipf(archive.to_str().unwrap(), "ies.ipf/skill.ies");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for table in [by_full_path, by_inner_path, mounted] {
+            assert_eq!(table.unwrap().row_count(), 2);
+        }
+        let err = missing.unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.path.clone()), Some(archive));
+    }
+
+    #[test]
+    fn merge_upserts_rows_by_key() {
+        let overlay = IESFile::from_json(
+            r#"[{"ClassName": "Item_1", "Level": 10, "Weight": 3},
+                {"ClassName": "Item_9", "Level": 9},
+                {"ClassName": "Item_0", "Level": 0}]"#,
+        )
+        .unwrap();
+        let merge = |policy| {
+            let mut ies = IESFile::load_from_slice(&table(3)).unwrap();
+            let summary = ies.merge(&overlay, "ClassName", policy);
+            (ies, summary)
+        };
+
+        let (kept, summary) = merge(ConflictPolicy::FirstWins);
+        let summary = summary.unwrap();
+        assert_eq!((summary.added, summary.updated, summary.kept), (1, 0, 1));
+        assert_eq!(kept.row(1).unwrap().get::<u32>("Level"), Some(1));
+
+        let (taken, summary) = merge(ConflictPolicy::LastWins);
+        assert_eq!(summary.unwrap().updated, 1);
+        let reloaded = IESFile::load_from_slice(&taken.to_bytes().unwrap()).unwrap();
+        let names: Vec<&str> = reloaded.columns().iter().map(IESColumn::name).collect();
+        assert_eq!(names, ["Level", "Weight", "ClassName"]);
+        let item = reloaded.row(1).unwrap();
+        assert_eq!(
+            (item.get::<u32>("Level"), item.get::<u32>("Weight")),
+            (Some(10), Some(3))
+        );
+        let added = reloaded.row(3).unwrap();
+        assert_eq!(added.get::<String>("ClassName").as_deref(), Some("Item_9"));
+        assert!(added.value("Weight").unwrap().is_null() || added.get::<u32>("Weight") == Some(0));
+
+        let (unchanged, summary) = merge(ConflictPolicy::Error);
+        assert_eq!(summary.unwrap_err().context().and_then(|c| c.row), Some(0));
+        assert_eq!((unchanged.row_count(), unchanged.columns().len()), (3, 2));
+    }
+
+    #[test]
+    fn xml_uses_the_idspace_layout() {
+        let mut ies = IESFile::from_json(
+            r#"[{"ClassID": 1, "ClassName": "Sword", "Price": 2.5, "Desc": "A \"fine\" <blade> & more"},
+                {"ClassID": 2, "ClassName": "Bow", "Price": null, "Desc": "Line\nbreak"}]"#,
+        )
+        .unwrap();
+        ies.header.name = "item.ies".to_string();
+        let xml = ies.to_xml();
+        let lines: Vec<&str> = xml.lines().collect();
+        assert_eq!(
+            lines[1..],
+            [
+                r#"<idspace id="item">"#,
+                "\t<Class ClassID=\"1\" ClassName=\"Sword\" Price=\"2.5\" Desc=\"A &quot;fine&quot; &lt;blade&gt; &amp; more\"/>",
+                "\t<Class ClassID=\"2\" ClassName=\"Bow\" Desc=\"Line&#10;break\"/>",
+                "</idspace>",
+            ]
+        );
+
+        let root = crate::search::parse_xml(xml.as_bytes()).unwrap();
+        let classes: Vec<_> = root.find_all("Class").collect();
+        assert_eq!(classes[1].get_attr("Desc"), Some("Line\nbreak"));
+        assert_eq!(
+            classes[0].get_attr("Desc"),
+            Some(r#"A "fine" <blade> & more"#)
+        );
+    }
+
+    #[test]
+    fn cp949_cells_keep_their_bytes() {
+        let cp949 = [0xC7, 0xD1, 0xB1, 0xDB]; // "한글"
+        let ies = IESFile::from_json(r#"[{"Level": 1, "Note": "ABCD"}]"#).unwrap();
+        let mut bytes = ies.to_bytes().unwrap();
+        let at = bytes
+            .windows(4)
+            .position(|window| window == b"ABCD".map(|b| b ^ 1))
+            .unwrap();
+        bytes[at..at + 4].copy_from_slice(&cp949.map(|b| b ^ 1));
+
+        let lossy = IESFile::load_from_slice(&bytes).unwrap();
+        let row = lossy.row(0).unwrap();
+        assert!(row.get::<String>("Note").unwrap().contains('\u{FFFD}'));
+        assert_eq!(row.raw("Note").as_deref(), Some(&cp949[..]));
+        assert_eq!(row.raw("Level"), None);
+        assert_eq!(lossy.to_bytes().unwrap(), bytes);
+
+        let options = ParseOptions::default().with_text(TextDecoding::Cp949);
+        let mut korean = IESFile::load_from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(
+            korean.row(0).unwrap().get::<String>("Note").as_deref(),
+            Some("한글")
+        );
+        assert_eq!(korean.to_bytes().unwrap(), bytes);
+        let mut lazy = LazyIESFile::from_reader(Cursor::new(&bytes), &options).unwrap();
+        let lazy_row = lazy.rows().next().unwrap().unwrap();
+        assert_eq!(lazy_row.raw("Note").as_deref(), Some(&cp949[..]));
+
+        korean
+            .set_data_by_column_name_and_index("Note", 0, "새")
+            .unwrap();
+        let raw = korean.row(0).unwrap().raw("Note").unwrap().into_owned();
+        assert_eq!(raw, "새".as_bytes());
+    }
+
+    #[test]
+    fn batch_conversion_reports_each_file() {
+        let dir = std::env::temp_dir().join(format!("toslib-ies-batch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("patch")).unwrap();
+        let inputs = [
+            dir.join("item.ies"),
+            dir.join("broken.ies"),
+            dir.join("patch/item.ies"),
+            dir.join("skill.ies"),
+        ];
+        std::fs::write(&inputs[0], table(2)).unwrap();
+        std::fs::write(&inputs[1], &table(2)[..200]).unwrap();
+        std::fs::write(&inputs[2], table(1)).unwrap();
+        std::fs::write(&inputs[3], table(1)).unwrap();
+
+        let out = dir.join("out");
+        let results = convert_batch(&inputs, IesFormat::Csv, &out).unwrap();
+        let written = std::fs::read_to_string(out.join("item.csv"));
+        let skill = std::fs::read_to_string(out.join("skill.csv"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let inputs_back: Vec<&PathBuf> = results.iter().map(|(input, _)| input).collect();
+        assert_eq!(inputs_back, inputs.iter().collect::<Vec<_>>());
+        assert_eq!(results[0].1.as_ref().unwrap(), &out.join("item.csv"));
+        assert_eq!(
+            written.unwrap(),
+            "Level,ClassName\r\n0,Item_0\r\n1,Item_1\r\n"
+        );
+        let broken = results[1].1.as_ref().unwrap_err();
+        assert_eq!(
+            broken.context().and_then(|c| c.path.as_deref()),
+            Some(inputs[1].as_path())
+        );
+        assert!(results[2].1.is_err());
+        assert!(results[3].1.is_ok() && skill.is_ok());
+        assert_eq!(
+            IesFormat::from_name("xml").map(IesFormat::extension),
+            Some("xml")
+        );
+    }
 }
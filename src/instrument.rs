@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Stage of the pipeline a timing belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    /// Deciphering an archive entry; bytes are the compressed size
+    Decrypt,
+    /// Inflating an archive entry; bytes are the compressed input
+    Inflate,
+    /// Decoding one XAC chunk body; bytes are the declared chunk size
+    ParseChunk,
+    /// Converting parsed meshes for export
+    Export,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::Decrypt,
+        Phase::Inflate,
+        Phase::ParseChunk,
+        Phase::Export,
+    ];
+}
+
+/// Receiver of timings from every loader in the process, see `set_instrumentation`.
+/// Called from worker threads, so implementations should be cheap and lock-free.
+pub trait Instrumentation: Send + Sync {
+    fn phase(&self, phase: Phase, elapsed: Duration, bytes: u64);
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static HOOK: RwLock<Option<Arc<dyn Instrumentation>>> = RwLock::new(None);
+
+/// Installs `hook` for the whole process, replacing any previous one. With no hook
+/// installed the timing points cost a single relaxed load.
+pub fn set_instrumentation(hook: Arc<dyn Instrumentation>) {
+    *HOOK.write().unwrap() = Some(hook);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn clear_instrumentation() {
+    ENABLED.store(false, Ordering::Relaxed);
+    *HOOK.write().unwrap() = None;
+}
+
+/// Runs `f` and reports its duration as `phase` when a hook is installed.
+pub(crate) fn timed<T>(phase: Phase, bytes: u64, f: impl FnOnce() -> T) -> T {
+    if !enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    record(phase, start.elapsed(), bytes);
+    result
+}
+
+/// Reports a duration measured by the caller, for phases that run interleaved.
+pub(crate) fn record(phase: Phase, elapsed: Duration, bytes: u64) {
+    if let Some(hook) = HOOK.read().unwrap().as_ref() {
+        hook.phase(phase, elapsed, bytes);
+    }
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Runs `f` and adds its duration to `total` when a hook is installed.
+pub(crate) fn lap<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    if !enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    *total += start.elapsed();
+    result
+}
+
+/// Totals for one phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub calls: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Ready-made `Instrumentation` that sums calls, bytes and time per phase.
+#[derive(Debug, Default)]
+pub struct PhaseTotals {
+    // calls, bytes, nanoseconds for every phase
+    counters: [[AtomicU64; 3]; Phase::ALL.len()],
+}
+
+impl PhaseTotals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, phase: Phase) -> PhaseStats {
+        let [calls, bytes, nanos] = &self.counters[phase as usize];
+        PhaseStats {
+            calls: calls.load(Ordering::Relaxed),
+            bytes: bytes.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Instrumentation for PhaseTotals {
+    fn phase(&self, phase: Phase, elapsed: Duration, bytes: u64) {
+        let [calls, total_bytes, nanos] = &self.counters[phase as usize];
+        calls.fetch_add(1, Ordering::Relaxed);
+        total_bytes.fetch_add(bytes, Ordering::Relaxed);
+        nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::{IPFFile, IPFWriter};
+    use crate::tosreader::{BinaryReader, BinaryWriter};
+    use std::io::Cursor;
+
+    #[test]
+    fn extraction_reports_decrypt_and_inflate() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer.add_file("patch.ipf", "a.bin", vec![1u8; 5000]);
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(writer.writer.into_inner()));
+        let ipf = IPFFile::load_from_reader(&mut reader).unwrap();
+        let entry = &ipf.file_table()[0];
+
+        let totals = Arc::new(PhaseTotals::new());
+        set_instrumentation(totals.clone());
+        entry.extract(&mut reader).unwrap();
+        entry
+            .extract_into(&mut reader, &mut std::io::sink())
+            .unwrap();
+        clear_instrumentation();
+
+        // Other tests may extract at the same time, so only lower bounds hold
+        for phase in [Phase::Decrypt, Phase::Inflate] {
+            let stats = totals.get(phase);
+            assert!(stats.calls >= 2);
+            assert!(stats.bytes >= 2 * entry.file_size_compressed() as u64);
+        }
+    }
+}
@@ -1,8 +1,26 @@
 #![allow(dead_code)]
-use crate::tosreader::BinaryReader;
+use crate::error::{Error, Result};
+use crate::events::{Operation, Task};
+use crate::glob::{self, Glob};
+use crate::instrument::{self, Phase};
+use crate::options::ParseOptions;
+use crate::progress::{Progress, ProgressReporter};
+use crate::serial;
+use crate::text::TextDecoding;
+use crate::tosreader::{BinaryReader, BinaryWriter};
+use crate::warning::ParseWarning;
+use flate2::Compression;
+use flate2::write::{DeflateDecoder, DeflateEncoder};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 
 const HEADER_LOCATION: i64 = -24;
 const MAGIC_NUMBER: u32 = 0x6054B50;
@@ -45,6 +63,214 @@ const PASSWORD: [u8; 20] = [
     0x68, 0x20, 0x25, 0x3F,
 ];
 
+/// Key state of the archive cipher, a ZipCrypto variant that only touches every
+/// other byte of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CipherKeys(u32, u32, u32);
+
+impl CipherKeys {
+    /// Keys after feeding the client password, computed at compile time.
+    const INITIAL: CipherKeys = CipherKeys::from_password(&PASSWORD);
+
+    const fn from_password(password: &[u8]) -> CipherKeys {
+        let mut keys = CipherKeys(0x12345678, 0x23456789, 0x34567890);
+        let mut i = 0;
+        while i < password.len() {
+            keys = keys.update(password[i]);
+            i += 1;
+        }
+        keys
+    }
+
+    #[inline(always)]
+    const fn crc32_byte(crc: u32, b: u8) -> u32 {
+        CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    }
+
+    /// Advances the keys with a plain byte.
+    #[inline(always)]
+    const fn update(self, b: u8) -> CipherKeys {
+        let k0 = Self::crc32_byte(self.0, b);
+        let k1 = 0x8088405u32
+            .wrapping_mul((k0 as u8 as u32).wrapping_add(self.1))
+            .wrapping_add(1);
+        let k2 = Self::crc32_byte(self.2, (k1 >> 24) as u8);
+        CipherKeys(k0, k1, k2)
+    }
+
+    /// Byte the current keys XOR into the data.
+    #[inline(always)]
+    const fn stream_byte(self) -> u8 {
+        let v = (self.2 & 0xFFFD) | 2;
+        (v.wrapping_mul(v ^ 1) >> 8) as u8
+    }
+}
+
+/// Encryption of the entry payloads of an archive.
+///
+/// The default is the cipher of the live clients. Some builds (test servers, older
+/// clients) use another password or store payloads unencrypted; pass the matching
+/// value in `ParseOptions::crypto` to read them and to `IPFWriter::crypto` to write
+/// them.
+///
+/// Whatever the cipher, the client stores media files (`.fsb`, `.jpg`, `.mp3`) as
+/// is, neither encrypted nor compressed; `with_plain_extensions` changes that list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpfCrypto {
+    keys: Option<CipherKeys>,
+    plain: &'static [&'static str],
+}
+
+/// Extensions the client stores as is.
+const PLAIN_EXTENSIONS: &[&str] = &["fsb", "jpg", "mp3"];
+
+impl Default for IpfCrypto {
+    fn default() -> Self {
+        Self {
+            keys: Some(CipherKeys::INITIAL),
+            plain: PLAIN_EXTENSIONS,
+        }
+    }
+}
+
+impl IpfCrypto {
+    /// The cipher keyed with `password` instead of the client password.
+    pub fn with_password(password: &[u8]) -> Self {
+        Self {
+            keys: Some(CipherKeys::from_password(password)),
+            ..Self::default()
+        }
+    }
+
+    /// Payloads stored as plain deflate streams.
+    pub fn none() -> Self {
+        Self {
+            keys: None,
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the extensions (without dot, any case) of entries stored as is.
+    pub fn with_plain_extensions(mut self, extensions: &'static [&'static str]) -> Self {
+        self.plain = extensions;
+        self
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Whether entries named `path` are stored as is rather than compressed and
+    /// encrypted.
+    pub fn is_plain(&self, path: &str) -> bool {
+        self.is_plain_name(path.as_bytes())
+    }
+
+    fn is_plain_name(&self, name: &[u8]) -> bool {
+        let Some(dot) = name.iter().rposition(|&b| b == b'.') else {
+            return false;
+        };
+        let extension = &name[dot + 1..];
+        self.plain
+            .iter()
+            .any(|plain| plain.as_bytes().eq_ignore_ascii_case(extension))
+    }
+
+    pub fn decrypt_in_place(&self, data: &mut [u8]) {
+        if let Some(keys) = self.keys {
+            decrypt_with(keys, data);
+        }
+    }
+
+    pub fn encrypt_in_place(&self, data: &mut [u8]) {
+        if let Some(keys) = self.keys {
+            encrypt_with(keys, data);
+        }
+    }
+}
+
+/// Decrypts an entry payload in place. Works on any slice, so callers can decrypt
+/// buffers they own, e.g. from several threads at once.
+pub fn decrypt_in_place(data: &mut [u8]) {
+    decrypt_with(CipherKeys::INITIAL, data);
+}
+
+/// Decrypts `data` starting from `keys` and returns the keys to continue with.
+/// Only a block of even length can be followed by another one.
+fn decrypt_with(mut keys: CipherKeys, data: &mut [u8]) -> CipherKeys {
+    let mut pairs = data.chunks_exact_mut(2);
+    for pair in &mut pairs {
+        pair[0] ^= keys.stream_byte();
+        keys = keys.update(pair[0]);
+    }
+    if let [last] = pairs.into_remainder() {
+        *last ^= keys.stream_byte();
+    }
+    keys
+}
+
+/// Inverse of `decrypt_in_place`: the key stream is advanced with the plain byte.
+pub fn encrypt_in_place(data: &mut [u8]) {
+    encrypt_with(CipherKeys::INITIAL, data);
+}
+
+fn encrypt_with(mut keys: CipherKeys, data: &mut [u8]) {
+    let mut pairs = data.chunks_exact_mut(2);
+    for pair in &mut pairs {
+        let plain = pair[0];
+        pair[0] ^= keys.stream_byte();
+        keys = keys.update(plain);
+    }
+    if let [last] = pairs.into_remainder() {
+        *last ^= keys.stream_byte();
+    }
+}
+
+/// Broad category of an archive entry, derived from its file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IPFEntryKind {
+    Table,   // .ies
+    Xml,     // .xml
+    Model,   // .xac
+    Motion,  // .xsm, .xpm
+    Texture, // .dds, .tga, .png, .jpg, .bmp
+    Audio,   // .fsb, .mp3, .wav, .ogg
+    Script,  // .lua
+    Other,
+}
+
+impl IPFEntryKind {
+    pub fn from_path(path: &str) -> Self {
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "ies" => IPFEntryKind::Table,
+            "xml" => IPFEntryKind::Xml,
+            "xac" => IPFEntryKind::Model,
+            "xsm" | "xpm" => IPFEntryKind::Motion,
+            "dds" | "tga" | "png" | "jpg" | "jpeg" | "bmp" => IPFEntryKind::Texture,
+            "fsb" | "mp3" | "wav" | "ogg" => IPFEntryKind::Audio,
+            "lua" => IPFEntryKind::Script,
+            _ => IPFEntryKind::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IPFEntryKind::Table => "table",
+            IPFEntryKind::Xml => "xml",
+            IPFEntryKind::Model => "model",
+            IPFEntryKind::Motion => "motion",
+            IPFEntryKind::Texture => "texture",
+            IPFEntryKind::Audio => "audio",
+            IPFEntryKind::Script => "script",
+            IPFEntryKind::Other => "other",
+        }
+    }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct IPFFooter {
     file_count: u16,
@@ -55,7 +281,7 @@ pub struct IPFFooter {
     new_version: u32,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct IPFFileTable {
     directory_name_length: u16,
     crc32: u32,
@@ -65,31 +291,179 @@ pub struct IPFFileTable {
     container_name_length: u16,
     container_name: Vec<u8>,
     directory_name: Vec<u8>,
+    #[serde(skip)]
+    text: TextDecoding,
+    #[serde(skip)]
+    crypto: IpfCrypto,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct IPFFile {
     footer: IPFFooter,
     file_table: Vec<IPFFileTable>,
+    #[serde(skip)]
+    warnings: Vec<ParseWarning>,
+    // Archive on disk, when loaded from a file
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    // Normalized `container/path` to file table position, built on the first lookup
+    #[serde(skip)]
+    index: OnceLock<HashMap<String, usize>>,
+}
+
+/// Settings for `IPFFile::extract_all`.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractOptions {
+    /// Worker threads; 0 uses the global rayon pool
+    pub threads: usize,
+    /// Replace files that already exist in the target directory
+    pub overwrite: bool,
+    /// Check every entry against the size and CRC32 in the file table
+    pub verify: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            overwrite: true,
+            verify: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
 }
 
 impl IPFFile {
-    pub fn _load_from_file<P: AsRef<std::path::Path>>(file_path: P) -> io::Result<Self> {
-        let file = File::open(file_path)?;
-        let buf_reader = BufReader::new(file);
-        let mut reader = BinaryReader::new(buf_reader);
+    pub fn load_from_file<P: AsRef<std::path::Path>>(file_path: P) -> Result<Self> {
+        Self::load_from_file_with_options(file_path, &ParseOptions::default())
+    }
+
+    /// Parses the archive index from a borrowed buffer. Any byte slice is accepted and
+    /// malformed input yields an error, which makes this the entry point for fuzzing.
+    pub fn load_from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryReader::new(Cursor::new(bytes));
         Self::load_from_reader(&mut reader)
     }
 
-    pub fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> io::Result<Self> {
-        let footer = Self::read_footer(reader)?;
-        let file_table =
-            Self::read_file_table(reader, footer.file_table_pointer, footer.file_count)?;
+    pub fn load_from_file_with_options<P: AsRef<std::path::Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        let load = || {
+            let file = File::open(file_path)?;
+            let mut reader = BinaryReader::new(BufReader::new(file));
+            Self::load_from_reader_with_options(&mut reader, options)
+        };
+        let mut ipf = load().map_err(|err| err.in_file(file_path))?;
+        ipf.path = Some(file_path.to_path_buf());
+        Ok(ipf)
+    }
+
+    pub fn load_from_slice_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Self> {
+        let mut reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_from_reader_with_options(&mut reader, options)
+    }
 
-        Ok(IPFFile { footer, file_table })
+    pub fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        Self::load_from_reader_with_options(reader, &ParseOptions::default())
     }
 
-    fn read_footer<R: Read + Seek>(reader: &mut BinaryReader<R>) -> io::Result<IPFFooter> {
+    pub fn load_from_reader_with_options<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let footer_offset = reader.file_size()?.saturating_sub(-HEADER_LOCATION as u64);
+        let footer = Self::read_footer(reader).map_err(|err| err.at_offset(footer_offset))?;
+        let file_table = Self::read_file_table(
+            reader,
+            footer.file_table_pointer,
+            footer.file_count,
+            options,
+        )?;
+
+        let mut ipf = IPFFile {
+            footer,
+            file_table,
+            warnings: Vec::new(),
+            path: None,
+            index: OnceLock::new(),
+        };
+        ipf.check_layout(reader, options)?;
+        Ok(ipf)
+    }
+
+    /// The file table must run right up to the footer, which sits at the very end.
+    fn check_layout<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        let footer_start = reader.file_size()? - (-HEADER_LOCATION) as u64;
+        if self.footer.footer_pointer as u64 != footer_start {
+            options.report(
+                &mut self.warnings,
+                ParseWarning::SuspiciousValue {
+                    field: "footer pointer",
+                    declared: self.footer.footer_pointer as u64,
+                    actual: footer_start,
+                },
+            )?;
+        }
+
+        let offset = reader.tell()?;
+        if offset < footer_start {
+            options.report(
+                &mut self.warnings,
+                ParseWarning::SkippedBytes {
+                    offset,
+                    count: footer_start - offset,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Problems that were recovered from while loading in lenient mode.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Dumps the parsed archive manifest (footer and file table, no entry data) as JSON, see `from_json`.
+    pub fn to_json(&self) -> Result<String> {
+        serial::to_json(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serial::from_json(json)
+    }
+
+    /// Dumps the parsed archive manifest (footer and file table, no entry data) as compact binary CBOR, see `from_cbor`.
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        serial::to_cbor(self)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        serial::from_cbor(bytes)
+    }
+
+    fn read_footer<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<IPFFooter> {
         let mut footer = IPFFooter::default();
 
         reader.seek(SeekFrom::End(HEADER_LOCATION))?;
@@ -103,13 +477,11 @@ impl IPFFile {
         footer.new_version = reader.read_u32()?;
 
         if footer.magic != MAGIC_NUMBER {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "Invalid IPF magic number: expected {:08x}, got {:08x}",
-                    MAGIC_NUMBER, footer.magic
-                ),
-            ));
+            return Err(Error::BadMagic {
+                format: "IPF",
+                expected: MAGIC_NUMBER,
+                found: footer.magic,
+            });
         }
 
         Ok(footer)
@@ -119,19 +491,36 @@ impl IPFFile {
         reader: &mut BinaryReader<R>,
         table_offset: u32,
         file_count: u16,
-    ) -> io::Result<Vec<IPFFileTable>> {
+        options: &ParseOptions,
+    ) -> Result<Vec<IPFFileTable>> {
+        // Every entry is at least 20 bytes, so a table that cannot fit before the
+        // footer is corrupt and would otherwise over-allocate
+        let table_end = reader.file_size()?.saturating_sub(-HEADER_LOCATION as u64);
+        if table_offset as u64 + file_count as u64 * 20 > table_end {
+            return Err(Error::Ipf(format!(
+                "File table of {} entries at offset {} does not fit in the archive",
+                file_count, table_offset
+            )));
+        }
+
         reader.seek(SeekFrom::Start(table_offset as u64))?;
         let mut file_table = Vec::with_capacity(file_count as usize);
 
         for _ in 0..file_count {
-            let file_entry = Self::read_file_entry(reader)?;
+            let offset = reader.tell()?;
+            let file_entry =
+                Self::read_file_entry(reader, options).map_err(|err| err.at_offset(offset))?;
             file_table.push(file_entry);
         }
 
         Ok(file_table)
     }
 
-    fn read_file_entry<R: Read + Seek>(reader: &mut BinaryReader<R>) -> io::Result<IPFFileTable> {
+    fn read_file_entry<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<IPFFileTable> {
+        let text = options.text;
         let directory_name_length = reader.read_u16()?;
         let crc32 = reader.read_u32()?;
         let file_size_compressed = reader.read_u32()?;
@@ -142,6 +531,10 @@ impl IPFFile {
         let container_name = reader.read_bytes_u16(container_name_length)?;
         let directory_name = reader.read_bytes_u16(directory_name_length)?;
 
+        // Reject names the policy cannot decode up front so the getters never fail
+        text.decode_cow(&container_name)?;
+        text.decode_cow(&directory_name)?;
+
         Ok(IPFFileTable {
             directory_name_length,
             crc32,
@@ -151,6 +544,8 @@ impl IPFFile {
             container_name_length,
             container_name,
             directory_name,
+            text,
+            crypto: options.crypto,
         })
     }
 
@@ -164,7 +559,282 @@ impl IPFFile {
         &self.file_table
     }
 
-    pub fn test() -> io::Result<()> {
+    /// A listing of every entry in file table order.
+    pub fn entries_info(&self) -> Vec<IpfEntryInfo> {
+        self.file_table
+            .iter()
+            .map(|entry| IpfEntryInfo {
+                container: entry.container_name_str().into_owned(),
+                path: entry.directory_name_str().into_owned(),
+                kind: entry.kind(),
+                compressed_size: entry.file_size_compressed,
+                uncompressed_size: entry.file_size_uncompressed,
+                ratio: match entry.file_size_uncompressed {
+                    0 => 1.0,
+                    size => entry.file_size_compressed as f64 / size as f64,
+                },
+                crc32: entry.crc32,
+                offset: entry.file_pointer,
+                version: self.footer.new_version,
+            })
+            .collect()
+    }
+
+    /// The entry stored under its full `container/path` name, compared ignoring case
+    /// and separator style, e.g. `xml.ipf/item.xml`. When a name repeats, the last
+    /// entry wins. The first call indexes the file table, later ones are hash lookups.
+    pub fn get_by_path(&self, path: &str) -> Option<&IPFFileTable> {
+        let index = self.index.get_or_init(|| {
+            self.file_table
+                .iter()
+                .enumerate()
+                .map(|(position, entry)| (full_path_key(entry), position))
+                .collect()
+        });
+        index
+            .get(&glob::normalize(path))
+            .map(|&position| &self.file_table[position])
+    }
+
+    /// The entry stored under `path`, compared ignoring case and separator style.
+    /// When a name repeats, the last entry wins, as it does when the archive is applied.
+    pub fn find(&self, path: &str) -> Option<&IPFFileTable> {
+        let path = glob::normalize(path);
+        self.file_table
+            .iter()
+            .rev()
+            .find(|entry| glob::normalize(&entry.directory_name_str()) == path)
+    }
+
+    /// Entries whose directory name matches `pattern`, e.g. `"**/*.xac"`; see `Glob`.
+    pub fn entries_matching(&self, pattern: &str) -> Matching<std::slice::Iter<'_, IPFFileTable>> {
+        self.file_table.iter().matching(pattern)
+    }
+
+    /// The archive on disk, set by `load_from_file`.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Replaces the content of the entry stored under `path` (see `find`) in the
+    /// archive on disk, keeping its name and its place in the file table.
+    ///
+    /// Nothing is repacked: the new payload goes where the file table was, followed
+    /// by a rewritten table and footer, and the old payload stays behind as unused
+    /// bytes. Only archives loaded with `load_from_file` can be changed this way, and
+    /// a write that is interrupted leaves the archive unreadable.
+    pub fn replace_entry(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        let key = glob::normalize(path);
+        let Some(index) = self
+            .file_table
+            .iter()
+            .rposition(|entry| glob::normalize(&entry.directory_name_str()) == key)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no entry '{}' in the archive", path),
+            )
+            .into());
+        };
+        let table = self.file_table[index].clone();
+        self.write_in_place(table, Some(index), data)
+    }
+
+    /// Adds an entry to the end of the archive on disk, like `replace_entry` does
+    /// with a changed one. The entry is encrypted like the existing ones.
+    pub fn append_entry(
+        &mut self,
+        container_name: &str,
+        directory_name: &str,
+        data: &[u8],
+    ) -> Result<()> {
+        if self.file_table.len() >= u16::MAX as usize {
+            return Err(Error::Ipf(format!(
+                "Too many entries for one IPF archive: {} (max {})",
+                self.file_table.len() + 1,
+                u16::MAX
+            )));
+        }
+        let mut table = IPFFileTable::named(container_name, directory_name)?;
+        if let Some(first) = self.file_table.first() {
+            table.text = first.text;
+            table.crypto = first.crypto;
+        }
+        self.write_in_place(table, None, data)
+    }
+
+    /// Writes `data` for `table` at the file table position, then the file table with
+    /// `table` at `index` or appended, and the footer.
+    fn write_in_place(
+        &mut self,
+        mut table: IPFFileTable,
+        index: Option<usize>,
+        data: &[u8],
+    ) -> Result<()> {
+        let archive = self.path.clone().ok_or_else(|| {
+            Error::Ipf("Archive was not loaded from a file, so it cannot be changed".to_string())
+        })?;
+        let name = table.directory_name_str().into_owned();
+        let mut write = || -> Result<(IPFFooter, Vec<IPFFileTable>)> {
+            let payload = table.pack(data, Compression::default())?;
+            table.file_pointer = self.footer.file_table_pointer;
+            let end = table.file_pointer as u64 + payload.len() as u64;
+            if end > u32::MAX as u64 {
+                return Err(Error::Ipf(
+                    "Archive would grow past the 4 GiB the format can address".to_string(),
+                ));
+            }
+
+            let mut file_table = self.file_table.clone();
+            match index {
+                Some(index) => file_table[index] = table.clone(),
+                None => file_table.push(table.clone()),
+            }
+
+            let file = File::options().read(true).write(true).open(&archive)?;
+            let mut writer = BinaryWriter::new(BufWriter::new(file));
+            writer.seek(SeekFrom::Start(table.file_pointer as u64))?;
+            writer.write_bytes(&payload)?;
+            let footer = write_index(
+                &mut writer,
+                &file_table,
+                self.footer.version_to_patch,
+                self.footer.new_version,
+            )?;
+            // A replaced entry can leave the archive shorter than before
+            let len = writer.tell()?;
+            let file = writer.writer.into_inner().map_err(|err| err.into_error())?;
+            file.set_len(len)?;
+            Ok((footer, file_table))
+        };
+        let (footer, file_table) = write().map_err(|err| err.in_entry(&name).in_file(&archive))?;
+        self.footer = footer;
+        self.file_table = file_table;
+        self.index = OnceLock::new();
+        Ok(())
+    }
+
+    /// Extracts every entry below `target_dir` at its directory name, in parallel.
+    /// Each worker opens its own handle on the archive and streams entries to disk, so
+    /// memory use does not grow with entry size. `progress` is called from the workers
+    /// after each file with the entry, the number of files done and the total.
+    ///
+    /// Only archives loaded with `load_from_file` can be extracted this way. Stops at
+    /// the first failing entry; returns the number of files written.
+    pub fn extract_all<P, F>(
+        &self,
+        target_dir: P,
+        options: &ExtractOptions,
+        progress: F,
+    ) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        F: Fn(&IPFFileTable, usize, usize) + Sync,
+    {
+        self.extract_all_with_progress(target_dir, options, &progress)
+    }
+
+    /// Like `extract_all`, reporting bytes as well as files to `reporter`, which can
+    /// also cancel the extraction from another thread.
+    pub fn extract_all_with_progress<P: AsRef<Path>>(
+        &self,
+        target_dir: P,
+        options: &ExtractOptions,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<usize> {
+        let target_dir = target_dir.as_ref();
+        let archive = self.path.as_deref().ok_or_else(|| {
+            Error::Ipf("Archive was not loaded from a file, so it cannot be reopened".to_string())
+        })?;
+        let task = Task::start(
+            Operation::Extract,
+            &archive.display().to_string(),
+            Some(self.file_table.len() as u64),
+        );
+        let result = match options.threads {
+            0 => self.extract_entries(archive, target_dir, options, reporter, &task),
+            threads => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|err| Error::Io(io::Error::other(err)))
+                .and_then(|pool| {
+                    pool.install(|| {
+                        self.extract_entries(archive, target_dir, options, reporter, &task)
+                    })
+                }),
+        };
+        task.finish(result.map_err(|err| err.in_file(archive)))
+    }
+
+    fn extract_entries(
+        &self,
+        archive: &Path,
+        target_dir: &Path,
+        options: &ExtractOptions,
+        reporter: &dyn ProgressReporter,
+        task: &Task,
+    ) -> Result<usize> {
+        let counter = ProgressCounter::new(&self.file_table);
+        let written = AtomicUsize::new(0);
+        self.file_table.par_iter().try_for_each_init(
+            || None,
+            |reader: &mut Option<BinaryReader<BufReader<File>>>, entry| -> Result<()> {
+                if reporter.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                let mut write = || -> Result<()> {
+                    let target = target_dir.join(entry.relative_path()?);
+                    if !options.overwrite && target.exists() {
+                        return Ok(());
+                    }
+                    let reader = open_worker_reader(reader, archive)?;
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    let mut file = CrcTee::new(BufWriter::new(File::create(&target)?));
+                    entry.extract_into(reader, &mut file)?;
+                    file.inner.flush()?;
+                    if options.verify {
+                        entry.check_crc(&file.crc)?;
+                    }
+                    written.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                };
+                write().map_err(|err| err.in_entry(&entry.directory_name_str()))?;
+                let progress = counter.add(entry);
+                reporter.progress(entry, progress);
+                task.progress(progress.entries_done as u64);
+                Ok(())
+            },
+        )?;
+        Ok(written.into_inner())
+    }
+
+    /// Checks every entry against the size and CRC32 in the file table, in parallel
+    /// like `extract_all` and without writing anything. Returns the entries that
+    /// failed; fails itself only when the archive cannot be reopened.
+    pub fn verify_all(&self) -> Result<Vec<(String, Error)>> {
+        let archive = self.path.as_deref().ok_or_else(|| {
+            Error::Ipf("Archive was not loaded from a file, so it cannot be reopened".to_string())
+        })?;
+        File::open(archive).map_err(|err| Error::from(err).in_file(archive))?;
+        let failures: Vec<(String, Error)> = self
+            .file_table
+            .par_iter()
+            .map_init(
+                || None,
+                |reader: &mut Option<BinaryReader<BufReader<File>>>, entry| {
+                    let result = open_worker_reader(reader, archive)
+                        .and_then(|reader| entry.verify_crc(reader));
+                    (entry.directory_name(), result)
+                },
+            )
+            .filter_map(|(name, result)| result.err().map(|err| (name, err.in_file(archive))))
+            .collect();
+        Ok(failures)
+    }
+
+    pub fn test() -> Result<()> {
         // Open the file and create a buffered reader
         let file = File::open("/home/ridwan/Documents/TreeOfSaviorCN/data/xml_client.ipf")?;
         let mut reader = BinaryReader::new(BufReader::new(file));
@@ -180,7 +850,7 @@ impl IPFFile {
             println!("Directory: {}", file.directory_name());
         }
         // Extract the first file (if available)
-        if let Some(file_entry) = ipf.file_table.get(0) {
+        if let Some(file_entry) = ipf.file_table.first() {
             println!("\nFilename : {}", file_entry.container_name());
             let result = file_entry.extract(&mut reader)?;
             println!("Extracted Data: {}", String::from_utf8_lossy(&result));
@@ -193,73 +863,309 @@ impl IPFFile {
 }
 
 impl IPFFileTable {
-    pub fn extract<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> io::Result<Vec<u8>> {
-        reader.seek(SeekFrom::Start(self.file_pointer as u64))?;
+    pub fn extract<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<Vec<u8>> {
+        self.extract_data(reader).map_err(|err| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
+    }
 
-        let mut encrypted_data = reader.read_bytes(self.file_size_compressed as usize)?;
+    fn extract_data<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<Vec<u8>> {
+        let encrypted_data = self.read_payload(reader)?;
+        self.decode(encrypted_data)
+    }
 
-        self.decrypt(&mut encrypted_data);
-        let decompressed_data = self.decompress(&encrypted_data)?;
+    /// The payload as stored, still compressed and encrypted, for copying the entry
+    /// into another archive with `IPFWriter::add_raw`. The sizes and CRC32 that go
+    /// with it are on this entry.
+    pub fn read_raw<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<Vec<u8>> {
+        self.read_payload(reader).map_err(|err| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
+    }
+
+    fn read_payload<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<Vec<u8>> {
+        self.seek_to_data(reader)?;
+        Ok(reader.read_bytes(self.file_size_compressed as usize)?)
+    }
+
+    /// Extracts the entry from the whole archive held in `archive`, e.g. a memory
+    /// mapping of the file, slicing the payload out instead of seeking a reader.
+    pub fn extract_from(&self, archive: &[u8]) -> Result<Vec<u8>> {
+        let extract = || {
+            let start = self.file_pointer as usize;
+            let payload = archive
+                .get(start..start + self.file_size_compressed as usize)
+                .ok_or_else(|| {
+                    Error::Ipf("Entry extends past the end of the archive".to_string())
+                })?;
+            self.decode(payload.to_vec())
+        };
+        extract().map_err(|err: Error| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
+    }
+
+    /// Extracts the entry with positional reads, which leave the file cursor alone, so
+    /// any number of threads can extract from one shared handle at the same time.
+    pub fn extract_at(&self, archive: &File) -> Result<Vec<u8>> {
+        let extract = || {
+            let end = self.file_pointer as u64 + self.file_size_compressed as u64;
+            if end > archive.metadata()?.len() {
+                return Err(Error::Ipf(
+                    "Entry extends past the end of the archive".to_string(),
+                ));
+            }
+            let mut payload = vec![0u8; self.file_size_compressed as usize];
+            read_exact_at(archive, &mut payload, self.file_pointer as u64)?;
+            self.decode(payload)
+        };
+        extract().map_err(|err| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
+    }
+
+    /// Deciphers and inflates a payload read from the archive.
+    fn decode(&self, mut encrypted_data: Vec<u8>) -> Result<Vec<u8>> {
+        if self.is_stored() {
+            return Ok(encrypted_data);
+        }
+        let size = encrypted_data.len() as u64;
+        instrument::timed(Phase::Decrypt, size, || self.decrypt(&mut encrypted_data));
+        let decompressed_data =
+            instrument::timed(Phase::Inflate, size, || self.decompress(&encrypted_data))?;
 
         Ok(decompressed_data)
     }
 
-    /// Computes the CRC32 value for a single byte using the given CRC32 table.
-    fn compute_crc32(&self, crc: u32, b: u8) -> u32 {
-        CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    /// Like `extract`, but checks the data against the size and CRC32 in the file
+    /// table when `options.verify` is set.
+    pub fn extract_with_options<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        let data = self.extract(reader)?;
+        if options.verify {
+            let mut hasher = Crc32Writer::new();
+            hasher.update(&data);
+            self.check_crc(&hasher)?;
+        }
+        Ok(data)
     }
 
-    /// Extracts a specific byte from a 32-bit integer.
-    fn extract_byte(&self, value: u32, byte_index: usize) -> u8 {
-        (value >> (byte_index * 8)) as u8
+    /// Inflates the entry without keeping it and checks it against the size and
+    /// CRC32 in the file table. Corruption is reported as `Error::CrcMismatch`.
+    pub fn verify_crc<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<()> {
+        let mut hasher = Crc32Writer::new();
+        self.extract_into(reader, &mut hasher)?;
+        self.check_crc(&hasher)
     }
 
-    /// Updates the encryption keys based on the given byte.
-    fn keys_update(&self, keys: &mut [u32; 3], b: u8) {
-        keys[0] = self.compute_crc32(keys[0], b);
-        keys[1] = 0x8088405u32.wrapping_mul((keys[0] as u8 as u32) + keys[1]) + 1;
-        keys[2] = self.compute_crc32(keys[2], self.extract_byte(keys[1], 3));
+    fn check_crc(&self, hasher: &Crc32Writer) -> Result<()> {
+        let check = || {
+            if hasher.len() != self.file_size_uncompressed as u64 {
+                return Err(Error::Ipf(format!(
+                    "size mismatch: expected {} bytes, got {}",
+                    self.file_size_uncompressed,
+                    hasher.len()
+                )));
+            }
+            let actual = hasher.finish();
+            if actual != self.crc32 {
+                return Err(Error::CrcMismatch {
+                    expected: self.crc32,
+                    actual,
+                });
+            }
+            Ok(())
+        };
+        check().map_err(|err| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
+    }
+
+    /// Streams the inflated entry into `writer` without holding the whole entry in
+    /// memory. Returns the number of bytes written.
+    pub fn extract_into<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        writer: &mut W,
+    ) -> Result<u64> {
+        self.extract_stream(reader, writer).map_err(|err| {
+            err.in_entry(&self.directory_name_str())
+                .at_offset(self.file_pointer as u64)
+        })
     }
 
-    /// Generates an initial set of encryption keys based on a predefined password.
-    fn keys_generate(&self) -> [u32; 3] {
-        let mut keys = [0x12345678, 0x23456789, 0x34567890];
+    fn extract_stream<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        writer: &mut W,
+    ) -> Result<u64> {
+        // Even, so the cipher keys carry over from one block to the next
+        const BLOCK_SIZE: usize = 64 * 1024;
 
-        for &byte in PASSWORD.iter() {
-            self.keys_update(&mut keys, byte);
+        self.seek_to_data(reader)?;
+        if self.is_stored() {
+            let size = self.file_size_compressed as u64;
+            return Ok(io::copy(&mut reader.reader.by_ref().take(size), writer)?);
         }
 
-        keys
+        let mut decoder = DeflateDecoder::new(writer);
+        let mut keys = self.cipher_keys();
+        let mut remaining = self.file_size_compressed as usize;
+        let mut block = vec![0u8; BLOCK_SIZE.min(remaining)];
+        // Inflate time includes writing to the destination
+        let (mut decrypt_time, mut inflate_time) = (Duration::ZERO, Duration::ZERO);
+        while remaining > 0 {
+            let block = &mut block[..BLOCK_SIZE.min(remaining)];
+            reader.reader.read_exact(block)?;
+            if let Some(current) = keys {
+                keys = Some(instrument::lap(&mut decrypt_time, || {
+                    decrypt_with(current, block)
+                }));
+            }
+            instrument::lap(&mut inflate_time, || decoder.write_all(block))
+                .map_err(inflate_error)?;
+            remaining -= block.len();
+        }
+        instrument::lap(&mut inflate_time, || decoder.try_finish()).map_err(inflate_error)?;
+
+        if instrument::enabled() {
+            let size = self.file_size_compressed as u64;
+            instrument::record(Phase::Decrypt, decrypt_time, size);
+            instrument::record(Phase::Inflate, inflate_time, size);
+        }
+
+        Ok(decoder.total_out())
+    }
+
+    fn seek_to_data<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<()> {
+        let end = self.file_pointer as u64 + self.file_size_compressed as u64;
+        if end > reader.file_size()? {
+            return Err(Error::Ipf(
+                "Entry extends past the end of the archive".to_string(),
+            ));
+        }
+
+        reader.seek(SeekFrom::Start(self.file_pointer as u64))?;
+        Ok(())
+    }
+
+    /// Whether the entry has a media extension the client stores as is.
+    fn is_plain(&self) -> bool {
+        self.crypto.is_plain_name(&self.directory_name)
+    }
+
+    /// Whether the payload is the data itself. Plain entries whose sizes differ were
+    /// compressed anyway, and are only left unencrypted.
+    fn is_stored(&self) -> bool {
+        self.is_plain() && self.file_size_compressed == self.file_size_uncompressed
+    }
+
+    /// Keys the payload is encrypted with, none for plain entries.
+    fn cipher_keys(&self) -> Option<CipherKeys> {
+        if self.is_plain() {
+            None
+        } else {
+            self.crypto.keys
+        }
     }
 
     fn decrypt(&self, buffer: &mut [u8]) {
-        if buffer.is_empty() {
-            return;
+        if let Some(keys) = self.cipher_keys() {
+            decrypt_with(keys, buffer);
         }
+    }
 
-        let mut keys = self.keys_generate();
-        let buffer_size = (buffer.len() - 1) / 2 + 1;
+    fn encrypt(&self, buffer: &mut [u8]) {
+        if let Some(keys) = self.cipher_keys() {
+            encrypt_with(keys, buffer);
+        }
+    }
 
-        for i in 0..buffer_size {
-            let v = (keys[2] & 0xFFFD) | 2;
-            let idx = i * 2;
-            if idx < buffer.len() {
-                buffer[idx] ^= ((v.wrapping_mul(v ^ 1)) >> 8) as u8;
-                self.keys_update(&mut keys, buffer[idx]);
-            }
+    /// Entry for `directory_name` inside `container_name`, without content yet.
+    fn named(container_name: &str, directory_name: &str) -> Result<Self> {
+        let length = |name: &str| {
+            u16::try_from(name.len()).map_err(|_| {
+                Error::Ipf(format!(
+                    "Name is {} bytes, longer than the {} an archive can store",
+                    name.len(),
+                    u16::MAX
+                ))
+            })
+        };
+        Ok(IPFFileTable {
+            directory_name_length: length(directory_name)?,
+            container_name_length: length(container_name)?,
+            container_name: container_name.as_bytes().to_vec(),
+            directory_name: directory_name.as_bytes().to_vec(),
+            ..Default::default()
+        })
+    }
+
+    /// Sets the checksum and sizes for `data` and returns its encrypted payload.
+    /// The file pointer is left to the caller.
+    fn pack(&mut self, data: &[u8], level: Compression) -> Result<Vec<u8>> {
+        let size = |len: usize| {
+            u32::try_from(len).map_err(|_| {
+                Error::Ipf(format!(
+                    "Entry is {} bytes, more than the 4 GiB an archive can store",
+                    len
+                ))
+            })
+        };
+        self.crc32 = crc32(data);
+        self.file_size_uncompressed = size(data.len())?;
+        if self.is_plain() {
+            self.file_size_compressed = self.file_size_uncompressed;
+            return Ok(data.to_vec());
         }
+        let mut payload = self.compress(data, level)?;
+        self.encrypt(&mut payload);
+        self.file_size_compressed = size(payload.len())?;
+        Ok(payload)
     }
 
-    fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
-        let mut output_data = Vec::with_capacity(self.file_size_uncompressed as usize);
+    fn compress(&self, data: &[u8], level: Compression) -> Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len() / 2), level);
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        // Deflate cannot expand beyond roughly 1032:1, so never trust the header
+        // for more than the compressed data could possibly produce
+        let capacity = (self.file_size_uncompressed as usize)
+            .min(data.len().saturating_mul(1032).saturating_add(64));
+        let mut output_data = Vec::with_capacity(capacity);
 
         flate2::Decompress::new(false)
             .decompress_vec(data, &mut output_data, flate2::FlushDecompress::Finish)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to decompress data"))?;
+            .map_err(|err| Error::Decompress(err.to_string()))?;
 
         Ok(output_data)
     }
 
+    /// The directory name as a relative path. Empty, `.` and `..` parts are dropped
+    /// so the entry cannot point outside the directory it is extracted to.
+    pub fn relative_path(&self) -> Result<PathBuf> {
+        let name = self.directory_name_str();
+        let path: PathBuf = name
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty() && *part != "." && *part != ".." && !part.contains(':'))
+            .collect();
+        if path.as_os_str().is_empty() {
+            return Err(Error::Ipf(format!("Entry '{}' has no usable path", name)));
+        }
+        Ok(path)
+    }
+
     // Getter for the directory name length
     pub fn directory_name_length(&self) -> u16 {
         self.directory_name_length
@@ -292,13 +1198,706 @@ impl IPFFileTable {
 
     // Example getter for the file name (container name or some specific field)
     pub fn container_name(&self) -> String {
-        String::from_utf8_lossy(&self.container_name).to_string()
+        self.container_name_str().into_owned()
     }
 
     // Example getter for the container name (if different from the directory name)
     pub fn directory_name(&self) -> String {
-        String::from_utf8_lossy(&self.directory_name).to_string()
+        self.directory_name_str().into_owned()
+    }
+
+    // Borrowing variants of the name getters, for names that are valid UTF-8
+    pub fn container_name_str(&self) -> Cow<'_, str> {
+        self.decode_name(&self.container_name)
+    }
+
+    pub fn directory_name_str(&self) -> Cow<'_, str> {
+        self.decode_name(&self.directory_name)
+    }
+
+    // Names were validated against the policy when the table was read
+    fn decode_name<'a>(&self, name: &'a [u8]) -> Cow<'a, str> {
+        self.text
+            .decode_cow(name)
+            .unwrap_or_else(|_| String::from_utf8_lossy(name))
+    }
+
+    // Category of the entry based on its extension
+    pub fn kind(&self) -> IPFEntryKind {
+        IPFEntryKind::from_path(&self.directory_name_str())
+    }
+}
+
+/// Everything a listing shows about one entry, see `IPFFile::entries_info`. The
+/// format stores no timestamps, so the archive version stands in for the age.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IpfEntryInfo {
+    /// Container the entry belongs to, e.g. `xml.ipf`
+    pub container: String,
+    /// Path inside the container
+    pub path: String,
+    pub kind: IPFEntryKind,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    /// Compressed size over uncompressed size, 1.0 for empty entries
+    pub ratio: f64,
+    pub crc32: u32,
+    /// Offset of the payload in the archive
+    pub offset: u32,
+    /// `new_version` of the archive holding the entry
+    pub version: u32,
+}
+
+impl IpfEntryInfo {
+    /// `container/path`, the name the game uses.
+    pub fn full_path(&self) -> String {
+        format!("{}/{}", self.container, self.path)
+    }
+}
+
+/// Entries that differ between two versions of an archive, see `diff`. Names are
+/// `container/path` as stored in the archives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpfDiff {
+    /// `new_version` of the old and the new archive
+    pub versions: (u32, u32),
+    /// In the new archive only
+    pub added: Vec<String>,
+    /// In the old archive only. Patch archives cannot delete entries, so these are
+    /// reported but never written by `write_patch`.
+    pub removed: Vec<String>,
+    /// In both, with a different CRC32 or size
+    pub changed: Vec<String>,
+}
+
+/// Compares the file tables of `old` and `new` by CRC32 and size, without extracting
+/// anything. Entries are matched by `container/path` ignoring case; when a name
+/// repeats, the last entry counts. Each list is sorted.
+pub fn diff(old: &IPFFile, new: &IPFFile) -> IpfDiff {
+    let old_entries = entries_by_key(old);
+    let new_entries = entries_by_key(new);
+    let mut result = IpfDiff {
+        versions: (old.footer.new_version, new.footer.new_version),
+        ..Default::default()
+    };
+    for (key, entry) in &new_entries {
+        match old_entries.get(key) {
+            None => result.added.push(entry_name(entry)),
+            Some(before)
+                if before.crc32 != entry.crc32
+                    || before.file_size_uncompressed != entry.file_size_uncompressed =>
+            {
+                result.changed.push(entry_name(entry))
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, entry) in &old_entries {
+        if !new_entries.contains_key(key) {
+            result.removed.push(entry_name(entry));
+        }
+    }
+    result
+}
+
+impl IpfDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
     }
+
+    /// Writes a patch archive holding the added and changed entries, read from `new`,
+    /// which must be the archive this diff was made against and loaded with
+    /// `load_from_file`. Entries are copied as stored, without recompressing. The
+    /// footer patches the old version to the new one. Returns the number of entries
+    /// written.
+    pub fn write_patch<P: AsRef<Path>>(&self, new: &IPFFile, out: P) -> Result<usize> {
+        let archive = new.path.as_deref().ok_or_else(|| {
+            Error::Ipf("Archive was not loaded from a file, so it cannot be reopened".to_string())
+        })?;
+        let entries = entries_by_key(new);
+        let mut reader = BinaryReader::new(BufReader::new(
+            File::open(archive).map_err(|err| Error::from(err).in_file(archive))?,
+        ));
+
+        let mut writer = IPFWriter::new();
+        writer.versions(self.versions.0, self.versions.1);
+        for name in self.added.iter().chain(&self.changed) {
+            let entry = entries
+                .get(&name.to_lowercase())
+                .ok_or_else(|| Error::Ipf(format!("'{}' is not in {}", name, archive.display())))?;
+            let payload = entry
+                .read_raw(&mut reader)
+                .map_err(|err| err.in_file(archive))?;
+            writer.add_raw(entry, payload);
+        }
+        writer.save_to_file(out)?;
+        Ok(writer.entry_count())
+    }
+}
+
+/// Entries by lowercase `container/path`, later entries replacing earlier ones.
+fn entries_by_key(ipf: &IPFFile) -> std::collections::BTreeMap<String, &IPFFileTable> {
+    ipf.file_table
+        .iter()
+        .map(|entry| (entry_name(entry).to_lowercase(), entry))
+        .collect()
+}
+
+fn entry_name(entry: &IPFFileTable) -> String {
+    format!(
+        "{}/{}",
+        entry.container_name_str(),
+        entry.directory_name_str()
+    )
+}
+
+/// Filters any iterator of entries by directory name, for chaining onto file tables
+/// from several archives: `ipf.file_table().iter().matching("ies/*.ies")`.
+pub trait MatchEntries<'a>: Iterator<Item = &'a IPFFileTable> + Sized {
+    fn matching(self, pattern: &str) -> Matching<Self> {
+        self.matching_glob(Glob::new(pattern))
+    }
+
+    fn matching_glob(self, glob: Glob) -> Matching<Self> {
+        Matching { inner: self, glob }
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a IPFFileTable>> MatchEntries<'a> for I {}
+
+/// Iterator returned by `MatchEntries::matching`.
+#[derive(Debug, Clone)]
+pub struct Matching<I> {
+    inner: I,
+    glob: Glob,
+}
+
+impl<'a, I: Iterator<Item = &'a IPFFileTable>> Iterator for Matching<I> {
+    type Item = &'a IPFFileTable;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let glob = &self.glob;
+        self.inner
+            .find(|entry| glob.matches(&entry.directory_name_str()))
+    }
+}
+
+/// Computes the standard CRC32 of `data` using the archive CRC table.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut hasher = Crc32Writer::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Incremental CRC32 that can be used as the destination of `extract_into`.
+#[derive(Debug, Clone)]
+pub struct Crc32Writer {
+    crc: u32,
+    len: u64,
+}
+
+impl Crc32Writer {
+    pub fn new() -> Self {
+        Self {
+            crc: 0xFFFFFFFF,
+            len: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc = data
+            .iter()
+            .fold(self.crc, |crc, &b| CipherKeys::crc32_byte(crc, b));
+        self.len += data.len() as u64;
+    }
+
+    /// CRC32 of everything written so far.
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Passes data through to `inner` while computing its CRC32.
+struct CrcTee<W> {
+    inner: W,
+    crc: Crc32Writer,
+}
+
+impl<W> CrcTee<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            crc: Crc32Writer::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for CrcTee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// The archive handle of one parallel worker, opened on first use.
+fn open_worker_reader<'a>(
+    slot: &'a mut Option<BinaryReader<BufReader<File>>>,
+    archive: &Path,
+) -> Result<&'a mut BinaryReader<BufReader<File>>> {
+    if slot.is_none() {
+        *slot = Some(BinaryReader::new(BufReader::new(File::open(archive)?)));
+    }
+    Ok(slot.as_mut().expect("reader was just opened"))
+}
+
+impl Default for Crc32Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Crc32Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// flate2 reports a corrupt stream as `InvalidInput`; anything else came from the
+/// destination writer.
+fn inflate_error(err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::InvalidInput {
+        Error::Decompress(err.to_string())
+    } else {
+        Error::Io(err)
+    }
+}
+
+struct IPFWriterEntry {
+    container_name: String,
+    directory_name: String,
+    data: Vec<u8>,
+    // Entry `data` was read from by `read_raw`, when it is a stored payload
+    raw: Option<IPFFileTable>,
+}
+
+impl IPFWriterEntry {
+    fn uncompressed_size(&self) -> u64 {
+        match &self.raw {
+            Some(table) => table.file_size_uncompressed as u64,
+            None => self.data.len() as u64,
+        }
+    }
+}
+
+/// Builds a new `.ipf` archive from in-memory files or a directory tree.
+///
+/// Entries are compressed, encrypted and written in insertion order, followed
+/// by the file table and the footer.
+#[derive(Default)]
+pub struct IPFWriter {
+    entries: Vec<IPFWriterEntry>,
+    version_to_patch: u32,
+    new_version: u32,
+    compression: Compression,
+    crypto: IpfCrypto,
+}
+
+impl IPFWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `version_to_patch` and `new_version` fields of the footer.
+    pub fn versions(&mut self, version_to_patch: u32, new_version: u32) -> &mut Self {
+        self.version_to_patch = version_to_patch;
+        self.new_version = new_version;
+        self
+    }
+
+    /// Sets the zlib level (0-9) used for every entry.
+    pub fn compression_level(&mut self, level: u32) -> &mut Self {
+        self.compression = Compression::new(level.min(9));
+        self
+    }
+
+    /// Sets the payload encryption, the client cipher by default.
+    pub fn crypto(&mut self, crypto: IpfCrypto) -> &mut Self {
+        self.crypto = crypto;
+        self
+    }
+
+    /// Adds a file stored under `directory_name` inside `container_name`.
+    pub fn add_file(
+        &mut self,
+        container_name: &str,
+        directory_name: &str,
+        data: Vec<u8>,
+    ) -> &mut Self {
+        self.entries.push(IPFWriterEntry {
+            container_name: container_name.to_string(),
+            directory_name: directory_name.to_string(),
+            data,
+            raw: None,
+        });
+        self
+    }
+
+    /// Adds `entry` of another archive with the payload `read_raw` returned for it,
+    /// written as is without inflating and compressing again. The payload keeps the
+    /// cipher of its archive, whatever `crypto` is set here.
+    pub fn add_raw(&mut self, entry: &IPFFileTable, payload: Vec<u8>) -> &mut Self {
+        self.entries.push(IPFWriterEntry {
+            container_name: entry.container_name_str().into_owned(),
+            directory_name: entry.directory_name_str().into_owned(),
+            data: payload,
+            raw: Some(entry.clone()),
+        });
+        self
+    }
+
+    /// Adds every file below `dir`, using paths relative to `dir` joined with `/`.
+    /// Files are added sorted by that name, so the same tree always packs identically
+    /// whatever the platform or the order the file system lists it in.
+    pub fn add_directory<P: AsRef<Path>>(
+        &mut self,
+        container_name: &str,
+        dir: P,
+    ) -> Result<&mut Self> {
+        let root = dir.as_ref();
+        let mut files = Vec::new();
+        Self::collect_files(root, &mut files)?;
+        let mut named = Vec::with_capacity(files.len());
+        for path in files {
+            let relative = path
+                .strip_prefix(root)
+                .map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+            let directory_name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            named.push((directory_name, path));
+        }
+        named.sort();
+
+        for (directory_name, path) in named {
+            let data = std::fs::read(&path)?;
+            self.add_file(container_name, &directory_name, data);
+        }
+
+        Ok(self)
+    }
+
+    pub(crate) fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Self::collect_files(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        self.save_to_file_with_progress(file_path, &|_: &IPFFileTable, _, _| {})
+    }
+
+    /// Like `save_to_file`, reporting each packed entry to `reporter`, which can also
+    /// cancel the write. A cancelled write leaves an incomplete file behind.
+    pub fn save_to_file_with_progress<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<()> {
+        let file = File::create(file_path)?;
+        let mut writer = BinaryWriter::new(BufWriter::new(file));
+        self.write_to_with_progress(&mut writer, reporter)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Table entry without its file pointer, and the encrypted payload.
+    fn pack_entry(&self, entry: &IPFWriterEntry) -> Result<(IPFFileTable, Vec<u8>)> {
+        if let Some(table) = &entry.raw {
+            return Ok((table.clone(), entry.data.clone()));
+        }
+        let mut table = IPFFileTable::named(&entry.container_name, &entry.directory_name)?;
+        table.crypto = self.crypto;
+        let payload = table.pack(&entry.data, self.compression)?;
+        Ok((table, payload))
+    }
+
+    pub fn write_to<W: Write + Seek>(&self, writer: &mut BinaryWriter<W>) -> Result<()> {
+        self.write_to_with_progress(writer, &|_: &IPFFileTable, _, _| {})
+    }
+
+    /// Like `write_to`, reporting each entry to `reporter` once it is written.
+    /// Cancellation is checked between windows of entries packed in parallel.
+    pub fn write_to_with_progress<W: Write + Seek>(
+        &self,
+        writer: &mut BinaryWriter<W>,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<()> {
+        if self.entries.len() > u16::MAX as usize {
+            return Err(Error::Ipf(format!(
+                "Too many entries for one IPF archive: {} (max {})",
+                self.entries.len(),
+                u16::MAX
+            )));
+        }
+
+        let mut file_table = Vec::with_capacity(self.entries.len());
+
+        // Entries are compressed and hashed on the rayon pool a window at a time, then
+        // written in order, so the archive is identical to a sequential pack
+        const WINDOW: usize = 64;
+        let counter = ProgressCounter {
+            entries_total: self.entries.len(),
+            bytes_total: self.entries.iter().map(|e| e.uncompressed_size()).sum(),
+            ..Default::default()
+        };
+        for window in self.entries.chunks(WINDOW) {
+            if reporter.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let packed: Vec<Result<(IPFFileTable, Vec<u8>)>> = window
+                .par_iter()
+                .map(|entry| self.pack_entry(entry))
+                .collect();
+            for packed in packed {
+                let (mut table, payload) = packed?;
+                table.file_pointer = archive_offset(writer.tell()?)?;
+                writer.write_bytes(&payload)?;
+                reporter.progress(&table, counter.add(&table));
+                file_table.push(table);
+            }
+        }
+
+        write_index(writer, &file_table, self.version_to_patch, self.new_version)?;
+        Ok(())
+    }
+}
+
+/// Settings for `pack_dir`.
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// zlib level (0-9)
+    pub compression_level: u32,
+    /// `version_to_patch` and `new_version` of the footer
+    pub versions: (u32, u32),
+    /// Container name of every entry; the output file name when unset
+    pub container: Option<String>,
+    pub crypto: IpfCrypto,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            compression_level: 6,
+            versions: (0, 0),
+            container: None,
+            crypto: IpfCrypto::default(),
+        }
+    }
+}
+
+impl PackOptions {
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    pub fn with_versions(mut self, version_to_patch: u32, new_version: u32) -> Self {
+        self.versions = (version_to_patch, new_version);
+        self
+    }
+
+    pub fn with_container(mut self, container: &str) -> Self {
+        self.container = Some(container.to_string());
+        self
+    }
+
+    pub fn with_crypto(mut self, crypto: IpfCrypto) -> Self {
+        self.crypto = crypto;
+        self
+    }
+}
+
+/// Packs every file below `dir` into a new archive at `out_path`, see
+/// `IPFWriter::add_directory`. Nothing but the files and `options` goes into the
+/// archive, so packing the same tree twice gives byte-identical output. Returns the
+/// number of entries written.
+pub fn pack_dir<P, Q>(dir: P, out_path: Q, options: &PackOptions) -> Result<usize>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (dir, out_path) = (dir.as_ref(), out_path.as_ref());
+    if !dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' is not a directory", dir.display()),
+        )
+        .into());
+    }
+    let container = options.container.clone().unwrap_or_else(|| {
+        out_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+
+    let mut writer = IPFWriter::new();
+    writer
+        .versions(options.versions.0, options.versions.1)
+        .compression_level(options.compression_level)
+        .crypto(options.crypto)
+        .add_directory(&container, dir)?;
+    writer
+        .save_to_file(out_path)
+        .map_err(|err| err.in_file(out_path))?;
+    Ok(writer.entry_count())
+}
+
+/// Shared running totals of a bulk operation, updated from the workers.
+#[derive(Debug, Default)]
+struct ProgressCounter {
+    entries_total: usize,
+    bytes_total: u64,
+    entries_done: AtomicUsize,
+    bytes_done: AtomicU64,
+}
+
+impl ProgressCounter {
+    fn new(entries: &[IPFFileTable]) -> Self {
+        Self {
+            entries_total: entries.len(),
+            bytes_total: entries
+                .iter()
+                .map(|entry| entry.file_size_uncompressed as u64)
+                .sum(),
+            ..Default::default()
+        }
+    }
+
+    /// Counts `entry` as done and returns the totals including it.
+    fn add(&self, entry: &IPFFileTable) -> Progress {
+        let size = entry.file_size_uncompressed as u64;
+        Progress {
+            entries_done: self.entries_done.fetch_add(1, Ordering::Relaxed) + 1,
+            entries_total: self.entries_total,
+            bytes_done: self.bytes_done.fetch_add(size, Ordering::Relaxed) + size,
+            bytes_total: self.bytes_total,
+        }
+    }
+}
+
+/// Lookup key of an entry: its `container/path` name, normalized like `Glob` paths.
+fn full_path_key(entry: &IPFFileTable) -> String {
+    glob::normalize(&format!(
+        "{}/{}",
+        entry.container_name_str(),
+        entry.directory_name_str()
+    ))
+}
+
+/// `position` as a pointer stored in the archive, which cannot address past 4 GiB.
+fn archive_offset(position: u64) -> Result<u32> {
+    u32::try_from(position).map_err(|_| {
+        Error::Ipf("Archive would grow past the 4 GiB the format can address".to_string())
+    })
+}
+
+/// Writes the file table and the footer at the writer position, and returns the
+/// footer as written.
+fn write_index<W: Write + Seek>(
+    writer: &mut BinaryWriter<W>,
+    file_table: &[IPFFileTable],
+    version_to_patch: u32,
+    new_version: u32,
+) -> Result<IPFFooter> {
+    let file_count = u16::try_from(file_table.len()).map_err(|_| {
+        Error::Ipf(format!(
+            "Too many entries for one IPF archive: {} (max {})",
+            file_table.len(),
+            u16::MAX
+        ))
+    })?;
+    let file_table_pointer = archive_offset(writer.tell()?)?;
+    for table in file_table {
+        writer.write_u16(table.directory_name_length)?;
+        writer.write_u32(table.crc32)?;
+        writer.write_u32(table.file_size_compressed)?;
+        writer.write_u32(table.file_size_uncompressed)?;
+        writer.write_u32(table.file_pointer)?;
+        writer.write_u16(table.container_name_length)?;
+        writer.write_bytes(&table.container_name)?;
+        writer.write_bytes(&table.directory_name)?;
+    }
+
+    let footer_pointer = archive_offset(writer.tell()?)?;
+    writer.write_u16(file_count)?;
+    writer.write_u32(file_table_pointer)?;
+    writer.write_u16(0)?; // Padding
+    writer.write_u32(footer_pointer)?;
+    writer.write_u32(MAGIC_NUMBER)?;
+    writer.write_u32(version_to_patch)?;
+    writer.write_u32(new_version)?;
+
+    Ok(IPFFooter {
+        file_count,
+        file_table_pointer,
+        footer_pointer,
+        magic: MAGIC_NUMBER,
+        version_to_patch,
+        new_version,
+    })
 }
 
 impl IPFFooter {
@@ -332,3 +1931,576 @@ impl IPFFooter {
         self.new_version
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_round_trip() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer
+            .versions(10, 11)
+            .add_file("patch.ipf", "xml/a.xml", b"<root/>".to_vec())
+            .add_file("patch.ipf", "ies/b.ies", vec![7u8; 1000]);
+
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+
+        let mut reader = BinaryReader::new(Cursor::new(writer.writer.into_inner()));
+        let ipf =
+            IPFFile::load_from_reader_with_options(&mut reader, &ParseOptions::strict()).unwrap();
+        assert!(ipf.warnings().is_empty());
+        assert_eq!(ipf.footer().file_count(), 2);
+        assert_eq!(ipf.footer().version_to_patch(), 10);
+        assert_eq!(ipf.footer().new_version(), 11);
+
+        let entry = &ipf.file_table()[1];
+        assert_eq!(entry.container_name(), "patch.ipf");
+        assert_eq!(entry.directory_name(), "ies/b.ies");
+        assert_eq!(entry.extract(&mut reader).unwrap(), vec![7u8; 1000]);
+        assert_eq!(entry.crc32(), crc32(&[7u8; 1000]));
+    }
+
+    #[test]
+    fn streamed_extraction_matches_buffered() {
+        // Noise does not compress, so the entry spans several cipher blocks
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..200_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer.add_file("patch.ipf", "bin/noise.bin", data.clone());
+
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(writer.writer.into_inner()));
+        let ipf = IPFFile::load_from_reader(&mut reader).unwrap();
+        let entry = &ipf.file_table()[0];
+        assert!(entry.file_size_compressed() > 128 * 1024);
+
+        let mut streamed = Vec::new();
+        let written = entry.extract_into(&mut reader, &mut streamed).unwrap();
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(streamed, data);
+
+        let mut hasher = Crc32Writer::new();
+        entry.extract_into(&mut reader, &mut hasher).unwrap();
+        assert_eq!(hasher.finish(), entry.crc32());
+    }
+
+    #[test]
+    fn parallel_pack_is_deterministic() {
+        let mut ipf_writer = IPFWriter::new();
+        for index in 0..150u32 {
+            let data = index.to_le_bytes().repeat(index as usize + 1);
+            ipf_writer.add_file("patch.ipf", &format!("{}.bin", index), data);
+        }
+
+        let pack = || {
+            let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+            ipf_writer.write_to(&mut writer).unwrap();
+            writer.writer.into_inner()
+        };
+        let bytes = pack();
+        assert_eq!(bytes, pack());
+
+        let mut reader = BinaryReader::new(Cursor::new(bytes));
+        let ipf = IPFFile::load_from_reader(&mut reader).unwrap();
+        for (index, entry) in ipf.file_table().iter().enumerate() {
+            assert_eq!(entry.directory_name(), format!("{}.bin", index));
+            let data = (index as u32).to_le_bytes().repeat(index + 1);
+            assert_eq!(entry.extract(&mut reader).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn extract_all_writes_every_entry_in_parallel() {
+        let dir = std::env::temp_dir().join(format!("toslib-extract-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("patch.ipf");
+        let mut ipf_writer = IPFWriter::new();
+        for index in 0..20u32 {
+            let data = index.to_le_bytes().repeat(100);
+            ipf_writer.add_file("patch.ipf", &format!("bin/{}.bin", index), data);
+        }
+        ipf_writer.add_file("patch.ipf", "../../escape.txt", b"kept inside".to_vec());
+        ipf_writer.save_to_file(&archive).unwrap();
+
+        let ipf = IPFFile::load_from_file(&archive).unwrap();
+        let out = dir.join("out");
+        let calls = AtomicUsize::new(0);
+        let options = ExtractOptions::default().with_threads(3);
+        let written = ipf
+            .extract_all(&out, &options, |_, done, total| {
+                assert!(done <= total);
+                calls.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        let skipped = ipf
+            .extract_all(&out, &options.with_overwrite(false), |_, _, _| {})
+            .unwrap();
+        let last = std::fs::read(out.join("bin").join("19.bin")).unwrap();
+        let escaped = std::fs::read(out.join("escape.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((written, skipped), (21, 0));
+        assert_eq!(calls.into_inner(), 21);
+        assert_eq!(last, 19u32.to_le_bytes().repeat(100));
+        assert_eq!(escaped, b"kept inside");
+        assert!(
+            IPFFile::default()
+                .extract_all(&out, &options, |_, _, _| {})
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn corrupted_entries_fail_crc_verification() {
+        let dir = std::env::temp_dir().join(format!("toslib-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("patch.ipf");
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer
+            .compression_level(0)
+            .add_file("patch.ipf", "a.txt", vec![b'a'; 64])
+            .add_file("patch.ipf", "b.txt", vec![b'b'; 64]);
+        ipf_writer.save_to_file(&archive).unwrap();
+        let ipf = IPFFile::load_from_file(&archive).unwrap();
+        assert!(ipf.verify_all().unwrap().is_empty());
+
+        // Stored blocks keep the data as is, so flipping a byte inside the second
+        // entry's payload changes its content without breaking the deflate stream
+        let mut bytes = std::fs::read(&archive).unwrap();
+        let entry = &ipf.file_table()[1];
+        bytes[entry.file_pointer() as usize + 20] ^= 0x55;
+        std::fs::write(&archive, &bytes).unwrap();
+
+        let failures = ipf.verify_all().unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(&bytes));
+        let verified =
+            entry.extract_with_options(&mut reader, &ExtractOptions::default().with_verify(true));
+        let unverified = entry.extract_with_options(&mut reader, &ExtractOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "b.txt");
+        assert!(matches!(failures[0].1.root(), Error::CrcMismatch { .. }));
+        assert!(matches!(
+            verified.unwrap_err().root(),
+            Error::CrcMismatch { .. }
+        ));
+        assert!(unverified.is_ok());
+    }
+
+    #[test]
+    fn entries_are_found_by_path_and_pattern() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer
+            .add_file("patch.ipf", "char_hi/npc/Guard.xac", b"old".to_vec())
+            .add_file("patch.ipf", "xml/item.xml", b"<a/>".to_vec())
+            .add_file("patch.ipf", "char_hi/npc/guard.xac", b"new".to_vec())
+            .add_file("patch.ipf", "char_hi/pc/bow.xac", b"bow".to_vec());
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(writer.writer.into_inner()));
+        let ipf = IPFFile::load_from_reader(&mut reader).unwrap();
+
+        let guard = ipf.find("CHAR_HI\\npc\\guard.xac").unwrap();
+        assert_eq!(guard.extract(&mut reader).unwrap(), b"new");
+        assert!(ipf.find("char_hi/npc").is_none());
+        let indexed = ipf.get_by_path("Patch.ipf\\char_hi/npc/GUARD.xac").unwrap();
+        assert_eq!(indexed.file_pointer(), guard.file_pointer());
+        assert!(ipf.get_by_path("char_hi/npc/guard.xac").is_none());
+
+        let models: Vec<String> = ipf
+            .entries_matching("**/*.xac")
+            .map(IPFFileTable::directory_name)
+            .collect();
+        assert_eq!(models.len(), 3);
+        assert_eq!(ipf.file_table().iter().matching("char_hi/pc/*").count(), 1);
+    }
+
+    #[test]
+    fn diff_writes_only_added_and_changed_entries() {
+        let dir = std::env::temp_dir().join(format!("toslib-diff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut old_writer = IPFWriter::new();
+        old_writer
+            .versions(0, 100)
+            .add_file("xml.ipf", "same.xml", b"<a/>".to_vec())
+            .add_file("xml.ipf", "edit.xml", b"<a/>".to_vec())
+            .add_file("xml.ipf", "gone.xml", b"<a/>".to_vec());
+        old_writer.save_to_file(dir.join("old.ipf")).unwrap();
+        let mut new_writer = IPFWriter::new();
+        new_writer
+            .versions(0, 101)
+            .add_file("xml.ipf", "same.xml", b"<a/>".to_vec())
+            .add_file("xml.ipf", "Edit.xml", b"<b/>".to_vec())
+            .add_file("xml.ipf", "new.xml", b"<c/>".to_vec());
+        new_writer.save_to_file(dir.join("new.ipf")).unwrap();
+
+        let old = IPFFile::load_from_file(dir.join("old.ipf")).unwrap();
+        let new = IPFFile::load_from_file(dir.join("new.ipf")).unwrap();
+        let changes = diff(&old, &new);
+        let written = changes.write_patch(&new, dir.join("patch.ipf")).unwrap();
+        let patch = IPFFile::load_from_file(dir.join("patch.ipf")).unwrap();
+        let mut reader =
+            BinaryReader::new(Cursor::new(std::fs::read(dir.join("patch.ipf")).unwrap()));
+        let edited = patch
+            .find("edit.xml")
+            .unwrap()
+            .extract(&mut reader)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(changes.added, ["xml.ipf/new.xml"]);
+        assert_eq!(changes.removed, ["xml.ipf/gone.xml"]);
+        assert_eq!(changes.changed, ["xml.ipf/Edit.xml"]);
+        assert_eq!(written, 2);
+        assert_eq!(patch.footer().version_to_patch(), 100);
+        assert_eq!(patch.footer().new_version(), 101);
+        assert_eq!(edited, b"<b/>");
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn entries_extract_concurrently_from_one_handle() {
+        let dir = std::env::temp_dir().join(format!("toslib-shared-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("patch.ipf");
+        let mut ipf_writer = IPFWriter::new();
+        for index in 0..40u32 {
+            let data = index.to_le_bytes().repeat(index as usize * 50 + 1);
+            ipf_writer.add_file("patch.ipf", &format!("{}.bin", index), data);
+        }
+        ipf_writer.save_to_file(&archive).unwrap();
+
+        let ipf = IPFFile::load_from_file(&archive).unwrap();
+        let file = File::open(&archive).unwrap();
+        let mapped = std::fs::read(&archive).unwrap();
+        let positional: Vec<Vec<u8>> = ipf
+            .file_table()
+            .par_iter()
+            .map(|entry| entry.extract_at(&file).unwrap())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for (index, (entry, data)) in ipf.file_table().iter().zip(&positional).enumerate() {
+            let expected = (index as u32).to_le_bytes().repeat(index * 50 + 1);
+            assert_eq!(data, &expected);
+            assert_eq!(entry.extract_from(&mapped).unwrap(), expected);
+        }
+        assert!(ipf.file_table()[39].extract_from(&mapped[..100]).is_err());
+    }
+
+    #[test]
+    fn oversized_file_count_is_rejected() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer.add_file("patch.ipf", "xml/a.xml", b"<root/>".to_vec());
+
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut bytes = writer.writer.into_inner();
+
+        // Claim far more entries than the table can hold
+        let footer = bytes.len() - 24;
+        bytes[footer..footer + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(IPFFile::load_from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    fn malformed_archives_fail_with_typed_errors() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer.crypto(IpfCrypto::none()).add_file(
+            "patch.ipf",
+            "xml/a.xml",
+            b"<root/>".repeat(10),
+        );
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut bytes = writer.writer.into_inner();
+
+        let options = ParseOptions::default().with_crypto(IpfCrypto::none());
+        let ipf = IPFFile::load_from_slice_with_options(&bytes, &options).unwrap();
+        let entry = &ipf.file_table()[0];
+        // A deflate block of the reserved type 3
+        let start = entry.file_pointer() as usize;
+        bytes[start..start + entry.file_size_compressed() as usize].fill(0xFF);
+        let mut reader = BinaryReader::new(Cursor::new(&bytes));
+        let inflate = entry.extract(&mut reader).unwrap_err();
+        let stream = entry.verify_crc(&mut reader).unwrap_err();
+
+        let footer = bytes.len() - 12;
+        bytes[footer] ^= 1;
+        let magic = IPFFile::load_from_slice(&bytes).unwrap_err();
+
+        assert!(matches!(inflate.root(), Error::Decompress(_)));
+        assert!(matches!(stream.root(), Error::Decompress(_)));
+        assert!(matches!(
+            magic.root(),
+            Error::BadMagic {
+                format: "IPF",
+                expected: MAGIC_NUMBER,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn cipher_matches_known_vector() {
+        let encrypted = [
+            0x0a, 0x6f, 0xa0, 0x6c, 0x0f, 0x62, 0xe8, 0x63, 0x5f, 0x70, 0x85, 0x65, 0xa6, 0x20,
+            0x82, 0x65, 0x40, 0x74, 0x15, 0x76, 0x3b, 0x63, 0x35, 0x6f, 0x9c,
+        ];
+        let mut data = b"toslib cipher test vector".to_vec();
+        encrypt_in_place(&mut data);
+        assert_eq!(data, encrypted);
+        decrypt_in_place(&mut data);
+        assert_eq!(data, b"toslib cipher test vector");
+    }
+
+    #[test]
+    fn archives_of_other_ciphers_need_matching_options() {
+        let data = b"<root><item id=\"1\"/></root>".repeat(8);
+        for crypto in [IpfCrypto::with_password(b"test server"), IpfCrypto::none()] {
+            let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+            let mut ipf = IPFWriter::new();
+            ipf.crypto(crypto)
+                .add_file("xml.ipf", "item.xml", data.clone());
+            ipf.write_to(&mut writer).unwrap();
+            let bytes = writer.writer.into_inner();
+
+            let options = ParseOptions::default().with_crypto(crypto);
+            let parsed = IPFFile::load_from_slice_with_options(&bytes, &options).unwrap();
+            let entry = &parsed.file_table()[0];
+            let mut reader = BinaryReader::new(Cursor::new(&bytes));
+            assert_eq!(entry.extract(&mut reader).unwrap(), data);
+            entry.verify_crc(&mut reader).unwrap();
+
+            let default = IPFFile::load_from_slice(&bytes).unwrap();
+            assert!(default.file_table()[0].verify_crc(&mut reader).is_err());
+        }
+        let plain = IpfCrypto::none();
+        let mut untouched = data.clone();
+        plain.encrypt_in_place(&mut untouched);
+        assert_eq!(untouched, data);
+    }
+
+    #[test]
+    fn entries_are_replaced_and_appended_in_place() {
+        let dir = std::env::temp_dir().join(format!("toslib-inplace-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("ies.ipf");
+        let mut writer = IPFWriter::new();
+        writer
+            .versions(10, 11)
+            .add_file("ies.ipf", "item.ies", b"old item table".repeat(64))
+            .add_file("ies.ipf", "skill.ies", b"skills".to_vec());
+        writer.save_to_file(&archive).unwrap();
+
+        let mut ipf = IPFFile::load_from_file(&archive).unwrap();
+        ipf.replace_entry("ITEM.ies", b"new").unwrap();
+        assert!(ipf.get_by_path("ies.ipf/monster.ies").is_none());
+        ipf.append_entry("ies.ipf", "monster.ies", b"monsters")
+            .unwrap();
+        let appended = ipf.get_by_path("ies.ipf/monster.ies").map(|e| e.crc32());
+        let missing = ipf.replace_entry("buff.ies", b"");
+        let reloaded = IPFFile::load_from_file(&archive).unwrap();
+        let failures = reloaded.verify_all().unwrap();
+        let file = File::open(&archive).unwrap();
+        let read = |name: &str| reloaded.find(name).unwrap().extract_at(&file).unwrap();
+        let contents = [read("item.ies"), read("skill.ies"), read("monster.ies")];
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(failures.is_empty());
+        assert_eq!(contents, [&b"new"[..], b"skills", b"monsters"]);
+        assert_eq!(reloaded.footer().file_count(), 3);
+        assert_eq!(reloaded.footer().new_version(), 11);
+        assert_eq!(
+            reloaded.footer().file_table_pointer(),
+            ipf.footer().file_table_pointer()
+        );
+        assert!(missing.is_err());
+        assert_eq!(appended, Some(crc32(b"monsters")));
+    }
+
+    #[test]
+    fn entries_info_describes_every_entry() {
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        let mut ipf = IPFWriter::new();
+        ipf.versions(7, 8)
+            .add_file("xml.ipf", "item.xml", b"<item/>".repeat(100))
+            .add_file("xml.ipf", "empty.txt", Vec::new());
+        ipf.write_to(&mut writer).unwrap();
+        let parsed = IPFFile::load_from_slice(&writer.writer.into_inner()).unwrap();
+
+        let info = parsed.entries_info();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].full_path(), "xml.ipf/item.xml");
+        assert_eq!(info[0].kind, IPFEntryKind::Xml);
+        assert_eq!(info[0].uncompressed_size, 700);
+        assert_eq!(info[0].crc32, crc32(&b"<item/>".repeat(100)));
+        assert!(info[0].ratio < 0.1);
+        assert_eq!((info[1].ratio, info[1].version), (1.0, 8));
+        assert_eq!(info[1].offset, parsed.file_table()[1].file_pointer());
+    }
+
+    #[test]
+    fn raw_entries_copy_between_archives_unchanged() {
+        let mut source = BinaryWriter::new(Cursor::new(Vec::new()));
+        let mut ipf = IPFWriter::new();
+        ipf.crypto(IpfCrypto::with_password(b"old client"))
+            .add_file("ies.ipf", "item.ies", b"item table".repeat(50));
+        ipf.write_to(&mut source).unwrap();
+        let source = source.writer.into_inner();
+        let options = ParseOptions::default().with_crypto(IpfCrypto::with_password(b"old client"));
+        let parsed = IPFFile::load_from_slice_with_options(&source, &options).unwrap();
+        let entry = &parsed.file_table()[0];
+        let payload = entry
+            .read_raw(&mut BinaryReader::new(Cursor::new(&source)))
+            .unwrap();
+
+        let mut copy = BinaryWriter::new(Cursor::new(Vec::new()));
+        let mut ipf = IPFWriter::new();
+        ipf.add_file("ies.ipf", "skill.ies", b"skills".to_vec())
+            .add_raw(entry, payload.clone());
+        ipf.write_to(&mut copy).unwrap();
+        let copy = copy.writer.into_inner();
+        let copied = IPFFile::load_from_slice_with_options(&copy, &options).unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(&copy));
+        let item = copied.find("item.ies").unwrap();
+
+        assert_eq!(item.read_raw(&mut reader).unwrap(), payload);
+        assert_eq!(item.crc32(), entry.crc32());
+        assert_eq!(item.extract(&mut reader).unwrap(), b"item table".repeat(50));
+    }
+
+    #[test]
+    fn media_entries_are_stored_as_is() {
+        let sound = b"FSB5 not really a sound bank".to_vec();
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        let mut ipf = IPFWriter::new();
+        ipf.add_file("sound.ipf", "bgm/Town.FSB", sound.clone())
+            .add_file("sound.ipf", "bgm/town.xml", b"<bgm/>".to_vec());
+        ipf.write_to(&mut writer).unwrap();
+        let bytes = writer.writer.into_inner();
+
+        let parsed = IPFFile::load_from_slice(&bytes).unwrap();
+        let (fsb, xml) = (&parsed.file_table()[0], &parsed.file_table()[1]);
+        let mut reader = BinaryReader::new(Cursor::new(&bytes));
+        assert_eq!(fsb.read_raw(&mut reader).unwrap(), sound);
+        assert_eq!(fsb.extract(&mut reader).unwrap(), sound);
+        fsb.verify_crc(&mut reader).unwrap();
+        assert_eq!(xml.extract(&mut reader).unwrap(), b"<bgm/>");
+
+        // Read with an empty list the stored bank is taken for an encrypted stream
+        let options =
+            ParseOptions::default().with_crypto(IpfCrypto::default().with_plain_extensions(&[]));
+        let strict = IPFFile::load_from_slice_with_options(&bytes, &options).unwrap();
+        assert!(strict.file_table()[0].extract(&mut reader).is_err());
+        assert!(IpfCrypto::default().is_plain("ui/Loading.jpg"));
+        assert!(!IpfCrypto::default().is_plain("ui/jpg"));
+    }
+
+    #[test]
+    fn packing_a_tree_twice_gives_identical_archives() {
+        let dir = std::env::temp_dir().join(format!("toslib-packdir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("mod/xml-extra")).unwrap();
+        std::fs::create_dir_all(dir.join("mod/xml")).unwrap();
+        std::fs::write(dir.join("mod/xml/item.xml"), b"<item/>").unwrap();
+        std::fs::write(dir.join("mod/xml-extra/a.xml"), b"<a/>").unwrap();
+        std::fs::write(dir.join("mod/b.txt"), b"b").unwrap();
+
+        let options = PackOptions::default()
+            .with_versions(5, 6)
+            .with_container("mod.ipf")
+            .with_compression_level(9);
+        let count = pack_dir(dir.join("mod"), dir.join("first.ipf"), &options).unwrap();
+        pack_dir(dir.join("mod"), dir.join("second.ipf"), &options).unwrap();
+        let first = std::fs::read(dir.join("first.ipf")).unwrap();
+        let second = std::fs::read(dir.join("second.ipf")).unwrap();
+        let missing = pack_dir(dir.join("none"), dir.join("none.ipf"), &options);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(first, second);
+        let ipf = IPFFile::load_from_slice(&first).unwrap();
+        let names: Vec<String> = ipf.file_table().iter().map(entry_name).collect();
+        assert_eq!(
+            names,
+            [
+                "mod.ipf/b.txt",
+                "mod.ipf/xml-extra/a.xml",
+                "mod.ipf/xml/item.xml"
+            ]
+        );
+        assert_eq!(ipf.footer().version_to_patch(), 5);
+        assert!(missing.is_err());
+    }
+
+    /// Counts the bytes written to it without keeping them.
+    #[derive(Default)]
+    struct Sink {
+        position: u64,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.position += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Sink {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            if let SeekFrom::Start(position) = pos {
+                self.position = position;
+            }
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    fn entries_past_4_gib_are_refused() {
+        let mut ipf = IPFWriter::new();
+        ipf.add_file("data.ipf", "a.xml", b"<a/>".to_vec());
+        let mut writer = BinaryWriter::new(Sink::default());
+        writer.seek(SeekFrom::Start(u32::MAX as u64 + 1)).unwrap();
+        let err = ipf.write_to(&mut writer).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "IPF error: Archive would grow past the 4 GiB the format can address"
+        );
+    }
+
+    #[test]
+    fn names_and_indexes_past_the_format_limits_are_refused() {
+        let long = "a".repeat(u16::MAX as usize + 1);
+        let mut ipf = IPFWriter::new();
+        ipf.add_file("data.ipf", &long, b"<a/>".to_vec());
+        let err = ipf.write_to(&mut BinaryWriter::new(Sink::default()));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "IPF error: Name is 65536 bytes, longer than the 65535 an archive can store"
+        );
+
+        // The entry still starts below 4 GiB, but the file table would not
+        let mut ipf = IPFWriter::new();
+        ipf.add_file("data.ipf", "a.xml", b"<a/>".to_vec());
+        let mut writer = BinaryWriter::new(Sink::default());
+        writer.seek(SeekFrom::Start(u32::MAX as u64 - 2)).unwrap();
+        let err = ipf.write_to(&mut writer).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "IPF error: Archive would grow past the 4 GiB the format can address"
+        );
+    }
+}
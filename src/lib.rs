@@ -1,17 +1,76 @@
-use crate::xac::Mesh;
+#[cfg(feature = "python")]
+use crate::xac::{Mesh, MorphTarget, SubMesh};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use xac::SubMesh;
+
+pub use error::{Error, ErrorContext, Result};
+pub use events::{Event, EventListener, Operation};
+pub use export::{CollisionMeshes, EnginePreset, ExportOptions, GltfOptions};
+pub use game::{ClientVersion, GameData, GameOptions};
+pub use glob::Glob;
+pub use instrument::{Instrumentation, Phase};
+pub use ipf::{IpfCrypto, IpfEntryInfo};
+pub use manifest::Manifest;
+pub use mount::{EntrySource, IpfMount};
+pub use naming::{NameTemplate, OutputLayout};
+pub use options::{ConflictPolicy, ParseOptions};
+pub use partial::Parsed;
+pub use plugin::ChunkHandler;
+pub use progress::{CancelToken, Progress, ProgressReporter};
+pub use project::ModProject;
+pub use scan::{ScanItem, ScanOptions};
+pub use search::{SearchHit, SearchOptions};
+pub use text::{Encoding, TextDecoding};
+pub use warning::ParseWarning;
+pub use xac::ChunkSet;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
 
+mod collada;
+pub mod columnar;
+#[cfg(any(feature = "serve", feature = "daemon"))]
+mod convert;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod ffi;
+pub mod game;
+pub mod glob;
+mod gltf;
 pub mod ies;
+pub mod instrument;
 pub mod ipf;
+pub mod manifest;
+pub mod mount;
+pub mod naming;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod options;
+pub mod partial;
+pub mod plugin;
+pub mod progress;
+pub mod project;
+pub mod query;
+pub mod scan;
+pub mod search;
+mod serial;
+#[cfg(feature = "serve")]
+pub mod serve;
+mod tangents;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+pub mod text;
+pub mod texture;
 pub mod tosreader;
+pub mod warning;
 pub mod xac;
 
 // Python bindings function
+#[cfg(feature = "python")]
 #[pyfunction]
 fn extract_xac_data_py(ipf_path: String, xac_filename: String) -> PyResult<Vec<Mesh>> {
     match xac::extract_xac_data(&ipf_path, &xac_filename) {
@@ -27,10 +86,12 @@ fn extract_xac_data_py(ipf_path: String, xac_filename: String) -> PyResult<Vec<M
 }
 
 // PyO3 module initialization
+#[cfg(feature = "python")]
 #[pymodule]
 fn toslib(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<SubMesh>()?;
     m.add_class::<Mesh>()?;
+    m.add_class::<MorphTarget>()?;
     m.add_function(wrap_pyfunction!(extract_xac_data_py, m)?)?;
     Ok(())
 }
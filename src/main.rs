@@ -0,0 +1,1259 @@
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+use toslib::ies::{self, IESFile};
+use toslib::ipf::{self, IPFEntryKind, IPFFile, IPFFileTable};
+use toslib::manifest::{Manifest, ManifestMismatch};
+use toslib::scan::{self, ScanOptions};
+use toslib::search::SearchOptions;
+use toslib::tosreader::BinaryReader;
+use toslib::warning::ParseWarning;
+use toslib::xac::XACFile;
+use toslib::{GameData, ParseOptions, Result};
+
+#[derive(Parser)]
+#[command(name = "toslib", version, about = "Tree of Savior asset tool")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// IPF archive commands
+    Ipf {
+        #[command(subcommand)]
+        command: IpfCommand,
+    },
+    /// IES table commands
+    Ies {
+        #[command(subcommand)]
+        command: IesCommand,
+    },
+    /// XAC model commands
+    Xac {
+        #[command(subcommand)]
+        command: XacCommand,
+    },
+    /// Checksum manifests for distributing mods
+    Manifest {
+        #[command(subcommand)]
+        command: ManifestCommand,
+    },
+    /// Watch a client's patch folder and report what every new patch changes
+    Watch {
+        /// Client root (containing `data/` and `patch/`) or the patch folder itself
+        client_dir: PathBuf,
+        /// Polling interval in seconds
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    /// Summarize entry counts and sizes of an archive, a folder of archives or a client
+    Stats {
+        /// An `.ipf` file, a folder of archives or a client root
+        path: PathBuf,
+        /// Number of largest entries to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Decrypt, inflate and CRC-check every entry of a client's archives in parallel
+    Verify {
+        /// An `.ipf` file, a folder of archives or a client root
+        client_dir: PathBuf,
+    },
+    /// Report the client build from archive footers and revision files
+    Version {
+        /// Client root containing `data/` and `patch/`
+        client_dir: PathBuf,
+    },
+    /// Find a value in entry paths, IES cells and XML documents of a client
+    Search {
+        /// Client root containing `data/` and `patch/`
+        client_dir: PathBuf,
+        query: String,
+        /// Match case exactly
+        #[arg(long)]
+        case_sensitive: bool,
+        /// Match whole values only
+        #[arg(long)]
+        exact: bool,
+    },
+    /// Serve a client's archives over HTTP: raw entries, IES and XAC as JSON
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Client root containing `data/` and `patch/`
+        client_dir: PathBuf,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Most converted responses to keep in memory, in MiB
+        #[arg(long, default_value_t = 64)]
+        cache: u64,
+    },
+    /// Answer JSON-RPC requests (list, read, convert, table) over a local socket
+    #[cfg(feature = "daemon")]
+    Daemon {
+        /// Client root containing `data/` and `patch/`
+        client_dir: PathBuf,
+        /// Unix socket to listen on
+        #[cfg(unix)]
+        #[arg(long, conflicts_with = "addr")]
+        socket: Option<PathBuf>,
+        /// TCP address to listen on when no socket is given
+        #[arg(long, default_value = "127.0.0.1:8765")]
+        addr: String,
+        /// Most converted assets to keep in memory, in MiB
+        #[arg(long, default_value_t = 256)]
+        cache: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum IpfCommand {
+    /// List entries added, removed or changed between two versions of an archive
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Write a patch archive holding the added and changed entries
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Extract every entry of an archive to a directory in parallel
+    Extract {
+        archive: PathBuf,
+        /// Directory the entries are written below
+        #[arg(short, long, default_value = ".")]
+        out: PathBuf,
+        /// Worker threads (defaults to one per core)
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+        /// Keep files that already exist instead of replacing them
+        #[arg(long)]
+        keep_existing: bool,
+        /// Check every entry against the CRC32 in the file table
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Replace one entry of an archive with a file, or add it when missing, without
+    /// repacking the archive
+    Replace {
+        archive: PathBuf,
+        /// Entry path inside the archive, e.g. `item.ies`
+        entry: String,
+        /// File holding the new content
+        file: PathBuf,
+    },
+    /// Build a patch archive from a directory tree
+    Pack {
+        /// Directory whose files are packed, paths relative to it
+        dir: PathBuf,
+        /// Output archive path
+        #[arg(long)]
+        out: PathBuf,
+        /// Client version the patch applies to
+        #[arg(long, default_value_t = 0)]
+        base_version: u32,
+        /// Version after applying the patch (defaults to base version + 1)
+        #[arg(long)]
+        new_version: Option<u32>,
+        /// Container name stored for every entry (defaults to the output file name)
+        #[arg(long)]
+        container: Option<String>,
+        /// zlib compression level (0-9)
+        #[arg(long, default_value_t = 6)]
+        level: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum IesCommand {
+    /// List rows and cells changed between two versions of a table
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Column identifying a row in both versions
+        #[arg(long, default_value = "ClassID")]
+        key: String,
+    },
+    /// Check a table's header, columns and cells for inconsistencies
+    Validate { file: PathBuf },
+    /// Convert many tables at once, e.g. every table extracted from xml_client.ipf
+    Convert {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// json, csv or xml
+        #[arg(long, default_value = "json")]
+        format: String,
+        /// Folder the converted files are written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum XacCommand {
+    /// Parse every model in the archives and report chunks whose body size does not
+    /// match the size declared in the chunk header
+    Check {
+        /// An `.ipf` file, a folder of archives or a client root
+        path: PathBuf,
+        /// Most entry data to hold in memory at once, in MiB
+        #[arg(long, default_value_t = 256)]
+        memory_budget: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ManifestCommand {
+    /// Write the size, CRC32 and SHA-256 of every file below a directory
+    Create {
+        /// Directory of built archives or extracted files
+        dir: PathBuf,
+        /// Manifest file to write
+        #[arg(short, long, default_value = "manifest.txt")]
+        out: PathBuf,
+    },
+    /// Check the files below a directory against a manifest
+    Verify {
+        manifest: PathBuf,
+        /// Directory the manifest paths are relative to
+        dir: PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Ipf { command } => match command {
+            IpfCommand::Diff { old, new, out } => diff(&old, &new, out.as_deref()),
+            IpfCommand::Extract {
+                archive,
+                out,
+                threads,
+                keep_existing,
+                verify,
+            } => extract(&archive, &out, threads, keep_existing, verify),
+            IpfCommand::Replace {
+                archive,
+                entry,
+                file,
+            } => replace(&archive, &entry, &file),
+            IpfCommand::Pack {
+                dir,
+                out,
+                base_version,
+                new_version,
+                container,
+                level,
+            } => pack(&dir, &out, base_version, new_version, container, level),
+        },
+        Command::Ies { command } => match command {
+            IesCommand::Diff { old, new, key } => ies_diff(&old, &new, &key),
+            IesCommand::Validate { file } => ies_validate(&file),
+            IesCommand::Convert {
+                inputs,
+                format,
+                out,
+            } => ies_convert(&inputs, &format, &out),
+        },
+        Command::Xac { command } => match command {
+            XacCommand::Check {
+                path,
+                memory_budget,
+            } => xac_check(&path, memory_budget),
+        },
+        Command::Manifest { command } => match command {
+            ManifestCommand::Create { dir, out } => {
+                let manifest = Manifest::from_dir(&dir)?;
+                manifest.save_to_file(&out)?;
+                println!(
+                    "Wrote {} files to {} (sha256 {})",
+                    manifest.entries.len(),
+                    out.display(),
+                    manifest.digest_hex()
+                );
+                Ok(())
+            }
+            ManifestCommand::Verify { manifest, dir } => manifest_verify(&manifest, &dir),
+        },
+        Command::Watch {
+            client_dir,
+            interval,
+        } => watch(&client_dir, Duration::from_secs(interval.max(1))),
+        Command::Stats { path, top } => stats(&path, top),
+        Command::Verify { client_dir } => verify(&client_dir),
+        Command::Version { client_dir } => version(&client_dir),
+        Command::Search {
+            client_dir,
+            query,
+            case_sensitive,
+            exact,
+        } => search(&client_dir, &query, case_sensitive, exact),
+        #[cfg(feature = "serve")]
+        Command::Serve {
+            client_dir,
+            addr,
+            cache,
+        } => {
+            let options =
+                toslib::serve::ServeOptions::default().with_cache_budget(cache * 1024 * 1024);
+            println!("Serving {} on http://{}", client_dir.display(), addr);
+            toslib::serve::serve(&client_dir, addr.as_str(), &options)
+        }
+        #[cfg(feature = "daemon")]
+        Command::Daemon {
+            client_dir,
+            #[cfg(unix)]
+            socket,
+            addr,
+            cache,
+        } => {
+            let options =
+                toslib::daemon::DaemonOptions::default().with_cache_budget(cache * 1024 * 1024);
+            let daemon = std::sync::Arc::new(toslib::daemon::Daemon::open(&client_dir, &options)?);
+            println!(
+                "Loaded {} entries from {}",
+                daemon.game().len(),
+                client_dir.display()
+            );
+            #[cfg(unix)]
+            if let Some(socket) = socket {
+                // A socket left behind by an earlier run would make bind fail
+                let _ = std::fs::remove_file(&socket);
+                let listener = std::os::unix::net::UnixListener::bind(&socket)?;
+                println!("Listening on {}", socket.display());
+                return daemon.listen_unix(listener);
+            }
+            let listener = std::net::TcpListener::bind(addr.as_str())?;
+            println!("Listening on {}", addr);
+            daemon.listen_tcp(listener)
+        }
+    }
+}
+
+fn diff(old: &Path, new: &Path, out: Option<&Path>) -> Result<()> {
+    let new_ipf = IPFFile::load_from_file(new)?;
+    let changes = ipf::diff(&IPFFile::load_from_file(old)?, &new_ipf);
+    for (label, names) in [
+        ("added  ", &changes.added),
+        ("removed", &changes.removed),
+        ("changed", &changes.changed),
+    ] {
+        for name in names {
+            println!("{} {}", label, name);
+        }
+    }
+    if let Some(out) = out {
+        let written = changes.write_patch(&new_ipf, out)?;
+        println!(
+            "Wrote {} entries to {} (version {} -> {})",
+            written,
+            out.display(),
+            changes.versions.0,
+            changes.versions.1
+        );
+    }
+    Ok(())
+}
+
+fn ies_diff(old: &Path, new: &Path, key: &str) -> Result<()> {
+    let changes = ies::diff(
+        &IESFile::load_from_file(old)?,
+        &IESFile::load_from_file(new)?,
+        key,
+    )?;
+    for (label, columns) in [
+        ("added column  ", &changes.added_columns),
+        ("removed column", &changes.removed_columns),
+    ] {
+        for column in columns {
+            println!("{} {}", label, column);
+        }
+    }
+    for (label, keys) in [("added  ", &changes.added), ("removed", &changes.removed)] {
+        for row in keys {
+            println!("{} {}={}", label, key, row);
+        }
+    }
+    for row in &changes.changed {
+        println!("changed {}={}", key, row.key);
+        for cell in &row.cells {
+            println!("  {}: {} -> {}", cell.column, cell.old, cell.new);
+        }
+    }
+    Ok(())
+}
+
+fn ies_validate(file: &Path) -> Result<()> {
+    let report = IESFile::validate_file(file)?;
+    for issue in &report.issues {
+        println!("{}", issue);
+    }
+    println!(
+        "{} of {} rows readable, {} issues",
+        report.rows_read,
+        report.declared_rows,
+        report.issues.len()
+    );
+    if report.is_valid() {
+        Ok(())
+    } else {
+        Err(toslib::Error::Ies(format!(
+            "{} is inconsistent",
+            file.display()
+        )))
+    }
+}
+
+fn ies_convert(inputs: &[PathBuf], format: &str, out: &Path) -> Result<()> {
+    let Some(format) = ies::IesFormat::from_name(format) else {
+        return Err(toslib::Error::Ies(format!(
+            "Unknown format '{}', expected json, csv or xml",
+            format
+        )));
+    };
+    let results = ies::convert_batch(inputs, format, out)?;
+    let mut failed = 0;
+    for (_, result) in &results {
+        if let Err(err) = result {
+            println!("FAILED {}", err);
+            failed += 1;
+        }
+    }
+    println!(
+        "Converted {} of {} tables into {}",
+        results.len() - failed,
+        results.len(),
+        out.display()
+    );
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(toslib::Error::Ies(format!("{} tables failed", failed)))
+    }
+}
+
+fn replace(archive: &Path, entry: &str, file: &Path) -> Result<()> {
+    let data = std::fs::read(file)?;
+    let mut ipf = IPFFile::load_from_file(archive)?;
+    if ipf.find(entry).is_some() {
+        ipf.replace_entry(entry, &data)?;
+        println!("Replaced {} in {}", entry, archive.display());
+    } else {
+        let container = archive
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        ipf.append_entry(&container, entry, &data)?;
+        println!("Added {} to {}", entry, archive.display());
+    }
+    Ok(())
+}
+
+fn extract(
+    archive: &Path,
+    out: &Path,
+    threads: usize,
+    keep_existing: bool,
+    verify: bool,
+) -> Result<()> {
+    let ipf = IPFFile::load_from_file(archive)?;
+    let options = ipf::ExtractOptions::default()
+        .with_threads(threads)
+        .with_overwrite(!keep_existing)
+        .with_verify(verify);
+    let written = ipf.extract_all(out, &options, |_, done, total| {
+        if done % 1000 == 0 || done == total {
+            eprint!("\r{}/{} entries", done, total);
+        }
+    })?;
+    eprintln!();
+    println!("Extracted {} files to {}", written, out.display());
+    Ok(())
+}
+
+fn pack(
+    dir: &Path,
+    out: &Path,
+    base_version: u32,
+    new_version: Option<u32>,
+    container: Option<String>,
+    level: u32,
+) -> Result<()> {
+    let new_version = next_version(base_version, new_version)?;
+    let mut options = ipf::PackOptions::default()
+        .with_versions(base_version, new_version)
+        .with_compression_level(level);
+    options.container = container;
+    let count = ipf::pack_dir(dir, out, &options)?;
+
+    println!(
+        "Packed {} files into {} (version {} -> {})",
+        count,
+        out.display(),
+        base_version,
+        new_version
+    );
+    Ok(())
+}
+
+/// `new_version`, or the version after `base_version` when it is not given.
+fn next_version(base_version: u32, new_version: Option<u32>) -> Result<u32> {
+    match new_version {
+        Some(version) => Ok(version),
+        None => base_version.checked_add(1).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "--base-version {} has no next version, pass --new-version",
+                    base_version
+                ),
+            )
+            .into()
+        }),
+    }
+}
+
+/// Lists the `.ipf` files directly inside `dir`, sorted by file name so numbered
+/// patches are applied in order.
+fn list_archives(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_ipf = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ipf"));
+        if path.is_file() && is_ipf {
+            archives.push(path);
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+/// Resolves a command argument to archives: a single `.ipf`, the archives in a
+/// folder, or the `data/` and `patch/` archives of a client root.
+fn collect_archives(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut archives = list_archives(path)?;
+    for sub in ["data", "patch"] {
+        let dir = path.join(sub);
+        if dir.is_dir() {
+            archives.extend(list_archives(&dir)?);
+        }
+    }
+    Ok(archives)
+}
+
+/// Key used to identify an entry across archives: lowercase `container/path`.
+fn entry_key(container_name: &str, directory_name: &str) -> String {
+    format!("{}/{}", container_name, directory_name).to_lowercase()
+}
+
+#[derive(Default)]
+struct PatchReport {
+    added: BTreeMap<IPFEntryKind, Vec<String>>,
+    changed: BTreeMap<IPFEntryKind, Vec<String>>,
+    unchanged: usize,
+}
+
+/// Tracks the CRC and size of every entry seen so far, later archives winning.
+#[derive(Default)]
+struct ClientState {
+    entries: HashMap<String, (u32, u32)>,
+}
+
+impl ClientState {
+    /// Applies `ipf` on top of the current state and reports what it changed.
+    fn apply(&mut self, ipf: &IPFFile) -> PatchReport {
+        let mut report = PatchReport::default();
+        for entry in ipf.file_table() {
+            let key = entry_key(&entry.container_name_str(), &entry.directory_name_str());
+            let value = (entry.crc32(), entry.file_size_uncompressed());
+            let path = entry.directory_name();
+            match self.entries.insert(key, value) {
+                None => report.added.entry(entry.kind()).or_default().push(path),
+                Some(old) if old != value => {
+                    report.changed.entry(entry.kind()).or_default().push(path)
+                }
+                Some(_) => report.unchanged += 1,
+            }
+        }
+        report
+    }
+}
+
+fn print_patch_report(archive: &Path, ipf: &IPFFile, report: &PatchReport) {
+    println!(
+        "== {} (version {} -> {}, {} entries)",
+        archive.display(),
+        ipf.footer().version_to_patch(),
+        ipf.footer().new_version(),
+        ipf.footer().file_count()
+    );
+    for (label, groups) in [("added", &report.added), ("changed", &report.changed)] {
+        for (kind, paths) in groups {
+            println!("  {} {} ({}):", label, kind.name(), paths.len());
+            for path in paths {
+                println!("    {}", path);
+            }
+        }
+    }
+    if report.unchanged > 0 {
+        println!("  unchanged: {}", report.unchanged);
+    }
+}
+
+/// A client's patch folder between polls of `watch`.
+struct PatchWatcher {
+    patch_dir: PathBuf,
+    state: ClientState,
+    seen: HashSet<PathBuf>,
+    // Archives still being written are only processed once their size settles
+    pending: HashMap<PathBuf, u64>,
+}
+
+impl PatchWatcher {
+    /// Builds the current state from the base data and every patch already present.
+    fn open(client_dir: &Path) -> Result<Self> {
+        let patch_dir = if client_dir.join("patch").is_dir() {
+            client_dir.join("patch")
+        } else {
+            client_dir.to_path_buf()
+        };
+
+        let mut state = ClientState::default();
+        let mut seen = HashSet::new();
+        let data_dir = client_dir.join("data");
+        let mut initial = if data_dir.is_dir() {
+            list_archives(&data_dir)?
+        } else {
+            Vec::new()
+        };
+        initial.extend(list_archives(&patch_dir)?);
+        for archive in initial {
+            match IPFFile::load_from_file(&archive) {
+                Ok(ipf) => {
+                    state.apply(&ipf);
+                }
+                Err(err) => eprintln!("warning: skipping {}: {}", archive.display(), err),
+            }
+            seen.insert(archive);
+        }
+
+        Ok(Self {
+            patch_dir,
+            state,
+            seen,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Applies the archives that appeared since the last poll and kept their size
+    /// since then, returning each with what it changed.
+    fn poll(&mut self) -> Result<Vec<(PathBuf, IPFFile, PatchReport)>> {
+        let mut applied = Vec::new();
+        for archive in list_archives(&self.patch_dir)? {
+            if self.seen.contains(&archive) {
+                continue;
+            }
+            let size = std::fs::metadata(&archive)?.len();
+            if self.pending.insert(archive.clone(), size) != Some(size) {
+                continue;
+            }
+            self.pending.remove(&archive);
+            self.seen.insert(archive.clone());
+
+            match IPFFile::load_from_file(&archive) {
+                Ok(ipf) => {
+                    let report = self.state.apply(&ipf);
+                    applied.push((archive, ipf, report));
+                }
+                Err(err) => eprintln!("warning: skipping {}: {}", archive.display(), err),
+            }
+        }
+        Ok(applied)
+    }
+}
+
+fn watch(client_dir: &Path, interval: Duration) -> Result<()> {
+    let mut watcher = PatchWatcher::open(client_dir)?;
+    println!(
+        "Watching {} ({} known entries)",
+        watcher.patch_dir.display(),
+        watcher.state.entries.len()
+    );
+
+    loop {
+        thread::sleep(interval);
+        for (archive, ipf, report) in watcher.poll()? {
+            print_patch_report(&archive, &ipf, &report);
+        }
+    }
+}
+
+#[derive(Default)]
+struct SizeTotals {
+    count: usize,
+    compressed: u64,
+    uncompressed: u64,
+}
+
+impl SizeTotals {
+    fn add(&mut self, compressed: u32, uncompressed: u32) {
+        self.count += 1;
+        self.compressed += compressed as u64;
+        self.uncompressed += uncompressed as u64;
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn print_totals(label: &str, totals: &SizeTotals) {
+    println!(
+        "  {:<12} {:>8} entries  {:>12} compressed  {:>12} uncompressed",
+        label,
+        totals.count,
+        format_size(totals.compressed),
+        format_size(totals.uncompressed)
+    );
+}
+
+/// Entry sizes of a set of archives, as `stats` prints them.
+#[derive(Default)]
+struct ArchiveStats {
+    total: SizeTotals,
+    by_kind: BTreeMap<IPFEntryKind, SizeTotals>,
+    by_extension: BTreeMap<String, SizeTotals>,
+    /// Uncompressed size and `container/path` of every entry, largest first
+    largest: Vec<(u32, String)>,
+}
+
+impl ArchiveStats {
+    /// Sums the entries of every archive that loads, skipping the others.
+    fn collect(archives: &[PathBuf]) -> Self {
+        let mut stats = Self::default();
+        for archive in archives {
+            let ipf = match IPFFile::load_from_file(archive) {
+                Ok(ipf) => ipf,
+                Err(err) => {
+                    eprintln!("warning: skipping {}: {}", archive.display(), err);
+                    continue;
+                }
+            };
+
+            for entry in ipf.file_table() {
+                let (compressed, uncompressed) =
+                    (entry.file_size_compressed(), entry.file_size_uncompressed());
+                let name = entry.directory_name_str();
+                let extension = name
+                    .rsplit_once('.')
+                    .map(|(_, ext)| ext.to_ascii_lowercase())
+                    .unwrap_or_else(|| "(none)".to_string());
+
+                stats.total.add(compressed, uncompressed);
+                stats
+                    .by_kind
+                    .entry(entry.kind())
+                    .or_default()
+                    .add(compressed, uncompressed);
+                stats
+                    .by_extension
+                    .entry(extension)
+                    .or_default()
+                    .add(compressed, uncompressed);
+                stats.largest.push((
+                    uncompressed,
+                    format!("{}/{}", entry.container_name_str(), name),
+                ));
+            }
+        }
+        stats
+            .largest
+            .sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+        stats
+    }
+}
+
+fn stats(path: &Path, top: usize) -> Result<()> {
+    let archives = collect_archives(path)?;
+    if archives.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no .ipf archives found in '{}'", path.display()),
+        )
+        .into());
+    }
+
+    let stats = ArchiveStats::collect(&archives);
+    println!("{} archives", archives.len());
+    print_totals("total", &stats.total);
+
+    println!("By type:");
+    for (kind, totals) in &stats.by_kind {
+        print_totals(kind.name(), totals);
+    }
+
+    println!("By extension:");
+    for (extension, totals) in &stats.by_extension {
+        print_totals(extension, totals);
+    }
+
+    println!("Largest entries:");
+    for (size, name) in stats.largest.iter().take(top) {
+        println!("  {:>12}  {}", format_size(*size as u64), name);
+    }
+
+    Ok(())
+}
+
+/// Runs `task` on every `(archive index, entry)` job in parallel. Each worker keeps its
+/// own handle per archive so entries of the same archive are read concurrently.
+fn par_map_entries<T, F>(
+    archives: &[&Path],
+    jobs: &[(usize, &IPFFileTable)],
+    task: F,
+) -> Vec<(usize, String, std::result::Result<T, String>)>
+where
+    T: Send,
+    F: Fn(&mut BinaryReader<BufReader<File>>, &IPFFileTable) -> std::result::Result<T, String>
+        + Sync,
+{
+    jobs.par_iter()
+        .map_init(HashMap::new, |readers, &(index, entry)| {
+            let result = match readers.entry(index) {
+                std::collections::hash_map::Entry::Occupied(slot) => Ok(slot.into_mut()),
+                std::collections::hash_map::Entry::Vacant(slot) => File::open(archives[index])
+                    .map(|file| slot.insert(BinaryReader::new(BufReader::new(file))))
+                    .map_err(|err| err.to_string()),
+            };
+            let result = result.and_then(|reader| task(reader, entry));
+            (index, entry.directory_name(), result)
+        })
+        .collect()
+}
+
+/// Extracts `entry` and checks the inflated data against the stored CRC.
+fn check_entry(
+    reader: &mut BinaryReader<BufReader<File>>,
+    entry: &IPFFileTable,
+) -> std::result::Result<(), String> {
+    entry
+        .verify_crc(reader)
+        .map_err(|err| err.root().to_string())
+}
+
+fn manifest_verify(manifest: &Path, dir: &Path) -> Result<()> {
+    let manifest = Manifest::load_from_file(manifest)?;
+    let mismatches = manifest.verify(dir)?;
+    for mismatch in &mismatches {
+        match mismatch {
+            ManifestMismatch::Missing { path } => println!("missing  {}", path),
+            ManifestMismatch::Size {
+                path,
+                expected,
+                actual,
+            } => println!(
+                "size     {} ({} bytes, expected {})",
+                path, actual, expected
+            ),
+            ManifestMismatch::Checksum { path } => println!("checksum {}", path),
+        }
+    }
+    if mismatches.is_empty() {
+        println!("All {} files match", manifest.entries.len());
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} of {} files do not match",
+                mismatches.len(),
+                manifest.entries.len()
+            ),
+        )
+        .into())
+    }
+}
+
+fn version(client_dir: &Path) -> Result<()> {
+    let version = GameData::open(client_dir)?.detect_version()?;
+    println!("version {}", version.version);
+    for (name, revision) in &version.revisions {
+        println!("{}.revision.txt: {}", name, revision);
+    }
+    for gap in &version.gaps {
+        println!(
+            "warning: {} patches a version no archive provides",
+            gap.display()
+        );
+    }
+    Ok(())
+}
+
+fn search(client_dir: &Path, query: &str, case_sensitive: bool, exact: bool) -> Result<()> {
+    let game = GameData::open(client_dir)?;
+    let options = SearchOptions::default()
+        .with_case_sensitive(case_sensitive)
+        .with_whole_value(exact);
+    let results = toslib::search::search(&game, query, &options);
+    for hit in &results.hits {
+        println!("{}", hit);
+    }
+    for (entry, err) in &results.failures {
+        eprintln!("warning: skipping {}: {}", entry, err);
+    }
+    println!("{} hits", results.hits.len());
+    Ok(())
+}
+
+/// What `verify` found in a set of archives.
+#[derive(Default)]
+struct VerifyReport {
+    entries: usize,
+    unreadable: Vec<(PathBuf, String)>,
+    warnings: Vec<(PathBuf, String)>,
+    /// Archives with failing entries, each with the entry names and reasons sorted
+    corrupt: Vec<(PathBuf, Vec<(String, String)>)>,
+}
+
+impl VerifyReport {
+    /// Loads every archive and checks each entry against its stored CRC.
+    fn check(archives: &[PathBuf]) -> Self {
+        let mut report = Self::default();
+        let mut loaded = Vec::new();
+        for archive in archives {
+            match IPFFile::load_from_file(archive) {
+                Ok(ipf) => loaded.push((archive, ipf)),
+                Err(err) => report.unreadable.push((archive.clone(), err.to_string())),
+            }
+        }
+
+        let jobs: Vec<(usize, &IPFFileTable)> = loaded
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (_, ipf))| ipf.file_table().iter().map(move |entry| (index, entry)))
+            .collect();
+        let paths: Vec<&Path> = loaded
+            .iter()
+            .map(|(archive, _)| archive.as_path())
+            .collect();
+        let mut corrupt_by_archive: BTreeMap<usize, Vec<(String, String)>> = BTreeMap::new();
+        for (index, name, result) in par_map_entries(&paths, &jobs, check_entry) {
+            if let Err(reason) = result {
+                corrupt_by_archive
+                    .entry(index)
+                    .or_default()
+                    .push((name, reason));
+            }
+        }
+
+        report.entries = jobs.len();
+        for (archive, ipf) in &loaded {
+            for warning in ipf.warnings() {
+                report
+                    .warnings
+                    .push((archive.to_path_buf(), warning.to_string()));
+            }
+        }
+        for (index, mut entries) in corrupt_by_archive {
+            entries.sort();
+            report.corrupt.push((loaded[index].0.clone(), entries));
+        }
+        report
+    }
+
+    fn corrupt_count(&self) -> usize {
+        self.corrupt.iter().map(|(_, entries)| entries.len()).sum()
+    }
+}
+
+fn verify(client_dir: &Path) -> Result<()> {
+    let archives = collect_archives(client_dir)?;
+    if archives.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no .ipf archives found in '{}'", client_dir.display()),
+        )
+        .into());
+    }
+
+    let report = VerifyReport::check(&archives);
+    for (archive, err) in &report.unreadable {
+        println!("UNREADABLE {}: {}", archive.display(), err);
+    }
+    for (archive, warning) in &report.warnings {
+        println!("WARN {}: {}", archive.display(), warning);
+    }
+    for (archive, entries) in &report.corrupt {
+        println!("CORRUPT {} ({} entries)", archive.display(), entries.len());
+        for (name, reason) in entries {
+            println!("  {}: {}", name, reason);
+        }
+    }
+
+    let corrupt_count = report.corrupt_count();
+    println!(
+        "Checked {} entries in {} archives: {} corrupt entries, {} unreadable archives",
+        report.entries,
+        archives.len(),
+        corrupt_count,
+        report.unreadable.len()
+    );
+
+    if corrupt_count > 0 || !report.unreadable.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "client installation failed verification",
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Size mismatches of one chunk id and version across a set of models.
+#[derive(Default)]
+struct ChunkMismatches {
+    under_reads: usize,
+    over_reads: usize,
+    example: String,
+}
+
+fn xac_check(path: &Path, memory_budget_mib: u64) -> Result<()> {
+    let archives = collect_archives(path)?;
+    let options = ScanOptions::default().with_memory_budget(memory_budget_mib * 1024 * 1024);
+
+    let mut mismatches: BTreeMap<(u32, u32), ChunkMismatches> = BTreeMap::new();
+    let mut checked = 0;
+    let mut failed = 0;
+    scan::scan(
+        &archives,
+        &options,
+        |entry| entry.kind() == IPFEntryKind::Model,
+        |_, _, data| {
+            XACFile::load_from_slice_with_options(&data, &ParseOptions::lenient())
+                .map(|xac| xac.warnings().to_vec())
+        },
+        |item| {
+            let Some(name) = item.entry else {
+                if let Err(err) = item.result {
+                    println!("UNREADABLE {}: {}", item.archive.display(), err);
+                }
+                return;
+            };
+            checked += 1;
+            let warnings = match item.result {
+                Ok(warnings) => warnings,
+                Err(err) => {
+                    println!("FAILED {}: {}", name, err);
+                    failed += 1;
+                    return;
+                }
+            };
+            for warning in warnings {
+                if let ParseWarning::SizeMismatch {
+                    chunk_id,
+                    version,
+                    declared,
+                    consumed,
+                    ..
+                } = warning
+                {
+                    let stats = mismatches.entry((chunk_id, version)).or_default();
+                    if consumed < declared as u64 {
+                        stats.under_reads += 1;
+                    } else {
+                        stats.over_reads += 1;
+                    }
+                    if stats.example.is_empty() {
+                        stats.example = name.clone();
+                    }
+                }
+            }
+        },
+    );
+
+    for ((chunk_id, version), stats) in &mismatches {
+        println!(
+            "chunk {:>3} v{}: {} under-reads, {} over-reads (e.g. {})",
+            chunk_id, version, stats.under_reads, stats.over_reads, stats.example
+        );
+    }
+    println!(
+        "Checked {} models: {} chunk layouts with size mismatches, {} failed to parse",
+        checked,
+        mismatches.len(),
+        failed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toslib::ipf::IPFWriter;
+
+    fn write_archive(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = IPFWriter::new();
+        for (name, data) in files {
+            writer.add_file("data.ipf", name, data.to_vec());
+        }
+        writer.save_to_file(path).unwrap();
+    }
+
+    #[test]
+    fn pack_versions_default_to_the_next_one() {
+        assert_eq!(next_version(7, None).unwrap(), 8);
+        assert_eq!(next_version(7, Some(20)).unwrap(), 20);
+        assert_eq!(next_version(u32::MAX, Some(0)).unwrap(), 0);
+        let err = next_version(u32::MAX, None).unwrap_err();
+        assert!(err.to_string().contains("has no next version"));
+    }
+
+    #[test]
+    fn watcher_reports_settled_patches_once() {
+        let dir = std::env::temp_dir().join(format!("toslib-watch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        std::fs::create_dir_all(dir.join("patch")).unwrap();
+        write_archive(
+            &dir.join("data/data.ipf"),
+            &[("a.xml", b"old"), ("b.xml", b"same")],
+        );
+
+        let mut watcher = PatchWatcher::open(&dir).unwrap();
+        let known = watcher.state.entries.len();
+        let quiet = watcher.poll().unwrap();
+
+        write_archive(
+            &dir.join("patch/001001.ipf"),
+            &[("a.xml", b"new"), ("b.xml", b"same"), ("c.xml", b"added")],
+        );
+        // The first poll only records the size of a new archive
+        let settling = watcher.poll().unwrap();
+        let applied = watcher.poll().unwrap();
+        let again = watcher.poll().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(known, 2);
+        assert!(quiet.is_empty() && settling.is_empty() && again.is_empty());
+        assert_eq!(applied.len(), 1);
+        let (archive, _, report) = &applied[0];
+        assert!(archive.ends_with("001001.ipf"));
+        let paths = |groups: &BTreeMap<IPFEntryKind, Vec<String>>| {
+            groups.values().flatten().cloned().collect::<Vec<_>>()
+        };
+        assert_eq!(paths(&report.changed), ["a.xml"]);
+        assert_eq!(paths(&report.added), ["c.xml"]);
+        assert_eq!(report.unchanged, 1);
+    }
+
+    #[test]
+    fn stats_sum_entries_by_type_and_extension() {
+        let dir = std::env::temp_dir().join(format!("toslib-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("data.ipf");
+        write_archive(
+            &archive,
+            &[
+                ("a.xml", &[b'a'; 10]),
+                ("sub/b.XML", &[b'b'; 30]),
+                ("readme", &[b'c'; 20]),
+            ],
+        );
+        let missing = dir.join("missing.ipf");
+        let stats = ArchiveStats::collect(&[archive, missing]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stats.total.count, 3);
+        assert_eq!(stats.total.uncompressed, 60);
+        let xml = &stats.by_extension["xml"];
+        assert_eq!((xml.count, xml.uncompressed), (2, 40));
+        assert_eq!(stats.by_extension["(none)"].uncompressed, 20);
+        assert_eq!(stats.by_kind.values().map(|t| t.count).sum::<usize>(), 3);
+        let largest: Vec<&str> = stats.largest.iter().map(|(_, n)| n.as_str()).collect();
+        assert_eq!(
+            largest,
+            ["data.ipf/sub/b.XML", "data.ipf/readme", "data.ipf/a.xml"]
+        );
+    }
+
+    /// Overwrites the little-endian `u32` at `offset` into the table record of the
+    /// first entry of the archive at `path`.
+    fn patch_first_entry(path: &Path, offset: usize, value: u32) {
+        let mut bytes = std::fs::read(path).unwrap();
+        let footer = bytes.len() - 24;
+        let table = u32::from_le_bytes(bytes[footer + 2..footer + 6].try_into().unwrap()) as usize;
+        bytes[table + offset..table + offset + 4].copy_from_slice(&value.to_le_bytes());
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn verify_reports_corrupt_entries_and_unreadable_archives() {
+        let dir = std::env::temp_dir().join(format!("toslib-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: &[(&str, &[u8])] = &[("a.xml", b"first entry"), ("b.xml", b"second")];
+        let [good, crc, size, garbage] =
+            ["good", "crc", "size", "garbage"].map(|name| dir.join(format!("{}.ipf", name)));
+        for archive in [&good, &crc, &size] {
+            write_archive(archive, files);
+        }
+        // The record starts with the name length (u16), then CRC and sizes
+        patch_first_entry(&crc, 2, 0xDEAD_BEEF);
+        patch_first_entry(&size, 10, 99);
+        std::fs::write(&garbage, b"not an archive").unwrap();
+
+        let clean = VerifyReport::check(std::slice::from_ref(&good));
+        let report = VerifyReport::check(&[good, crc.clone(), size.clone(), garbage.clone()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((clean.entries, clean.corrupt_count()), (2, 0));
+        assert!(clean.unreadable.is_empty());
+
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.corrupt_count(), 2);
+        let (archive, entries) = &report.corrupt[0];
+        assert_eq!(archive, &crc);
+        assert_eq!(entries[0].0, "a.xml");
+        assert!(entries[0].1.starts_with("CRC mismatch: expected deadbeef"));
+        let (archive, entries) = &report.corrupt[1];
+        assert_eq!(archive, &size);
+        assert!(
+            entries[0]
+                .1
+                .ends_with("size mismatch: expected 99 bytes, got 11")
+        );
+        assert_eq!(report.unreadable.len(), 1);
+        assert_eq!(report.unreadable[0].0, garbage);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::error::{Error, Result};
+use crate::ipf::{Crc32Writer, IPFWriter};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Size and checksums of one distributed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest root, joined with `/`
+    pub path: String,
+    pub size: u64,
+    pub crc32: u32,
+    pub sha256: [u8; 32],
+}
+
+/// Checksum list for a mod release, so players can check what they downloaded.
+///
+/// The text form has one `<sha256> <crc32> <size> <path>` line per file, sorted by
+/// path, so the same files always give the same manifest. Publishing `digest()`
+/// (or signing the text) covers every file at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A difference found by `Manifest::verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    Missing {
+        path: String,
+    },
+    Size {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// Same size, different content
+    Checksum {
+        path: String,
+    },
+}
+
+impl Manifest {
+    /// Hashes `files`, which must lie below `root`.
+    pub fn from_files<P: AsRef<Path>>(root: &Path, files: &[P]) -> Result<Self> {
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let file = file.as_ref();
+            let relative = file.strip_prefix(root).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' is not below '{}'", file.display(), root.display()),
+                )
+            })?;
+            let path = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let (size, crc32, sha256) = hash_file(file).map_err(|err| err.in_file(file))?;
+            entries.push(ManifestEntry {
+                path,
+                size,
+                crc32,
+                sha256,
+            });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+
+    /// Hashes every file below `dir`, such as a folder of built patch archives.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        IPFWriter::collect_files(dir, &mut files)?;
+        Self::from_files(dir, &files)
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|text| Self::parse(&text))
+            .map_err(|err| err.in_file(path))
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(std::fs::write(path, self.to_text())?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid =
+                || Error::Serialization(format!("manifest line {}: '{}'", index + 1, line));
+            let mut fields = line.splitn(4, ' ');
+            let mut next = || fields.next().ok_or_else(invalid);
+            let (sha256, crc32, size, path) = (next()?, next()?, next()?, next()?);
+            entries.push(ManifestEntry {
+                path: path.to_string(),
+                size: size.parse().map_err(|_| invalid())?,
+                crc32: u32::from_str_radix(crc32, 16).map_err(|_| invalid())?,
+                sha256: parse_hex(sha256).ok_or_else(invalid)?,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(
+                text,
+                "{} {:08x} {} {}",
+                hex(&entry.sha256),
+                entry.crc32,
+                entry.size,
+                entry.path
+            );
+        }
+        text
+    }
+
+    /// SHA-256 of the text form, identifying the whole release.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.to_text().as_bytes());
+        hasher.finish()
+    }
+
+    pub fn digest_hex(&self) -> String {
+        hex(&self.digest())
+    }
+
+    /// Checks the files below `root` against the manifest. Files not listed are
+    /// ignored; an empty result means every listed file is intact.
+    pub fn verify<P: AsRef<Path>>(&self, root: P) -> Result<Vec<ManifestMismatch>> {
+        let root = root.as_ref();
+        let mut mismatches = Vec::new();
+        for entry in &self.entries {
+            let file = root.join(&entry.path);
+            let path = entry.path.clone();
+            if !file.is_file() {
+                mismatches.push(ManifestMismatch::Missing { path });
+                continue;
+            }
+            let (size, crc32, sha256) = hash_file(&file).map_err(|err| err.in_file(&file))?;
+            if size != entry.size {
+                mismatches.push(ManifestMismatch::Size {
+                    path,
+                    expected: entry.size,
+                    actual: size,
+                });
+            } else if crc32 != entry.crc32 || sha256 != entry.sha256 {
+                mismatches.push(ManifestMismatch::Checksum { path });
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+fn hash_file(path: &Path) -> Result<(u64, u32, [u8; 32])> {
+    let mut file = File::open(path)?;
+    let mut crc = Crc32Writer::new();
+    let mut sha = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        crc.update(&buffer[..read]);
+        sha.update(&buffer[..read]);
+    }
+    Ok((crc.len(), crc.finish(), sha.finish()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut text, byte| {
+        let _ = write!(text, "{:02x}", byte);
+        text
+    })
+}
+
+fn parse_hex(text: &str) -> Option<[u8; 32]> {
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; 32];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// SHA-256 (FIPS 180-4).
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    len: u64,
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Sha256 {
+    fn new() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len * 8;
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (word, chunk) in w.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_round_trips_and_detects_changes() {
+        let mut sha = Sha256::new();
+        sha.update(b"abc");
+        assert_eq!(
+            hex(&sha.finish()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let dir = std::env::temp_dir().join(format!("toslib-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("patch")).unwrap();
+        std::fs::write(dir.join("patch").join("mod.ipf"), b"abc").unwrap();
+        std::fs::write(dir.join("readme.txt"), vec![7; 100]).unwrap();
+
+        let manifest = Manifest::from_dir(&dir).unwrap();
+        let text = manifest.to_text();
+        assert!(text.starts_with(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad 352441c2 3 patch/mod.ipf\n"
+        ));
+        assert_eq!(Manifest::parse(&text).unwrap(), manifest);
+        assert!(manifest.verify(&dir).unwrap().is_empty());
+
+        std::fs::write(dir.join("patch").join("mod.ipf"), b"abd").unwrap();
+        std::fs::remove_file(dir.join("readme.txt")).unwrap();
+        let mismatches = manifest.verify(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            mismatches,
+            [
+                ManifestMismatch::Checksum {
+                    path: "patch/mod.ipf".to_string()
+                },
+                ManifestMismatch::Missing {
+                    path: "readme.txt".to_string()
+                },
+            ]
+        );
+    }
+}
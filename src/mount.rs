@@ -0,0 +1,249 @@
+use crate::error::{Error, Result};
+use crate::game::entry_key;
+use crate::glob::Glob;
+use crate::ipf::{IPFFile, IPFFileTable};
+use crate::options::ParseOptions;
+use crate::text::TextDecoding;
+use crate::tosreader::BinaryReader;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// One archive of an `IpfMount`.
+#[derive(Debug, Clone)]
+pub(crate) struct MountedArchive {
+    pub(crate) path: PathBuf,
+    pub(crate) version_to_patch: u32,
+    pub(crate) new_version: u32,
+    pub(crate) text: TextDecoding,
+}
+
+/// Several archives seen as one file system.
+///
+/// Entries are keyed by their lowercase `container/path`, the name the game uses, and
+/// an entry of a later archive replaces the same entry of every earlier one. Patches
+/// name the data archive they patch as container, so `xml.ipf/item.xml` resolves to
+/// the newest copy whichever archive holds it.
+#[derive(Debug, Default)]
+pub struct IpfMount {
+    archives: Vec<MountedArchive>,
+    entries: BTreeMap<String, (usize, IPFFileTable)>,
+}
+
+impl IpfMount {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts `archives` ordered by patch version: archives producing a lower
+    /// `new_version` are applied first, equal versions keep the given order.
+    pub fn from_archives<I, P>(archives: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut loaded = Vec::new();
+        for archive in archives {
+            let archive = archive.as_ref();
+            loaded.push((archive.to_path_buf(), IPFFile::load_from_file(archive)?));
+        }
+        loaded.sort_by_key(|(_, ipf)| ipf.footer().new_version());
+
+        let mut mount = Self::new();
+        for (path, ipf) in loaded {
+            mount.insert(path, &ipf, TextDecoding::default());
+        }
+        Ok(mount)
+    }
+
+    /// Mounts `archive` on top of everything mounted so far, whatever its version.
+    pub fn mount<P: AsRef<Path>>(&mut self, archive: P) -> Result<&mut Self> {
+        self.mount_with(archive, TextDecoding::default())
+    }
+
+    /// Like `mount`, decoding entry names and text of the archive with `text`.
+    pub fn mount_with<P: AsRef<Path>>(
+        &mut self,
+        archive: P,
+        text: TextDecoding,
+    ) -> Result<&mut Self> {
+        let archive = archive.as_ref();
+        let ipf = IPFFile::load_from_file_with_options(
+            archive,
+            &ParseOptions::default().with_text(text),
+        )?;
+        self.insert(archive.to_path_buf(), &ipf, text);
+        Ok(self)
+    }
+
+    /// Adds the entries of an already loaded archive on top.
+    pub(crate) fn insert(&mut self, path: PathBuf, ipf: &IPFFile, text: TextDecoding) {
+        let index = self.archives.len();
+        self.archives.push(MountedArchive {
+            path,
+            version_to_patch: ipf.footer().version_to_patch(),
+            new_version: ipf.footer().new_version(),
+            text,
+        });
+        for entry in ipf.file_table() {
+            let key = entry_key(&entry.container_name_str(), &entry.directory_name_str());
+            self.entries.insert(key, (index, entry.clone()));
+        }
+    }
+
+    pub(crate) fn mounted(&self) -> &[MountedArchive] {
+        &self.archives
+    }
+
+    /// Archives in the order they are applied.
+    pub fn archives(&self) -> impl Iterator<Item = &Path> {
+        self.archives.iter().map(|archive| archive.path.as_path())
+    }
+
+    /// Keys of every entry, sorted.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Keys matching `pattern`, sorted; see `Glob`.
+    pub fn paths_matching(&self, pattern: &str) -> impl Iterator<Item = &str> {
+        let glob = Glob::new(pattern);
+        self.paths().filter(move |path| glob.matches(path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The winning entry for `path` (`container/path`, any case) and the archive that
+    /// holds it.
+    pub fn entry(&self, path: &str) -> Option<(&Path, &IPFFileTable)> {
+        let (index, entry) = self.entries.get(&path.to_lowercase())?;
+        Some((&self.archives[*index].path, entry))
+    }
+
+    /// Text decoding of the archive holding `path`.
+    pub fn text(&self, path: &str) -> Option<TextDecoding> {
+        let (index, _) = self.entries.get(&path.to_lowercase())?;
+        Some(self.archives[*index].text)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(&path.to_lowercase())
+    }
+
+    /// Extracts the winning entry for `path`.
+    pub fn open(&self, path: &str) -> Result<Vec<u8>> {
+        let Some((archive, entry)) = self.entry(path) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no entry '{}' in the mounted archives", path),
+            )
+            .into());
+        };
+        let file = File::open(archive).map_err(|err| Error::from(err).in_file(archive))?;
+        entry
+            .extract(&mut BinaryReader::new(BufReader::new(file)))
+            .map_err(|err| err.in_file(archive))
+    }
+}
+
+/// Something entries can be read from by `container/path` name: an archive on disk,
+/// an `IpfMount` or a `GameData`. See `IESFile::from_ipf`.
+pub trait EntrySource {
+    /// Extracts the entry stored under `path`.
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Options for parsing the entry at `path`, such as the text decoding of the
+    /// archive holding it.
+    fn parse_options(&self, _path: &str) -> ParseOptions {
+        ParseOptions::default()
+    }
+}
+
+/// An archive on disk. Entries are found by their full `container/path` name, or by
+/// their path inside the archive.
+impl EntrySource for Path {
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>> {
+        let read = || {
+            let ipf = IPFFile::load_from_file(self)?;
+            let Some(entry) = ipf.get_by_path(path).or_else(|| ipf.find(path)) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no entry '{}' in the archive", path),
+                )
+                .into());
+            };
+            let file = File::open(self)?;
+            entry
+                .extract(&mut BinaryReader::new(BufReader::new(file)))
+                .map_err(|err| err.in_entry(path))
+        };
+        read().map_err(|err: Error| err.in_file(self))
+    }
+}
+
+impl EntrySource for PathBuf {
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>> {
+        self.as_path().read_entry(path)
+    }
+}
+
+impl EntrySource for str {
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>> {
+        Path::new(self).read_entry(path)
+    }
+}
+
+impl EntrySource for IpfMount {
+    fn read_entry(&self, path: &str) -> Result<Vec<u8>> {
+        self.open(path)
+    }
+
+    fn parse_options(&self, path: &str) -> ParseOptions {
+        ParseOptions::default().with_text(self.text(path).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn newer_patches_override_older_entries() {
+        let dir = std::env::temp_dir().join(format!("toslib-mount-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, versions, content) in [
+            ("b_patch.ipf", (101, 102), "second"),
+            ("a_patch.ipf", (100, 101), "first"),
+            ("xml.ipf", (0, 100), "base"),
+        ] {
+            let mut writer = IPFWriter::new();
+            writer.versions(versions.0, versions.1);
+            writer.add_file("xml.ipf", "item.xml", content.as_bytes().to_vec());
+            writer.add_file("xml.ipf", &format!("{}.xml", content), Vec::new());
+            writer.save_to_file(dir.join(name)).unwrap();
+        }
+
+        let names = ["b_patch.ipf", "a_patch.ipf", "xml.ipf"];
+        let mut mount = IpfMount::from_archives(names.map(|name| dir.join(name))).unwrap();
+        let newest = mount.open("XML.ipf/item.xml").unwrap();
+        let order: Vec<PathBuf> = mount.archives().map(Path::to_path_buf).collect();
+        mount.mount(dir.join("xml.ipf")).unwrap();
+        let remounted = mount.open("xml.ipf/item.xml").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(newest, b"second");
+        assert_eq!(order[0], dir.join("xml.ipf"));
+        assert_eq!(remounted, b"base");
+        assert_eq!(mount.len(), 4);
+        assert_eq!(mount.paths_matching("xml.ipf/*st.xml").count(), 1);
+        assert!(mount.open("xml.ipf/missing.xml").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::error::Result;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Values an exporter offers for one output file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameFields<'a> {
+    /// Model or table the output comes from, usually its file stem
+    pub actor: &'a str,
+    pub lod: u32,
+    /// Mesh within the model
+    pub mesh: usize,
+    /// Submesh within the mesh, or the running number of the output
+    pub index: usize,
+    /// Material or texture name without extension
+    pub material: &'a str,
+    /// Name of the exported item itself, e.g. a texture or table
+    pub name: &'a str,
+    /// File extension without the dot
+    pub ext: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Actor,
+    Lod,
+    Mesh,
+    Index,
+    Material,
+    Name,
+    Ext,
+}
+
+/// Output path pattern such as `{actor}/{lod}/{material}_{index}.{ext}`.
+///
+/// Placeholders are `{actor}`, `{lod}`, `{mesh}`, `{index}`, `{material}`, `{name}`
+/// and `{ext}`; `/` separates directories. Values are made safe as single path
+/// components before they are inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid(format!("unclosed '{{' in '{}'", template)))?;
+            segments.push(match &rest[start + 1..start + end] {
+                "actor" => Segment::Actor,
+                "lod" => Segment::Lod,
+                "mesh" => Segment::Mesh,
+                "index" => Segment::Index,
+                "material" => Segment::Material,
+                "name" => Segment::Name,
+                "ext" => Segment::Ext,
+                other => return Err(invalid(format!("unknown placeholder '{{{}}}'", other)).into()),
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// The relative path for `fields`.
+    pub fn render(&self, fields: &NameFields) -> PathBuf {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => rendered.push_str(text),
+                Segment::Actor => rendered.push_str(&sanitize(fields.actor)),
+                Segment::Lod => rendered.push_str(&fields.lod.to_string()),
+                Segment::Mesh => rendered.push_str(&fields.mesh.to_string()),
+                Segment::Index => rendered.push_str(&fields.index.to_string()),
+                Segment::Material => rendered.push_str(&sanitize(fields.material)),
+                Segment::Name => rendered.push_str(&sanitize(fields.name)),
+                Segment::Ext => rendered.push_str(&sanitize(fields.ext)),
+            }
+        }
+        // Literal `..` or absolute parts must not leave the output root
+        rendered
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+            .collect()
+    }
+}
+
+/// Hands out output paths under a root from a template, never the same path twice.
+///
+/// When two outputs render to the same path, the later one gets `_1`, `_2`, ...
+/// appended to its file stem.
+#[derive(Debug, Clone)]
+pub struct OutputLayout {
+    root: PathBuf,
+    template: NameTemplate,
+    used: HashSet<PathBuf>,
+}
+
+impl OutputLayout {
+    pub fn new<P: AsRef<Path>>(root: P, template: NameTemplate) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            template,
+            used: HashSet::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// A path for `fields` that no earlier call returned.
+    pub fn path(&mut self, fields: &NameFields) -> PathBuf {
+        let path = self.root.join(self.template.render(fields));
+        if self.used.insert(path.clone()) {
+            return path;
+        }
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        (1..)
+            .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, extension)))
+            .find(|candidate| self.used.insert(candidate.clone()))
+            .unwrap()
+    }
+}
+
+/// Replaces characters that are not allowed in file names on common platforms.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// `to` as referenced from a file in `from_dir`, e.g. an MTL file naming its texture,
+/// with `/` separators.
+pub(crate) fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from_dir.components().collect();
+    let target: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let parents = (common..from.len()).map(|_| "..".into());
+    let rest = target[common..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy());
+    parents.chain(rest).collect::<Vec<_>>().join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_renders_template_without_collisions() {
+        let template = NameTemplate::parse("{actor}/{lod}/{material}_{index}.{ext}").unwrap();
+        let mut layout = OutputLayout::new("out", template);
+        let fields = NameFields {
+            actor: "npc_guard",
+            lod: 1,
+            material: "armor:metal",
+            ext: "obj",
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.path(&fields),
+            Path::new("out/npc_guard/1/armor_metal_0.obj")
+        );
+        assert_eq!(
+            layout.path(&fields),
+            Path::new("out/npc_guard/1/armor_metal_0_1.obj")
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("out/npc_guard/1"),
+                Path::new("out/textures/a.png")
+            ),
+            "../../textures/a.png"
+        );
+        assert!(NameTemplate::parse("{actor}/{colour}").is_err());
+    }
+}
@@ -0,0 +1,591 @@
+//! Node.js addon over N-API, typed in `node/index.d.ts`.
+//!
+//! Build with `cargo build --release --no-default-features --features node` and copy
+//! `libtoslib.so` to `toslib.node`. The N-API symbols are resolved by the Node process
+//! that loads the addon. Failures are thrown as JavaScript `Error`s.
+
+use crate::error::Error;
+use crate::export::GltfOptions;
+use crate::ies::IESFile;
+use crate::ipf::IPFFile;
+use crate::tosreader::BinaryReader;
+use crate::xac::XACFile;
+use std::ffi::{CString, c_char, c_void};
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+type NapiEnv = *mut c_void;
+type NapiValue = *mut c_void;
+type NapiCallbackInfo = *mut c_void;
+type NapiStatus = i32;
+type NapiCallback = unsafe extern "C" fn(NapiEnv, NapiCallbackInfo) -> NapiValue;
+
+const NAPI_OK: NapiStatus = 0;
+
+unsafe extern "C" {
+    fn napi_create_function(
+        env: NapiEnv,
+        name: *const c_char,
+        length: usize,
+        cb: NapiCallback,
+        data: *mut c_void,
+        result: *mut NapiValue,
+    ) -> NapiStatus;
+    fn napi_set_named_property(
+        env: NapiEnv,
+        object: NapiValue,
+        name: *const c_char,
+        value: NapiValue,
+    ) -> NapiStatus;
+    fn napi_get_cb_info(
+        env: NapiEnv,
+        info: NapiCallbackInfo,
+        argc: *mut usize,
+        argv: *mut NapiValue,
+        this: *mut NapiValue,
+        data: *mut *mut c_void,
+    ) -> NapiStatus;
+    fn napi_get_value_string_utf8(
+        env: NapiEnv,
+        value: NapiValue,
+        buf: *mut c_char,
+        bufsize: usize,
+        result: *mut usize,
+    ) -> NapiStatus;
+    fn napi_create_string_utf8(
+        env: NapiEnv,
+        str: *const c_char,
+        length: usize,
+        result: *mut NapiValue,
+    ) -> NapiStatus;
+    fn napi_get_buffer_info(
+        env: NapiEnv,
+        value: NapiValue,
+        data: *mut *mut c_void,
+        length: *mut usize,
+    ) -> NapiStatus;
+    fn napi_create_buffer_copy(
+        env: NapiEnv,
+        length: usize,
+        data: *const c_void,
+        result_data: *mut *mut c_void,
+        result: *mut NapiValue,
+    ) -> NapiStatus;
+    fn napi_create_array_with_length(
+        env: NapiEnv,
+        length: usize,
+        result: *mut NapiValue,
+    ) -> NapiStatus;
+    fn napi_set_element(
+        env: NapiEnv,
+        object: NapiValue,
+        index: u32,
+        value: NapiValue,
+    ) -> NapiStatus;
+    fn napi_throw_error(env: NapiEnv, code: *const c_char, msg: *const c_char) -> NapiStatus;
+}
+
+/// A JavaScript function implemented in Rust. Errors are thrown to the caller.
+type Method = fn(Env, &[NapiValue]) -> Result<NapiValue, String>;
+
+const METHODS: [(&str, Method); 5] = [
+    ("ipfEntries", ipf_entries),
+    ("ipfRead", ipf_read),
+    ("iesToJson", ies_to_json),
+    ("xacToGltf", xac_to_gltf),
+    ("xacMeshesToJson", xac_meshes_to_json),
+];
+
+#[derive(Clone, Copy)]
+struct Env(NapiEnv);
+
+impl Env {
+    fn check(self, status: NapiStatus, call: &str) -> Result<(), String> {
+        if status == NAPI_OK {
+            Ok(())
+        } else {
+            Err(format!("{} failed with status {}", call, status))
+        }
+    }
+
+    fn arg(self, args: &[NapiValue], index: usize) -> Result<NapiValue, String> {
+        args.get(index)
+            .copied()
+            .ok_or_else(|| format!("missing argument {}", index + 1))
+    }
+
+    fn string_arg(self, args: &[NapiValue], index: usize) -> Result<String, String> {
+        let value = self.arg(args, index)?;
+        let mut len = 0;
+        let status =
+            unsafe { napi_get_value_string_utf8(self.0, value, ptr::null_mut(), 0, &mut len) };
+        self.check(status, "napi_get_value_string_utf8")
+            .map_err(|_| format!("argument {} must be a string", index + 1))?;
+        let mut bytes = vec![0u8; len + 1];
+        let status = unsafe {
+            napi_get_value_string_utf8(
+                self.0,
+                value,
+                bytes.as_mut_ptr() as *mut c_char,
+                bytes.len(),
+                &mut len,
+            )
+        };
+        self.check(status, "napi_get_value_string_utf8")?;
+        bytes.truncate(len);
+        String::from_utf8(bytes).map_err(|err| err.to_string())
+    }
+
+    /// Contents of a `Buffer` argument, valid for the duration of the call.
+    fn buffer_arg<'a>(self, args: &[NapiValue], index: usize) -> Result<&'a [u8], String> {
+        let value = self.arg(args, index)?;
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let status = unsafe { napi_get_buffer_info(self.0, value, &mut data, &mut len) };
+        self.check(status, "napi_get_buffer_info")
+            .map_err(|_| format!("argument {} must be a Buffer", index + 1))?;
+        if len == 0 {
+            return Ok(&[]);
+        }
+        Ok(unsafe { std::slice::from_raw_parts(data as *const u8, len) })
+    }
+
+    fn string(self, value: &str) -> Result<NapiValue, String> {
+        let mut result = ptr::null_mut();
+        let status = unsafe {
+            napi_create_string_utf8(
+                self.0,
+                value.as_ptr() as *const c_char,
+                value.len(),
+                &mut result,
+            )
+        };
+        self.check(status, "napi_create_string_utf8")?;
+        Ok(result)
+    }
+
+    fn buffer(self, value: &[u8]) -> Result<NapiValue, String> {
+        let mut result = ptr::null_mut();
+        let status = unsafe {
+            napi_create_buffer_copy(
+                self.0,
+                value.len(),
+                value.as_ptr() as *const c_void,
+                ptr::null_mut(),
+                &mut result,
+            )
+        };
+        self.check(status, "napi_create_buffer_copy")?;
+        Ok(result)
+    }
+
+    fn string_array<S: AsRef<str>>(self, values: &[S]) -> Result<NapiValue, String> {
+        let mut array = ptr::null_mut();
+        let status = unsafe { napi_create_array_with_length(self.0, values.len(), &mut array) };
+        self.check(status, "napi_create_array_with_length")?;
+        for (index, value) in values.iter().enumerate() {
+            let value = self.string(value.as_ref())?;
+            let status = unsafe { napi_set_element(self.0, array, index as u32, value) };
+            self.check(status, "napi_set_element")?;
+        }
+        Ok(array)
+    }
+
+    fn throw(self, message: &str) {
+        let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+        unsafe { napi_throw_error(self.0, ptr::null(), message.as_ptr()) };
+    }
+}
+
+/// Entry point of every exported function: `data` carries the `Method` to run.
+unsafe extern "C" fn call(env: NapiEnv, info: NapiCallbackInfo) -> NapiValue {
+    let env = Env(env);
+    let mut argv = [ptr::null_mut(); 4];
+    let mut argc = argv.len();
+    let mut data = ptr::null_mut();
+    let status = unsafe {
+        napi_get_cb_info(
+            env.0,
+            info,
+            &mut argc,
+            argv.as_mut_ptr(),
+            ptr::null_mut(),
+            &mut data,
+        )
+    };
+    let result = env.check(status, "napi_get_cb_info").and_then(|()| {
+        let method: Method = unsafe { std::mem::transmute::<*mut c_void, Method>(data) };
+        let args = &argv[..argc.min(argv.len())];
+        panic::catch_unwind(AssertUnwindSafe(|| method(env, args)))
+            .unwrap_or_else(|_| Err("internal error".to_string()))
+    });
+    result.unwrap_or_else(|message| {
+        env.throw(&message);
+        ptr::null_mut()
+    })
+}
+
+/// Called by Node when the addon is loaded.
+///
+/// # Safety
+/// Only to be called by the Node.js runtime.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn napi_register_module_v1(env: NapiEnv, exports: NapiValue) -> NapiValue {
+    let env = Env(env);
+    for (name, method) in METHODS {
+        let name = CString::new(name).unwrap();
+        let mut function = ptr::null_mut();
+        let status = unsafe {
+            napi_create_function(
+                env.0,
+                name.as_ptr(),
+                usize::MAX,
+                call,
+                method as *mut c_void,
+                &mut function,
+            )
+        };
+        let status = if status == NAPI_OK {
+            unsafe { napi_set_named_property(env.0, exports, name.as_ptr(), function) }
+        } else {
+            status
+        };
+        if let Err(message) = env.check(status, "registering toslib") {
+            env.throw(&message);
+            return ptr::null_mut();
+        }
+    }
+    exports
+}
+
+/// `ipfEntries(archive: string): string[]`
+fn ipf_entries(env: Env, args: &[NapiValue]) -> Result<NapiValue, String> {
+    let archive = env.string_arg(args, 0)?;
+    let ipf = IPFFile::load_from_file(&archive).map_err(|err| err.to_string())?;
+    let names: Vec<_> = ipf
+        .file_table()
+        .iter()
+        .map(|entry| entry.directory_name_str())
+        .collect();
+    env.string_array(&names)
+}
+
+/// `ipfRead(archive: string, entry: string): Buffer`
+fn ipf_read(env: Env, args: &[NapiValue]) -> Result<NapiValue, String> {
+    let archive = env.string_arg(args, 0)?;
+    let path = env.string_arg(args, 1)?;
+    let read = || -> crate::Result<Option<Vec<u8>>> {
+        let ipf = IPFFile::load_from_file(&archive)?;
+        let Some(entry) = ipf
+            .file_table()
+            .iter()
+            .find(|entry| entry.directory_name_str() == path)
+        else {
+            return Ok(None);
+        };
+        let file = File::open(&archive).map_err(|err| Error::from(err).in_file(&archive))?;
+        entry
+            .extract(&mut BinaryReader::new(BufReader::new(file)))
+            .map(Some)
+    };
+    match read().map_err(|err| err.to_string())? {
+        Some(data) => env.buffer(&data),
+        None => Err(format!("{}: no entry {}", archive, path)),
+    }
+}
+
+/// `iesToJson(data: Buffer): string`
+fn ies_to_json(env: Env, args: &[NapiValue]) -> Result<NapiValue, String> {
+    let data = env.buffer_arg(args, 0)?;
+    let ies = IESFile::load_from_slice(data).map_err(|err| err.to_string())?;
+    env.string(&ies.to_json().map_err(|err| err.to_string())?)
+}
+
+/// `xacToGltf(data: Buffer): Buffer`
+fn xac_to_gltf(env: Env, args: &[NapiValue]) -> Result<NapiValue, String> {
+    let data = env.buffer_arg(args, 0)?;
+    let glb = XACFile::load_from_slice(data)
+        .and_then(|xac| xac.to_glb(&GltfOptions::default()))
+        .map_err(|err| err.to_string())?;
+    env.buffer(&glb)
+}
+
+/// `xacMeshesToJson(data: Buffer): string`
+fn xac_meshes_to_json(env: Env, args: &[NapiValue]) -> Result<NapiValue, String> {
+    let data = env.buffer_arg(args, 0)?;
+    let meshes = XACFile::load_from_slice(data)
+        .and_then(|xac| xac.export_all_meshes_into_struct())
+        .map_err(|err| err.to_string())?;
+    env.string(&serde_json::to_string(&meshes).map_err(|err| err.to_string())?)
+}
+
+#[cfg(test)]
+mod tests {
+    //! Stand-ins for the N-API functions Node provides, enough to run the methods.
+
+    use super::*;
+    use crate::ipf::IPFWriter;
+    use std::cell::RefCell;
+    use std::ffi::CStr;
+
+    const NAPI_INVALID_ARG: NapiStatus = 1;
+
+    /// A JavaScript value, leaked for the rest of the test run.
+    enum Value {
+        String(String),
+        Buffer(Vec<u8>),
+        Array(RefCell<Vec<NapiValue>>),
+        Object(RefCell<Vec<(String, NapiValue)>>),
+        Function(*mut c_void),
+    }
+
+    /// Arguments of a call, passed as its `NapiCallbackInfo`.
+    struct CallInfo {
+        args: Vec<NapiValue>,
+        data: *mut c_void,
+    }
+
+    thread_local! {
+        static THROWN: RefCell<Option<String>> = const { RefCell::new(None) };
+    }
+
+    fn value(value: Value) -> NapiValue {
+        Box::into_raw(Box::new(value)) as NapiValue
+    }
+
+    fn get<'a>(value: NapiValue) -> &'a Value {
+        unsafe { &*(value as *const Value) }
+    }
+
+    fn string(value: NapiValue) -> String {
+        match get(value) {
+            Value::String(string) => string.clone(),
+            _ => panic!("not a string"),
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_create_function(
+        _env: NapiEnv,
+        _name: *const c_char,
+        _length: usize,
+        _cb: NapiCallback,
+        data: *mut c_void,
+        result: *mut NapiValue,
+    ) -> NapiStatus {
+        unsafe { *result = value(Value::Function(data)) };
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_set_named_property(
+        _env: NapiEnv,
+        object: NapiValue,
+        name: *const c_char,
+        property: NapiValue,
+    ) -> NapiStatus {
+        let Value::Object(properties) = get(object) else {
+            return NAPI_INVALID_ARG;
+        };
+        let name = unsafe { CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned();
+        properties.borrow_mut().push((name, property));
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_get_cb_info(
+        _env: NapiEnv,
+        info: NapiCallbackInfo,
+        argc: *mut usize,
+        argv: *mut NapiValue,
+        _this: *mut NapiValue,
+        data: *mut *mut c_void,
+    ) -> NapiStatus {
+        let info = unsafe { &*(info as *const CallInfo) };
+        unsafe {
+            let count = info.args.len().min(*argc);
+            ptr::copy_nonoverlapping(info.args.as_ptr(), argv, count);
+            *argc = info.args.len();
+            *data = info.data;
+        }
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_get_value_string_utf8(
+        _env: NapiEnv,
+        value: NapiValue,
+        buf: *mut c_char,
+        bufsize: usize,
+        result: *mut usize,
+    ) -> NapiStatus {
+        let Value::String(string) = get(value) else {
+            return NAPI_INVALID_ARG;
+        };
+        unsafe {
+            if buf.is_null() {
+                *result = string.len();
+            } else {
+                let count = string.len().min(bufsize - 1);
+                ptr::copy_nonoverlapping(string.as_ptr(), buf as *mut u8, count);
+                *buf.add(count) = 0;
+                *result = count;
+            }
+        }
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_create_string_utf8(
+        _env: NapiEnv,
+        str: *const c_char,
+        length: usize,
+        result: *mut NapiValue,
+    ) -> NapiStatus {
+        let bytes = unsafe { std::slice::from_raw_parts(str as *const u8, length) };
+        let string = String::from_utf8_lossy(bytes).into_owned();
+        unsafe { *result = value(Value::String(string)) };
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_get_buffer_info(
+        _env: NapiEnv,
+        value: NapiValue,
+        data: *mut *mut c_void,
+        length: *mut usize,
+    ) -> NapiStatus {
+        let Value::Buffer(buffer) = get(value) else {
+            return NAPI_INVALID_ARG;
+        };
+        unsafe {
+            *data = buffer.as_ptr() as *mut c_void;
+            *length = buffer.len();
+        }
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_create_buffer_copy(
+        _env: NapiEnv,
+        length: usize,
+        data: *const c_void,
+        _result_data: *mut *mut c_void,
+        result: *mut NapiValue,
+    ) -> NapiStatus {
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, length) };
+        unsafe { *result = value(Value::Buffer(bytes.to_vec())) };
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_create_array_with_length(
+        _env: NapiEnv,
+        length: usize,
+        result: *mut NapiValue,
+    ) -> NapiStatus {
+        let elements = vec![ptr::null_mut(); length];
+        unsafe { *result = value(Value::Array(RefCell::new(elements))) };
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_set_element(
+        _env: NapiEnv,
+        object: NapiValue,
+        index: u32,
+        element: NapiValue,
+    ) -> NapiStatus {
+        let Value::Array(elements) = get(object) else {
+            return NAPI_INVALID_ARG;
+        };
+        let mut elements = elements.borrow_mut();
+        let index = index as usize;
+        if elements.len() <= index {
+            elements.resize(index + 1, ptr::null_mut());
+        }
+        elements[index] = element;
+        NAPI_OK
+    }
+
+    #[unsafe(no_mangle)]
+    unsafe extern "C" fn napi_throw_error(
+        _env: NapiEnv,
+        _code: *const c_char,
+        msg: *const c_char,
+    ) -> NapiStatus {
+        let message = unsafe { CStr::from_ptr(msg) }
+            .to_string_lossy()
+            .into_owned();
+        THROWN.with(|thrown| *thrown.borrow_mut() = Some(message));
+        NAPI_OK
+    }
+
+    #[test]
+    fn methods_read_archives_and_throw_failures() {
+        let path = std::env::temp_dir().join(format!("toslib-node-{}.ipf", std::process::id()));
+        let mut writer = IPFWriter::new();
+        writer.add_file("xml.ipf", "xml/a.xml", b"<a/>".to_vec());
+        writer.add_file("xml.ipf", "xml/b.xml", b"hello".to_vec());
+        writer.save_to_file(&path).unwrap();
+        let env = Env(ptr::null_mut());
+        let archive = value(Value::String(path.to_string_lossy().into_owned()));
+
+        let entries = ipf_entries(env, &[archive]).unwrap();
+        let Value::Array(elements) = get(entries) else {
+            panic!("ipfEntries did not return an array");
+        };
+        let names: Vec<String> = elements.borrow().iter().map(|&e| string(e)).collect();
+        assert_eq!(names, ["xml/a.xml", "xml/b.xml"]);
+
+        let entry = value(Value::String("xml/b.xml".into()));
+        let read = ipf_read(env, &[archive, entry]).unwrap();
+        assert!(matches!(get(read), Value::Buffer(data) if data == b"hello"));
+
+        assert_eq!(ipf_read(env, &[archive]).unwrap_err(), "missing argument 2");
+        let not_a_buffer = ies_to_json(env, &[archive]).unwrap_err();
+        assert_eq!(not_a_buffer, "argument 1 must be a Buffer");
+        let empty = value(Value::Buffer(b"XAC \x01\x00\x00\x00".to_vec()));
+        assert_eq!(string(xac_meshes_to_json(env, &[empty]).unwrap()), "[]");
+        let glb = XACFile::load_from_slice(b"XAC \x01\x00\x00\x00")
+            .unwrap()
+            .to_glb(&GltfOptions::default())
+            .unwrap();
+        let converted = xac_to_gltf(env, &[empty]).unwrap();
+        assert!(
+            matches!(get(converted), Value::Buffer(data) if data[..4] == *b"glTF" && *data == glb)
+        );
+        assert_eq!(xac_to_gltf(env, &[]).unwrap_err(), "missing argument 1");
+
+        // Registered functions carry their method, and failures are thrown
+        let exports = value(Value::Object(RefCell::new(Vec::new())));
+        assert_eq!(unsafe { napi_register_module_v1(env.0, exports) }, exports);
+        let Value::Object(properties) = get(exports) else {
+            unreachable!()
+        };
+        let names: Vec<String> = properties
+            .borrow()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        assert_eq!(names, METHODS.map(|(name, _)| name));
+        let Value::Function(data) = get(properties.borrow()[1].1) else {
+            panic!("ipfRead is not a function");
+        };
+        let missing = value(Value::String("xml/c.xml".into()));
+        let mut info = CallInfo {
+            args: vec![archive, missing],
+            data: *data,
+        };
+        let result = unsafe { call(env.0, &mut info as *mut CallInfo as NapiCallbackInfo) };
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_null());
+        let thrown = THROWN.with(|thrown| thrown.borrow_mut().take()).unwrap();
+        assert_eq!(thrown, format!("{}: no entry xml/c.xml", path.display()));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::Result;
+use crate::ipf::IpfCrypto;
+use crate::text::TextDecoding;
+use crate::warning::ParseWarning;
+use crate::xac::ChunkSet;
+
+/// Controls how the IPF, IES and XAC loaders react to data they do not fully understand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail on any chunk-size mismatch, unknown version or trailing bytes. When unset
+    /// the loaders recover and record a warning on the parsed file instead.
+    pub strict: bool,
+    /// Policy for every byte to string conversion: XAC names, IES cells and IPF paths.
+    pub text: TextDecoding,
+    /// What to do with chunks that may only appear once but repeat, and with chunks
+    /// that reference data the file does not contain.
+    pub conflicts: ConflictPolicy,
+    /// Decode independent XAC chunk bodies and the rows of large IES tables on the rayon
+    /// thread pool. The data is read into memory first; the parsed result is identical
+    /// to a sequential load.
+    pub parallel: bool,
+    /// XAC chunk kinds to decode; the rest are skipped by their size. Skinning chunks
+    /// are sized by their mesh, so keep meshes when decoding skinning.
+    pub chunks: ChunkSet,
+    /// Encryption of IPF entry payloads; the client cipher unless the archive comes
+    /// from a build with other keys or none.
+    pub crypto: IpfCrypto,
+}
+
+/// Resolution of duplicate or conflicting data. Every conflict is also recorded as a
+/// warning unless the policy is `Error`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the first occurrence and drop later ones.
+    #[default]
+    FirstWins,
+    /// Replace earlier occurrences with the last one.
+    LastWins,
+    /// Fail the parse.
+    Error,
+}
+
+impl ParseOptions {
+    pub fn strict() -> Self {
+        ParseOptions {
+            strict: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn lenient() -> Self {
+        ParseOptions {
+            strict: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_text(mut self, text: TextDecoding) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn with_conflicts(mut self, conflicts: ConflictPolicy) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn with_chunks(mut self, chunks: ChunkSet) -> Self {
+        self.chunks = chunks;
+        self
+    }
+
+    pub fn with_crypto(mut self, crypto: IpfCrypto) -> Self {
+        self.crypto = crypto;
+        self
+    }
+
+    /// Fails with `warning` in strict mode, otherwise records it and carries on.
+    pub(crate) fn report(
+        &self,
+        warnings: &mut Vec<ParseWarning>,
+        warning: ParseWarning,
+    ) -> Result<()> {
+        if self.strict {
+            return Err(warning.into());
+        }
+        warnings.push(warning);
+        Ok(())
+    }
+}
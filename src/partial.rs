@@ -0,0 +1,53 @@
+use crate::error::{Error, Result};
+use crate::options::ParseOptions;
+
+/// Outcome of a best-effort load. Truncated input still yields everything that was
+/// parsed before the end of the data, together with the error that stopped parsing.
+#[derive(Debug)]
+pub enum Parsed<T> {
+    Complete(T),
+    Partial { value: T, truncation: Error },
+}
+
+impl<T> Parsed<T> {
+    /// Wraps `value` according to how parsing it ended. Truncation keeps the value
+    /// unless the options are strict, any other error is returned as is.
+    pub(crate) fn from_outcome(
+        value: T,
+        outcome: Result<()>,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        match outcome {
+            Ok(()) => Ok(Parsed::Complete(value)),
+            Err(err) if err.is_truncation() && !options.strict => Ok(Parsed::Partial {
+                value,
+                truncation: err,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Parsed::Complete(_))
+    }
+
+    pub fn value(&self) -> &T {
+        match self {
+            Parsed::Complete(value) | Parsed::Partial { value, .. } => value,
+        }
+    }
+
+    pub fn into_value(self) -> T {
+        match self {
+            Parsed::Complete(value) | Parsed::Partial { value, .. } => value,
+        }
+    }
+
+    /// The error that cut parsing short, if the input was truncated.
+    pub fn truncation(&self) -> Option<&Error> {
+        match self {
+            Parsed::Complete(_) => None,
+            Parsed::Partial { truncation, .. } => Some(truncation),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::error::Result;
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Parser for an XAC chunk kind or version toslib has no decoder for, see
+/// `register_chunk_handler`.
+pub trait ChunkHandler: Send + Sync {
+    /// Parses one chunk body; `body` is exactly the chunk's declared size. An error
+    /// fails the chunk like a built-in decoder would.
+    fn parse(&self, chunk_id: u32, version: u32, body: &[u8])
+    -> Result<Arc<dyn Any + Send + Sync>>;
+}
+
+/// Result of a registered handler, attached to the parsed `XACFile`.
+#[derive(Debug, Clone)]
+pub struct CustomChunk {
+    pub chunk_id: u32,
+    pub version: u32,
+    /// Offset of the chunk header in the file
+    pub offset: u64,
+    pub value: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomChunk {
+    /// The parsed value, when the handler produced a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+/// Returned by `register_chunk_handler`, for `unregister_chunk_handler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registration(u64);
+
+struct Entry {
+    id: u64,
+    chunk_id: u32,
+    version: Option<u32>,
+    handler: Arc<dyn ChunkHandler>,
+}
+
+static HANDLERS: RwLock<Vec<Entry>> = RwLock::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Routes chunks with `chunk_id` to `handler` for the whole process, for one
+/// `version` or for every version when `None`. Only chunks the built-in decoders
+/// would report as unknown or of an unsupported version reach a handler. A handler
+/// for the exact version wins over one for every version; among equals the latest
+/// registration wins.
+pub fn register_chunk_handler(
+    chunk_id: u32,
+    version: Option<u32>,
+    handler: Arc<dyn ChunkHandler>,
+) -> Registration {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    HANDLERS.write().unwrap().push(Entry {
+        id,
+        chunk_id,
+        version,
+        handler,
+    });
+    Registration(id)
+}
+
+pub fn unregister_chunk_handler(registration: Registration) {
+    HANDLERS
+        .write()
+        .unwrap()
+        .retain(|entry| entry.id != registration.0);
+}
+
+pub(crate) fn chunk_handler(chunk_id: u32, version: u32) -> Option<Arc<dyn ChunkHandler>> {
+    let handlers = HANDLERS.read().unwrap();
+    let find = |exact: bool| {
+        handlers.iter().rev().find(|entry| {
+            entry.chunk_id == chunk_id
+                && match entry.version {
+                    Some(v) => exact && v == version,
+                    None => !exact,
+                }
+        })
+    };
+    find(true)
+        .or_else(|| find(false))
+        .map(|entry| entry.handler.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::options::ParseOptions;
+    use crate::xac::XACFile;
+
+    // Ids no other test uses, as the registry is shared by the whole process
+    const TAGS: u32 = 0x7A00;
+    const BROKEN: u32 = 0x7A01;
+
+    struct Tags;
+
+    impl ChunkHandler for Tags {
+        fn parse(
+            &self,
+            _chunk_id: u32,
+            version: u32,
+            body: &[u8],
+        ) -> Result<Arc<dyn Any + Send + Sync>> {
+            let text = String::from_utf8_lossy(body);
+            Ok(Arc::new(format!("v{} {}", version, text)))
+        }
+    }
+
+    struct Broken;
+
+    impl ChunkHandler for Broken {
+        fn parse(&self, _: u32, _: u32, _: &[u8]) -> Result<Arc<dyn Any + Send + Sync>> {
+            Err(Error::Xac("bad tag list".to_string()))
+        }
+    }
+
+    fn model(chunks: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        for (chunk_id, version, body) in chunks {
+            bytes.extend_from_slice(&chunk_id.to_le_bytes());
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&version.to_le_bytes());
+            bytes.extend_from_slice(body);
+        }
+        bytes
+    }
+
+    #[test]
+    fn unknown_chunks_reach_registered_handlers() {
+        let bytes = model(&[(TAGS, 1, b"hat"), (TAGS, 2, b"cape")]);
+        let before = XACFile::load_from_slice(&bytes).unwrap();
+        assert!(before.custom_chunks().is_empty());
+        assert_eq!(before.warnings().len(), 2);
+
+        let any_version = register_chunk_handler(TAGS, None, Arc::new(Tags));
+        let broken = register_chunk_handler(BROKEN, Some(1), Arc::new(Broken));
+        let xac = XACFile::load_from_slice_with_options(
+            &bytes,
+            &ParseOptions::strict().with_parallel(true),
+        )
+        .unwrap();
+        let failed = XACFile::load_from_slice(&model(&[(BROKEN, 1, b"x")]));
+        unregister_chunk_handler(any_version);
+        unregister_chunk_handler(broken);
+
+        let values: Vec<&str> = xac
+            .custom_chunks()
+            .iter()
+            .filter_map(|chunk| chunk.downcast_ref::<String>())
+            .map(String::as_str)
+            .collect();
+        assert_eq!(values, ["v1 hat", "v2 cape"]);
+        assert_eq!(xac.custom_chunks()[1].offset, 23);
+        assert_eq!(xac.custom_chunk::<String>(TAGS).unwrap(), "v1 hat");
+        let err = failed.unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.chunk), Some((BROKEN, 1)));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::ipf::IPFFileTable;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How far a bulk operation over archive entries has come.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub entries_done: usize,
+    pub entries_total: usize,
+    /// Uncompressed bytes of the entries done so far
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+/// Receiver of the progress of `IPFFile::extract_all_with_progress` and
+/// `IPFWriter::write_to_with_progress`, which can also stop the operation.
+///
+/// Calls come from the worker threads, in completion order. Closures taking the
+/// entry, the entries done and the total are reporters that never cancel.
+pub trait ProgressReporter: Sync {
+    /// Called after each entry.
+    fn progress(&self, entry: &IPFFileTable, progress: Progress);
+
+    /// Polled before each entry. Once it returns true no further entry is started,
+    /// and the operation fails with `Error::Cancelled` when the running ones finish.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl<F> ProgressReporter for F
+where
+    F: Fn(&IPFFileTable, usize, usize) + Sync,
+{
+    fn progress(&self, entry: &IPFFileTable, progress: Progress) {
+        self(entry, progress.entries_done, progress.entries_total)
+    }
+}
+
+/// Flag for stopping an operation from another thread. Clones share the flag, so
+/// keep one and hand a clone to the reporter.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reports nothing and cancels when the token is cancelled.
+impl ProgressReporter for CancelToken {
+    fn progress(&self, _entry: &IPFFileTable, _progress: Progress) {}
+
+    fn is_cancelled(&self) -> bool {
+        CancelToken::is_cancelled(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::ipf::{ExtractOptions, IPFFile, IPFWriter};
+    use std::sync::Mutex;
+
+    /// Records the byte counts and cancels after `limit` entries.
+    struct Recorder {
+        token: CancelToken,
+        limit: usize,
+        seen: Mutex<Vec<Progress>>,
+    }
+
+    impl ProgressReporter for Recorder {
+        fn progress(&self, _entry: &IPFFileTable, progress: Progress) {
+            let mut seen = self.seen.lock().unwrap();
+            seen.push(progress);
+            if seen.len() == self.limit {
+                self.token.cancel();
+            }
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.token.is_cancelled()
+        }
+    }
+
+    fn recorder(limit: usize) -> Recorder {
+        Recorder {
+            token: CancelToken::new(),
+            limit,
+            seen: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[test]
+    fn reporters_see_bytes_and_can_cancel() {
+        let dir = std::env::temp_dir().join(format!("toslib-progress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut writer = IPFWriter::new();
+        for i in 0..8 {
+            writer.add_file("xml.ipf", &format!("{}.xml", i), vec![b'x'; 100]);
+        }
+        let packed = recorder(0);
+        let archive = dir.join("xml.ipf");
+        writer
+            .save_to_file_with_progress(&archive, &packed)
+            .unwrap();
+
+        let ipf = IPFFile::load_from_file(&archive).unwrap();
+        let options = ExtractOptions::default().with_threads(1);
+        let full = recorder(0);
+        let extracted = ipf.extract_all_with_progress(dir.join("all"), &options, &full);
+        let stopped = recorder(3);
+        let cancelled = ipf.extract_all_with_progress(dir.join("some"), &options, &stopped);
+        let token = CancelToken::new();
+        token.cancel();
+        let cancelled_pack = writer.save_to_file_with_progress(dir.join("none.ipf"), &token);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let last = *packed.seen.lock().unwrap().last().unwrap();
+        assert_eq!(
+            (last.entries_done, last.bytes_done, last.bytes_total),
+            (8, 800, 800)
+        );
+        assert_eq!(extracted.unwrap(), 8);
+        assert_eq!(full.seen.lock().unwrap().last(), Some(&last));
+        assert_eq!(stopped.seen.lock().unwrap().len(), 3);
+        assert!(matches!(cancelled.unwrap_err().root(), Error::Cancelled));
+        assert!(matches!(
+            cancelled_pack.unwrap_err().root(),
+            Error::Cancelled
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::error::{Error, Result};
+use crate::events::{Operation, Task};
+use crate::game::GameData;
+use crate::ies::IESFile;
+use crate::ipf::{IPFEntryKind, IPFWriter, crc32};
+use crate::search::parse_xml;
+use crate::xac::XACFile;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One edited file and the entry it replaces or adds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModFile {
+    pub source: PathBuf,
+    /// `GameData` key of the entry, `container/path`
+    pub target: String,
+}
+
+/// How an entry of the built patch relates to the client it was built against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    /// Identical to the client's copy and left out of the patch
+    Unchanged,
+}
+
+/// Outcome of `ModProject::build`.
+#[derive(Debug, Clone)]
+pub struct BuildReport {
+    pub version_to_patch: u32,
+    pub new_version: u32,
+    /// Every declared file in declaration order, including unchanged ones.
+    pub changes: Vec<(String, Change)>,
+}
+
+impl BuildReport {
+    /// Number of entries written to the patch.
+    pub fn packed(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|(_, change)| *change != Change::Unchanged)
+            .count()
+    }
+
+    /// Markdown summary of the patch for release notes.
+    pub fn changelog(&self, name: &str) -> String {
+        let mut text = format!(
+            "# {} ({} -> {})\n",
+            name, self.version_to_patch, self.new_version
+        );
+        for (heading, kind) in [("Added", Change::Added), ("Modified", Change::Modified)] {
+            let paths: Vec<&str> = self
+                .changes
+                .iter()
+                .filter(|(_, change)| *change == kind)
+                .map(|(path, _)| path.as_str())
+                .collect();
+            if !paths.is_empty() {
+                let _ = write!(text, "\n## {}\n\n", heading);
+                for path in paths {
+                    let _ = writeln!(text, "- {}", path);
+                }
+            }
+        }
+        text
+    }
+}
+
+/// The path from edited files to an installable patch archive.
+///
+/// Declare files with their target entries, then `build` validates each one with the
+/// parser for its type (IES, XAC, XML), works out the patch versions from the client
+/// and writes the archive.
+#[derive(Debug, Clone)]
+pub struct ModProject {
+    name: String,
+    files: Vec<ModFile>,
+    versions: Option<(u32, u32)>,
+    compression_level: u32,
+}
+
+impl ModProject {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            files: Vec::new(),
+            versions: None,
+            compression_level: 6,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn files(&self) -> &[ModFile] {
+        &self.files
+    }
+
+    /// Fixes the footer versions instead of deriving them from the client.
+    pub fn with_versions(mut self, version_to_patch: u32, new_version: u32) -> Self {
+        self.versions = Some((version_to_patch, new_version));
+        self
+    }
+
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.compression_level = level.min(9);
+        self
+    }
+
+    /// Declares `source` as the new content of `target` (`container/path`).
+    pub fn add_file<P: AsRef<Path>>(&mut self, source: P, target: &str) -> &mut Self {
+        self.files.push(ModFile {
+            source: source.as_ref().to_path_buf(),
+            target: target.to_string(),
+        });
+        self
+    }
+
+    /// Declares every file below `dir` as an entry of `container`, at its path
+    /// relative to `dir`.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, container: &str, dir: P) -> Result<&mut Self> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        IPFWriter::collect_files(dir, &mut files)?;
+        files.sort();
+        for file in files {
+            let relative = file
+                .strip_prefix(dir)
+                .map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let target = format!("{}/{}", container, relative);
+            self.add_file(file, &target);
+        }
+        Ok(self)
+    }
+
+    /// Reads every declared file and checks it parses as its target type. Returns
+    /// the problems found, each with the target entry attached; empty when the
+    /// project is ready to build.
+    pub fn validate(&self) -> Vec<Error> {
+        self.files
+            .iter()
+            .filter_map(|file| self.load(file).err())
+            .collect()
+    }
+
+    /// Validates the files and writes the patch to `out`.
+    ///
+    /// With `client`, the patch applies on top of the client's detected version and
+    /// files identical to the client's copy are left out. Without it, the versions
+    /// set with `with_versions` are used (`0 -> 1` when unset) and every file is
+    /// packed as added.
+    pub fn build<P: AsRef<Path>>(&self, out: P, client: Option<&GameData>) -> Result<BuildReport> {
+        let progress = Task::start(Operation::Build, &self.name, Some(self.files.len() as u64));
+        progress.finish(self.build_with(out.as_ref(), client, &progress))
+    }
+
+    fn build_with(
+        &self,
+        out: &Path,
+        client: Option<&GameData>,
+        progress: &Task,
+    ) -> Result<BuildReport> {
+        let (version_to_patch, new_version) = match (self.versions, client) {
+            (Some(versions), _) => versions,
+            (None, Some(client)) => {
+                let version = client.detect_version()?.version;
+                (version, version + 1)
+            }
+            (None, None) => (0, 1),
+        };
+
+        let mut writer = IPFWriter::new();
+        writer
+            .versions(version_to_patch, new_version)
+            .compression_level(self.compression_level);
+        let mut changes = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let data = self.load(file)?;
+            let (container, path) = split_target(&file.target)?;
+            let change = match client.and_then(|client| client.entry(&file.target)) {
+                None => Change::Added,
+                Some((_, entry))
+                    if entry.crc32() == crc32(&data)
+                        && entry.file_size_uncompressed() as usize == data.len() =>
+                {
+                    Change::Unchanged
+                }
+                Some(_) => Change::Modified,
+            };
+            if change != Change::Unchanged {
+                writer.add_file(container, path, data);
+            }
+            changes.push((file.target.clone(), change));
+            progress.progress(changes.len() as u64);
+        }
+        writer.save_to_file(out)?;
+
+        Ok(BuildReport {
+            version_to_patch,
+            new_version,
+            changes,
+        })
+    }
+
+    /// Reads a declared file and runs the parser for its target type over it.
+    fn load(&self, file: &ModFile) -> Result<Vec<u8>> {
+        let check = || {
+            split_target(&file.target)?;
+            let data = std::fs::read(&file.source)
+                .map_err(|err| Error::from(err).in_file(&file.source))?;
+            match IPFEntryKind::from_path(&file.target) {
+                IPFEntryKind::Table => drop(IESFile::load_from_slice(&data)?),
+                IPFEntryKind::Model => drop(XACFile::load_from_slice(&data)?),
+                IPFEntryKind::Xml => drop(parse_xml(&data)?),
+                _ => {}
+            }
+            Ok(data)
+        };
+        check().map_err(|err: Error| err.in_entry(&file.target))
+    }
+}
+
+fn split_target(target: &str) -> Result<(&str, &str)> {
+    target
+        .split_once('/')
+        .filter(|(container, path)| !container.is_empty() && !path.is_empty())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("target '{}' is not of the form container/path", target),
+            )
+            .into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFFile;
+
+    #[test]
+    fn builds_patch_of_changed_files_on_client_version() {
+        let dir = std::env::temp_dir().join(format!("toslib-project-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("client").join("data")).unwrap();
+        let mut writer = IPFWriter::new();
+        writer.versions(0, 120);
+        writer.add_file("xml.ipf", "same.xml", b"<a/>".to_vec());
+        writer.add_file("xml.ipf", "edit.xml", b"<a/>".to_vec());
+        writer
+            .save_to_file(dir.join("client").join("data").join("xml.ipf"))
+            .unwrap();
+        let client = GameData::open(dir.join("client")).unwrap();
+
+        let src = dir.join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("same.xml"), "<a/>").unwrap();
+        std::fs::write(src.join("edit.xml"), "<a x='1'/>").unwrap();
+        std::fs::write(src.join("new.xml"), "<b/>").unwrap();
+        std::fs::write(src.join("broken.xml"), "<b>").unwrap();
+
+        let mut project = ModProject::new("Test mod");
+        project.add_directory("xml.ipf", &src).unwrap();
+        let problems = project.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            problems[0].context().and_then(|c| c.entry.as_deref()),
+            Some("xml.ipf/broken.xml")
+        );
+        assert!(project.build(dir.join("bad.ipf"), Some(&client)).is_err());
+
+        std::fs::remove_file(src.join("broken.xml")).unwrap();
+        let mut project = ModProject::new("Test mod");
+        project.add_directory("xml.ipf", &src).unwrap();
+        let out = dir.join("patch.ipf");
+        let report = project.build(&out, Some(&client)).unwrap();
+        let patch = IPFFile::load_from_file(&out).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((report.version_to_patch, report.new_version), (120, 121));
+        assert_eq!(report.packed(), 2);
+        assert_eq!(patch.footer().new_version(), 121);
+        assert_eq!(
+            report.changelog(project.name()),
+            "# Test mod (120 -> 121)\n\n## Added\n\n- xml.ipf/new.xml\n\n## Modified\n\n- xml.ipf/edit.xml\n"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::ies::{IESFile, IESRowView, IESValue};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Test applied to one cell by `IESQuery::filter`, built with `eq`, `lt`, `contains`
+/// and friends.
+///
+/// Numbers compare by value whether stored whole or fractional, strings compare
+/// bytewise, and an empty cell only equals `IESValue::Null`. A number never equals or
+/// orders against a string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    op: Op,
+    value: IESValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+pub fn eq(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Eq, value)
+}
+
+pub fn ne(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Ne, value)
+}
+
+pub fn lt(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Lt, value)
+}
+
+pub fn le(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Le, value)
+}
+
+pub fn gt(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Gt, value)
+}
+
+pub fn ge(value: impl Into<IESValue>) -> Predicate {
+    Predicate::new(Op::Ge, value)
+}
+
+/// String cells containing `text`, case sensitive.
+pub fn contains(text: &str) -> Predicate {
+    Predicate::new(Op::Contains, text)
+}
+
+/// Empty cells.
+pub fn is_null() -> Predicate {
+    Predicate::new(Op::Eq, IESValue::Null)
+}
+
+impl Predicate {
+    fn new(op: Op, value: impl Into<IESValue>) -> Self {
+        Self {
+            op,
+            value: value.into(),
+        }
+    }
+
+    pub fn matches(&self, cell: &IESValue) -> bool {
+        let ordering = compare(cell, &self.value);
+        match self.op {
+            Op::Eq => ordering == Some(Ordering::Equal),
+            Op::Ne => ordering != Some(Ordering::Equal),
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            Op::Contains => match (cell, &self.value) {
+                (IESValue::Str(cell), IESValue::Str(text)) => cell.contains(text.as_str()),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn compare(a: &IESValue, b: &IESValue) -> Option<Ordering> {
+    match (a, b) {
+        (IESValue::Int(a), IESValue::Int(b)) => Some(a.cmp(b)),
+        (IESValue::Str(a), IESValue::Str(b)) => Some(a.cmp(b)),
+        (IESValue::Null, IESValue::Null) => Some(Ordering::Equal),
+        (IESValue::Float(_) | IESValue::Int(_), IESValue::Float(_) | IESValue::Int(_)) => {
+            number(a)?.partial_cmp(&number(b)?)
+        }
+        _ => None,
+    }
+}
+
+fn number(value: &IESValue) -> Option<f64> {
+    match value {
+        IESValue::Float(value) => Some(f64::from(*value)),
+        IESValue::Int(value) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+/// Filtered view of the rows of a table, see `IESFile::query`. Rows are borrowed,
+/// never copied.
+#[derive(Debug, Clone)]
+pub struct IESQuery<'a> {
+    file: &'a IESFile,
+    /// Column index, `None` for a column the table lacks
+    filters: Vec<(Option<usize>, Predicate)>,
+    indexes: Vec<&'a IESIndex<'a>>,
+}
+
+impl<'a> IESQuery<'a> {
+    pub(crate) fn new(file: &'a IESFile) -> Self {
+        Self {
+            file,
+            filters: Vec::new(),
+            indexes: Vec::new(),
+        }
+    }
+
+    /// Keeps rows whose `column` cell satisfies `predicate`. Cells of a column the
+    /// table lacks are empty.
+    pub fn filter(mut self, column: &str, predicate: Predicate) -> Self {
+        let column = self.file.get_column_index_by_name(column);
+        self.filters.push((column, predicate));
+        self
+    }
+
+    /// Looks up `eq` filters on the column of `index` there instead of scanning every
+    /// row. The index must be of the same table.
+    pub fn with_index(mut self, index: &'a IESIndex<'a>) -> Self {
+        if std::ptr::eq(index.file, self.file) {
+            self.indexes.push(index);
+        }
+        self
+    }
+
+    /// Matching rows in file order.
+    pub fn rows(&self) -> impl Iterator<Item = IESRowView<'a>> + '_ {
+        let indexed = self.filters.iter().find_map(|(column, predicate)| {
+            let index = self
+                .indexes
+                .iter()
+                .find(|index| Some(index.column) == *column && predicate.op == Op::Eq)?;
+            Some(index.row_indices(&predicate.value))
+        });
+        let candidates: Box<dyn Iterator<Item = usize>> = match indexed {
+            Some(rows) => Box::new(rows.iter().copied()),
+            None => Box::new(0..self.file.row_count()),
+        };
+        candidates
+            .filter_map(|index| self.file.row(index))
+            .filter(|row| {
+                self.filters.iter().all(|(column, predicate)| {
+                    let cell = column.map_or(&IESValue::Null, |column| row.value_at(column));
+                    predicate.matches(cell)
+                })
+            })
+    }
+
+    /// The `columns` cells of every matching row, in the given order; columns the
+    /// table lacks give empty cells.
+    pub fn select<I, S>(&self, columns: I) -> impl Iterator<Item = Vec<&'a IESValue>> + '_
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let columns: Vec<Option<usize>> = columns
+            .into_iter()
+            .map(|column| self.file.get_column_index_by_name(column.as_ref()))
+            .collect();
+        self.rows().map(move |row| {
+            columns
+                .iter()
+                .map(|column| column.map_or(&NULL, |column| row.value_at(column)))
+                .collect()
+        })
+    }
+
+    pub fn first(&self) -> Option<IESRowView<'a>> {
+        self.rows().next()
+    }
+
+    pub fn count(&self) -> usize {
+        self.rows().count()
+    }
+}
+
+static NULL: IESValue = IESValue::Null;
+
+/// Rows of a table by the value of one column, for repeated lookups on key columns
+/// like `ClassID`. Build with `IESFile::index` and pass to `IESQuery::with_index`, or
+/// look up directly.
+#[derive(Debug, Clone)]
+pub struct IESIndex<'a> {
+    file: &'a IESFile,
+    column: usize,
+    rows: HashMap<Key, Vec<usize>>,
+}
+
+/// Hashable form of a cell; whole floats key like the integer they equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Int(i64),
+    Float(u32),
+    Str(String),
+    Null,
+}
+
+impl Key {
+    fn new(value: &IESValue) -> Self {
+        match value {
+            IESValue::Int(value) => Key::Int(*value),
+            IESValue::Float(value) if value.fract() == 0.0 && value.abs() < i64::MAX as f32 => {
+                Key::Int(*value as i64)
+            }
+            IESValue::Float(value) => Key::Float(value.to_bits()),
+            IESValue::Str(value) => Key::Str(value.clone()),
+            IESValue::Null => Key::Null,
+        }
+    }
+}
+
+impl<'a> IESIndex<'a> {
+    pub(crate) fn new(file: &'a IESFile, column: usize) -> Self {
+        let mut rows: HashMap<Key, Vec<usize>> = HashMap::new();
+        for row in file.rows() {
+            let key = Key::new(row.value_at(column));
+            rows.entry(key).or_default().push(row.index());
+        }
+        Self { file, column, rows }
+    }
+
+    fn row_indices(&self, value: &IESValue) -> &[usize] {
+        self.rows.get(&Key::new(value)).map_or(&[], Vec::as_slice)
+    }
+
+    /// Rows whose cell equals `value`, in file order.
+    pub fn get(&self, value: impl Into<IESValue>) -> impl Iterator<Item = IESRowView<'a>> + '_ {
+        let file = self.file;
+        self.row_indices(&value.into())
+            .iter()
+            .filter_map(move |&index| file.row(index))
+    }
+
+    /// The first row whose cell equals `value`.
+    pub fn first(&self, value: impl Into<IESValue>) -> Option<IESRowView<'a>> {
+        self.get(value).next()
+    }
+
+    /// Number of distinct values.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> IESFile {
+        let json = r#"[
+            {"ClassID": 1, "ClassName": "Sword", "Level": 1, "Price": 100},
+            {"ClassID": 2, "ClassName": "Broad Sword", "Level": 15, "Price": 2.5},
+            {"ClassID": 3, "ClassName": "Shield", "Level": 15, "Price": null},
+            {"ClassID": 4, "ClassName": "Sword", "Level": 40, "Price": 900}
+        ]"#;
+        IESFile::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn filters_select_and_use_indexes() {
+        let ies = items();
+        let swords = ies.query().filter("ClassName", eq("Sword"));
+        let selected: Vec<Vec<&IESValue>> = swords.select(["Level", "Price", "Missing"]).collect();
+        assert_eq!(
+            selected,
+            [
+                [&IESValue::Int(1), &IESValue::Int(100), &IESValue::Null],
+                [&IESValue::Int(40), &IESValue::Int(900), &IESValue::Null],
+            ]
+        );
+
+        let mid = ies
+            .query()
+            .filter("Level", ge(15))
+            .filter("Price", lt(1000.0));
+        let ids: Vec<u32> = mid.rows().filter_map(|row| row.get("ClassID")).collect();
+        assert_eq!(ids, [2, 4]);
+        assert_eq!(ies.query().filter("Price", is_null()).count(), 1);
+        assert_eq!(ies.query().filter("ClassName", contains("Sw")).count(), 3);
+        assert_eq!(ies.query().filter("Missing", eq(1)).count(), 0);
+
+        let by_level = ies.index("Level").unwrap();
+        assert_eq!(by_level.len(), 3);
+        assert_eq!(by_level.get(15.0).count(), 2);
+        let indexed = ies
+            .query()
+            .with_index(&by_level)
+            .filter("Level", eq(15))
+            .filter("ClassName", ne("Shield"));
+        assert_eq!(indexed.first().map(|row| row.index()), Some(1));
+        assert!(ies.index("Missing").is_none());
+    }
+}
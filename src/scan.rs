@@ -0,0 +1,243 @@
+use crate::error::Result;
+use crate::events::{Operation, Task};
+use crate::ipf::{IPFFile, IPFFileTable};
+use crate::tosreader::BinaryReader;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Limits for `scan`.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanOptions {
+    /// Upper bound on the bytes of entry data held at once, counting the compressed
+    /// payload and the inflated entry. An entry larger than the whole budget is still
+    /// processed, but only while nothing else is in flight.
+    pub memory_budget: u64,
+    /// Worker threads that extract and process entries.
+    pub threads: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            memory_budget: 256 * 1024 * 1024,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+/// Outcome of processing one entry, handed to the caller as soon as it is ready.
+#[derive(Debug)]
+pub struct ScanItem<T> {
+    pub archive: PathBuf,
+    /// Directory name of the entry, `None` when the archive itself could not be read
+    pub entry: Option<String>,
+    pub result: Result<T>,
+}
+
+/// Streams the entries of `archives` that pass `filter` through `task` and hands every
+/// result to `emit` on the calling thread, in completion order.
+///
+/// Archive tables are read one archive at a time and entries are extracted only when
+/// the memory budget has room for them, so peak memory stays around the budget no
+/// matter how large the client is.
+pub fn scan<T, P, F, E>(
+    archives: &[PathBuf],
+    options: &ScanOptions,
+    filter: P,
+    task: F,
+    mut emit: E,
+) where
+    T: Send,
+    P: Fn(&IPFFileTable) -> bool + Sync,
+    F: Fn(&Path, &IPFFileTable, Vec<u8>) -> Result<T> + Sync,
+    E: FnMut(ScanItem<T>),
+{
+    let progress = Task::start(
+        Operation::Extract,
+        &format!("{} archives", archives.len()),
+        None,
+    );
+    let threads = options.threads.max(1);
+    let budget = MemoryBudget::new(options.memory_budget);
+    let (job_sender, jobs) = sync_channel::<(usize, IPFFileTable)>(threads * 2);
+    let jobs = Mutex::new(jobs);
+    let (result_sender, results) = sync_channel::<ScanItem<T>>(threads);
+
+    thread::scope(|scope| {
+        let producer_results = result_sender.clone();
+        let filter = &filter;
+        scope.spawn(move || {
+            for (index, archive) in archives.iter().enumerate() {
+                let ipf = match IPFFile::load_from_file(archive) {
+                    Ok(ipf) => ipf,
+                    Err(err) => {
+                        let item = ScanItem {
+                            archive: archive.clone(),
+                            entry: None,
+                            result: Err(err),
+                        };
+                        if producer_results.send(item).is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                for entry in ipf.file_table().iter().filter(|entry| filter(entry)) {
+                    if job_sender.send((index, entry.clone())).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        for _ in 0..threads {
+            let results = result_sender.clone();
+            let (jobs, budget, task) = (&jobs, &budget, &task);
+            scope.spawn(move || {
+                let mut reader: Option<(usize, BinaryReader<BufReader<File>>)> = None;
+                loop {
+                    let Ok((index, entry)) = jobs.lock().unwrap().recv() else {
+                        return;
+                    };
+                    let archive = &archives[index];
+                    let cost =
+                        entry.file_size_compressed() as u64 + entry.file_size_uncompressed() as u64;
+                    let reserved = budget.acquire(cost);
+                    let result = open_reader(&mut reader, index, archive)
+                        .and_then(|reader| entry.extract(reader))
+                        .and_then(|data| task(archive, &entry, data));
+                    budget.release(reserved);
+                    let item = ScanItem {
+                        archive: archive.clone(),
+                        entry: Some(entry.directory_name()),
+                        result,
+                    };
+                    if results.send(item).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+
+        for (done, item) in results.into_iter().enumerate() {
+            if let Err(err) = &item.result {
+                let entry = item.entry.as_deref().unwrap_or_default();
+                progress.warning(&format_args!(
+                    "{} {}: {}",
+                    item.archive.display(),
+                    entry,
+                    err
+                ));
+            }
+            emit(item);
+            progress.progress(done as u64 + 1);
+        }
+    });
+    progress.done();
+}
+
+/// Reader for `archive`, reusing the open one while consecutive entries share it.
+fn open_reader<'a>(
+    reader: &'a mut Option<(usize, BinaryReader<BufReader<File>>)>,
+    index: usize,
+    archive: &Path,
+) -> Result<&'a mut BinaryReader<BufReader<File>>> {
+    if !matches!(reader, Some((open, _)) if *open == index) {
+        let file = File::open(archive).map_err(|err| crate::Error::from(err).in_file(archive))?;
+        *reader = Some((index, BinaryReader::new(BufReader::new(file))));
+    }
+    Ok(&mut reader.as_mut().unwrap().1)
+}
+
+/// Byte counter that blocks until a reservation fits under the limit.
+struct MemoryBudget {
+    limit: u64,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits for room and reserves it. Returns the amount to release afterwards.
+    fn acquire(&self, bytes: u64) -> u64 {
+        let bytes = bytes.min(self.limit);
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && *used + bytes > self.limit {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+        bytes
+    }
+
+    fn release(&self, bytes: u64) {
+        *self.used.lock().unwrap() -= bytes;
+        self.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn scan_emits_every_entry_within_budget() {
+        let dir = std::env::temp_dir().join(format!("toslib-scan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut archives = Vec::new();
+        for archive in ["a.ipf", "b.ipf"] {
+            let mut writer = IPFWriter::new();
+            for index in 0..5 {
+                writer.add_file(archive, &format!("{}.bin", index), vec![index as u8; 4096]);
+            }
+            let path = dir.join(archive);
+            writer.save_to_file(&path).unwrap();
+            archives.push(path);
+        }
+        archives.push(dir.join("missing.ipf"));
+
+        let mut sizes = Vec::new();
+        let mut failed = 0;
+        let options = ScanOptions::default()
+            .with_memory_budget(1024)
+            .with_threads(3);
+        scan(
+            &archives,
+            &options,
+            |entry| entry.directory_name() != "4.bin",
+            |_, _, data| Ok(data.len()),
+            |item| match item.result {
+                Ok(size) => sizes.push(size),
+                Err(_) => failed += 1,
+            },
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(sizes, vec![4096; 8]);
+        assert_eq!(failed, 1);
+    }
+}
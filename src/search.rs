@@ -0,0 +1,332 @@
+use crate::error::{Error, Result};
+use crate::events::{Operation, Task};
+use crate::game::{GameData, entry_key};
+use crate::ies::IESFile;
+use crate::ipf::IPFEntryKind;
+use crate::options::ParseOptions;
+use crate::scan::{ScanOptions, scan};
+use crate::text::TextDecoding;
+use elementtree::Element;
+
+/// What `search` looks at and how values are compared.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchOptions {
+    /// Match entry keys (`container/path`)
+    pub paths: bool,
+    /// Match IES cell values
+    pub ies: bool,
+    /// Match XML attribute values and element text
+    pub xml: bool,
+    pub case_sensitive: bool,
+    /// The whole value must equal the query instead of containing it.
+    pub whole_value: bool,
+    /// Extraction limits for the tables and documents searched.
+    pub scan: ScanOptions,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            paths: true,
+            ies: true,
+            xml: true,
+            case_sensitive: false,
+            whole_value: false,
+            scan: ScanOptions::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn with_paths(mut self, paths: bool) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    pub fn with_ies(mut self, ies: bool) -> Self {
+        self.ies = ies;
+        self
+    }
+
+    pub fn with_xml(mut self, xml: bool) -> Self {
+        self.xml = xml;
+        self
+    }
+
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn with_whole_value(mut self, whole_value: bool) -> Self {
+        self.whole_value = whole_value;
+        self
+    }
+
+    pub fn with_scan(mut self, scan: ScanOptions) -> Self {
+        self.scan = scan;
+        self
+    }
+}
+
+/// One place the query was found. `path` is always the `GameData` key of the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchHit {
+    /// The entry key itself matched.
+    Path { path: String },
+    /// An IES cell matched.
+    IesCell {
+        path: String,
+        row: usize,
+        column: String,
+        value: String,
+    },
+    /// An XML attribute value, or element text when `attribute` is `None`, matched.
+    Xml {
+        path: String,
+        /// Tag names from the root down to the element, joined by `/`
+        element: String,
+        attribute: Option<String>,
+        value: String,
+    },
+}
+
+impl SearchHit {
+    pub fn path(&self) -> &str {
+        match self {
+            SearchHit::Path { path }
+            | SearchHit::IesCell { path, .. }
+            | SearchHit::Xml { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchHit::Path { path } => write!(f, "{}", path),
+            SearchHit::IesCell {
+                path,
+                row,
+                column,
+                value,
+            } => write!(f, "{} row {} {}: {}", path, row, column, value),
+            SearchHit::Xml {
+                path,
+                element,
+                attribute: Some(attribute),
+                value,
+            } => write!(f, "{} <{} {}>: {}", path, element, attribute, value),
+            SearchHit::Xml {
+                path,
+                element,
+                attribute: None,
+                value,
+            } => write!(f, "{} <{}>: {}", path, element, value),
+        }
+    }
+}
+
+/// Hits of a `search`, plus the entries that could not be read or parsed.
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    /// Sorted by entry key, then in the order they appear within the entry.
+    pub hits: Vec<SearchHit>,
+    pub failures: Vec<(String, Error)>,
+}
+
+/// Finds `query` in entry keys, IES cells and XML documents of `game` in one pass.
+///
+/// Only the winning copy of each entry is searched, so data replaced by a later patch
+/// is not reported.
+pub fn search(game: &GameData, query: &str, options: &SearchOptions) -> SearchResults {
+    let progress = Task::start(Operation::Search, query, None);
+    let matcher = Matcher::new(query, options);
+    let mut results = SearchResults::default();
+    if options.paths {
+        results.hits.extend(
+            game.paths()
+                .filter(|path| matcher.matches(path))
+                .map(|path| SearchHit::Path {
+                    path: path.to_string(),
+                }),
+        );
+    }
+
+    if options.ies || options.xml {
+        scan(
+            game.archives(),
+            &options.scan,
+            |entry| match entry.kind() {
+                IPFEntryKind::Table => options.ies,
+                IPFEntryKind::Xml => options.xml,
+                _ => false,
+            },
+            |archive, entry, data| {
+                let key = entry_key(&entry.container_name_str(), &entry.directory_name_str());
+                // Replaced by a later archive; the winning copy is searched on its own
+                if game.entry(&key).is_none_or(|(winner, _)| winner != archive) {
+                    return Ok((key, Vec::new()));
+                }
+                let hits = match entry.kind() {
+                    IPFEntryKind::Table => {
+                        search_ies(&key, &data, &game.parse_options(&key), &matcher)
+                    }
+                    _ => search_xml(&key, &data, &matcher),
+                };
+                hits.map(|hits| (key, hits))
+            },
+            |item| match item.result {
+                Ok((_, hits)) => results.hits.extend(hits),
+                Err(err) => {
+                    let entry = item.entry.unwrap_or_default();
+                    results.failures.push((entry, err.in_file(&item.archive)));
+                }
+            },
+        );
+    }
+
+    // Stable, so hits within an entry stay in document order
+    results.hits.sort_by(|a, b| a.path().cmp(b.path()));
+    progress.done();
+    results
+}
+
+fn search_ies(
+    path: &str,
+    data: &[u8],
+    options: &ParseOptions,
+    matcher: &Matcher,
+) -> Result<Vec<SearchHit>> {
+    let ies = IESFile::load_from_slice_with_options(data, options)?;
+    let mut hits = Vec::new();
+    for row in ies.rows() {
+        for (column, cell) in row.cells() {
+            let Some(value) = cell.text() else {
+                continue;
+            };
+            if matcher.matches(&value) {
+                hits.push(SearchHit::IesCell {
+                    path: path.to_string(),
+                    row: row.index(),
+                    column: column.name().to_string(),
+                    value,
+                });
+            }
+        }
+    }
+    Ok(hits)
+}
+
+fn search_xml(path: &str, data: &[u8], matcher: &Matcher) -> Result<Vec<SearchHit>> {
+    let root = parse_xml(data)?;
+    let mut hits = Vec::new();
+    let mut stack = vec![(&root, root.tag().name().to_string())];
+    while let Some((element, element_path)) = stack.pop() {
+        for (name, value) in element.attrs() {
+            if matcher.matches(value) {
+                hits.push(SearchHit::Xml {
+                    path: path.to_string(),
+                    element: element_path.clone(),
+                    attribute: Some(name.name().to_string()),
+                    value: value.to_string(),
+                });
+            }
+        }
+        let text = element.text().trim();
+        if !text.is_empty() && matcher.matches(text) {
+            hits.push(SearchHit::Xml {
+                path: path.to_string(),
+                element: element_path.clone(),
+                attribute: None,
+                value: text.to_string(),
+            });
+        }
+        // Pushed last to first so the first child is visited next
+        let children: Vec<&Element> = element.children().collect();
+        for child in children.into_iter().rev() {
+            stack.push((child, format!("{}/{}", element_path, child.tag().name())));
+        }
+    }
+    Ok(hits)
+}
+
+/// Parses an XML document, detecting the code page of text that is not valid UTF-8.
+pub(crate) fn parse_xml(data: &[u8]) -> Result<Element> {
+    let text = TextDecoding::Detect.decode_cow(data)?;
+    Element::from_reader(text.as_bytes()).map_err(|err| Error::Xml(err.to_string()))
+}
+
+struct Matcher {
+    query: String,
+    case_sensitive: bool,
+    whole_value: bool,
+}
+
+impl Matcher {
+    fn new(query: &str, options: &SearchOptions) -> Self {
+        Self {
+            query: if options.case_sensitive {
+                query.to_string()
+            } else {
+                query.to_lowercase()
+            },
+            case_sensitive: options.case_sensitive,
+            whole_value: options.whole_value,
+        }
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        let lowered;
+        let value = if self.case_sensitive {
+            value
+        } else {
+            lowered = value.to_lowercase();
+            &lowered
+        };
+        if self.whole_value {
+            value == self.query
+        } else {
+            value.contains(&self.query)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+
+    #[test]
+    fn finds_paths_and_xml_values_in_winning_entries() {
+        let dir = std::env::temp_dir().join(format!("toslib-search-{}", std::process::id()));
+        let documents = [
+            ("data", r#"<Skills><Skill Name="Old_Fireball"/></Skills>"#),
+            (
+                "patch",
+                r#"<Skills><Skill Name="Fireball">Casts a fireball</Skill></Skills>"#,
+            ),
+        ];
+        for (sub, xml) in documents {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+            let mut writer = IPFWriter::new();
+            writer.add_file("xml.ipf", "skill.xml", xml.as_bytes().to_vec());
+            writer.add_file("xml.ipf", "fireball.lua", Vec::new());
+            writer.save_to_file(dir.join(sub).join("001.ipf")).unwrap();
+        }
+        let game = GameData::open(&dir).unwrap();
+        let results = search(&game, "FIREBALL", &SearchOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(results.failures.is_empty());
+        let hits: Vec<String> = results.hits.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            hits,
+            [
+                "xml.ipf/fireball.lua",
+                "xml.ipf/skill.xml <Skills/Skill Name>: Fireball",
+                "xml.ipf/skill.xml <Skills/Skill>: Casts a fireball",
+            ]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Plain-data dumps behind `to_json`/`to_cbor` and `from_json`/`from_cbor` on the
+//! parsed file types. Parse warnings are not part of a dump.
+
+use crate::error::{Error, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub(crate) fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|err| Error::Serialization(err.to_string()))
+}
+
+pub(crate) fn from_json<T: DeserializeOwned>(json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|err| Error::Serialization(err.to_string()))
+}
+
+pub(crate) fn to_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes)
+        .map_err(|err| Error::Serialization(err.to_string()))?;
+    Ok(bytes)
+}
+
+pub(crate) fn from_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    ciborium::from_reader(bytes).map_err(|err| Error::Serialization(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipf::{IPFFile, IPFWriter};
+    use crate::tosreader::{BinaryReader, BinaryWriter};
+    use std::io::Cursor;
+
+    #[test]
+    fn ipf_manifest_round_trips() {
+        let mut ipf_writer = IPFWriter::new();
+        ipf_writer.versions(10, 11);
+        ipf_writer.add_file("xml.ipf", "xml/a.xml", b"<a/>".to_vec());
+        ipf_writer.add_file("xml.ipf", "xml/b.xml", b"<b/>".to_vec());
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        ipf_writer.write_to(&mut writer).unwrap();
+        let mut reader = BinaryReader::new(Cursor::new(writer.writer.into_inner()));
+        let ipf = IPFFile::load_from_reader(&mut reader).unwrap();
+
+        let json = ipf.to_json().unwrap();
+        let from_json = IPFFile::from_json(&json).unwrap();
+        let from_cbor = IPFFile::from_cbor(&ipf.to_cbor().unwrap()).unwrap();
+        for copy in [from_json, from_cbor] {
+            assert_eq!(copy.to_json().unwrap(), json);
+            assert_eq!(copy.footer().new_version(), 11);
+            assert_eq!(copy.file_table()[1].directory_name(), "xml/b.xml");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::convert::{Conversion, ConversionCache};
+use crate::error::Result;
+use crate::game::GameData;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// Settings for `serve`.
+#[derive(Debug, Clone, Copy)]
+pub struct ServeOptions {
+    /// Upper bound on the bytes of converted responses (IES and XAC JSON, PNG) kept in
+    /// memory. Oldest conversions are dropped first.
+    pub cache_budget: u64,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            cache_budget: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl ServeOptions {
+    pub fn with_cache_budget(mut self, bytes: u64) -> Self {
+        self.cache_budget = bytes;
+        self
+    }
+}
+
+/// Serves the archives of `client_dir` over HTTP on `addr` until the process exits.
+///
+/// Entries are addressed by their `GameData` key, lowercase `container/path`:
+///
+/// - `GET /entries`: JSON array of every key
+/// - `GET /raw/<key>`: the extracted entry
+/// - `GET /ies/<key>`: an IES table as JSON
+/// - `GET /xac/<key>`: the meshes of an XAC model as JSON
+/// - `GET /png/<key>`: a DDS texture as PNG
+pub fn serve<A: ToSocketAddrs>(client_dir: &Path, addr: A, options: &ServeOptions) -> Result<()> {
+    serve_on(TcpListener::bind(addr)?, client_dir, options)
+}
+
+/// `serve` on an already bound listener.
+pub fn serve_on(listener: TcpListener, client_dir: &Path, options: &ServeOptions) -> Result<()> {
+    let server = Arc::new(Server {
+        game: GameData::open(client_dir)?,
+        cache: ConversionCache::new(options.cache_budget),
+    });
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let server = server.clone();
+        thread::spawn(move || {
+            // The client went away; there is nobody left to report to
+            let _ = server.handle(stream);
+        });
+    }
+    Ok(())
+}
+
+struct Server {
+    game: GameData,
+    cache: ConversionCache,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Arc<[u8]>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.as_bytes().into(),
+        }
+    }
+}
+
+impl Server {
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Headers are not used, but must be consumed before responding
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(target)) => self.route(target),
+            (Some(_), Some(_)) => {
+                Response::error("405 Method Not Allowed", "only GET is supported")
+            }
+            _ => Response::error("400 Bad Request", "malformed request line"),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        )?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+
+    fn route(&self, target: &str) -> Response {
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        let Some(path) = percent_decode(path) else {
+            return Response::error("400 Bad Request", "invalid percent-encoding");
+        };
+        if path == "/" || path == "/entries" {
+            let keys: Vec<&str> = self.game.paths().collect();
+            return match serde_json::to_vec(&keys) {
+                Ok(body) => Response::ok("application/json", body),
+                Err(err) => Response::error("500 Internal Server Error", &err.to_string()),
+            };
+        }
+
+        let Some((kind, key)) = path.trim_start_matches('/').split_once('/') else {
+            return Response::error("404 Not Found", "unknown route");
+        };
+        if !self.game.contains(key) {
+            return Response::error("404 Not Found", "no such entry");
+        }
+        if kind == "raw" {
+            return match self.game.read(key) {
+                Ok(data) => Response::ok("application/octet-stream", data),
+                Err(err) => Response::error("500 Internal Server Error", &err.to_string()),
+            };
+        }
+        let Some(conversion) = Conversion::from_name(kind) else {
+            return Response::error("404 Not Found", "unknown route");
+        };
+
+        let cache_key = format!("{}/{}", conversion.name(), key.to_lowercase());
+        let body = self.cache.get_or_insert(&cache_key, || {
+            conversion
+                .apply(self.game.read(key)?)
+                .map_err(|err| err.in_entry(key))
+        });
+        match body {
+            Ok(body) => Response::ok(conversion.content_type(), body),
+            Err(err) => Response::error("422 Unprocessable Entity", &err.to_string()),
+        }
+    }
+}
+
+/// Decodes `%XX` escapes of a request path.
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(index + 1..index + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipf::IPFWriter;
+    use std::io::Read;
+
+    fn get(addr: std::net::SocketAddr, target: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", target).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn later_archives_replace_entries() {
+        let dir = std::env::temp_dir().join(format!("toslib-serve-{}", std::process::id()));
+        for (sub, body) in [("data", "old"), ("patch", "new")] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+            let mut writer = IPFWriter::new();
+            writer.add_file("xml.ipf", "a b.xml", body.as_bytes().to_vec());
+            writer.save_to_file(dir.join(sub).join("001.ipf")).unwrap();
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client_dir = dir.clone();
+        thread::spawn(move || serve_on(listener, &client_dir, &ServeOptions::default()));
+
+        let entries = get(addr, "/entries");
+        assert!(entries.ends_with(r#"["xml.ipf/a b.xml"]"#), "{}", entries);
+        let raw = get(addr, "/raw/XML.ipf/a%20b.xml");
+        assert!(raw.starts_with("HTTP/1.1 200 OK"));
+        assert!(raw.ends_with("\r\n\r\nnew"));
+        assert!(get(addr, "/raw/xml.ipf/missing").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/ies/xml.ipf/a%20b.xml").starts_with("HTTP/1.1 422"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! Per-vertex tangents for meshes that come without a tangent layer.
+//!
+//! Each triangle contributes the directions in which its UVs grow along U and V, which
+//! are summed per vertex and made perpendicular to the normal, as MikkTSpace does for
+//! shared vertices. It does not split vertices at UV seams or reproduce MikkTSpace to
+//! the bit, which only matters for bakes made with that exact basis.
+
+/// Tangents with the bitangent sign in `w`, one per vertex. `None` when the normals or
+/// UVs do not cover every position.
+pub(crate) fn compute(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: &[u32],
+) -> Option<Vec<[f32; 4]>> {
+    let count = positions.len();
+    if normals.len() != count || uvs.len() != count {
+        return None;
+    }
+
+    let mut tangents = vec![[0.0; 3]; count];
+    let mut bitangents = vec![[0.0; 3]; count];
+    for face in indices.chunks_exact(3) {
+        let [a, b, c] = [face[0], face[1], face[2]].map(|index| index as usize);
+        if a >= count || b >= count || c >= count {
+            continue;
+        }
+        let edge1 = sub(positions[b], positions[a]);
+        let edge2 = sub(positions[c], positions[a]);
+        let [du1, dv1] = [uvs[b][0] - uvs[a][0], uvs[b][1] - uvs[a][1]];
+        let [du2, dv2] = [uvs[c][0] - uvs[a][0], uvs[c][1] - uvs[a][1]];
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent: [f32; 3] = std::array::from_fn(|i| (edge1[i] * dv2 - edge2[i] * dv1) / det);
+        let bitangent: [f32; 3] = std::array::from_fn(|i| (edge2[i] * du1 - edge1[i] * du2) / det);
+        for vertex in [a, b, c] {
+            tangents[vertex] = add(tangents[vertex], tangent);
+            bitangents[vertex] = add(bitangents[vertex], bitangent);
+        }
+    }
+
+    let tangents = (0..count)
+        .map(|vertex| {
+            let normal = normals[vertex];
+            // Gram-Schmidt against the normal, or any perpendicular where the UVs
+            // give no direction
+            let tangent = tangents[vertex];
+            let along = dot(normal, tangent);
+            let tangent = normalized(std::array::from_fn(|i| tangent[i] - normal[i] * along))
+                .unwrap_or_else(|| perpendicular(normal));
+            let sign = if dot(cross(normal, tangent), bitangents[vertex]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [tangent[0], tangent[1], tangent[2], sign]
+        })
+        .collect();
+    Some(tangents)
+}
+
+/// The bitangent a tangent with its sign implies: `cross(normal, tangent) * w`.
+pub(crate) fn bitangent(normal: [f32; 3], [x, y, z, w]: [f32; 4]) -> [f32; 3] {
+    cross(normal, [x, y, z]).map(|value| value * w)
+}
+
+fn perpendicular(normal: [f32; 3]) -> [f32; 3] {
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    normalized(cross(axis, normal)).unwrap_or(axis)
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] + b[i])
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalized(v: [f32; 3]) -> Option<[f32; 3]> {
+    let length = dot(v, v).sqrt();
+    (length > f32::EPSILON).then(|| v.map(|value| value / length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents_follow_u_and_sign_follows_v() {
+        // A quad in the XY plane facing +Z, with U along +X
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 4];
+        let indices = [0, 1, 2, 0, 2, 3];
+        let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let tangents = compute(&positions, &normals, &uvs, &indices).unwrap();
+        assert!(tangents.iter().all(|&t| t == [1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(bitangent(normals[0], tangents[0]), [0.0, 1.0, 0.0]);
+
+        // Flipping V mirrors the bitangent
+        let flipped = uvs.map(|[u, v]| [u, 1.0 - v]);
+        let tangents = compute(&positions, &normals, &flipped, &indices).unwrap();
+        assert!(tangents.iter().all(|&t| t == [1.0, 0.0, 0.0, -1.0]));
+
+        assert!(compute(&positions, &normals, &uvs[..3], &indices).is_none());
+    }
+}
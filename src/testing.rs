@@ -0,0 +1,288 @@
+//! Seeded generators of structurally valid files, for property tests of the parsers
+//! and writers against each other. Enabled by the `test-utils` feature.
+//!
+//! Every generator draws from an `Rng`, so a failing case is reproduced from its seed.
+
+use crate::error::Result;
+use crate::ipf::IPFWriter;
+use crate::tosreader::BinaryWriter;
+use std::io::Cursor;
+
+const HEADER_NAME: usize = 128;
+const DATA_NAME: usize = 64;
+
+/// Small deterministic generator (SplitMix64). Not for anything but tests.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..n`; `n` must not be zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    pub fn chance(&mut self, one_in: u64) -> bool {
+        self.below(one_in) == 0
+    }
+
+    /// `len` random bytes.
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    /// A name of 1 to `max_len` characters from `[A-Za-z0-9_]`.
+    pub fn identifier(&mut self, max_len: usize) -> String {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_";
+        let len = 1 + self.below(max_len.max(1) as u64) as usize;
+        (0..len)
+            .map(|_| CHARS[self.below(CHARS.len() as u64) as usize] as char)
+            .collect()
+    }
+
+    /// Flips or overwrites a few bytes of `bytes`, and sometimes cuts it short, for
+    /// checking that parsers reject damage with an error rather than a panic.
+    pub fn corrupt(&mut self, bytes: &mut Vec<u8>) {
+        if bytes.is_empty() {
+            return;
+        }
+        for _ in 0..1 + self.below(4) {
+            let at = self.below(bytes.len() as u64) as usize;
+            bytes[at] = self.next_u64() as u8;
+        }
+        if self.chance(3) {
+            let len = self.below(bytes.len() as u64) as usize;
+            bytes.truncate(len);
+        }
+    }
+}
+
+/// One cell of a generated table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    /// Whole number stored in a float column
+    Number(u32),
+    /// Text of a string column; empty text reads back as an empty cell
+    Text(String),
+}
+
+impl Cell {
+    /// The cell as `IESValue` text reports it.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            Cell::Number(value) => Some(value.to_string()),
+            Cell::Text(text) if text.is_empty() => None,
+            Cell::Text(text) => Some(text.clone()),
+        }
+    }
+}
+
+/// An IES table described by value, serialized with `to_bytes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSpec {
+    pub name: String,
+    /// Column names and whether the column holds strings
+    pub columns: Vec<(String, bool)>,
+    /// One cell per column in every row, matching the column kinds
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl TableSpec {
+    /// A table of up to 8 columns with unique names and up to 20 rows.
+    pub fn random(rng: &mut Rng) -> Self {
+        let columns: Vec<(String, bool)> = (0..1 + rng.below(8))
+            .map(|i| (format!("{}_{}", rng.identifier(12), i), rng.chance(2)))
+            .collect();
+        let rows = (0..rng.below(21))
+            .map(|_| {
+                columns
+                    .iter()
+                    .map(|(_, is_string)| match is_string {
+                        true if rng.chance(5) => Cell::Text(String::new()),
+                        true => Cell::Text(rng.identifier(24)),
+                        // Float columns are exact up to 2^24
+                        false => Cell::Number(rng.below(1 << 24) as u32),
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            name: rng.identifier(32),
+            columns,
+            rows,
+        }
+    }
+
+    /// The table in the on-disk IES layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let xor = |text: &str| text.bytes().map(|b| b ^ 1).collect::<Vec<u8>>();
+        let string_columns = self.columns.iter().filter(|(_, s)| *s).count();
+
+        // Positions count within each kind, which is the order the parser sorts by
+        let mut column_data = Vec::new();
+        let mut positions = [0u16; 2];
+        for (name, is_string) in &self.columns {
+            let mut field = [0u8; DATA_NAME];
+            let encrypted = xor(name);
+            field[..encrypted.len().min(DATA_NAME)]
+                .copy_from_slice(&encrypted[..encrypted.len().min(DATA_NAME)]);
+            column_data.extend_from_slice(&field);
+            column_data.extend_from_slice(&field);
+            column_data.extend_from_slice(&(*is_string as u16).to_le_bytes());
+            column_data.extend_from_slice(&[0; 4]);
+            let position = &mut positions[*is_string as usize];
+            column_data.extend_from_slice(&position.to_le_bytes());
+            *position += 1;
+        }
+
+        // Row cells are stored in parsed column order: numbers, then strings
+        let order: Vec<usize> = (0..self.columns.len())
+            .filter(|&i| !self.columns[i].1)
+            .chain((0..self.columns.len()).filter(|&i| self.columns[i].1))
+            .collect();
+        let mut row_data = Vec::new();
+        for row in &self.rows {
+            row_data.extend_from_slice(&[0; 4]);
+            row_data.extend_from_slice(&0u16.to_le_bytes());
+            for &i in &order {
+                match &row[i] {
+                    Cell::Number(value) => {
+                        row_data.extend_from_slice(&(*value as f32).to_le_bytes())
+                    }
+                    Cell::Text(text) => {
+                        row_data.extend_from_slice(&(text.len() as u16).to_le_bytes());
+                        row_data.extend(xor(text));
+                    }
+                }
+            }
+            row_data.resize(row_data.len() + string_columns, 0);
+        }
+
+        let mut bytes = vec![0u8; HEADER_NAME];
+        bytes[..self.name.len().min(HEADER_NAME)]
+            .copy_from_slice(&self.name.as_bytes()[..self.name.len().min(HEADER_NAME)]);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(column_data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(row_data.len() as u32).to_le_bytes());
+        let file_size = bytes.len() + 4 + 12 + column_data.len() + row_data.len();
+        bytes.extend_from_slice(&(file_size as u32).to_le_bytes());
+        let counts = [
+            0,
+            self.rows.len() as u16,
+            self.columns.len() as u16,
+            (self.columns.len() - string_columns) as u16,
+            string_columns as u16,
+            0,
+        ];
+        for value in counts {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&column_data);
+        bytes.extend_from_slice(&row_data);
+        bytes
+    }
+}
+
+/// An IPF archive described by value, packed with `to_bytes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveSpec {
+    pub version_to_patch: u32,
+    pub new_version: u32,
+    /// Container name, path inside it and content of every entry, in order
+    pub entries: Vec<(String, String, Vec<u8>)>,
+}
+
+impl ArchiveSpec {
+    /// An archive of up to 12 entries; some are empty, some compress well and some
+    /// are random bytes.
+    pub fn random(rng: &mut Rng) -> Self {
+        let container = format!("{}.ipf", rng.identifier(10));
+        let entries = (0..rng.below(13))
+            .map(|i| {
+                let path = format!("{}/{}_{}.xml", rng.identifier(8), rng.identifier(8), i);
+                let len = rng.below(2048) as usize;
+                let data = match rng.below(3) {
+                    0 => Vec::new(),
+                    1 => rng.identifier(8).repeat(len / 4).into_bytes(),
+                    _ => rng.bytes(len),
+                };
+                (container.clone(), path, data)
+            })
+            .collect();
+        let version_to_patch = rng.below(100_000) as u32;
+        Self {
+            version_to_patch,
+            new_version: version_to_patch + 1 + rng.below(10) as u32,
+            entries,
+        }
+    }
+
+    pub fn to_writer(&self) -> IPFWriter {
+        let mut writer = IPFWriter::new();
+        writer.versions(self.version_to_patch, self.new_version);
+        for (container, path, data) in &self.entries {
+            writer.add_file(container, path, data.clone());
+        }
+        writer
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut writer = BinaryWriter::new(Cursor::new(Vec::new()));
+        self.to_writer().write_to(&mut writer)?;
+        Ok(writer.writer.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ies::IESFile;
+    use crate::ipf::IPFFile;
+    use crate::tosreader::BinaryReader;
+
+    #[test]
+    fn generated_files_round_trip_through_the_parsers() {
+        for seed in 0..64 {
+            let mut rng = Rng::new(seed);
+
+            let table = TableSpec::random(&mut rng);
+            let parsed = IESFile::load_from_slice(&table.to_bytes()).unwrap();
+            assert!(parsed.warnings().is_empty(), "seed {}", seed);
+            assert_eq!(parsed.row_count(), table.rows.len());
+            for (parsed_row, row) in parsed.rows().zip(&table.rows) {
+                for ((name, _), cell) in table.columns.iter().zip(row) {
+                    let value = parsed_row.value(name);
+                    assert_eq!(value.and_then(|v| v.text()), cell.text(), "seed {}", seed);
+                }
+            }
+
+            let archive = ArchiveSpec::random(&mut rng);
+            let bytes = archive.to_bytes().unwrap();
+            let parsed = IPFFile::load_from_slice(&bytes).unwrap();
+            assert_eq!(parsed.footer().new_version(), archive.new_version);
+            let mut reader = BinaryReader::new(Cursor::new(&bytes));
+            for (entry, (_, path, data)) in parsed.file_table().iter().zip(&archive.entries) {
+                assert_eq!(entry.directory_name_str(), path.as_str());
+                assert_eq!(&entry.extract(&mut reader).unwrap(), data, "seed {}", seed);
+            }
+
+            // Damaged copies may fail to load, but must not panic
+            for mut damaged in [table.to_bytes(), bytes] {
+                rng.corrupt(&mut damaged);
+                let _ = IESFile::load_from_slice(&damaged);
+                let _ = IPFFile::load_from_slice(&damaged);
+            }
+        }
+    }
+}
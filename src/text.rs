@@ -0,0 +1,150 @@
+use crate::error::{Error, Result};
+use std::borrow::Cow;
+
+/// How raw name and cell bytes are turned into strings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextDecoding {
+    /// Invalid UTF-8 is an error.
+    Utf8,
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
+    #[default]
+    Lossy,
+    /// Text that is not valid UTF-8 is decoded as CP949, the Korean code page older
+    /// client data was authored in.
+    Cp949Fallback,
+    /// Text is always decoded as CP949, for Korean tables whose bytes may happen to
+    /// be valid UTF-8 as well.
+    Cp949,
+    /// Text that is not valid UTF-8 is decoded as CP949 or GBK, whichever
+    /// `Encoding::detect` finds more plausible. For clients of other regions whose
+    /// data mixes code pages.
+    Detect,
+}
+
+/// Code pages found in client data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    /// Korean
+    Cp949,
+    /// Simplified Chinese
+    Gbk,
+}
+
+impl Encoding {
+    /// Guesses the encoding of `bytes`: UTF-8 when valid, otherwise the legacy code
+    /// page that decodes without errors. When both do, CP949 is chosen for text that
+    /// reads as Hangul, since Chinese text decoded as CP949 comes out mostly as Hanja.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if std::str::from_utf8(bytes).is_ok() {
+            return Encoding::Utf8;
+        }
+        let (korean, _, korean_errors) = encoding_rs::EUC_KR.decode(bytes);
+        let (_, _, chinese_errors) = encoding_rs::GBK.decode(bytes);
+        match (korean_errors, chinese_errors) {
+            (false, true) => Encoding::Cp949,
+            (true, false) => Encoding::Gbk,
+            (false, false) => {
+                let (hangul, hanja) = korean.chars().fold((0, 0), |(hangul, hanja), c| match c {
+                    '\u{AC00}'..='\u{D7A3}' => (hangul + 1, hanja),
+                    '\u{4E00}'..='\u{9FFF}' => (hangul, hanja + 1),
+                    _ => (hangul, hanja),
+                });
+                if hangul > 0 && hanja * 4 <= hangul {
+                    Encoding::Cp949
+                } else {
+                    Encoding::Gbk
+                }
+            }
+            // Neither fits; CP949 is what most client data uses
+            (true, true) => Encoding::Cp949,
+        }
+    }
+
+    /// Decodes `bytes`, replacing invalid sequences with U+FFFD.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            Encoding::Cp949 => encoding_rs::EUC_KR.decode_without_bom_handling(bytes).0,
+            Encoding::Gbk => encoding_rs::GBK.decode_without_bom_handling(bytes).0,
+        }
+    }
+}
+
+impl TextDecoding {
+    pub fn decode(self, bytes: &[u8]) -> Result<String> {
+        self.decode_cow(bytes).map(Cow::into_owned)
+    }
+
+    /// Like `decode`, but borrows `bytes` when they are already valid UTF-8.
+    pub fn decode_cow(self, bytes: &[u8]) -> Result<Cow<'_, str>> {
+        match self {
+            TextDecoding::Utf8 => std::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|err| Error::Text(format!("invalid UTF-8: {}", err))),
+            TextDecoding::Lossy => Ok(String::from_utf8_lossy(bytes)),
+            TextDecoding::Cp949Fallback => Ok(match std::str::from_utf8(bytes) {
+                Ok(text) => Cow::Borrowed(text),
+                Err(_) => encoding_rs::EUC_KR.decode_without_bom_handling(bytes).0,
+            }),
+            TextDecoding::Cp949 => Ok(Encoding::Cp949.decode(bytes)),
+            TextDecoding::Detect => Ok(Encoding::detect(bytes).decode(bytes)),
+        }
+    }
+
+    /// Like `decode`, but reuses the buffer when it is already valid UTF-8.
+    pub fn decode_vec(self, bytes: Vec<u8>) -> Result<String> {
+        if self == TextDecoding::Cp949 && !bytes.is_ascii() {
+            return self.decode(&bytes);
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok(text),
+            Err(err) => self.decode(err.as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_differ_only_on_invalid_utf8() {
+        let cp949 = [0xC7, 0xD1, 0xB1, 0xDB]; // "한글"
+        assert!(TextDecoding::Utf8.decode(&cp949).is_err());
+        assert!(
+            TextDecoding::Lossy
+                .decode(&cp949)
+                .unwrap()
+                .contains('\u{FFFD}')
+        );
+        assert_eq!(TextDecoding::Cp949Fallback.decode(&cp949).unwrap(), "한글");
+        assert_eq!(TextDecoding::Detect.decode(&cp949).unwrap(), "한글");
+        assert_eq!(
+            TextDecoding::Cp949.decode_vec(cp949.to_vec()).unwrap(),
+            "한글"
+        );
+        // Forcing the code page reads even valid UTF-8 as CP949
+        let utf8 = "한".as_bytes().to_vec();
+        assert_ne!(TextDecoding::Cp949.decode_vec(utf8).unwrap(), "한");
+        let gbk = [0xD6, 0xD0, 0xCE, 0xC4]; // "中文"
+        assert_eq!(Encoding::detect(&gbk), Encoding::Gbk);
+        assert_eq!(TextDecoding::Detect.decode(&gbk).unwrap(), "中文");
+
+        for text in [
+            TextDecoding::Utf8,
+            TextDecoding::Lossy,
+            TextDecoding::Cp949Fallback,
+            TextDecoding::Detect,
+        ] {
+            assert!(matches!(
+                text.decode_cow("한글".as_bytes()).unwrap(),
+                Cow::Borrowed("한글")
+            ));
+            assert_eq!(
+                text.decode_vec(cp949.to_vec()).ok(),
+                text.decode(&cp949).ok()
+            );
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use crate::error::{Error, Result};
+use crate::ipf::Crc32Writer;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use std::io::Write;
+use std::path::Path;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+// DDS_PIXELFORMAT flags
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+// DXGI_FORMAT values understood in DX10 headers
+const DXGI_R8G8B8A8_UNORM: u32 = 28;
+const DXGI_R8G8B8A8_UNORM_SRGB: u32 = 29;
+const DXGI_BC1_UNORM: u32 = 71;
+const DXGI_BC1_UNORM_SRGB: u32 = 72;
+const DXGI_BC2_UNORM: u32 = 74;
+const DXGI_BC2_UNORM_SRGB: u32 = 75;
+const DXGI_BC3_UNORM: u32 = 77;
+const DXGI_BC3_UNORM_SRGB: u32 = 78;
+const DXGI_B8G8R8A8_UNORM: u32 = 87;
+const DXGI_B8G8R8A8_UNORM_SRGB: u32 = 91;
+
+/// Decoded 8-bit RGBA image, rows top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Pixel layouts the DDS decoder understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DdsFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    /// Uncompressed pixels described by channel masks
+    Masked {
+        bits: u32,
+        masks: [u32; 4],
+    },
+}
+
+impl Image {
+    /// Decodes the top mip level of a DDS texture. Supports DXT1/BC1, DXT3/BC2,
+    /// DXT5/BC3 and uncompressed RGB(A) and luminance layouts.
+    pub fn from_dds(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != DDS_MAGIC {
+            return Err(Error::Texture("not a DDS file".to_string()));
+        }
+        let field =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let height = field(12);
+        let width = field(16);
+        let pf_flags = field(80);
+        let four_cc = &bytes[84..88];
+
+        let mut data_offset = HEADER_SIZE;
+        let format = if pf_flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => DdsFormat::Bc1,
+                b"DXT2" | b"DXT3" => DdsFormat::Bc2,
+                b"DXT4" | b"DXT5" => DdsFormat::Bc3,
+                b"DX10" => {
+                    if bytes.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+                        return Err(truncated(HEADER_SIZE + DX10_HEADER_SIZE, bytes.len()));
+                    }
+                    data_offset += DX10_HEADER_SIZE;
+                    dxgi_format(field(HEADER_SIZE))?
+                }
+                other => {
+                    return Err(Error::Texture(format!(
+                        "unsupported DDS format {}",
+                        String::from_utf8_lossy(other)
+                    )));
+                }
+            }
+        } else if pf_flags & (DDPF_RGB | DDPF_LUMINANCE) != 0 {
+            let alpha_mask = if pf_flags & DDPF_ALPHAPIXELS != 0 {
+                field(104)
+            } else {
+                0
+            };
+            let (red, green, blue) = if pf_flags & DDPF_LUMINANCE != 0 {
+                (field(92), field(92), field(92))
+            } else {
+                (field(92), field(96), field(100))
+            };
+            DdsFormat::Masked {
+                bits: field(88),
+                masks: [red, green, blue, alpha_mask],
+            }
+        } else {
+            return Err(Error::Texture(format!(
+                "unsupported DDS pixel format flags {:#x}",
+                pf_flags
+            )));
+        };
+
+        let data = &bytes[data_offset..];
+        let rgba = match format {
+            DdsFormat::Bc1 | DdsFormat::Bc2 | DdsFormat::Bc3 => {
+                decode_blocks(format, width, height, data)?
+            }
+            DdsFormat::Masked { bits, masks } => decode_masked(bits, masks, width, height, data)?,
+        };
+        Ok(Image {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// Encodes the image as an RGBA PNG.
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let row_len = self.width as usize * 4;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.rgba.chunks_exact(row_len.max(1)) {
+            // Filter type 0 (none) before every scanline
+            encoder.write_all(&[0])?;
+            encoder.write_all(row)?;
+        }
+        let compressed = encoder.finish()?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlace
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_png_chunk(writer, b"IHDR", &header)?;
+        write_png_chunk(writer, b"IDAT", &compressed)?;
+        write_png_chunk(writer, b"IEND", &[])?;
+        Ok(())
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut png = Vec::new();
+        self.write_png(&mut png)?;
+        Ok(png)
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(path).map_err(|err| Error::from(err).in_file(path))?,
+        );
+        self.write_png(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Decodes a DDS texture and re-encodes it as PNG.
+pub fn dds_to_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    Image::from_dds(bytes)?.to_png()
+}
+
+fn truncated(needed: usize, available: usize) -> Error {
+    Error::Truncated {
+        needed: needed as u64,
+        available: available as u64,
+    }
+}
+
+fn dxgi_format(format: u32) -> Result<DdsFormat> {
+    Ok(match format {
+        DXGI_BC1_UNORM | DXGI_BC1_UNORM_SRGB => DdsFormat::Bc1,
+        DXGI_BC2_UNORM | DXGI_BC2_UNORM_SRGB => DdsFormat::Bc2,
+        DXGI_BC3_UNORM | DXGI_BC3_UNORM_SRGB => DdsFormat::Bc3,
+        DXGI_R8G8B8A8_UNORM | DXGI_R8G8B8A8_UNORM_SRGB => DdsFormat::Masked {
+            bits: 32,
+            masks: [0xFF, 0xFF00, 0xFF0000, 0xFF000000],
+        },
+        DXGI_B8G8R8A8_UNORM | DXGI_B8G8R8A8_UNORM_SRGB => DdsFormat::Masked {
+            bits: 32,
+            masks: [0xFF0000, 0xFF00, 0xFF, 0xFF000000],
+        },
+        other => {
+            return Err(Error::Texture(format!("unsupported DXGI format {}", other)));
+        }
+    })
+}
+
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    let mut crc = Crc32Writer::new();
+    crc.update(kind);
+    crc.update(data);
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.finish().to_be_bytes())?;
+    Ok(())
+}
+
+fn decode_blocks(format: DdsFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>> {
+    let (width, height) = (width as usize, height as usize);
+    let block_size = if format == DdsFormat::Bc1 { 8 } else { 16 };
+    let blocks_wide = width.div_ceil(4);
+    let blocks_high = height.div_ceil(4);
+    let needed = blocks_wide * blocks_high * block_size;
+    if data.len() < needed {
+        return Err(truncated(needed, data.len()));
+    }
+
+    let mut rgba = vec![0u8; width * height * 4];
+    for (index, block) in data[..needed].chunks_exact(block_size).enumerate() {
+        let (block_x, block_y) = (index % blocks_wide * 4, index / blocks_wide * 4);
+        let pixels = match format {
+            DdsFormat::Bc1 => decode_color_block(block, true),
+            DdsFormat::Bc2 => {
+                let mut pixels = decode_color_block(&block[8..], false);
+                let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+                for (texel, pixel) in pixels.iter_mut().enumerate() {
+                    pixel[3] = ((alpha >> (texel * 4)) & 0xF) as u8 * 17;
+                }
+                pixels
+            }
+            _ => {
+                let mut pixels = decode_color_block(&block[8..], false);
+                let alphas = alpha_palette(block[0], block[1]);
+                let mut indices = [0u8; 8];
+                indices[..6].copy_from_slice(&block[2..8]);
+                let indices = u64::from_le_bytes(indices);
+                for (texel, pixel) in pixels.iter_mut().enumerate() {
+                    pixel[3] = alphas[((indices >> (texel * 3)) & 0x7) as usize];
+                }
+                pixels
+            }
+        };
+        for (texel, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (block_x + texel % 4, block_y + texel / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+/// The 16 texels of a BC1 color block. Only BC1 itself has the three-color mode with
+/// transparent black.
+fn decode_color_block(block: &[u8], bc1: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (c0, c1) = (rgb565(color0), rgb565(color1));
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+    let blend = |wa, wb| {
+        [
+            mix(c0[0], c1[0], wa, wb),
+            mix(c0[1], c1[1], wa, wb),
+            mix(c0[2], c1[2], wa, wb),
+            255,
+        ]
+    };
+    let palette = if color0 > color1 || !bc1 {
+        [c0, c1, blend(2, 1), blend(1, 2)]
+    } else {
+        [c0, c1, blend(1, 1), [0, 0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    std::array::from_fn(|texel| palette[((indices >> (texel * 2)) & 0x3) as usize])
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = (color >> 11) & 0x1F;
+    let g = (color >> 5) & 0x3F;
+    let b = color & 0x1F;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+        255,
+    ]
+}
+
+fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a0w, a1w) = (a0 as u16, a1 as u16);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for step in 1..7 {
+            palette[step + 1] = (((7 - step as u16) * a0w + step as u16 * a1w) / 7) as u8;
+        }
+    } else {
+        for step in 1..5 {
+            palette[step + 1] = (((5 - step as u16) * a0w + step as u16 * a1w) / 5) as u8;
+        }
+    }
+    palette
+}
+
+fn decode_masked(
+    bits: u32,
+    masks: [u32; 4],
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    if !matches!(bits, 8 | 16 | 24 | 32) {
+        return Err(Error::Texture(format!(
+            "unsupported DDS bit count {}",
+            bits
+        )));
+    }
+    let pixel_size = bits as usize / 8;
+    let pixels = width as usize * height as usize;
+    let needed = pixels * pixel_size;
+    if data.len() < needed {
+        return Err(truncated(needed, data.len()));
+    }
+
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    for pixel in data[..needed].chunks_exact(pixel_size) {
+        let mut value = [0u8; 4];
+        value[..pixel_size].copy_from_slice(pixel);
+        let value = u32::from_le_bytes(value);
+        for (channel, mask) in masks.iter().enumerate() {
+            rgba.push(match mask {
+                0 if channel == 3 => 255,
+                0 => 0,
+                mask => {
+                    let max = mask >> mask.trailing_zeros();
+                    (((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64) as u8
+                }
+            });
+        }
+    }
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds_header(width: u32, height: u32, pf_flags: u32, four_cc: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[76..80].copy_from_slice(&32u32.to_le_bytes());
+        bytes[80..84].copy_from_slice(&pf_flags.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes
+    }
+
+    #[test]
+    fn decodes_dxt1_and_bgra_textures() {
+        // 2x2 DXT1 between red and blue: palette entries 0 and 1 on the first row,
+        // the two blends on the second
+        let mut dds = dds_header(2, 2, DDPF_FOURCC, b"DXT1");
+        dds.extend_from_slice(&0xF800u16.to_le_bytes());
+        dds.extend_from_slice(&0x001Fu16.to_le_bytes());
+        dds.extend_from_slice(&0b1110_0000_0100u32.to_le_bytes());
+        let image = Image::from_dds(&dds).unwrap();
+        assert_eq!(
+            image.rgba,
+            [
+                255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255
+            ]
+        );
+
+        let mut dds = dds_header(1, 1, DDPF_RGB | DDPF_ALPHAPIXELS, &[0; 4]);
+        dds[88..92].copy_from_slice(&32u32.to_le_bytes());
+        for (offset, mask) in [
+            (92, 0xFF0000u32),
+            (96, 0xFF00),
+            (100, 0xFF),
+            (104, 0xFF000000),
+        ] {
+            dds[offset..offset + 4].copy_from_slice(&mask.to_le_bytes());
+        }
+        dds.extend_from_slice(&[30, 20, 10, 128]);
+        let image = Image::from_dds(&dds).unwrap();
+        assert_eq!(image.rgba, [10, 20, 30, 128]);
+
+        let png = image.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // CRC of an empty IEND chunk
+        assert_eq!(&png[png.len() - 4..], [0xAE, 0x42, 0x60, 0x82]);
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{self, Read, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 pub struct BinaryReader<R: Read + Seek> {
     pub reader: R,
@@ -60,7 +60,7 @@ impl<R: Read + Seek> BinaryReader<R> {
     }
 
     pub fn tell(&mut self) -> io::Result<u64> {
-        self.reader.seek(SeekFrom::Current(0))
+        self.reader.stream_position()
     }
 
     pub fn file_size(&mut self) -> io::Result<u64> {
@@ -84,8 +84,8 @@ impl<R: Read + Seek> BinaryReader<R> {
     /// Reads a fixed-size array of 16 `f32` values.
     pub fn read_f32_array_16(&mut self) -> io::Result<[f32; 16]> {
         let mut buffer = [0.0_f32; 16];
-        for i in 0..16 {
-            buffer[i] = self.read_f32()?;
+        for value in buffer.iter_mut() {
+            *value = self.read_f32()?;
         }
         Ok(buffer)
     }
@@ -104,3 +104,51 @@ impl<R: Read + Seek> BinaryReader<R> {
         Ok(())
     }
 }
+
+pub struct BinaryWriter<W: Write + Seek> {
+    pub writer: W,
+}
+
+impl<W: Write + Seek> BinaryWriter<W> {
+    /// Creates a new `BinaryWriter` instance.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> io::Result<()> {
+        self.writer.write_u8(value)
+    }
+
+    pub fn write_i32(&mut self, value: i32) -> io::Result<()> {
+        self.writer.write_i32::<LittleEndian>(value)
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.writer.write_u32::<LittleEndian>(value)
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.writer.write_u16::<LittleEndian>(value)
+    }
+
+    pub fn write_f32(&mut self, value: f32) -> io::Result<()> {
+        self.writer.write_f32::<LittleEndian>(value)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)
+    }
+
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+        self.writer.seek(pos)?;
+        Ok(())
+    }
+
+    pub fn tell(&mut self) -> io::Result<u64> {
+        self.writer.stream_position()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Serialize;
+use thiserror::Error;
+
+/// A data-quality problem a loader recovered from. Collected on the parsed file in
+/// lenient mode and turned into an error in strict mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Error)]
+pub enum ParseWarning {
+    #[error("unknown chunk id {chunk_id} (size {size}, version {version}) at offset {offset}")]
+    UnknownChunk {
+        offset: u64,
+        chunk_id: u32,
+        size: u32,
+        version: u32,
+    },
+
+    #[error("unsupported {format} version {version} at offset {offset}")]
+    UnsupportedVersion {
+        offset: u64,
+        format: &'static str,
+        version: u32,
+    },
+
+    /// A parsed chunk body did not consume exactly its declared size. `consumed` below
+    /// `declared` is an under-read, above it an over-read into the next chunk.
+    #[error(
+        "chunk id {chunk_id} (version {version}) at offset {offset} declares {declared} bytes but {consumed} were read"
+    )]
+    SizeMismatch {
+        offset: u64,
+        chunk_id: u32,
+        version: u32,
+        declared: u32,
+        consumed: u64,
+    },
+
+    #[error("chunk id {chunk_id} (version {version}) at offset {offset} may only appear once")]
+    DuplicateChunk {
+        offset: u64,
+        chunk_id: u32,
+        version: u32,
+    },
+
+    #[error(
+        "chunk id {chunk_id} at offset {offset} references the mesh of node {node_index}, which does not exist"
+    )]
+    MissingMesh {
+        offset: u64,
+        chunk_id: u32,
+        node_index: u32,
+    },
+
+    #[error("skipped {count} bytes at offset {offset}")]
+    SkippedBytes { offset: u64, count: u64 },
+
+    #[error("suspicious {field}: declared {declared}, actual {actual}")]
+    SuspiciousValue {
+        field: &'static str,
+        declared: u64,
+        actual: u64,
+    },
+
+    #[error("bad magic: expected {expected:08x}, found {found:08x}")]
+    BadMagic { expected: u32, found: u32 },
+}
@@ -1,12 +1,38 @@
 #![allow(dead_code)]
+#![allow(clippy::enum_variant_names)]
+use crate::collada;
+use crate::error::{Error, Result};
+use crate::events::{Operation, Task};
+use crate::export::{CollisionMeshes, ExportOptions, GltfOptions};
+use crate::gltf::{self, GltfMaterial, GltfMesh, GltfNode, GltfPrimitive, GltfScene};
+use crate::instrument::{self, Phase};
 use crate::ipf::IPFFile;
+use crate::naming::{NameFields, OutputLayout, relative_path};
+use crate::options::{ConflictPolicy, ParseOptions};
+use crate::partial::Parsed;
+use crate::plugin::{self, CustomChunk};
+use crate::serial;
+use crate::tangents;
+use crate::text::TextDecoding;
+use crate::texture;
 use crate::tosreader::BinaryReader;
-use binrw::{BinRead, binread};
+use crate::warning::ParseWarning;
+use binrw::{BinRead, BinWrite, Endian, binrw};
+use bytemuck::Pod;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const XAC_FOURCC: u32 = u32::from_le_bytes(*b"XAC ");
+/// `MaterialLayer::map_type` of diffuse textures
+const DIFFUSE_MAP: u8 = 2;
 
 enum SkeletalMotionType {
     SkelmotiontypeNormal = 0, // A regular keyframe and keytrack based skeletal motion.
@@ -114,6 +140,7 @@ enum XacAttribute {
 }
 
 // collection of XAC chunk IDs
+#[repr(u32)]
 enum XacChunk {
     XacChunkNode = 0,
     XacChunkMesh = 1,
@@ -134,6 +161,104 @@ enum XacChunk {
     XacForce32bit = 0xFFFFFFFF,
 }
 
+/// Set of XAC chunk kinds for `ParseOptions::chunks`. Chunks outside the set are
+/// skipped by their declared size without being decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSet {
+    known: u32,
+    // Ids the parser has no decoder for, which are only reported as unknown
+    unknown: bool,
+}
+
+impl ChunkSet {
+    pub const NODE: u32 = XacChunk::XacChunkNode as u32;
+    pub const MESH: u32 = XacChunk::XacChunkMesh as u32;
+    pub const SKINNING_INFO: u32 = XacChunk::XacChunkSkinninginfo as u32;
+    pub const STD_MATERIAL: u32 = XacChunk::XacChunkStdmaterial as u32;
+    pub const STD_MATERIAL_LAYER: u32 = XacChunk::XacChunkStdmateriallayer as u32;
+    pub const FX_MATERIAL: u32 = XacChunk::XacChunkFxmaterial as u32;
+    pub const LIMIT: u32 = XacChunk::XacLimit as u32;
+    pub const INFO: u32 = XacChunk::XacChunkInfo as u32;
+    pub const MESH_LOD_LEVELS: u32 = XacChunk::XacChunkMeshlodlevels as u32;
+    pub const STD_PROG_MORPH_TARGET: u32 = XacChunk::XacChunkStdprogmorphtarget as u32;
+    pub const NODE_GROUPS: u32 = XacChunk::XacChunkNodegroups as u32;
+    pub const NODES: u32 = XacChunk::XacChunkNodes as u32;
+    pub const STD_PMORPH_TARGETS: u32 = XacChunk::XacChunkStdpmorphtargets as u32;
+    pub const MATERIAL_INFO: u32 = XacChunk::XacChunkMaterialinfo as u32;
+    pub const NODE_MOTION_SOURCES: u32 = XacChunk::XacChunkNodemotionsources as u32;
+    pub const ATTACHMENT_NODES: u32 = XacChunk::XacChunkAttachmentnodes as u32;
+
+    /// Every chunk, the default.
+    pub const ALL: ChunkSet = ChunkSet {
+        known: u32::MAX,
+        unknown: true,
+    };
+
+    pub const NONE: ChunkSet = ChunkSet {
+        known: 0,
+        unknown: false,
+    };
+
+    /// What mesh export needs: info, nodes, meshes and materials. Skinning, morph
+    /// targets, limits and the rest are skipped.
+    pub const GEOMETRY: ChunkSet = ChunkSet::NONE
+        .with(Self::INFO)
+        .with(Self::NODE)
+        .with(Self::NODES)
+        .with(Self::MESH)
+        .with(Self::MATERIAL_INFO)
+        .with(Self::STD_MATERIAL)
+        .with(Self::STD_MATERIAL_LAYER)
+        .with(Self::FX_MATERIAL);
+
+    /// What inspecting an actor needs: info, nodes, node groups, limits, attachments
+    /// and materials. Meshes, skinning, morph targets and embedded LOD actors, which
+    /// hold nearly all of the bytes, are skipped.
+    pub const METADATA: ChunkSet = ChunkSet::NONE
+        .with(Self::INFO)
+        .with(Self::NODE)
+        .with(Self::NODES)
+        .with(Self::NODE_GROUPS)
+        .with(Self::LIMIT)
+        .with(Self::ATTACHMENT_NODES)
+        .with(Self::MATERIAL_INFO)
+        .with(Self::STD_MATERIAL)
+        .with(Self::STD_MATERIAL_LAYER)
+        .with(Self::FX_MATERIAL);
+
+    pub const fn with(mut self, chunk_id: u32) -> Self {
+        if chunk_id < 32 {
+            self.known |= 1 << chunk_id;
+        } else {
+            self.unknown = true;
+        }
+        self
+    }
+
+    pub const fn without(mut self, chunk_id: u32) -> Self {
+        if chunk_id < 32 {
+            self.known &= !(1 << chunk_id);
+        } else {
+            self.unknown = false;
+        }
+        self
+    }
+
+    pub const fn contains(&self, chunk_id: u32) -> bool {
+        if chunk_id < 32 {
+            self.known & (1 << chunk_id) != 0
+        } else {
+            self.unknown
+        }
+    }
+}
+
+impl Default for ChunkSet {
+    fn default() -> Self {
+        ChunkSet::ALL
+    }
+}
+
 // material layer map types
 enum XacMaterialLayer {
     XacLayeridUnknown = 0,       // unknown layer
@@ -199,20 +324,258 @@ enum XacChunkData {
 
     XACNodeMotionSources(XACNodeMotionSources),
     XACAttachmentNodes(XACAttachmentNodes),
+
+    /// Raw body of a chunk with an unknown id or an unsupported version
+    Unknown {
+        id: u32,
+        version: u32,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Reads `count` raw bytes after checking that the stream still holds them, so
+/// a corrupted length field fails cleanly instead of forcing a huge allocation.
+fn checked_bytes<R: Read + Seek>(
+    count: u64,
+) -> impl Fn(&mut R, binrw::Endian, ()) -> binrw::BinResult<Vec<u8>> {
+    move |reader, _, _| {
+        let pos = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(pos))?;
+
+        if count > end.saturating_sub(pos) {
+            return Err(binrw::Error::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "length {} at offset {} exceeds the {} bytes left in the file",
+                    count,
+                    pos,
+                    end.saturating_sub(pos)
+                ),
+            )));
+        }
+
+        let mut buffer = vec![0u8; count as usize];
+        reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Result of decoding one chunk body. Short-lived, so the chunk data is not boxed.
+#[allow(clippy::large_enum_variant)]
+enum DecodedChunk {
+    Data(XacChunkData),
+    /// Parsed by a registered `ChunkHandler`
+    Custom(u32, Arc<dyn std::any::Any + Send + Sync>),
+    /// Skipped chunks carry their body once `decode_custom_chunk` has read it
+    UnsupportedVersion(&'static str, Vec<u8>),
+    Unknown(Vec<u8>),
+}
+
+/// What follows the current position in the chunk stream.
+enum ChunkHeader {
+    /// A chunk header and the offset of its body
+    Chunk(FileChunk, u64),
+    /// Offset and length of bytes too short to hold another header
+    Trailing(u64, u64),
+    End,
+}
+
+/// Original vertex count of the first mesh in `chunks` attached to `node_index`.
+fn find_mesh_num_org_verts<'a>(
+    chunks: impl IntoIterator<Item = &'a XacChunkData>,
+    node_index: u32,
+) -> Option<u32> {
+    chunks.into_iter().find_map(|chunk| match chunk {
+        XacChunkData::XACMesh(data) if data.node_index == node_index => Some(data.num_org_verts),
+        XacChunkData::XACMesh2(data) if data.node_index == node_index => Some(data.num_org_verts),
+        _ => None,
+    })
 }
 
-#[binread]
+impl XacChunkData {
+    /// Node whose mesh a skinning chunk belongs to.
+    fn skinning_node_index(&self) -> Option<u32> {
+        match self {
+            XacChunkData::XacSkinningInfo(data) => Some(data.node_index),
+            XacChunkData::XacSkinningInfo2(data) => Some(data.node_index),
+            XacChunkData::XacSkinningInfo3(data) => Some(data.node_index),
+            XacChunkData::XacSkinningInfo4(data) => Some(data.node_index),
+            _ => None,
+        }
+    }
+
+    /// Chunk id and version this body is stored under.
+    fn chunk_id_and_version(&self) -> (u32, u32) {
+        let (chunk, version) = match self {
+            XacChunkData::Unknown { id, version, .. } => return (*id, *version),
+            XacChunkData::XacInfo(_) => (XacChunk::XacChunkInfo, 1),
+            XacChunkData::XacInfo2(_) => (XacChunk::XacChunkInfo, 2),
+            XacChunkData::XacInfo3(_) => (XacChunk::XacChunkInfo, 3),
+            XacChunkData::XacInfo4(_) => (XacChunk::XacChunkInfo, 4),
+            XacChunkData::XacNode(_) => (XacChunk::XacChunkNode, 1),
+            XacChunkData::XacNode2(_) => (XacChunk::XacChunkNode, 2),
+            XacChunkData::XacNode3(_) => (XacChunk::XacChunkNode, 3),
+            XacChunkData::XacNode4(_) => (XacChunk::XacChunkNode, 4),
+            XacChunkData::XacSkinningInfo(_) => (XacChunk::XacChunkSkinninginfo, 1),
+            XacChunkData::XacSkinningInfo2(_) => (XacChunk::XacChunkSkinninginfo, 2),
+            XacChunkData::XacSkinningInfo3(_) => (XacChunk::XacChunkSkinninginfo, 3),
+            XacChunkData::XacSkinningInfo4(_) => (XacChunk::XacChunkSkinninginfo, 4),
+            XacChunkData::XacStandardMaterial(_) => (XacChunk::XacChunkStdmaterial, 1),
+            XacChunkData::XacStandardMaterial2(_) => (XacChunk::XacChunkStdmaterial, 2),
+            XacChunkData::XacStandardMaterial3(_) => (XacChunk::XacChunkStdmaterial, 3),
+            XacChunkData::XACStandardMaterialLayer(_) => (XacChunk::XacChunkStdmateriallayer, 1),
+            XacChunkData::XACStandardMaterialLayer2(_) => (XacChunk::XacChunkStdmateriallayer, 2),
+            XacChunkData::XACSubMesh(_) | XacChunkData::XACMesh(_) => (XacChunk::XacChunkMesh, 1),
+            XacChunkData::XACMesh2(_) => (XacChunk::XacChunkMesh, 2),
+            XacChunkData::XACLimit(_) => (XacChunk::XacLimit, 1),
+            XacChunkData::XACPMorphTarget(_) => (XacChunk::XacChunkStdprogmorphtarget, 1),
+            XacChunkData::XACPMorphTargets(_) => (XacChunk::XacChunkStdpmorphtargets, 1),
+            XacChunkData::XACFXMaterial(_) => (XacChunk::XacChunkFxmaterial, 1),
+            XacChunkData::XACFXMaterial2(_) => (XacChunk::XacChunkFxmaterial, 2),
+            XacChunkData::XACFXMaterial3(_) => (XacChunk::XacChunkFxmaterial, 3),
+            XacChunkData::XACNodeGroup(_) => (XacChunk::XacChunkNodegroups, 1),
+            XacChunkData::XACNodes(_) => (XacChunk::XacChunkNodes, 1),
+            XacChunkData::XACMaterialInfo(_) => (XacChunk::XacChunkMaterialinfo, 1),
+            XacChunkData::XACMaterialInfo2(_) => (XacChunk::XacChunkMaterialinfo, 2),
+            XacChunkData::XACMeshLodLevel(_) => (XacChunk::XacChunkMeshlodlevels, 1),
+            XacChunkData::XACNodeMotionSources(_) => (XacChunk::XacChunkNodemotionsources, 1),
+            XacChunkData::XACAttachmentNodes(_) => (XacChunk::XacChunkAttachmentnodes, 1),
+        };
+        (chunk as u32, version)
+    }
+
+    /// The chunk body in the on-disk layout, in `endian` byte order. Skinning info
+    /// version 1 is followed by data the parser skips, so it cannot be written back.
+    fn to_bytes(&self, endian: Endian) -> Result<Vec<u8>> {
+        let mut body = Cursor::new(Vec::new());
+        match self {
+            XacChunkData::XacInfo(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacInfo2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacInfo3(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacInfo4(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacNode(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacNode2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacNode3(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacNode4(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacSkinningInfo2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacSkinningInfo3(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacSkinningInfo4(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacStandardMaterial(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacStandardMaterial2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XacStandardMaterial3(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACStandardMaterialLayer(data) => {
+                data.write_options(&mut body, endian, ())
+            }
+            XacChunkData::XACStandardMaterialLayer2(data) => {
+                data.write_options(&mut body, endian, ())
+            }
+            XacChunkData::XACMesh(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACMesh2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACLimit(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACPMorphTarget(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACPMorphTargets(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACFXMaterial(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACFXMaterial2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACFXMaterial3(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACNodeGroup(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACNodes(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACMaterialInfo(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACMaterialInfo2(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACNodeMotionSources(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACAttachmentNodes(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::XACMeshLodLevel(data) => data.write_options(&mut body, endian, ()),
+            XacChunkData::Unknown { bytes, .. } => return Ok(bytes.clone()),
+            XacChunkData::XacSkinningInfo(_) | XacChunkData::XACSubMesh(_) => {
+                let (chunk_id, version) = self.chunk_id_and_version();
+                return Err(Error::Xac(format!(
+                    "Chunk {} version {} is only partly parsed and cannot be written",
+                    chunk_id, version
+                )));
+            }
+        }?;
+        Ok(body.into_inner())
+    }
+}
+
+thread_local! {
+    // binrw field parsers cannot see the load options, so the loader publishes the
+    // text policy here for the duration of a parse
+    static TEXT_DECODING: Cell<TextDecoding> = Cell::new(TextDecoding::default());
+    // Node, material and texture names repeat across chunks, so equal names read in
+    // the same parse share one allocation
+    static NAMES: RefCell<HashSet<Arc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// Runs `parse` with `text` as the policy used by `checked_string`, and a name table
+/// of its own for `checked_name`.
+fn with_text_decoding<T>(text: TextDecoding, parse: impl FnOnce() -> T) -> T {
+    let previous = TEXT_DECODING.with(|current| current.replace(text));
+    let names = NAMES.with(RefCell::take);
+    let result = parse();
+    TEXT_DECODING.with(|current| current.set(previous));
+    NAMES.with(|current| current.replace(names));
+    result
+}
+
+/// Length-prefixed string variant of `checked_bytes`.
+fn checked_string<R: Read + Seek>(
+    length: u32,
+) -> impl Fn(&mut R, binrw::Endian, ()) -> binrw::BinResult<String> {
+    move |reader, endian, args| {
+        let pos = reader.stream_position()?;
+        let bytes = checked_bytes(length as u64)(reader, endian, args)?;
+        TEXT_DECODING
+            .with(Cell::get)
+            .decode_vec(bytes)
+            .map_err(|err| binrw::Error::AssertFail {
+                pos,
+                message: err.to_string(),
+            })
+    }
+}
+
+/// Interned variant of `checked_string`.
+fn checked_name<R: Read + Seek>(
+    length: u32,
+) -> impl Fn(&mut R, binrw::Endian, ()) -> binrw::BinResult<Arc<str>> {
+    move |reader, endian, args| {
+        let name = checked_string(length)(reader, endian, args)?;
+        Ok(NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            if let Some(interned) = names.get(name.as_str()) {
+                return interned.clone();
+            }
+            let interned: Arc<str> = name.into();
+            names.insert(interned.clone());
+            interned
+        }))
+    }
+}
+
+/// Writes a string read by `checked_string` or `checked_name` as UTF-8, the encoding
+/// its computed length field counts.
+fn write_text<W: Write + Seek, T: AsRef<str>>(
+    text: &T,
+    writer: &mut W,
+    _: binrw::Endian,
+    _: (),
+) -> binrw::BinResult<()> {
+    writer.write_all(text.as_ref().as_bytes())?;
+    Ok(())
+}
+
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct FileChunk {
     chunk_id: u32,      // The chunk ID
     size_in_bytes: u32, // The size in bytes of this chunk (excluding this struct)
     version: u32,       // The version of the chunk
 }
 
-#[binread]
+// Color [0..1] range
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // Color [0..1] range
 struct FileColor {
     color_red: f32,   // Red
     color_green: f32, // Green
@@ -220,36 +583,69 @@ struct FileColor {
     color_alpha: f32, // Alpha
 }
 
-#[binread]
+// A 3D vector
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // A 3D vector
 struct FileVector3 {
     axis_x: f32, // x+ = to the right
     axis_y: f32, // y+ = up
     axis_z: f32, // z+ = forwards (into the depth)
 }
 
-#[binread]
+// A compressed 3D vector
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // A compressed 3D vector
 struct File16BitVector3 {
     axis_x: u16, // x+ = to the right
     axis_y: u16, // y+ = up
     axis_z: u16, // z+ = forwards (into the depth)
 }
 
-#[binread]
+// A compressed 3D vector
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // A compressed 3D vector
 struct File8BitVector3 {
     axis_x: u8, // x+ = to the right
     axis_y: u8, // y+ = up
     axis_z: u8, // z+ = forwards (into the depth)
 }
 
-#[binread]
+impl FileColor {
+    fn to_array(&self) -> [f32; 4] {
+        [
+            self.color_red,
+            self.color_green,
+            self.color_blue,
+            self.color_alpha,
+        ]
+    }
+}
+
+impl FileVector3 {
+    fn to_array(&self) -> [f32; 3] {
+        [self.axis_x, self.axis_y, self.axis_z]
+    }
+}
+
+impl File16BitVector3 {
+    /// The vector with each component mapped linearly from `0..=u16::MAX` to `min..=max`.
+    fn decompress(&self, min: f32, max: f32) -> [f32; 3] {
+        [self.axis_x, self.axis_y, self.axis_z]
+            .map(|value| min + (max - min) * value as f32 / u16::MAX as f32)
+    }
+}
+
+impl File8BitVector3 {
+    /// The vector with each component mapped linearly from `0..=u8::MAX` to `min..=max`.
+    fn decompress(&self, min: f32, max: f32) -> [f32; 3] {
+        [self.axis_x, self.axis_y, self.axis_z]
+            .map(|value| min + (max - min) * value as f32 / u8::MAX as f32)
+    }
+}
+
+// A quaternion
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // A quaternion
 struct FileQuaternion {
     axis_x: f32,
     axis_y: f32,
@@ -257,9 +653,9 @@ struct FileQuaternion {
     axis_w: f32,
 }
 
-#[binread]
+// The 16-bit component quaternion
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)] // The 16-bit component quaternion
 struct File16BitQuaternion {
     axis_x: i16,
     axis_y: i16,
@@ -267,9 +663,9 @@ struct File16BitQuaternion {
     axis_w: i16,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
+#[brw(little)]
 struct XacHeader {
     fourcc: u32,     // Must be "XAC "
     hi_version: u8,  // High version (e.g., 2 in v2.34)
@@ -278,9 +674,19 @@ struct XacHeader {
     mul_order: u8,   // See enum MULORDER_...
 }
 
-#[binread]
+impl XacHeader {
+    /// Byte order of everything after the header.
+    fn endian(&self) -> Endian {
+        if self.endian_type == 1 {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+}
+
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacInfo {
     repositioning_mask: u32,
     repositioning_node_index: u32,
@@ -289,29 +695,36 @@ struct XacInfo {
     padding: u16,
 
     #[br(temp)]
+    #[bw(calc = source_app.len() as u32)]
     source_app_length: u32,
-    #[br(count = source_app_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(source_app_length))]
+    #[bw(write_with = write_text)]
     source_app: String,
 
     #[br(temp)]
+    #[bw(calc = original_filename.len() as u32)]
     original_filename_length: u32,
-    #[br(count = original_filename_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(original_filename_length))]
+    #[bw(write_with = write_text)]
     original_filename: String,
 
     #[br(temp)]
+    #[bw(calc = compilation_date.len() as u32)]
     compilation_date_length: u32,
-    #[br(count = compilation_date_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(compilation_date_length))]
+    #[bw(write_with = write_text)]
     compilation_date: String,
 
     #[br(temp)]
+    #[bw(calc = actor_name.len() as u32)]
     actor_name_length: u32,
-    #[br(count = actor_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(actor_name_length))]
+    #[bw(write_with = write_text)]
     actor_name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacInfo2 {
     repositioning_mask: u32,
     repositioning_node_index: u32,
@@ -321,29 +734,36 @@ struct XacInfo2 {
     padding: u16,
 
     #[br(temp)]
+    #[bw(calc = source_app.len() as u32)]
     source_app_length: u32,
-    #[br(count = source_app_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(source_app_length))]
+    #[bw(write_with = write_text)]
     source_app: String,
 
     #[br(temp)]
+    #[bw(calc = original_filename.len() as u32)]
     original_filename_length: u32,
-    #[br(count = original_filename_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(original_filename_length))]
+    #[bw(write_with = write_text)]
     original_filename: String,
 
     #[br(temp)]
+    #[bw(calc = compilation_date.len() as u32)]
     compilation_date_length: u32,
-    #[br(count = compilation_date_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(compilation_date_length))]
+    #[bw(write_with = write_text)]
     compilation_date: String,
 
     #[br(temp)]
+    #[bw(calc = actor_name.len() as u32)]
     actor_name_length: u32,
-    #[br(count = actor_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(actor_name_length))]
+    #[bw(write_with = write_text)]
     actor_name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacInfo3 {
     trajectory_node_index: u32,
     motion_extraction_node_index: u32,
@@ -354,29 +774,36 @@ struct XacInfo3 {
     padding: u16,
 
     #[br(temp)]
+    #[bw(calc = source_app.len() as u32)]
     source_app_length: u32,
-    #[br(count = source_app_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(source_app_length))]
+    #[bw(write_with = write_text)]
     source_app: String,
 
     #[br(temp)]
+    #[bw(calc = original_filename.len() as u32)]
     original_filename_length: u32,
-    #[br(count = original_filename_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(original_filename_length))]
+    #[bw(write_with = write_text)]
     original_filename: String,
 
     #[br(temp)]
+    #[bw(calc = compilation_date.len() as u32)]
     compilation_date_length: u32,
-    #[br(count = compilation_date_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(compilation_date_length))]
+    #[bw(write_with = write_text)]
     compilation_date: String,
 
     #[br(temp)]
+    #[bw(calc = actor_name.len() as u32)]
     actor_name_length: u32,
-    #[br(count = actor_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(actor_name_length))]
+    #[bw(write_with = write_text)]
     actor_name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacInfo4 {
     num_lods: u32,
     trajectory_node_index: u32,
@@ -387,29 +814,36 @@ struct XacInfo4 {
     padding: u16,
 
     #[br(temp)]
+    #[bw(calc = source_app.len() as u32)]
     source_app_length: u32,
-    #[br(count = source_app_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(source_app_length))]
+    #[bw(write_with = write_text)]
     source_app: String,
 
     #[br(temp)]
+    #[bw(calc = original_filename.len() as u32)]
     original_filename_length: u32,
-    #[br(count = original_filename_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(original_filename_length))]
+    #[bw(write_with = write_text)]
     original_filename: String,
 
     #[br(temp)]
+    #[bw(calc = compilation_date.len() as u32)]
     compilation_date_length: u32,
-    #[br(count = compilation_date_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(compilation_date_length))]
+    #[bw(write_with = write_text)]
     compilation_date: String,
 
     #[br(temp)]
+    #[bw(calc = actor_name.len() as u32)]
     actor_name_length: u32,
-    #[br(count = actor_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(actor_name_length))]
+    #[bw(write_with = write_text)]
     actor_name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacNode {
     local_quat: FileQuaternion,
     scale_rot: FileQuaternion,
@@ -420,14 +854,15 @@ struct XacNode {
     parent_index: u32,
 
     #[br(temp)]
+    #[bw(calc = node_name.len() as u32)]
     node_name_length: u32,
-    #[br(count = node_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    node_name: String,
+    #[br(parse_with = checked_name(node_name_length))]
+    #[bw(write_with = write_text)]
+    node_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacNode2 {
     local_quat: FileQuaternion,
     scale_rot: FileQuaternion,
@@ -440,14 +875,15 @@ struct XacNode2 {
     padding: [u8; 3],
 
     #[br(temp)]
+    #[bw(calc = node_name.len() as u32)]
     node_name_length: u32,
-    #[br(count = node_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    node_name: String,
+    #[br(parse_with = checked_name(node_name_length))]
+    #[bw(write_with = write_text)]
+    node_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacNode3 {
     local_quat: FileQuaternion,
     scale_rot: FileQuaternion,
@@ -461,14 +897,15 @@ struct XacNode3 {
     padding: [u8; 3],
 
     #[br(temp)]
+    #[bw(calc = node_name.len() as u32)]
     node_name_length: u32,
-    #[br(count = node_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    node_name: String,
+    #[br(parse_with = checked_name(node_name_length))]
+    #[bw(write_with = write_text)]
+    node_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacNode4 {
     local_quat: FileQuaternion,
     scale_rot: FileQuaternion,
@@ -485,31 +922,33 @@ struct XacNode4 {
     padding: [u8; 3],
 
     #[br(temp)]
+    #[bw(calc = node_name.len() as u32)]
     node_name_length: u32,
-    #[br(count = node_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    node_name: String,
+    #[br(parse_with = checked_name(node_name_length))]
+    #[bw(write_with = write_text)]
+    node_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACMeshLodLevel {
     lod_level: u32,
+    #[br(temp)]
+    #[bw(calc = lod_model.len() as u32)]
     size_in_bytes: u32,
-    // Followed by:
-    // Vec<u8> representing LOD model memory file
+    // A whole actor file holding the meshes of this level
+    #[br(parse_with = checked_bytes(size_in_bytes as u64))]
+    lod_model: Vec<u8>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacUv {
     axis_u: f32, // U texture coordinate
     axis_v: f32, // V texture coordinate
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
-#[br(little)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 struct XacSkinningInfo {
     node_index: u32,
     is_for_collision_mesh: u8,
@@ -521,9 +960,8 @@ struct XacSkinningInfo {
     //         XacSkinInfluence[num_influences]
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 #[br(import(num_org_verts:u32))]
-#[br(little)]
 struct XacSkinningInfo2 {
     node_index: u32,           // The node number in the actor
     num_total_influences: u32, // Total number of influences of all vertices together
@@ -537,9 +975,8 @@ struct XacSkinningInfo2 {
     skinning_info_table_entry: Vec<XacSkinningInfoTableEntry>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 #[br(import(num_org_verts:u32))]
-#[br(little)]
 struct XacSkinningInfo3 {
     node_index: u32,           // The node number in the actor
     num_local_bones: u32,      // Number of local bones used by the mesh
@@ -554,9 +991,8 @@ struct XacSkinningInfo3 {
     skinning_info_table_entry: Vec<XacSkinningInfoTableEntry>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 #[br(import(num_org_verts:u32))]
-#[br(little)]
 struct XacSkinningInfo4 {
     node_index: u32,           // The node number in the actor
     lod: u32,                  // Level of detail
@@ -572,25 +1008,22 @@ struct XacSkinningInfo4 {
     skinning_info_table_entry: Vec<XacSkinningInfoTableEntry>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacSkinningInfoTableEntry {
     start_index: u32,  // Index inside the SkinInfluence array
     num_elements: u32, // Number of influences for this item/entry
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacSkinInfluence {
     weight: f32,
     node_number: u32,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacStandardMaterial {
     ambient: FileColor,    // Ambient color
     diffuse: FileColor,    // Diffuse color
@@ -606,14 +1039,15 @@ struct XacStandardMaterial {
     padding: u8,
 
     #[br(temp)]
+    #[bw(calc = material_name.len() as u32)]
     material_name_length: u32,
-    #[br(count = material_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    material_name: String,
+    #[br(parse_with = checked_name(material_name_length))]
+    #[bw(write_with = write_text)]
+    material_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacStandardMaterial2 {
     ambient: FileColor,
     diffuse: FileColor,
@@ -629,16 +1063,17 @@ struct XacStandardMaterial2 {
     num_layers: u8, // Number of material layers
 
     #[br(temp)]
+    #[bw(calc = material_name.len() as u32)]
     material_name_length: u32,
-    #[br(count = material_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    material_name: String,
+    #[br(parse_with = checked_name(material_name_length))]
+    #[bw(write_with = write_text)]
+    material_name: Arc<str>,
     #[br(count = num_layers)]
     standard_material_layer2: Vec<XACStandardMaterialLayer2>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XacStandardMaterial3 {
     lod: u32, // Level of detail
     ambient: FileColor,
@@ -655,16 +1090,17 @@ struct XacStandardMaterial3 {
     num_layers: u8, // Number of material layers
 
     #[br(temp)]
+    #[bw(calc = material_name.len() as u32)]
     material_name_length: u32,
-    #[br(count = material_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    material_name: String,
+    #[br(parse_with = checked_name(material_name_length))]
+    #[bw(write_with = write_text)]
+    material_name: Arc<str>,
     #[br(count = num_layers)]
     standard_material_layer2: Vec<XACStandardMaterialLayer2>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACStandardMaterialLayer {
     amount: f32,           // the amount, between 0 and 1
     u_offset: f32,         // u offset (horizontal texture shift)
@@ -676,14 +1112,15 @@ struct XACStandardMaterialLayer {
     map_type: u8,          // the map type
     padding: u8,           // alignment
     #[br(temp)]
+    #[bw(calc = texture_name.len() as u32)]
     texture_name_length: u32,
-    #[br(count = texture_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    texture_name: String,
+    #[br(parse_with = checked_name(texture_name_length))]
+    #[bw(write_with = write_text)]
+    texture_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACStandardMaterialLayer2 {
     amount: f32,
     u_offset: f32,
@@ -695,14 +1132,15 @@ struct XACStandardMaterialLayer2 {
     map_type: u8,
     blend_mode: u8, // blend mode for texture layering
     #[br(temp)]
+    #[bw(calc = texture_name.len() as u32)]
     texture_name_length: u32,
-    #[br(count = texture_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    texture_name: String,
+    #[br(parse_with = checked_name(texture_name_length))]
+    #[bw(write_with = write_text)]
+    texture_name: Arc<str>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 #[br(import(total_verts:u32))]
-#[br(little)]
 struct XACVertexAttributeLayer {
     layer_type_id: u32,
     attrib_size_in_bytes: u32,
@@ -710,13 +1148,128 @@ struct XACVertexAttributeLayer {
     is_scale: u8,
     padding: [u8; 2],
 
-    #[br(count = attrib_size_in_bytes * total_verts )]
-    mesh_data: Vec<u8>,
+    #[br(parse_with = vertex_layer_data(layer_type_id, attrib_size_in_bytes, total_verts))]
+    #[bw(write_with = write_vertex_layer_data)]
+    data: VertexLayerData,
+}
+
+/// Vertex attribute layer contents, decoded by `layer_type_id`. Layers of an unknown
+/// type, or whose element size does not match their type, keep their bytes.
+#[derive(Debug, Serialize, Deserialize)]
+enum VertexLayerData {
+    Positions(Vec<[f32; 3]>),
+    Normals(Vec<[f32; 3]>),
+    Tangents(Vec<[f32; 4]>),
+    Uvs(Vec<[f32; 2]>),
+    Colors32(Vec<u32>),
+    OriginalVertexNumbers(Vec<u32>),
+    Colors128(Vec<[f32; 4]>),
+    Bitangents(Vec<[f32; 3]>),
+    Other(Vec<u8>),
+}
+
+impl Default for VertexLayerData {
+    fn default() -> Self {
+        VertexLayerData::Other(Vec::new())
+    }
+}
+
+impl VertexLayerData {
+    /// Attribute elements are built from 32-bit words in `endian` byte order.
+    fn decode(layer_type_id: u32, element_size: u32, bytes: Vec<u8>, endian: Endian) -> Self {
+        fn elements<T: Pod>(bytes: &[u8], endian: Endian) -> Vec<T> {
+            let mut values: Vec<T> = bytemuck::pod_collect_to_vec(bytes);
+            for word in bytemuck::cast_slice_mut::<T, u32>(&mut values) {
+                *word = match endian {
+                    Endian::Big => u32::from_be(*word),
+                    Endian::Little => u32::from_le(*word),
+                };
+            }
+            values
+        }
+
+        let is = |attribute: XacAttribute, size: usize| {
+            layer_type_id == attribute as u32 && element_size as usize == size
+        };
+        if is(XacAttribute::AttribPositions, 12) {
+            VertexLayerData::Positions(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribNormals, 12) {
+            VertexLayerData::Normals(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribTangents, 16) {
+            VertexLayerData::Tangents(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribUvcoords, 8) {
+            VertexLayerData::Uvs(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribColors32, 4) {
+            VertexLayerData::Colors32(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribOrgvtxnumbers, 4) {
+            VertexLayerData::OriginalVertexNumbers(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribColors128, 16) {
+            VertexLayerData::Colors128(elements(&bytes, endian))
+        } else if is(XacAttribute::AttribBitangents, 12) {
+            VertexLayerData::Bitangents(elements(&bytes, endian))
+        } else {
+            VertexLayerData::Other(bytes)
+        }
+    }
+
+    /// The layer bytes as stored in a file of `endian` byte order.
+    fn to_bytes(&self, endian: Endian) -> Vec<u8> {
+        fn words<T: Pod>(values: &[T], endian: Endian) -> Vec<u8> {
+            bytemuck::cast_slice::<T, u32>(values)
+                .iter()
+                .flat_map(|word| match endian {
+                    Endian::Big => word.to_be_bytes(),
+                    Endian::Little => word.to_le_bytes(),
+                })
+                .collect()
+        }
+
+        match self {
+            VertexLayerData::Positions(values)
+            | VertexLayerData::Normals(values)
+            | VertexLayerData::Bitangents(values) => words(values, endian),
+            VertexLayerData::Tangents(values) | VertexLayerData::Colors128(values) => {
+                words(values, endian)
+            }
+            VertexLayerData::Uvs(values) => words(values, endian),
+            VertexLayerData::Colors32(values) | VertexLayerData::OriginalVertexNumbers(values) => {
+                words(values, endian)
+            }
+            VertexLayerData::Other(bytes) => bytes.clone(),
+        }
+    }
+}
+
+/// Reads the `element_size * total_verts` bytes of a vertex attribute layer with
+/// `checked_bytes` and decodes them, see `VertexLayerData::decode`.
+fn vertex_layer_data<R: Read + Seek>(
+    layer_type_id: u32,
+    element_size: u32,
+    total_verts: u32,
+) -> impl Fn(&mut R, binrw::Endian, ()) -> binrw::BinResult<VertexLayerData> {
+    move |reader, endian, args| {
+        let bytes = checked_bytes(element_size as u64 * total_verts as u64)(reader, endian, args)?;
+        Ok(VertexLayerData::decode(
+            layer_type_id,
+            element_size,
+            bytes,
+            endian,
+        ))
+    }
+}
+
+fn write_vertex_layer_data<W: Write + Seek>(
+    data: &VertexLayerData,
+    writer: &mut W,
+    endian: binrw::Endian,
+    _: (),
+) -> binrw::BinResult<()> {
+    writer.write_all(&data.to_bytes(endian))?;
+    Ok(())
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
-#[br(little)]
 struct XACSubMesh {
     num_indices: u32,
     num_verts: u32,
@@ -730,8 +1283,7 @@ struct XACSubMesh {
     bones: Vec<u32>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
-#[br(little)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 struct XACMesh {
     node_index: u32,
     num_org_verts: u32,
@@ -749,8 +1301,7 @@ struct XACMesh {
     sub_meshes: Vec<XACSubMesh>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, BinRead)]
-#[br(little)]
+#[derive(Default, Debug, Serialize, Deserialize, BinRead, BinWrite)]
 struct XACMesh2 {
     node_index: u32,
     lod: u32,
@@ -769,9 +1320,8 @@ struct XACMesh2 {
     sub_meshes: Vec<XACSubMesh>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACLimit {
     translation_min: FileVector3,
     translation_max: FileVector3,
@@ -783,9 +1333,8 @@ struct XACLimit {
     node_number: u32,     // the node number where this info belongs
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACPMorphTarget {
     range_min: f32,              // the slider min
     range_max: f32,              // the slider max
@@ -795,8 +1344,10 @@ struct XACPMorphTarget {
     phoneme_sets: u32,           // number of phoneme sets
 
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
     #[br(count = num_mesh_deform_deltas)]
     morph_target_mesh_deltas: Vec<XACPMorphTargetMeshDeltas>,
@@ -804,19 +1355,17 @@ struct XACPMorphTarget {
     morph_target_transform: Vec<XACPMorphTargetTransform>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACPMorphTargets {
     num_morph_targets: u32, // number of morph targets
     lod: u32,               // LOD level
     #[br(count = num_morph_targets)]
-    morph_targets: Vec<XACPMorphTargets>,
+    morph_targets: Vec<XACPMorphTarget>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACPMorphTargetMeshDeltas {
     node_index: u32,
     min_value: f32,    // min range for x, y, z of compressed position vectors
@@ -832,9 +1381,8 @@ struct XACPMorphTargetMeshDeltas {
     vertex_numbers: Vec<u32>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACPMorphTargetTransform {
     node_index: u32,                // node name where transform belongs
     rotation: FileQuaternion,       // node rotation
@@ -843,25 +1391,30 @@ struct XACPMorphTargetTransform {
     scale: FileVector3,             // node delta scale
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXMaterial {
     num_int_params: u32,
     num_float_params: u32,
     num_color_params: u32,
     num_bitmap_params: u32,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
     #[br(temp)]
+    #[bw(calc = effect_file.len() as u32)]
     effect_file_length: u32,
-    #[br(count = effect_file_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(effect_file_length))]
+    #[bw(write_with = write_text)]
     effect_file: String,
     #[br(temp)]
+    #[bw(calc = shader_technique.len() as u32)]
     shader_technique_length: u32,
-    #[br(count = shader_technique_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(shader_technique_length))]
+    #[bw(write_with = write_text)]
     shader_technique: String,
 
     #[br(if(num_int_params > 0), count = num_int_params)]
@@ -877,9 +1430,8 @@ struct XACFXMaterial {
     xac_fx_bitmap_parameter: Option<Vec<XACFXBitmapParameter>>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXMaterial2 {
     num_int_params: u32,
     num_float_params: u32,
@@ -888,16 +1440,22 @@ struct XACFXMaterial2 {
     num_vector3_params: u32,
     num_bitmap_params: u32,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
     #[br(temp)]
+    #[bw(calc = effect_file.len() as u32)]
     effect_file_length: u32,
-    #[br(count = effect_file_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(effect_file_length))]
+    #[bw(write_with = write_text)]
     effect_file: String,
     #[br(temp)]
+    #[bw(calc = shader_technique.len() as u32)]
     shader_technique_length: u32,
-    #[br(count = shader_technique_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(shader_technique_length))]
+    #[bw(write_with = write_text)]
     shader_technique: String,
 
     #[br(if(num_int_params > 0), count = num_int_params)]
@@ -919,9 +1477,8 @@ struct XACFXMaterial2 {
     xac_fx_bitmap_parameter: Option<Vec<XACFXBitmapParameter>>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXMaterial3 {
     lod: u32, // level of detail
     num_int_params: u32,
@@ -931,16 +1488,22 @@ struct XACFXMaterial3 {
     num_vector3_params: u32,
     num_bitmap_params: u32,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
     #[br(temp)]
+    #[bw(calc = effect_file.len() as u32)]
     effect_file_length: u32,
-    #[br(count = effect_file_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(effect_file_length))]
+    #[bw(write_with = write_text)]
     effect_file: String,
     #[br(temp)]
+    #[bw(calc = shader_technique.len() as u32)]
     shader_technique_length: u32,
-    #[br(count = shader_technique_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(shader_technique_length))]
+    #[bw(write_with = write_text)]
     shader_technique: String,
 
     #[br(if(num_int_params > 0), count = num_int_params)]
@@ -962,95 +1525,103 @@ struct XACFXMaterial3 {
     xac_fx_bitmap_parameter: Option<Vec<XACFXBitmapParameter>>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXIntParameter {
     value: i32, // Beware, signed integer since negative values are allowed
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXFloatParameter {
     value: f32,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXColorParameter {
     value: FileColor,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXVector3Parameter {
     value: FileVector3,
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXBoolParameter {
     value: u8, // 0 = no, 1 = yes
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACFXBitmapParameter {
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 
     #[br(temp)]
+    #[bw(calc = value_name.len() as u32)]
     value_name_length: u32,
-    #[br(count = value_name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
-    value_name: String,
+    #[br(parse_with = checked_name(value_name_length))]
+    #[bw(write_with = write_text)]
+    value_name: Arc<str>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACNodeGroup {
     num_nodes: u16,
     disabled_on_default: u8, // 0 = no, 1 = yes
 
     #[br(temp)]
+    #[bw(calc = name.len() as u32)]
     name_length: u32,
-    #[br(count = name_length, map = |s: Vec<u8>| String::from_utf8_lossy(&s).to_string())]
+    #[br(parse_with = checked_string(name_length))]
+    #[bw(write_with = write_text)]
     name: String,
 
     #[br(count = num_nodes)]
     data: Vec<u16>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACNodes {
     num_nodes: u32,
     num_root_nodes: u32,
@@ -1059,18 +1630,16 @@ struct XACNodes {
     xac_node: Vec<XacNode4>,
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACMaterialInfo {
     num_total_materials: u32, // Total number of materials to follow (including default/extra material)
     num_standard_materials: u32, // Number of standard materials in the file
     num_fx_materials: u32,    // Number of FX materials in the file
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACMaterialInfo2 {
     lod: u32,                    // Level of detail
     num_total_materials: u32, // Total number of materials to follow (including default/extra material)
@@ -1078,9 +1647,8 @@ struct XACMaterialInfo2 {
     num_fx_materials: u32,    // Number of FX materials in the file
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACNodeMotionSources {
     num_nodes: u32,
 
@@ -1088,9 +1656,8 @@ struct XACNodeMotionSources {
     node_indices: Vec<u16>, // List of node indices (optional if mirroring is not set)
 }
 
-#[binread]
+#[binrw]
 #[derive(Default, Debug, Serialize, Deserialize)]
-#[br(little)]
 struct XACAttachmentNodes {
     num_nodes: u32,
 
@@ -1103,10 +1670,61 @@ pub struct XACFile {
     header: XacHeader,
     chunk: Vec<FileChunk>,
     chunk_data: Vec<XacChunkData>,
+    #[serde(skip)]
+    warnings: Vec<ParseWarning>,
+    // Index in `chunk_data` of every chunk that may only appear once
+    #[serde(skip)]
+    singleton_chunks: HashMap<u32, usize>,
+    #[serde(skip)]
+    custom_chunks: Vec<CustomChunk>,
+    // How each chunk of the last load was handled, for `ParseReport`
+    #[serde(skip)]
+    trace: Vec<ChunkTrace>,
+}
+
+/// How the loader handled a chunk, see `ParseReport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChunkOutcome {
+    /// Decoded by the built-in parsers
+    Parsed,
+    /// Decoded by a registered `ChunkHandler`
+    Custom,
+    /// Unknown id or unsupported version, kept as raw bytes
+    Raw,
+    /// Left out by `ParseOptions::chunks`
+    Skipped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ChunkTrace {
+    /// Offset of the chunk header
+    pub offset: u64,
+    pub chunk_id: u32,
+    pub version: u32,
+    /// Declared body size
+    pub size: u32,
+    pub outcome: ChunkOutcome,
+}
+
+/// Record of one load: every chunk in file order and the warnings raised, see
+/// `XACFile::load_from_slice_with_report`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ParseReport {
+    pub chunks: Vec<ChunkTrace>,
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl ParseReport {
+    /// Chunks whose body was shorter to parse than declared, leaving bytes unread.
+    pub fn under_reads(&self) -> impl Iterator<Item = &ParseWarning> {
+        self.warnings.iter().filter(|warning| {
+            matches!(warning, ParseWarning::SizeMismatch { declared, consumed, .. } if *consumed < *declared as u64)
+        })
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct SubMesh {
     pub texture_name: String,
     pub position_count: usize,
@@ -1127,10 +1745,18 @@ pub struct SubMesh {
     pub bitangents: Vec<[f32; 3]>,
     pub indices_count: usize,
     pub indices: Vec<u32>,
+    /// Four bones (node indices) per vertex, strongest first; empty for meshes
+    /// without skinning info
+    #[serde(default)]
+    pub bone_indices: Vec<[u16; 4]>,
+    /// Weights of `bone_indices`, summing to one per vertex
+    #[serde(default)]
+    pub bone_weights: Vec<[f32; 4]>,
 }
 
-#[pymethods]
+#[cfg_attr(feature = "python", pymethods)]
 impl SubMesh {
+    #[cfg(feature = "python")]
     #[new]
     fn new() -> Self {
         SubMesh::default()
@@ -1140,6 +1766,30 @@ impl SubMesh {
         &self.texture_name
     }
 
+    /// Fills in the tangents when the submesh has none, and the bitangents when those
+    /// are missing too, see `Mesh::compute_tangents`.
+    pub fn compute_tangents(&mut self) {
+        if !self.tangents.is_empty() {
+            return;
+        }
+        let Some(tangents) = tangents::compute(
+            &self.positions,
+            &self.normals,
+            &self.uvcoords,
+            &self.indices,
+        ) else {
+            return;
+        };
+        if self.bitangents.is_empty() {
+            self.bitangents = (self.normals.iter().zip(&tangents))
+                .map(|(&normal, &tangent)| tangents::bitangent(normal, tangent))
+                .collect();
+            self.bitangent_count = self.bitangents.len();
+        }
+        self.tangent_count = tangents.len();
+        self.tangents = tangents;
+    }
+
     pub fn position_count(&self) -> usize {
         self.position_count
     }
@@ -1211,17 +1861,74 @@ impl SubMesh {
     pub fn indices(&self) -> Vec<u32> {
         self.indices.clone()
     }
+
+    pub fn bone_indices(&self) -> Vec<[u16; 4]> {
+        self.bone_indices.clone()
+    }
+
+    pub fn bone_weights(&self) -> Vec<[f32; 4]> {
+        self.bone_weights.clone()
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct Mesh {
     pub submesh_count: usize,
     pub submeshes: Vec<SubMesh>,
+    /// Blend shapes of the mesh; empty when no morph target deforms it
+    #[serde(default)]
+    pub morph_targets: Vec<MorphTarget>,
+    /// Set for collision geometry, which is not meant to be rendered
+    #[serde(default)]
+    pub is_collision: bool,
+}
+
+/// A morph target (blend shape) of one mesh. The deltas are given per submesh, in the
+/// order of `Mesh::submeshes`, with one entry per vertex; vertices the target leaves
+/// alone have zero deltas.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct MorphTarget {
+    pub name: String,
+    /// Range of the weight slider
+    pub range_min: f32,
+    pub range_max: f32,
+    pub position_deltas: Vec<Vec<[f32; 3]>>,
+    pub normal_deltas: Vec<Vec<[f32; 3]>>,
+    pub tangent_deltas: Vec<Vec<[f32; 3]>>,
 }
 
-#[pymethods]
+#[cfg_attr(feature = "python", pymethods)]
+impl MorphTarget {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn range_min(&self) -> f32 {
+        self.range_min
+    }
+
+    pub fn range_max(&self) -> f32 {
+        self.range_max
+    }
+
+    pub fn position_deltas(&self) -> Vec<Vec<[f32; 3]>> {
+        self.position_deltas.clone()
+    }
+
+    pub fn normal_deltas(&self) -> Vec<Vec<[f32; 3]>> {
+        self.normal_deltas.clone()
+    }
+
+    pub fn tangent_deltas(&self) -> Vec<Vec<[f32; 3]>> {
+        self.tangent_deltas.clone()
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
 impl Mesh {
+    #[cfg(feature = "python")]
     #[new]
     fn new() -> Self {
         Mesh::default()
@@ -1234,1872 +1941,4481 @@ impl Mesh {
     pub fn submeshes(&self) -> Vec<SubMesh> {
         self.submeshes.clone()
     }
-}
 
-impl XACFile {
-    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> io::Result<Self> {
-        let file = std::fs::File::open(file_path)?;
-        let mut buf_reader = BufReader::new(file);
-        let mut binary_reader = BinaryReader::new(&mut buf_reader);
-        Self::load_from_reader(&mut binary_reader)
+    pub fn morph_targets(&self) -> Vec<MorphTarget> {
+        self.morph_targets.clone()
     }
 
-    pub fn load_from_bytes(mut bytes: Vec<u8>) -> io::Result<Self> {
-        let cursor = Cursor::new(&mut bytes);
-        let mut binary_reader = BinaryReader::new(cursor);
-        Self::load_from_reader(&mut binary_reader)
+    pub fn is_collision(&self) -> bool {
+        self.is_collision
     }
 
-    fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> io::Result<Self> {
-        let mut xac_data = XACFile::default();
-        xac_data.read_header(reader)?;
-        xac_data.read_chunk(reader)?;
-
-        Ok(xac_data)
+    /// The axis-aligned box around the vertices of every submesh as `(min, max)`,
+    /// `None` for a mesh without vertices.
+    pub fn aabb(&self) -> Option<([f32; 3], [f32; 3])> {
+        aabb(self.positions())
     }
 
-    fn read_header<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> io::Result<&mut Self> {
-        self.header = XacHeader::read(&mut reader.reader).unwrap(); // Use binread to read the struct
-        Ok(self)
+    /// A sphere around every vertex as `(center, radius)`, centered on the `aabb`.
+    pub fn bounding_sphere(&self) -> Option<([f32; 3], f32)> {
+        let (min, max) = self.aabb()?;
+        let center: [f32; 3] = std::array::from_fn(|i| (min[i] + max[i]) / 2.0);
+        let radius = self
+            .positions()
+            .map(|position| {
+                let squared: f32 = (0..3).map(|i| (position[i] - center[i]).powi(2)).sum();
+                squared.sqrt()
+            })
+            .fold(0.0, f32::max);
+        Some((center, radius))
     }
 
-    fn read_chunk<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> io::Result<&mut Self> {
-        while !reader.is_eof()? {
-            // Read chunk header: chunk_id, size_in_bytes, and version
-            let chunk = FileChunk {
-                chunk_id: reader.read_u32()?,
-                size_in_bytes: reader.read_u32()?,
-                version: reader.read_u32()?,
-            };
-
-            // Get the current position before processing the chunk
-            let position = reader.tell()?;
-
-            // Process the chunk (pass the reference to the chunk and reader)
-            self.process_chunk(&chunk, reader);
-
-            // Calculate the target position after the chunk is fully read
-            let target_pos = position + chunk.size_in_bytes as u64;
-
-            // Check if the current position matches the target position
-            if target_pos != reader.tell().unwrap() {
-                let missing_bytes = target_pos as i64 - reader.tell().unwrap() as i64;
-                println!(
-                    "Need {} more bytes to finish this chunk id : {}",
-                    missing_bytes, chunk.chunk_id
-                );
-            }
-
-            // Seek to the target position after the chunk has been processed
-            reader.seek(SeekFrom::Start(target_pos))?;
-
-            // Push the processed chunk into the chunk vector
-            self.chunk.push(chunk);
+    /// Fills in the tangents of submeshes that have none, and their bitangents when
+    /// those are missing too, from the positions, normals and UVs. Submeshes lacking
+    /// normals or UVs are left as they are.
+    pub fn compute_tangents(&mut self) {
+        for submesh in &mut self.submeshes {
+            submesh.compute_tangents();
         }
+    }
+}
 
-        Ok(self)
+impl Mesh {
+    fn positions(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        self.submeshes
+            .iter()
+            .flat_map(|submesh| submesh.positions.iter().copied())
     }
 
-    fn process_chunk<R: Read + Seek>(&mut self, chunk: &FileChunk, reader: &mut BinaryReader<R>) {
-        match chunk.chunk_id {
-            id if id == XacChunk::XacChunkNode as u32 => {
-                let node = match chunk.version {
-                    1 => Some(XacChunkData::XacNode(self.read_xac_node(reader))),
-                    2 => Some(XacChunkData::XacNode2(self.read_xac_node2(reader))),
-                    3 => Some(XacChunkData::XacNode3(self.read_xac_node3(reader))),
-                    4 => Some(XacChunkData::XacNode4(self.read_xac_node4(reader))),
-                    _ => None,
-                };
-                if let Some(data) = node {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkNode", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkMesh as u32 => {
-                let mesh = match chunk.version {
-                    1 => Some(XacChunkData::XACMesh(self.read_xac_mesh(reader))),
-                    2 => Some(XacChunkData::XACMesh2(self.read_xac_mesh2(reader))),
-                    _ => None,
-                };
-                if let Some(data) = mesh {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkMesh", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkSkinninginfo as u32 => {
-                let skinning_info = match chunk.version {
-                    1 => Some(XacChunkData::XacSkinningInfo(
-                        self.read_xac_skinning_info(reader),
-                    )),
-                    2 => Some(XacChunkData::XacSkinningInfo2(
-                        self.read_xac_skinning_info2(reader),
-                    )),
-                    3 => Some(XacChunkData::XacSkinningInfo3(
-                        self.read_xac_skinning_info3(reader),
-                    )),
-                    4 => Some(XacChunkData::XacSkinningInfo4(
-                        self.read_xac_skinning_info4(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = skinning_info {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkSkinninginfo", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkStdmaterial as u32 => {
-                let material = match chunk.version {
-                    1 => Some(XacChunkData::XacStandardMaterial(
-                        self.read_xac_standard_material(reader),
-                    )),
-                    2 => Some(XacChunkData::XacStandardMaterial2(
-                        self.read_xac_standard_material2(reader),
-                    )),
-                    3 => Some(XacChunkData::XacStandardMaterial3(
-                        self.read_xac_standard_material3(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = material {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkStdmaterial", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkStdmateriallayer as u32 => {
-                let material_layer = match chunk.version {
-                    1 => Some(XacChunkData::XACStandardMaterialLayer(
-                        self.read_xac_standard_material_layer(reader),
-                    )),
-                    2 => Some(XacChunkData::XACStandardMaterialLayer2(
-                        self.read_xac_standard_material_layer2(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = material_layer {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkStdmateriallayer",
-                        chunk.version
-                    );
-                }
+    /// Moves the vertices and morph target deltas by the column-major `matrix`.
+    fn transform(&mut self, matrix: &[f32; 16]) {
+        let normal_matrix = gltf::normal_matrix(matrix);
+        let direction = |value| gltf::normalized(gltf::transform_vector(matrix, value));
+        let normal = |value| gltf::normalized(gltf::transform_vector(&normal_matrix, value));
+        for submesh in &mut self.submeshes {
+            for position in &mut submesh.positions {
+                *position = gltf::transform_point(matrix, *position);
             }
-            id if id == XacChunk::XacChunkFxmaterial as u32 => {
-                let fx_material = match chunk.version {
-                    1 => Some(XacChunkData::XACFXMaterial(
-                        self.read_xac_fx_material(reader),
-                    )),
-                    2 => Some(XacChunkData::XACFXMaterial2(
-                        self.read_xac_fx_material2(reader),
-                    )),
-                    3 => Some(XacChunkData::XACFXMaterial3(
-                        self.read_xac_fx_material3(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = fx_material {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkFxmaterial", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkMaterialinfo as u32 => {
-                let material_info = match chunk.version {
-                    1 => Some(XacChunkData::XACMaterialInfo(
-                        self.read_xac_material_info(reader),
-                    )),
-                    2 => Some(XacChunkData::XACMaterialInfo2(
-                        self.read_xac_material_info2(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = material_info {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkMaterialinfo", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkNodes as u32 => {
-                let nodes = match chunk.version {
-                    1 => Some(XacChunkData::XACNodes(self.read_xac_nodes(reader))),
-                    _ => None,
-                };
-                if let Some(data) = nodes {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkNodes", chunk.version);
-                }
-            }
-            id if id == XacChunk::XacChunkNodegroups as u32 => {
-                let node_group = match chunk.version {
-                    1 => Some(XacChunkData::XACNodeGroup(self.read_xac_node_group(reader))),
-                    _ => None,
-                };
-                if let Some(data) = node_group {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkNodegroups", chunk.version);
-                }
+            for value in &mut submesh.normals {
+                *value = normal(*value);
             }
-            id if id == XacChunk::XacChunkMeshlodlevels as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACMeshLodLevel(
-                        self.read_xac_mesh_lod_level(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkMeshlodlevels",
-                        chunk.version
-                    );
-                }
+            for [x, y, z, _] in &mut submesh.tangents {
+                [*x, *y, *z] = direction([*x, *y, *z]);
             }
-            id if id == XacChunk::XacLimit as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACLimit(self.read_xac_limit(reader))),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacLimit", chunk.version);
-                }
+            for value in &mut submesh.bitangents {
+                *value = direction(*value);
             }
-            id if id == XacChunk::XacChunkInfo as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XacInfo(self.read_xac_info(reader))),
-                    2 => Some(XacChunkData::XacInfo2(self.read_xac_info2(reader))),
-                    3 => Some(XacChunkData::XacInfo3(self.read_xac_info3(reader))),
-                    4 => Some(XacChunkData::XacInfo4(self.read_xac_info4(reader))),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!("Unknown version {} for XacChunkInfo", chunk.version);
-                }
+        }
+        for target in &mut self.morph_targets {
+            let deltas = target
+                .position_deltas
+                .iter_mut()
+                .chain(&mut target.tangent_deltas);
+            for delta in deltas.flatten() {
+                *delta = gltf::transform_vector(matrix, *delta);
             }
-            id if id == XacChunk::XacChunkStdprogmorphtarget as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACPMorphTarget(
-                        self.read_xac_pmorph_target(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkStdprogmorphtarget",
-                        chunk.version
-                    );
-                }
+            for delta in target.normal_deltas.iter_mut().flatten() {
+                *delta = gltf::transform_vector(&normal_matrix, *delta);
             }
+        }
+    }
+}
 
-            id if id == XacChunk::XacChunkStdpmorphtargets as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACPMorphTargets(
-                        self.read_xac_pmorph_targets(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkStdpmorphtargets",
-                        chunk.version
-                    );
-                }
-            }
+/// The axis-aligned box around `points` as `(min, max)`, `None` without any.
+fn aabb(points: impl IntoIterator<Item = [f32; 3]>) -> Option<([f32; 3], [f32; 3])> {
+    points.into_iter().fold(None, |bounds, point| {
+        let (min, max) = bounds.unwrap_or((point, point));
+        Some((
+            std::array::from_fn(|i| min[i].min(point[i])),
+            std::array::from_fn(|i| max[i].max(point[i])),
+        ))
+    })
+}
 
-            id if id == XacChunk::XacChunkNodemotionsources as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACNodeMotionSources(
-                        self.read_xac_node_motion_sources(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkNodemotionsources",
-                        chunk.version
-                    );
-                }
-            }
+/// View of the vertices of one submesh in a decoded vertex attribute layer, without
+/// copying them.
+#[derive(Debug)]
+pub struct AttributeView<'a, T> {
+    data: &'a [T],
+}
 
-            id if id == XacChunk::XacChunkAttachmentnodes as u32 => {
-                let mesh_lod = match chunk.version {
-                    1 => Some(XacChunkData::XACAttachmentNodes(
-                        self.read_xac_attachment_nodes(reader),
-                    )),
-                    _ => None,
-                };
-                if let Some(data) = mesh_lod {
-                    self.chunk_data.push(data);
-                } else {
-                    println!(
-                        "Unknown version {} for XacChunkAttachmentnodes",
-                        chunk.version
-                    );
-                }
-            }
-            _ => {
-                println!(
-                    "Unknown Chunk ID: {}, Size: {}, Version: {}",
-                    chunk.chunk_id, chunk.size_in_bytes, chunk.version
-                );
-            }
-        }
+impl<T> Clone for AttributeView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
     }
+}
 
-    fn read_xac_info<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacInfo {
-        // Implement parsing logic
-        XacInfo::read(&mut reader.reader).unwrap()
+impl<T> Copy for AttributeView<'_, T> {}
+
+impl<'a, T: Pod> AttributeView<'a, T> {
+    fn new(data: &'a [T]) -> Self {
+        Self { data }
     }
 
-    fn read_xac_info2<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacInfo2 {
-        XacInfo2::read(&mut reader.reader).unwrap()
+    pub fn len(&self) -> usize {
+        self.data.len()
     }
 
-    fn read_xac_info3<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacInfo3 {
-        XacInfo3::read(&mut reader.reader).unwrap()
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
     }
 
-    fn read_xac_info4<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacInfo4 {
-        XacInfo4::read(&mut reader.reader).unwrap()
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.data.get(index).copied()
     }
 
-    fn read_xac_node<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacNode {
-        XacNode::read(&mut reader.reader).unwrap()
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        self.data.iter().copied()
     }
 
-    fn read_xac_node2<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacNode2 {
-        XacNode2::read(&mut reader.reader).unwrap()
+    pub fn as_slice(&self) -> &'a [T] {
+        self.data
     }
 
-    fn read_xac_node3<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacNode3 {
-        XacNode3::read(&mut reader.reader).unwrap()
+    /// Bytes of the elements, in native byte order.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        bytemuck::cast_slice(self.data)
     }
 
-    fn read_xac_node4<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XacNode4 {
-        XacNode4::read(&mut reader.reader).unwrap()
+    /// Copies the elements out in one bulk copy.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.data.to_vec()
     }
+}
 
-    fn read_xac_skinning_info<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacSkinningInfo {
-        XacSkinningInfo::read(&mut reader.reader).unwrap()
+/// Borrowed form of `SubMesh`. Positions and normals are in file space; the owned
+/// conversion mirrors them on the x axis like the OBJ export does.
+#[derive(Debug, Clone, Default)]
+pub struct SubMeshView<'a> {
+    pub texture_name: &'a str,
+    /// Vertices of the submesh within the attribute layers of its mesh
+    pub vertices: std::ops::Range<usize>,
+    pub positions: Option<AttributeView<'a, [f32; 3]>>,
+    pub normals: Option<AttributeView<'a, [f32; 3]>>,
+    pub tangents: Option<AttributeView<'a, [f32; 4]>>,
+    pub uvcoords: Option<AttributeView<'a, [f32; 2]>>,
+    pub colors32: Option<AttributeView<'a, u32>>,
+    pub original_vertex_numbers: Option<AttributeView<'a, u32>>,
+    pub colors128: Option<AttributeView<'a, [f32; 4]>>,
+    pub bitangents: Option<AttributeView<'a, [f32; 3]>>,
+    pub indices: &'a [u32],
+}
+
+impl SubMeshView<'_> {
+    fn has_vertex_data(&self) -> bool {
+        let lens = [
+            self.positions.map(|view| view.len()),
+            self.normals.map(|view| view.len()),
+            self.tangents.map(|view| view.len()),
+            self.uvcoords.map(|view| view.len()),
+            self.colors32.map(|view| view.len()),
+            self.original_vertex_numbers.map(|view| view.len()),
+            self.colors128.map(|view| view.len()),
+            self.bitangents.map(|view| view.len()),
+        ];
+        lens.into_iter().any(|len| len.unwrap_or(0) > 0)
     }
 
-    fn read_xac_skinning_info2<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacSkinningInfo2 {
-        let mut num_org_verts: u32 = 0;
-        // Read node_index first and check for matches
-        let node_id = reader.read_u32().unwrap(); // Read node_id once
-        // Loop through the chunk_data to find the right chunk based on node_id
-        for chunk in &self.chunk_data {
-            match chunk {
-                // Match the specific variant and check if node_id matches the read value
-                XacChunkData::XACMesh(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
-                }
-                XacChunkData::XACMesh2(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
-                }
-                // Exhaustive match for other variants (to avoid non-exhaustive match warnings)
-                _ => {
-                    // Optionally, you can log or do something else for unmatched variants
-                    // println!("Ignoring variant: {:?}", chunk);
-                }
-            }
+    /// Owned copy with positions and normals mirrored to right-handed space, see
+    /// `to_submesh_with`.
+    pub fn to_submesh(&self) -> SubMesh {
+        self.to_submesh_with(&ExportOptions::default())
+    }
+
+    /// Owned copy with the geometry converted to the conventions of `options`.
+    pub fn to_submesh_with(&self, options: &ExportOptions) -> SubMesh {
+        fn owned<T: Pod>(view: Option<AttributeView<'_, T>>) -> Vec<T> {
+            view.map(|view| view.to_vec()).unwrap_or_default()
+        }
+        fn converted<T: Pod>(
+            view: Option<AttributeView<'_, T>>,
+            convert: impl Fn(T) -> T,
+        ) -> Vec<T> {
+            view.map(|view| view.iter().map(convert).collect())
+                .unwrap_or_default()
         }
-        XacSkinningInfo2::read_args(&mut reader.reader, (num_org_verts,)).unwrap()
 
-        // Now that num_org_verts is set, read the XacSkinningInfo2 struct
+        let positions = converted(self.positions, |value| options.position(value));
+        let normals = converted(self.normals, |value| options.direction(value));
+        let tangents = converted(self.tangents, |value| options.tangent(value));
+        let uvcoords = owned(self.uvcoords);
+        let colors32 = owned(self.colors32);
+        let original_vertex_numbers = owned(self.original_vertex_numbers);
+        let colors128 = owned(self.colors128);
+        let bitangents = converted(self.bitangents, |value| options.direction(value));
+        SubMesh {
+            texture_name: self.texture_name.to_string(),
+            position_count: positions.len(),
+            positions,
+            normal_count: normals.len(),
+            normals,
+            tangent_count: tangents.len(),
+            tangents,
+            uvcoord_count: uvcoords.len(),
+            uvcoords,
+            color32_count: colors32.len(),
+            colors32,
+            original_vertex_numbers_count: original_vertex_numbers.len(),
+            original_vertex_numbers,
+            color128_count: colors128.len(),
+            colors128,
+            bitangent_count: bitangents.len(),
+            bitangents,
+            indices_count: self.indices.len(),
+            indices: self.indices.to_vec(),
+            ..Default::default()
+        }
     }
+}
 
-    fn read_xac_skinning_info3<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacSkinningInfo3 {
-        let mut num_org_verts: u32 = 0;
-        // Read node_index first and check for matches
-        let node_id = reader.read_u32().unwrap(); // Read node_id once
-        // Loop through the chunk_data to find the right chunk based on node_id
-        for chunk in &self.chunk_data {
-            match chunk {
-                // Match the specific variant and check if node_id matches the read value
-                XacChunkData::XACMesh(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
-                }
-                XacChunkData::XACMesh2(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
-                }
-                // Exhaustive match for other variants (to avoid non-exhaustive match warnings)
-                _ => {
-                    // Optionally, you can log or do something else for unmatched variants
-                    // println!("Ignoring variant: {:?}", chunk);
-                }
-            }
+/// Borrowed form of `Mesh`, see `XACFile::mesh_views`.
+#[derive(Debug, Clone)]
+pub struct MeshView<'a> {
+    pub submeshes: Vec<SubMeshView<'a>>,
+}
+
+impl MeshView<'_> {
+    pub fn to_mesh(&self) -> Mesh {
+        self.to_mesh_with(&ExportOptions::default())
+    }
+
+    pub fn to_mesh_with(&self, options: &ExportOptions) -> Mesh {
+        let submeshes: Vec<SubMesh> = self
+            .submeshes
+            .iter()
+            .map(|submesh| submesh.to_submesh_with(options))
+            .collect();
+        Mesh {
+            submesh_count: submeshes.len(),
+            submeshes,
+            morph_targets: Vec::new(),
+            is_collision: false,
         }
-        XacSkinningInfo3::read_args(&mut reader.reader, (num_org_verts,)).unwrap()
     }
+}
 
-    fn read_xac_skinning_info4<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacSkinningInfo4 {
-        let mut num_org_verts: u32 = 0;
-        // Read node_index first and check for matches
-        let node_id = reader.read_u32().unwrap(); // Read node_id once
-        // Loop through the chunk_data to find the right chunk based on node_id
-        for chunk in &self.chunk_data {
-            match chunk {
-                // Match the specific variant and check if node_id matches the read value
-                XacChunkData::XACMesh(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
+/// Splits the attribute layers of a mesh into per-submesh views. Submeshes without
+/// any vertex data are left out.
+fn mesh_view<'a>(
+    layers: &'a [XACVertexAttributeLayer],
+    sub_meshes: &'a [XACSubMesh],
+    texture_names: &[&'a str],
+) -> Result<MeshView<'a>> {
+    let mut vertex_offset: usize = 0;
+    let mut submeshes = Vec::new();
+
+    for submesh in sub_meshes {
+        let range = vertex_offset..vertex_offset + submesh.num_verts as usize;
+        let material_index = submesh.material_index as usize;
+
+        let mut view = SubMeshView {
+            // Material 0 is the default material, which has no texture
+            texture_name: match material_index {
+                0 => "",
+                index => texture_names.get(index).copied().unwrap_or(""),
+            },
+            vertices: range.clone(),
+            indices: &submesh.indices,
+            ..Default::default()
+        };
+        // The first layer of each kind is used
+        for layer in layers {
+            match &layer.data {
+                VertexLayerData::Positions(values) => {
+                    attribute_view(&mut view.positions, values, &range, "Vertex")?
                 }
-                XacChunkData::XACMesh2(data) => {
-                    if data.node_index == node_id {
-                        // Set num_org_verts based on the matched chunk
-                        num_org_verts = data.num_org_verts;
-                        // Move back 4 bytes since we've already read the node_id
-                        reader.skip_bytes(-4).unwrap();
-                    }
+                VertexLayerData::Normals(values) => {
+                    attribute_view(&mut view.normals, values, &range, "Normal")?
                 }
-                // Exhaustive match for other variants (to avoid non-exhaustive match warnings)
-                _ => {
-                    // Optionally, you can log or do something else for unmatched variants
-                    // println!("Ignoring variant: {:?}", chunk);
+                VertexLayerData::Tangents(values) => {
+                    attribute_view(&mut view.tangents, values, &range, "Tangent")?
+                }
+                VertexLayerData::Uvs(values) => {
+                    attribute_view(&mut view.uvcoords, values, &range, "UV")?
+                }
+                VertexLayerData::Colors32(values) => {
+                    attribute_view(&mut view.colors32, values, &range, "Color32")?
                 }
+                VertexLayerData::OriginalVertexNumbers(values) => attribute_view(
+                    &mut view.original_vertex_numbers,
+                    values,
+                    &range,
+                    "Original vertex numbers",
+                )?,
+                VertexLayerData::Colors128(values) => {
+                    attribute_view(&mut view.colors128, values, &range, "Color128")?
+                }
+                VertexLayerData::Bitangents(values) => {
+                    attribute_view(&mut view.bitangents, values, &range, "Bitangent")?
+                }
+                VertexLayerData::Other(_) => {}
             }
         }
-        XacSkinningInfo4::read_args(&mut reader.reader, (num_org_verts,)).unwrap()
+        if view.has_vertex_data() {
+            submeshes.push(view);
+        }
+
+        vertex_offset = range.end;
     }
 
-    fn read_xac_standard_material<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacStandardMaterial {
-        XacStandardMaterial::read(&mut reader.reader).unwrap()
+    Ok(MeshView { submeshes })
+}
+
+/// Sets `view` to `vertices` of a layer, unless an earlier layer already set it.
+fn attribute_view<'a, T: Pod>(
+    view: &mut Option<AttributeView<'a, T>>,
+    values: &'a [T],
+    vertices: &std::ops::Range<usize>,
+    name: &str,
+) -> Result<()> {
+    if view.is_none() {
+        let values = values
+            .get(vertices.clone())
+            .ok_or_else(|| Error::Xac(format!("{} data out of bounds", name)))?;
+        *view = Some(AttributeView::new(values));
     }
+    Ok(())
+}
 
-    fn read_xac_standard_material2<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacStandardMaterial2 {
-        XacStandardMaterial2::read(&mut reader.reader).unwrap()
+/// Actor metadata from the header and Info chunk, see `XACFile::load_info`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XacActorInfo {
+    /// File format version as (high, low), e.g. (1, 0)
+    pub version: (u8, u8),
+    /// Version of the exporter that wrote the file, as (high, low)
+    pub exporter_version: (u8, u8),
+    pub info_version: u32,
+    pub actor_name: String,
+    pub source_app: String,
+    pub original_filename: String,
+    pub compilation_date: String,
+    /// Only Info chunks from version 4 on store a LOD count; older actors have one
+    pub num_lods: u32,
+}
+
+impl XacActorInfo {
+    fn from_chunk(header: &XacHeader, info_version: u32, data: XacChunkData) -> Option<Self> {
+        let mut info = match data {
+            XacChunkData::XacInfo(data) => Self::from_fields(
+                (data.exporter_high_version, data.exporter_low_version),
+                [
+                    data.actor_name,
+                    data.source_app,
+                    data.original_filename,
+                    data.compilation_date,
+                ],
+            ),
+            XacChunkData::XacInfo2(data) => Self::from_fields(
+                (data.exporter_high_version, data.exporter_low_version),
+                [
+                    data.actor_name,
+                    data.source_app,
+                    data.original_filename,
+                    data.compilation_date,
+                ],
+            ),
+            XacChunkData::XacInfo3(data) => Self::from_fields(
+                (data.exporter_high_version, data.exporter_low_version),
+                [
+                    data.actor_name,
+                    data.source_app,
+                    data.original_filename,
+                    data.compilation_date,
+                ],
+            ),
+            XacChunkData::XacInfo4(data) => XacActorInfo {
+                num_lods: data.num_lods,
+                ..Self::from_fields(
+                    (data.exporter_high_version, data.exporter_low_version),
+                    [
+                        data.actor_name,
+                        data.source_app,
+                        data.original_filename,
+                        data.compilation_date,
+                    ],
+                )
+            },
+            _ => return None,
+        };
+        info.version = (header.hi_version, header.lo_version);
+        info.info_version = info_version;
+        Some(info)
     }
 
-    fn read_xac_standard_material3<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XacStandardMaterial3 {
-        XacStandardMaterial3::read(&mut reader.reader).unwrap()
+    fn from_fields(exporter_version: (u8, u8), strings: [String; 4]) -> Self {
+        let [actor_name, source_app, original_filename, compilation_date] = strings;
+        XacActorInfo {
+            exporter_version,
+            actor_name,
+            source_app,
+            original_filename,
+            compilation_date,
+            num_lods: 1,
+            ..Default::default()
+        }
     }
+}
 
-    fn read_xac_standard_material_layer<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACStandardMaterialLayer {
-        XACStandardMaterialLayer::read(&mut reader.reader).unwrap()
+/// A node of the skeleton in its bind pose, see `XACFile::nodes`. Transforms are
+/// local to the parent and in file space, unlike the exporters' output.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorNode {
+    pub name: String,
+    /// Index of the parent node, `None` for roots
+    pub parent: Option<u32>,
+    pub position: [f32; 3],
+    /// Quaternion as (x, y, z, w)
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    /// Node flags; version 1 node chunks have none
+    pub flags: u8,
+    /// Oriented bounding box of the node's vertices as stored, a 4x4 matrix; only
+    /// version 3 and later node chunks have one
+    pub obb: Option<[f32; 16]>,
+}
+
+impl ActorNode {
+    fn new(
+        name: &str,
+        parent_index: u32,
+        rotation: &FileQuaternion,
+        position: &FileVector3,
+        scale: &FileVector3,
+        flags: u8,
+    ) -> Self {
+        ActorNode {
+            name: name.to_string(),
+            parent: (parent_index != u32::MAX).then_some(parent_index),
+            position: position.to_array(),
+            rotation: [
+                rotation.axis_x,
+                rotation.axis_y,
+                rotation.axis_z,
+                rotation.axis_w,
+            ],
+            scale: scale.to_array(),
+            flags,
+            obb: None,
+        }
     }
+}
 
-    fn read_xac_standard_material_layer2<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACStandardMaterialLayer2 {
-        XACStandardMaterialLayer2::read(&mut reader.reader).unwrap()
+/// A material of the actor, see `XACFile::materials`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Material {
+    Standard(StdMaterial),
+    Fx(FxMaterial),
+}
+
+impl Material {
+    pub fn name(&self) -> &str {
+        match self {
+            Material::Standard(material) => &material.name,
+            Material::Fx(material) => &material.name,
+        }
     }
 
-    fn read_xac_sub_mesh<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XACSubMesh {
-        XACSubMesh::read(&mut reader.reader).unwrap()
+    pub fn lod(&self) -> u32 {
+        match self {
+            Material::Standard(material) => material.lod,
+            Material::Fx(material) => material.lod,
+        }
     }
 
-    fn read_xac_mesh<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XACMesh {
-        XACMesh::read(&mut reader.reader).unwrap()
+    /// The texture the exports show: the diffuse layer's, else the first layer's, or
+    /// the first bitmap parameter of an FX material. `None` without any texture.
+    pub fn texture_name(&self) -> Option<&str> {
+        match self {
+            Material::Standard(material) => material
+                .layers
+                .iter()
+                .find(|layer| layer.map_type == DIFFUSE_MAP)
+                .or(material.layers.first())
+                .map(|layer| layer.texture_name.as_str()),
+            Material::Fx(material) => {
+                material
+                    .parameters
+                    .iter()
+                    .find_map(|parameter| match &parameter.value {
+                        FxValue::Bitmap(texture) => Some(texture.as_str()),
+                        _ => None,
+                    })
+            }
+        }
     }
+}
+
+/// A standard material with its texture layers.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StdMaterial {
+    pub name: String,
+    /// Level of detail of the material table it belongs to
+    pub lod: u32,
+    pub ambient: [f32; 4],
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 4],
+    pub emissive: [f32; 4],
+    pub shine: f32,
+    pub shine_strength: f32,
+    /// 1.0 is fully opaque
+    pub opacity: f32,
+    /// Index of refraction
+    pub ior: f32,
+    pub double_sided: bool,
+    pub wireframe: bool,
+    /// F(ilter), S(ubtractive) or A(dditive)
+    pub transparency_type: u8,
+    pub layers: Vec<MaterialLayer>,
+}
 
-    fn read_xac_mesh2<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XACMesh2 {
-        XACMesh2::read(&mut reader.reader).unwrap()
+/// A texture layer of a standard material.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialLayer {
+    pub texture_name: String,
+    /// Strength of the layer, between 0 and 1
+    pub amount: f32,
+    pub u_offset: f32,
+    pub v_offset: f32,
+    pub u_tiling: f32,
+    pub v_tiling: f32,
+    pub rotation_radians: f32,
+    /// What the texture is used for, e.g. 2 for diffuse and 5 for bump maps
+    pub map_type: u8,
+    /// Zero for version 1 layer chunks, which have no blend mode
+    pub blend_mode: u8,
+}
+
+impl From<&XACStandardMaterialLayer2> for MaterialLayer {
+    fn from(layer: &XACStandardMaterialLayer2) -> Self {
+        MaterialLayer {
+            texture_name: layer.texture_name.to_string(),
+            amount: layer.amount,
+            u_offset: layer.u_offset,
+            v_offset: layer.v_offset,
+            u_tiling: layer.u_tiling,
+            v_tiling: layer.v_tiling,
+            rotation_radians: layer.rotation_radians,
+            map_type: layer.map_type,
+            blend_mode: layer.blend_mode,
+        }
     }
+}
+
+/// Range limits of a node's local transform, see `XACFile::limits`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeLimit {
+    pub node_index: u32,
+    pub translation_min: [f32; 3],
+    pub translation_max: [f32; 3],
+    pub rotation_min: [f32; 3],
+    pub rotation_max: [f32; 3],
+    pub scale_min: [f32; 3],
+    pub scale_max: [f32; 3],
+    /// Which limits apply: translation, rotation and scale, each as x, y, z
+    pub enabled: [bool; 9],
+}
+
+/// A shader-driven material with its parameters grouped by type.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxMaterial {
+    pub name: String,
+    /// Level of detail of the material table it belongs to
+    pub lod: u32,
+    pub effect_file: String,
+    pub shader_technique: String,
+    pub parameters: Vec<FxParameter>,
+}
+
+/// A named shader parameter of an `FxMaterial`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxParameter {
+    pub name: String,
+    pub value: FxValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FxValue {
+    Int(i32),
+    Float(f32),
+    Color([f32; 4]),
+    Bool(bool),
+    Vector3([f32; 3]),
+    /// Texture name
+    Bitmap(String),
+}
 
-    fn read_xac_limit<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XACLimit {
-        XACLimit::read(&mut reader.reader).unwrap()
+impl XACFile {
+    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        Self::load_from_file_with_options(file_path, &ParseOptions::default())
     }
 
-    fn read_xac_pmorph_target<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACPMorphTarget {
-        XACPMorphTarget::read(&mut reader.reader).unwrap()
+    pub fn load_from_bytes(mut bytes: Vec<u8>) -> Result<Self> {
+        let cursor = Cursor::new(&mut bytes);
+        let mut binary_reader = BinaryReader::new(cursor);
+        Self::load_from_reader(&mut binary_reader)
     }
 
-    fn read_xac_pmorph_targets<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACPMorphTargets {
-        XACPMorphTargets::read(&mut reader.reader).unwrap()
+    /// Parses an actor from a borrowed buffer. Any byte slice is accepted and
+    /// malformed input yields an error, which makes this the entry point for fuzzing.
+    pub fn load_from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_from_reader(&mut binary_reader)
     }
 
-    fn read_xac_fx_material<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACFXMaterial {
-        XACFXMaterial::read(&mut reader.reader).unwrap()
+    pub fn load_from_file_with_options<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        let load = || {
+            let file = std::fs::File::open(file_path)?;
+            let mut binary_reader = BinaryReader::new(BufReader::new(file));
+            Self::load_from_reader_with_options(&mut binary_reader, options)
+        };
+        load().map_err(|err| err.in_file(file_path))
     }
 
-    fn read_xac_fx_material2<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACFXMaterial2 {
-        XACFXMaterial2::read(&mut reader.reader).unwrap()
+    pub fn load_from_slice_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Self> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_from_reader_with_options(&mut binary_reader, options)
     }
 
-    fn read_xac_fx_material3<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACFXMaterial3 {
-        XACFXMaterial3::read(&mut reader.reader).unwrap()
+    /// Parses only the chunks in `ChunkSet::METADATA`, seeking past the rest, for
+    /// scans that need node names or material lists from many actors.
+    pub fn load_metadata_only(bytes: &[u8]) -> Result<Self> {
+        let options = ParseOptions::default().with_chunks(ChunkSet::METADATA);
+        Self::load_from_slice_with_options(bytes, &options)
     }
 
-    fn read_xac_node_group<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACNodeGroup {
-        XACNodeGroup::read(&mut reader.reader).unwrap()
+    /// Loads an actor along with a record of how every chunk was handled.
+    pub fn load_from_file_with_report<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<(Self, ParseReport)> {
+        Self::load_from_file_with_options(file_path, options).map(Self::with_report)
     }
 
-    fn read_xac_nodes<R: Read + Seek>(&mut self, reader: &mut BinaryReader<R>) -> XACNodes {
-        XACNodes::read(&mut reader.reader).unwrap()
+    pub fn load_from_slice_with_report(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(Self, ParseReport)> {
+        Self::load_from_slice_with_options(bytes, options).map(Self::with_report)
     }
 
-    fn read_xac_material_info<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACMaterialInfo {
-        XACMaterialInfo::read(&mut reader.reader).unwrap()
+    fn with_report(mut self) -> (Self, ParseReport) {
+        let mut chunks = std::mem::take(&mut self.trace);
+        // Parallel loads note skipped chunks ahead of the decoded ones
+        chunks.sort_by_key(|chunk| chunk.offset);
+        let warnings = self.warnings.clone();
+        (self, ParseReport { chunks, warnings })
     }
 
-    fn read_xac_material_info2<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACMaterialInfo2 {
-        XACMaterialInfo2::read(&mut reader.reader).unwrap()
+    /// Records how the chunk at `offset` was handled.
+    fn trace_chunk(&mut self, chunk: &FileChunk, offset: u64, outcome: ChunkOutcome) {
+        self.trace.push(ChunkTrace {
+            offset,
+            chunk_id: chunk.chunk_id,
+            version: chunk.version,
+            size: chunk.size_in_bytes,
+            outcome,
+        });
     }
 
-    fn read_xac_mesh_lod_level<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACMeshLodLevel {
-        XACMeshLodLevel::read(&mut reader.reader).unwrap()
+    /// Loads as much of a possibly truncated actor as is present. Chunks that end
+    /// before their declared size are dropped, everything before them is kept. Strict
+    /// options turn truncation into an error like any other.
+    pub fn load_partial_from_file<P: AsRef<Path>>(
+        file_path: P,
+        options: &ParseOptions,
+    ) -> Result<Parsed<Self>> {
+        let file_path = file_path.as_ref();
+        let load = || {
+            let file = std::fs::File::open(file_path)?;
+            let mut binary_reader = BinaryReader::new(BufReader::new(file));
+            Self::load_partial_from_reader(&mut binary_reader, options)
+        };
+        load().map_err(|err| err.in_file(file_path))
     }
 
-    fn read_xac_node_motion_sources<R: Read + Seek>(
-        &mut self,
-        reader: &mut BinaryReader<R>,
-    ) -> XACNodeMotionSources {
-        XACNodeMotionSources::read(&mut reader.reader).unwrap()
+    pub fn load_partial_from_slice(bytes: &[u8], options: &ParseOptions) -> Result<Parsed<Self>> {
+        let mut binary_reader = BinaryReader::new(Cursor::new(bytes));
+        Self::load_partial_from_reader(&mut binary_reader, options)
     }
 
-    fn read_xac_attachment_nodes<R: Read + Seek>(
-        &mut self,
+    fn load_partial_from_reader<R: Read + Seek>(
         reader: &mut BinaryReader<R>,
-    ) -> XACAttachmentNodes {
-        XACAttachmentNodes::read(&mut reader.reader).unwrap()
+        options: &ParseOptions,
+    ) -> Result<Parsed<Self>> {
+        with_text_decoding(options.text, || {
+            let mut xac_data = XACFile::default();
+            xac_data.read_header(reader, options)?;
+            let outcome = xac_data.read_chunk(reader, options).map(|_| ());
+            Parsed::from_outcome(xac_data, outcome, options)
+        })
     }
 
-    fn get_texture_names(&self) -> Vec<String> {
-        let mut textures = Vec::new();
+    /// Reads only the header and Info chunk, seeking over every other chunk body.
+    /// Much cheaper than a full load when cataloging many models.
+    pub fn load_info<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<XacActorInfo> {
+        Self::load_info_with_options(reader, &ParseOptions::default())
+    }
 
-        for chunk in &self.chunk_data {
-            match chunk {
-                XacChunkData::XacStandardMaterial(material) => {
-                    textures.push(material.material_name.clone());
-                }
-                XacChunkData::XacStandardMaterial2(material) => {
-                    textures.push(material.material_name.clone());
-                }
-                XacChunkData::XacStandardMaterial3(material) => {
-                    textures.push(material.material_name.clone());
-                }
-                XacChunkData::XACFXMaterial(material) => {
-                    if let Some(bitmap_params) = &material.xac_fx_bitmap_parameter {
-                        for bitmap in bitmap_params {
-                            textures.push(bitmap.value_name.clone());
-                        }
-                    }
+    pub fn load_info_from_slice(bytes: &[u8]) -> Result<XacActorInfo> {
+        Self::load_info(&mut BinaryReader::new(Cursor::new(bytes)))
+    }
+
+    pub fn load_info_with_options<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<XacActorInfo> {
+        with_text_decoding(options.text, || {
+            let mut xac_data = XACFile::default();
+            xac_data.read_header(reader, options)?;
+
+            let file_size = reader.file_size()?;
+            while let ChunkHeader::Chunk(chunk, position) =
+                Self::next_chunk_header(reader, file_size, xac_data.header.endian())?
+            {
+                if chunk.chunk_id != XacChunk::XacChunkInfo as u32 {
+                    reader.seek(SeekFrom::Start(position + chunk.size_in_bytes as u64))?;
+                    continue;
                 }
-                XacChunkData::XACFXMaterial2(material) => {
-                    if let Some(bitmap_params) = &material.xac_fx_bitmap_parameter {
-                        for bitmap in bitmap_params {
-                            textures.push(bitmap.value_name.clone());
-                        }
+                let decoded = xac_data
+                    .decode_chunk(&chunk, reader, &|_| None)
+                    .map_err(|err| {
+                        err.in_chunk(chunk.chunk_id, chunk.version)
+                            .at_offset(position - 12)
+                    })?;
+                return match decoded {
+                    DecodedChunk::Data(data) => {
+                        XacActorInfo::from_chunk(&xac_data.header, chunk.version, data)
+                            .ok_or_else(|| Error::Xac("Info chunk has no actor info".to_string()))
                     }
-                }
-                XacChunkData::XACFXMaterial3(material) => {
-                    if let Some(bitmap_params) = &material.xac_fx_bitmap_parameter {
-                        for bitmap in bitmap_params {
-                            textures.push(bitmap.value_name.clone());
-                        }
+                    _ => Err(ParseWarning::UnsupportedVersion {
+                        offset: position - 12,
+                        format: "XacChunkInfo",
+                        version: chunk.version,
                     }
-                }
-                _ => {}
+                    .into()),
+                };
             }
+            Err(Error::Xac("No Info chunk found".to_string()))
+        })
+    }
+
+    fn load_from_reader<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        Self::load_from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    fn load_from_reader_with_options<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        with_text_decoding(options.text, || {
+            let mut xac_data = XACFile::default();
+            xac_data.read_header(reader, options)?;
+            xac_data.read_chunk(reader, options)?;
+            Ok(xac_data)
+        })
+    }
+
+    fn read_header<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
+        self.header =
+            XacHeader::read(&mut reader.reader).map_err(|err| Error::from(err).at_offset(0))?; // Use binread to read the struct
+        if self.header.fourcc != XAC_FOURCC {
+            self.report(
+                options,
+                ParseWarning::BadMagic {
+                    expected: XAC_FOURCC,
+                    found: self.header.fourcc,
+                },
+            )?;
         }
+        Ok(self)
+    }
+
+    fn report(&mut self, options: &ParseOptions, warning: ParseWarning) -> Result<()> {
+        options.report(&mut self.warnings, warning)
+    }
+
+    /// Problems that were recovered from while loading in lenient mode.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Chunks parsed by registered `ChunkHandler`s, in file order.
+    pub fn custom_chunks(&self) -> &[CustomChunk] {
+        &self.custom_chunks
+    }
+
+    /// The first chunk with `chunk_id` a handler parsed into a `T`.
+    pub fn custom_chunk<T: std::any::Any>(&self, chunk_id: u32) -> Option<&T> {
+        self.custom_chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_id == chunk_id)
+            .find_map(CustomChunk::downcast_ref)
+    }
 
-        textures
+    /// Id, version and raw body of every chunk with an unknown id or an unsupported
+    /// version, in file order.
+    pub fn unknown_chunks(&self) -> impl Iterator<Item = (u32, u32, &[u8])> {
+        self.chunk_data.iter().filter_map(|chunk| match chunk {
+            XacChunkData::Unknown { id, version, bytes } => Some((*id, *version, bytes.as_slice())),
+            _ => None,
+        })
     }
 
-    pub fn export_all_meshes(&self, output_prefix: &str) -> io::Result<()> {
-        for (i, chunk) in self.chunk_data.iter().enumerate() {
+    /// The nodes of every node chunk, by node index.
+    pub fn nodes(&self) -> Vec<ActorNode> {
+        let mut nodes = Vec::new();
+        for chunk in &self.chunk_data {
             match chunk {
-                XacChunkData::XACMesh(mesh) => {
-                    let filename = format!("{}_mesh_{}", output_prefix, i);
-                    self.export_to_obj(mesh, &filename)?;
+                XacChunkData::XacNode(node) => nodes.push(ActorNode::new(
+                    &node.node_name,
+                    node.parent_index,
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    0,
+                )),
+                XacChunkData::XacNode2(node) => nodes.push(ActorNode::new(
+                    &node.node_name,
+                    node.parent_index,
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    node.node_flags,
+                )),
+                XacChunkData::XacNode3(node) => nodes.push(ActorNode {
+                    obb: Some(node.obb),
+                    ..ActorNode::new(
+                        &node.node_name,
+                        node.parent_index,
+                        &node.local_quat,
+                        &node.local_pos,
+                        &node.local_scale,
+                        node.node_flags,
+                    )
+                }),
+                XacChunkData::XacNode4(node) => nodes.push(ActorNode {
+                    obb: Some(node.obb),
+                    ..ActorNode::new(
+                        &node.node_name,
+                        node.parent_index,
+                        &node.local_quat,
+                        &node.local_pos,
+                        &node.local_scale,
+                        node.node_flags,
+                    )
+                }),
+                XacChunkData::XACNodes(chunk) => {
+                    nodes.extend(chunk.xac_node.iter().map(|node| ActorNode {
+                        obb: Some(node.obb),
+                        ..ActorNode::new(
+                            &node.node_name,
+                            node.parent_index,
+                            &node.local_quat,
+                            &node.local_pos,
+                            &node.local_scale,
+                            node.node_flags,
+                        )
+                    }))
                 }
-                XacChunkData::XACMesh2(mesh) => {
-                    let filename = format!("{}_mesh_{}", output_prefix, i);
-                    self.export_to_obj2(mesh, &filename)?;
-                }
-                _ => continue,
+                _ => {}
             }
         }
-        Ok(())
+        nodes
     }
 
-    pub fn export_all_meshes_into_struct(&mut self) -> io::Result<Vec<Mesh>> {
-        let mut all_meshes: Vec<Mesh> = Vec::new(); // Assuming Mesh is a struct and can be initialized with default values
+    /// The standard and FX materials in file order. Submeshes of a level of detail
+    /// refer to the materials of that level, counted in this order, see
+    /// `material_table`. Layers stored in chunks of their own are attached to the
+    /// material they name.
+    pub fn materials(&self) -> Vec<Material> {
+        // The three versions share their fields
+        macro_rules! material {
+            ($material:expr, $lod:expr, $layers:expr) => {
+                Material::Standard(StdMaterial {
+                    name: $material.material_name.to_string(),
+                    lod: $lod,
+                    ambient: $material.ambient.to_array(),
+                    diffuse: $material.diffuse.to_array(),
+                    specular: $material.specular.to_array(),
+                    emissive: $material.emissive.to_array(),
+                    shine: $material.shine,
+                    shine_strength: $material.shine_strength,
+                    opacity: $material.opacity,
+                    ior: $material.ior,
+                    double_sided: $material.double_sided != 0,
+                    wireframe: $material.wireframe != 0,
+                    transparency_type: $material.transparency_type,
+                    layers: $layers,
+                })
+            };
+        }
 
-        for (_, chunk) in self.chunk_data.iter().enumerate() {
+        let lods = self.material_lods();
+        let mut materials = Vec::new();
+        for (chunk, &lod) in self.chunk_data.iter().zip(&lods) {
             match chunk {
-                XacChunkData::XACMesh(mesh) => {
-                    // Directly move the mesh from chunk
-                    all_meshes.push(self.export_to_struct(mesh)?); // Move the mesh
+                XacChunkData::XacStandardMaterial(material) => {
+                    materials.push(material!(material, lod, Vec::new()))
                 }
-                XacChunkData::XACMesh2(mesh) => {
-                    // Directly move the mesh from chunk
-                    all_meshes.push(self.export_to_struct2(mesh)?); // Move the mesh
+                XacChunkData::XacStandardMaterial2(material) => materials.push(material!(
+                    material,
+                    lod,
+                    material
+                        .standard_material_layer2
+                        .iter()
+                        .map(MaterialLayer::from)
+                        .collect()
+                )),
+                XacChunkData::XacStandardMaterial3(material) => materials.push(material!(
+                    material,
+                    lod,
+                    material
+                        .standard_material_layer2
+                        .iter()
+                        .map(MaterialLayer::from)
+                        .collect()
+                )),
+                chunk => materials.extend(fx_material(chunk, lod).map(Material::Fx)),
+            }
+        }
+        for (chunk, &lod) in self.chunk_data.iter().zip(&lods) {
+            let (material_number, layer) = match chunk {
+                XacChunkData::XACStandardMaterialLayer(layer) => (
+                    layer.material_number,
+                    MaterialLayer {
+                        texture_name: layer.texture_name.to_string(),
+                        amount: layer.amount,
+                        u_offset: layer.u_offset,
+                        v_offset: layer.v_offset,
+                        u_tiling: layer.u_tiling,
+                        v_tiling: layer.v_tiling,
+                        rotation_radians: layer.rotation_radians,
+                        map_type: layer.map_type,
+                        blend_mode: 0,
+                    },
+                ),
+                XacChunkData::XACStandardMaterialLayer2(layer) => {
+                    (layer.material_number, MaterialLayer::from(layer))
                 }
                 _ => continue,
+            };
+            let material = materials
+                .iter_mut()
+                .filter(|material| material.lod() == lod)
+                .nth(material_number as usize);
+            if let Some(Material::Standard(material)) = material {
+                material.layers.push(layer);
             }
         }
-
-        Ok(all_meshes) // Return the final mesh after all iterations
+        materials
     }
 
-    fn export_to_obj(&self, mesh: &XACMesh, output_prefix: &str) -> io::Result<()> {
-        let texture_name = self.get_texture_names();
-
-        let positions_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribPositions as u32);
+    /// The materials submeshes of level of detail `lod` refer to, in material index
+    /// order. Levels without materials of their own use the table of level 0.
+    pub fn material_table(&self, lod: u32) -> Vec<Material> {
+        let materials = self.materials();
+        let lod = if materials.iter().any(|material| material.lod() == lod) {
+            lod
+        } else {
+            0
+        };
+        materials
+            .into_iter()
+            .filter(|material| material.lod() == lod)
+            .collect()
+    }
 
-        let normals_layer = mesh
-            .vertex_attribute_layer
+    /// The level of detail whose material table each chunk belongs to, parallel to
+    /// `chunk_data`. A material info chunk starts the table of the level it names and
+    /// version 3 material chunks name their own; without either, everything is level 0.
+    fn material_lods(&self) -> Vec<u32> {
+        let mut lod = 0;
+        self.chunk_data
             .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribNormals as u32);
+            .map(|chunk| match chunk {
+                XacChunkData::XACMaterialInfo(_) => {
+                    lod = 0;
+                    lod
+                }
+                XacChunkData::XACMaterialInfo2(info) => {
+                    lod = info.lod;
+                    lod
+                }
+                XacChunkData::XacStandardMaterial3(material) => material.lod,
+                XacChunkData::XACFXMaterial3(material) => material.lod,
+                _ => lod,
+            })
+            .collect()
+    }
 
-        let uvs_layer = mesh
-            .vertex_attribute_layer
+    /// The transform limits of the nodes that have them.
+    pub fn limits(&self) -> Vec<NodeLimit> {
+        self.chunk_data
             .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribUvcoords as u32);
-
-        if positions_layer.is_none() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "No vertex positions found",
-            ));
-        }
-
-        let positions_data = &positions_layer.unwrap().mesh_data;
-        let normals_data = normals_layer.map(|l| &l.mesh_data);
-        let uvs_data = uvs_layer.map(|l| &l.mesh_data);
-
-        let mut vertex_offset: u32 = 0;
-
-        for (i, submesh) in mesh.sub_meshes.iter().enumerate() {
-            let material_index = submesh.material_index as usize;
-
-            let obj_filename = format!("{}_submesh_{}.obj", output_prefix, i);
-            let file = File::create(&obj_filename)?;
-            let mut writer = BufWriter::new(file);
+            .filter_map(|chunk| match chunk {
+                XacChunkData::XACLimit(limit) => Some(NodeLimit {
+                    node_index: limit.node_number,
+                    translation_min: limit.translation_min.to_array(),
+                    translation_max: limit.translation_max.to_array(),
+                    rotation_min: limit.rotation_min.to_array(),
+                    rotation_max: limit.rotation_max.to_array(),
+                    scale_min: limit.scale_min.to_array(),
+                    scale_max: limit.scale_max.to_array(),
+                    enabled: limit.limit_flags.map(|flag| flag != 0),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
 
-            writeln!(writer, "o Submesh_{}", i)?;
+    /// Dumps the parsed model as JSON, see `from_json`.
+    pub fn to_json(&self) -> Result<String> {
+        serial::to_json(self)
+    }
 
-            if material_index != 0 {
-                // println!("material_index : {}", material_index);
-                // println!("texture_name length : {}", texture_name.len());
-                // println!("Texture : {:?}", texture_name);
+    pub fn from_json(json: &str) -> Result<Self> {
+        serial::from_json(json)
+    }
 
-                let material_name = texture_name.get(material_index).unwrap();
-                // Always write an MTL reference, even for submesh 0
-                let clean_prefix = output_prefix
-                    .strip_prefix("output/")
-                    .unwrap_or(output_prefix);
-                let mtl_filename = format!("{}_submesh_{}.mtl", clean_prefix, i);
+    /// Dumps the parsed model as compact binary CBOR, see `from_cbor`.
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        serial::to_cbor(self)
+    }
 
-                writeln!(writer, "mtllib {}", mtl_filename)?;
-                let mtl_filename_path = format!("{}_submesh_{}.mtl", output_prefix, i);
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        serial::from_cbor(bytes)
+    }
 
-                let mtl_file = File::create(&mtl_filename_path)?;
-                let mut mtl_writer = BufWriter::new(mtl_file);
+    /// Writes the actor back to disk, see `to_bytes`.
+    pub fn save_to_file<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        let file_path = file_path.as_ref();
+        let save = || -> Result<()> { Ok(std::fs::write(file_path, self.to_bytes()?)?) };
+        save().map_err(|err| err.in_file(file_path))
+    }
 
-                writeln!(mtl_writer, "newmtl {}", material_name)?;
-                writeln!(mtl_writer, "Kd 1.0 1.0 1.0")?;
-                writeln!(mtl_writer, "map_Kd {}", material_name)?;
+    /// The actor in the on-disk layout: the header followed by every parsed chunk in
+    /// file order, with chunk sizes and string lengths rebuilt, in the byte order the
+    /// header declares. Text is written as UTF-8. Unknown chunks are written back byte
+    /// for byte. Chunks that were skipped or handled by a registered `ChunkHandler` are
+    /// not written; skinning info version 1 fails, as its body is only partly parsed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Cursor::new(Vec::new());
+        let endian = self.header.endian();
+        self.header.write(&mut bytes)?;
+        for data in &self.chunk_data {
+            let (chunk_id, version) = data.chunk_id_and_version();
+            let write = |bytes: &mut Cursor<Vec<u8>>| -> Result<()> {
+                let body = data.to_bytes(endian)?;
+                let size_in_bytes = u32::try_from(body.len())
+                    .map_err(|_| Error::Xac(format!("{} byte chunk is too large", body.len())))?;
+                FileChunk {
+                    chunk_id,
+                    size_in_bytes,
+                    version,
+                }
+                .write_options(bytes, endian, ())?;
+                bytes.write_all(&body)?;
+                Ok(())
+            };
+            write(&mut bytes).map_err(|err| err.in_chunk(chunk_id, version))?;
+        }
+        Ok(bytes.into_inner())
+    }
 
-                // println!("🎨 Saved material {} to {}", material_name, mtl_filename);
-                writeln!(writer, "usemtl {}", material_name)?;
+    /// Renames every node called `from`, returning how many were renamed.
+    pub fn rename_node(&mut self, from: &str, to: &str) -> usize {
+        let to: Arc<str> = to.into();
+        let mut renamed = 0;
+        let mut rename = |name: &mut Arc<str>| {
+            if **name == *from {
+                *name = to.clone();
+                renamed += 1;
             }
-
-            // Write vertex positions
-            for v in 0..submesh.num_verts {
-                let actual_index = vertex_offset + v;
-                let offset = (actual_index * 12) as usize;
-
-                if offset + 12 > positions_data.len() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "Vertex data out of bounds",
-                    ));
+        };
+        for chunk in &mut self.chunk_data {
+            match chunk {
+                XacChunkData::XacNode(node) => rename(&mut node.node_name),
+                XacChunkData::XacNode2(node) => rename(&mut node.node_name),
+                XacChunkData::XacNode3(node) => rename(&mut node.node_name),
+                XacChunkData::XacNode4(node) => rename(&mut node.node_name),
+                XacChunkData::XACNodes(nodes) => {
+                    nodes
+                        .xac_node
+                        .iter_mut()
+                        .for_each(|node| rename(&mut node.node_name));
                 }
-
-                let px = f32::from_le_bytes(positions_data[offset..offset + 4].try_into().unwrap());
-                let py =
-                    f32::from_le_bytes(positions_data[offset + 4..offset + 8].try_into().unwrap());
-                let pz =
-                    f32::from_le_bytes(positions_data[offset + 8..offset + 12].try_into().unwrap());
-
-                writeln!(writer, "v {} {} {}", -px, py, pz)?;
+                _ => {}
             }
+        }
+        renamed
+    }
 
-            // Write normals
-            if let Some(normals) = normals_data {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
-
-                    if offset + 12 > normals.len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Normal data out of bounds",
-                        ));
+    /// Points every material, material layer and FX bitmap parameter that uses the
+    /// texture `from` at `to` instead, returning how many references changed.
+    pub fn rename_texture(&mut self, from: &str, to: &str) -> usize {
+        let to: Arc<str> = to.into();
+        let mut renamed = 0;
+        let mut rename = |name: &mut Arc<str>| {
+            if **name == *from {
+                *name = to.clone();
+                renamed += 1;
+            }
+        };
+        for chunk in &mut self.chunk_data {
+            let bitmaps = match chunk {
+                XacChunkData::XacStandardMaterial(material) => {
+                    rename(&mut material.material_name);
+                    None
+                }
+                XacChunkData::XacStandardMaterial2(material) => {
+                    rename(&mut material.material_name);
+                    for layer in &mut material.standard_material_layer2 {
+                        rename(&mut layer.texture_name);
                     }
-
-                    let nx = f32::from_le_bytes(normals[offset..offset + 4].try_into().unwrap());
-                    let ny =
-                        f32::from_le_bytes(normals[offset + 4..offset + 8].try_into().unwrap());
-                    let nz =
-                        f32::from_le_bytes(normals[offset + 8..offset + 12].try_into().unwrap());
-
-                    writeln!(writer, "vn {} {} {}", -nx, ny, nz)?;
+                    None
                 }
-            }
-
-            // Write texture coordinates
-            if let Some(uvs) = uvs_data {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 8) as usize;
-
-                    if offset + 8 > uvs.len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "UV data out of bounds",
-                        ));
+                XacChunkData::XacStandardMaterial3(material) => {
+                    rename(&mut material.material_name);
+                    for layer in &mut material.standard_material_layer2 {
+                        rename(&mut layer.texture_name);
                     }
-
-                    let u = f32::from_le_bytes(uvs[offset..offset + 4].try_into().unwrap());
-                    let v = f32::from_le_bytes(uvs[offset + 4..offset + 8].try_into().unwrap());
-
-                    writeln!(writer, "vt {} {}", u, 1.0 - v)?;
+                    None
                 }
-            }
-
-            // Write faces
-            for i in (0..submesh.num_indices).step_by(3) {
-                let idx1 = submesh.indices[i as usize] + 1;
-                let idx2 = submesh.indices[i as usize + 1] + 1;
-                let idx3 = submesh.indices[i as usize + 2] + 1;
-
-                if normals_data.is_some() && uvs_data.is_some() {
-                    writeln!(
-                        writer,
-                        "f {}/{}/{} {}/{}/{} {}/{}/{}",
-                        idx3, idx3, idx3, idx2, idx2, idx2, idx1, idx1, idx1
-                    )?;
-                } else if normals_data.is_some() {
-                    writeln!(
-                        writer,
-                        "f {}//{} {}//{} {}//{}",
-                        idx3, idx3, idx2, idx2, idx1, idx1
-                    )?;
-                } else {
-                    writeln!(writer, "f {} {} {}", idx3, idx2, idx1)?;
+                XacChunkData::XACStandardMaterialLayer(layer) => {
+                    rename(&mut layer.texture_name);
+                    None
+                }
+                XacChunkData::XACStandardMaterialLayer2(layer) => {
+                    rename(&mut layer.texture_name);
+                    None
                 }
+                XacChunkData::XACFXMaterial(material) => material.xac_fx_bitmap_parameter.as_mut(),
+                XacChunkData::XACFXMaterial2(material) => material.xac_fx_bitmap_parameter.as_mut(),
+                XacChunkData::XACFXMaterial3(material) => material.xac_fx_bitmap_parameter.as_mut(),
+                _ => None,
+            };
+            for bitmap in bitmaps.into_iter().flatten() {
+                rename(&mut bitmap.value_name);
             }
-
-            // println!("✅ Saved submesh {} to {}", i, obj_filename);
-
-            vertex_offset += submesh.num_verts;
         }
-
-        Ok(())
+        renamed
     }
 
-    fn export_to_obj2(&self, mesh: &XACMesh2, output_prefix: &str) -> io::Result<()> {
-        let texture_name = self.get_texture_names();
-
-        let positions_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribPositions as u32);
+    fn read_chunk<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
+        if options.parallel {
+            return self.read_chunk_parallel(reader, options);
+        }
 
-        let normals_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribNormals as u32);
+        let file_size = reader.file_size()?;
+        loop {
+            let (chunk, position) =
+                match Self::next_chunk_header(reader, file_size, self.header.endian())? {
+                    ChunkHeader::Chunk(chunk, position) => (chunk, position),
+                    ChunkHeader::Trailing(offset, count) => {
+                        self.report(options, ParseWarning::SkippedBytes { offset, count })?;
+                        break;
+                    }
+                    ChunkHeader::End => break,
+                };
+            if !options.chunks.contains(chunk.chunk_id) {
+                self.trace_chunk(&chunk, position - 12, ChunkOutcome::Skipped);
+                reader.seek(SeekFrom::Start(position + chunk.size_in_bytes as u64))?;
+                continue;
+            }
 
-        let uvs_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribUvcoords as u32);
+            // Process the chunk (pass the reference to the chunk and reader)
+            let decoded = self
+                .decode_chunk(&chunk, reader, &|node_index| {
+                    self.mesh_num_org_verts(node_index)
+                })
+                .and_then(|decoded| Ok((decoded, reader.tell()?)));
+            let target_pos = position + chunk.size_in_bytes as u64;
+            self.apply_chunk(chunk, position, decoded, options)?;
 
-        if positions_layer.is_none() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "No vertex positions found",
-            ));
+            // Seek to the target position after the chunk has been processed
+            reader.seek(SeekFrom::Start(target_pos))?;
         }
 
-        let positions_data = &positions_layer.unwrap().mesh_data;
-        let normals_data = normals_layer.map(|l| &l.mesh_data);
-        let uvs_data = uvs_layer.map(|l| &l.mesh_data);
+        Ok(self)
+    }
 
-        let mut vertex_offset: u32 = 0;
+    /// Same result as the sequential pass, but chunk bodies are decoded on the rayon
+    /// pool. A first pass only walks the chunk headers; skinning chunks, which need
+    /// the mesh they belong to, are decoded after every other chunk.
+    fn read_chunk_parallel<R: Read + Seek>(
+        &mut self,
+        reader: &mut BinaryReader<R>,
+        options: &ParseOptions,
+    ) -> Result<&mut Self> {
+        let start = reader.tell()?;
+        let mut bytes = Vec::new();
+        reader.seek(SeekFrom::Start(0))?;
+        reader.reader.read_to_end(&mut bytes)?;
+        let file_size = bytes.len() as u64;
+
+        let mut cursor = BinaryReader::new(Cursor::new(bytes.as_slice()));
+        cursor.seek(SeekFrom::Start(start))?;
+        let mut chunks = Vec::new();
+        let mut trailing = None;
+        let mut truncation = None;
+        loop {
+            match Self::next_chunk_header(&mut cursor, file_size, self.header.endian()) {
+                Ok(ChunkHeader::Chunk(chunk, position)) => {
+                    cursor.seek(SeekFrom::Start(position + chunk.size_in_bytes as u64))?;
+                    if options.chunks.contains(chunk.chunk_id) {
+                        chunks.push((chunk, position));
+                    } else {
+                        self.trace_chunk(&chunk, position - 12, ChunkOutcome::Skipped);
+                    }
+                }
+                Ok(ChunkHeader::Trailing(offset, count)) => {
+                    trailing = Some(ParseWarning::SkippedBytes { offset, count });
+                    break;
+                }
+                Ok(ChunkHeader::End) => break,
+                Err(err) => {
+                    truncation = Some(err);
+                    break;
+                }
+            }
+        }
 
-        for (i, submesh) in mesh.sub_meshes.iter().enumerate() {
-            let material_index = submesh.material_index as usize;
+        let decode_at =
+            |chunk: &FileChunk, position: u64, mesh_num_org_verts: &dyn Fn(u32) -> Option<u32>| {
+                with_text_decoding(options.text, || {
+                    let mut reader = BinaryReader::new(Cursor::new(bytes.as_slice()));
+                    reader.seek(SeekFrom::Start(position))?;
+                    let decoded = self.decode_chunk(chunk, &mut reader, mesh_num_org_verts)?;
+                    Ok((decoded, reader.tell()?))
+                })
+            };
+        let is_skinning =
+            |chunk: &FileChunk| chunk.chunk_id == XacChunk::XacChunkSkinninginfo as u32;
+
+        let mut decoded: Vec<Option<Result<(DecodedChunk, u64)>>> = chunks
+            .par_iter()
+            .map(|(chunk, position)| {
+                (!is_skinning(chunk)).then(|| decode_at(chunk, *position, &|_| None))
+            })
+            .collect();
+        let skinning: Vec<(usize, Result<(DecodedChunk, u64)>)> = chunks
+            .par_iter()
+            .enumerate()
+            .filter(|(_, (chunk, _))| is_skinning(chunk))
+            .map(|(index, (chunk, position))| {
+                // Only meshes that precede the skinning chunk count, as in a sequential read
+                let earlier = decoded[..index].iter().filter_map(|result| match result {
+                    Some(Ok((DecodedChunk::Data(data), _))) => Some(data),
+                    _ => None,
+                });
+                let num_org_verts =
+                    |node_index| find_mesh_num_org_verts(earlier.clone(), node_index);
+                (index, decode_at(chunk, *position, &num_org_verts))
+            })
+            .collect();
+        for (index, result) in skinning {
+            decoded[index] = Some(result);
+        }
 
-            let obj_filename = format!("{}_submesh_{}.obj", output_prefix, i);
-            let file = File::create(&obj_filename)?;
-            let mut writer = BufWriter::new(file);
+        for ((chunk, position), result) in chunks.into_iter().zip(decoded) {
+            let result = result.expect("every chunk is decoded in one of the passes");
+            self.apply_chunk(chunk, position, result, options)?;
+        }
+        if let Some(err) = truncation {
+            return Err(err);
+        }
+        if let Some(warning) = trailing {
+            self.report(options, warning)?;
+        }
 
-            writeln!(writer, "o Submesh_{}", i)?;
+        Ok(self)
+    }
 
-            if material_index != 0 {
-                // println!("material_index : {}", material_index);
-                // println!("texture_name length : {}", texture_name.len());
-                // println!("Texture : {:?}", texture_name);
+    /// Reads the next chunk header and checks that its body fits in the file.
+    fn next_chunk_header<R: Read + Seek>(
+        reader: &mut BinaryReader<R>,
+        file_size: u64,
+        endian: Endian,
+    ) -> Result<ChunkHeader> {
+        // Anything shorter than a chunk header is trailing garbage
+        let offset = reader.tell()?;
+        if offset >= file_size {
+            return Ok(ChunkHeader::End);
+        }
+        if file_size - offset < 12 {
+            return Ok(ChunkHeader::Trailing(offset, file_size - offset));
+        }
 
-                let material_name = texture_name.get(material_index).unwrap();
-                // Always write an MTL reference, even for submesh 0
-                let clean_prefix = output_prefix
-                    .strip_prefix("output/")
-                    .unwrap_or(output_prefix);
-                let mtl_filename = format!("{}_submesh_{}.mtl", clean_prefix, i);
+        // Read chunk header: chunk_id, size_in_bytes, and version
+        let chunk = FileChunk::read_options(&mut reader.reader, endian, ())?;
 
-                writeln!(writer, "mtllib {}", mtl_filename)?;
-                let mtl_filename_path = format!("{}_submesh_{}.mtl", output_prefix, i);
+        // Get the current position before processing the chunk
+        let position = reader.tell()?;
 
-                let mtl_file = File::create(&mtl_filename_path)?;
-                let mut mtl_writer = BufWriter::new(mtl_file);
+        // A chunk can never extend past the end of the file
+        let remaining = file_size.saturating_sub(position);
+        if chunk.size_in_bytes as u64 > remaining {
+            return Err(Error::Truncated {
+                needed: chunk.size_in_bytes as u64,
+                available: remaining,
+            }
+            .in_chunk(chunk.chunk_id, chunk.version)
+            .at_offset(offset));
+        }
+        Ok(ChunkHeader::Chunk(chunk, position))
+    }
 
-                writeln!(mtl_writer, "newmtl {}", material_name)?;
-                writeln!(mtl_writer, "Kd 1.0 1.0 1.0")?;
-                writeln!(mtl_writer, "map_Kd {}", material_name)?;
+    /// Records a decoded chunk: stores its data, reports what was skipped and checks
+    /// the body consumed exactly the declared size. `decoded` carries the position the
+    /// decoder stopped at.
+    fn apply_chunk(
+        &mut self,
+        chunk: FileChunk,
+        position: u64,
+        decoded: Result<(DecodedChunk, u64)>,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        let offset = position - 12;
+        let (chunk_id, version) = (chunk.chunk_id, chunk.version);
+        self.apply_decoded_chunk(chunk, offset, decoded, options)
+            .map_err(|err| err.in_chunk(chunk_id, version).at_offset(offset))
+    }
 
-                // println!("🎨 Saved material {} to {}", material_name, mtl_filename);
-                writeln!(writer, "usemtl {}", material_name)?;
+    fn apply_decoded_chunk(
+        &mut self,
+        chunk: FileChunk,
+        offset: u64,
+        decoded: Result<(DecodedChunk, u64)>,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        let (decoded, end) = decoded?;
+        let outcome = match decoded {
+            DecodedChunk::Data(_) => ChunkOutcome::Parsed,
+            DecodedChunk::Custom(..) => ChunkOutcome::Custom,
+            DecodedChunk::UnsupportedVersion(..) | DecodedChunk::Unknown(_) => ChunkOutcome::Raw,
+        };
+        let parsed = match decoded {
+            DecodedChunk::Data(data) => {
+                if let Some(node_index) = data.skinning_node_index()
+                    && self.mesh_num_org_verts(node_index).is_none()
+                {
+                    self.report_conflict(
+                        options,
+                        ParseWarning::MissingMesh {
+                            offset,
+                            chunk_id: chunk.chunk_id,
+                            node_index,
+                        },
+                    )?;
+                }
+                self.push_chunk(&chunk, offset, data, options)?;
+                true
+            }
+            DecodedChunk::Custom(version, value) => {
+                self.custom_chunks.push(CustomChunk {
+                    chunk_id: chunk.chunk_id,
+                    version,
+                    offset,
+                    value,
+                });
+                true
             }
+            DecodedChunk::UnsupportedVersion(format, bytes) => {
+                self.report(
+                    options,
+                    ParseWarning::UnsupportedVersion {
+                        offset,
+                        format,
+                        version: chunk.version,
+                    },
+                )?;
+                self.push_unknown_chunk(&chunk, bytes);
+                false
+            }
+            DecodedChunk::Unknown(bytes) => {
+                self.report(
+                    options,
+                    ParseWarning::UnknownChunk {
+                        offset,
+                        chunk_id: chunk.chunk_id,
+                        size: chunk.size_in_bytes,
+                        version: chunk.version,
+                    },
+                )?;
+                self.push_unknown_chunk(&chunk, bytes);
+                false
+            }
+        };
 
-            // Write vertex positions
-            for v in 0..submesh.num_verts {
-                let actual_index = vertex_offset + v;
-                let offset = (actual_index * 12) as usize;
+        // A parsed body must consume exactly the declared size. Under-reads leave
+        // data behind and over-reads run into the next chunk, both point at a
+        // layout bug in the struct for this chunk id and version. Skipped chunks
+        // were already reported.
+        let position = offset + 12;
+        let target_pos = position + chunk.size_in_bytes as u64;
+        if parsed && end != target_pos {
+            self.report(
+                options,
+                ParseWarning::SizeMismatch {
+                    offset,
+                    chunk_id: chunk.chunk_id,
+                    version: chunk.version,
+                    declared: chunk.size_in_bytes,
+                    consumed: end - position,
+                },
+            )?;
+        }
 
-                if offset + 12 > positions_data.len() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "Vertex data out of bounds",
-                    ));
-                }
+        // Push the processed chunk into the chunk vector
+        self.trace_chunk(&chunk, offset, outcome);
+        self.chunk.push(chunk);
+        Ok(())
+    }
 
-                let px = f32::from_le_bytes(positions_data[offset..offset + 4].try_into().unwrap());
-                let py =
-                    f32::from_le_bytes(positions_data[offset + 4..offset + 8].try_into().unwrap());
-                let pz =
-                    f32::from_le_bytes(positions_data[offset + 8..offset + 12].try_into().unwrap());
+    /// Keeps the body of a chunk nothing could parse, so it is written back as is.
+    fn push_unknown_chunk(&mut self, chunk: &FileChunk, bytes: Vec<u8>) {
+        self.chunk_data.push(XacChunkData::Unknown {
+            id: chunk.chunk_id,
+            version: chunk.version,
+            bytes,
+        });
+    }
 
-                writeln!(writer, "v {} {} {}", -px, py, pz)?;
+    /// Stores a parsed chunk body, resolving repeats of chunks that may only appear once.
+    fn push_chunk(
+        &mut self,
+        chunk: &FileChunk,
+        offset: u64,
+        data: XacChunkData,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        const SINGLETON_CHUNKS: [u32; 6] = [
+            XacChunk::XacChunkInfo as u32,
+            XacChunk::XacChunkNodes as u32,
+            XacChunk::XacChunkMaterialinfo as u32,
+            XacChunk::XacChunkStdpmorphtargets as u32,
+            XacChunk::XacChunkNodemotionsources as u32,
+            XacChunk::XacChunkAttachmentnodes as u32,
+        ];
+        if !SINGLETON_CHUNKS.contains(&chunk.chunk_id) {
+            self.chunk_data.push(data);
+            return Ok(());
+        }
+
+        let Some(&index) = self.singleton_chunks.get(&chunk.chunk_id) else {
+            self.singleton_chunks
+                .insert(chunk.chunk_id, self.chunk_data.len());
+            self.chunk_data.push(data);
+            return Ok(());
+        };
+        self.report_conflict(
+            options,
+            ParseWarning::DuplicateChunk {
+                offset,
+                chunk_id: chunk.chunk_id,
+                version: chunk.version,
+            },
+        )?;
+        if options.conflicts == ConflictPolicy::LastWins {
+            self.chunk_data[index] = data;
+        }
+        Ok(())
+    }
+
+    fn report_conflict(&mut self, options: &ParseOptions, warning: ParseWarning) -> Result<()> {
+        if options.conflicts == ConflictPolicy::Error {
+            return Err(Error::Xac(warning.to_string()));
+        }
+        self.report(options, warning)
+    }
+
+    /// Decodes one chunk body without touching the parsed file, so independent chunks
+    /// can be decoded concurrently. `mesh_num_org_verts` resolves the mesh a skinning
+    /// chunk belongs to.
+    fn decode_chunk<R: Read + Seek>(
+        &self,
+        chunk: &FileChunk,
+        reader: &mut BinaryReader<R>,
+        mesh_num_org_verts: &dyn Fn(u32) -> Option<u32>,
+    ) -> Result<DecodedChunk> {
+        instrument::timed(Phase::ParseChunk, chunk.size_in_bytes as u64, || {
+            let decoded = self.decode_chunk_body(chunk, reader, mesh_num_org_verts)?;
+            match decoded {
+                DecodedChunk::Data(_) => Ok(decoded),
+                _ => Self::decode_custom_chunk(chunk, reader, decoded),
             }
+        })
+    }
 
-            // Write normals
-            if let Some(normals) = normals_data {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
+    /// Hands a chunk the built-in decoders skipped to its registered handler, if any,
+    /// or else keeps its raw body. The reader is still at the start of the body.
+    fn decode_custom_chunk<R: Read + Seek>(
+        chunk: &FileChunk,
+        reader: &mut BinaryReader<R>,
+        skipped: DecodedChunk,
+    ) -> Result<DecodedChunk> {
+        let body = reader.read_bytes(chunk.size_in_bytes as usize)?;
+        let Some(handler) = plugin::chunk_handler(chunk.chunk_id, chunk.version) else {
+            return Ok(match skipped {
+                DecodedChunk::UnsupportedVersion(format, _) => {
+                    DecodedChunk::UnsupportedVersion(format, body)
+                }
+                DecodedChunk::Unknown(_) => DecodedChunk::Unknown(body),
+                decoded => decoded,
+            });
+        };
+        let value = handler.parse(chunk.chunk_id, chunk.version, &body)?;
+        Ok(DecodedChunk::Custom(chunk.version, value))
+    }
 
-                    if offset + 12 > normals.len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Normal data out of bounds",
+    fn decode_chunk_body<R: Read + Seek>(
+        &self,
+        chunk: &FileChunk,
+        reader: &mut BinaryReader<R>,
+        mesh_num_org_verts: &dyn Fn(u32) -> Option<u32>,
+    ) -> Result<DecodedChunk> {
+        let data = match chunk.chunk_id {
+            id if id == XacChunk::XacChunkNode as u32 => match chunk.version {
+                1 => XacChunkData::XacNode(self.read_xac_node(reader)?),
+                2 => XacChunkData::XacNode2(self.read_xac_node2(reader)?),
+                3 => XacChunkData::XacNode3(self.read_xac_node3(reader)?),
+                4 => XacChunkData::XacNode4(self.read_xac_node4(reader)?),
+                _ => return Ok(DecodedChunk::UnsupportedVersion("XacChunkNode", Vec::new())),
+            },
+            id if id == XacChunk::XacChunkMesh as u32 => match chunk.version {
+                1 => XacChunkData::XACMesh(self.read_xac_mesh(reader)?),
+                2 => XacChunkData::XACMesh2(self.read_xac_mesh2(reader)?),
+                _ => return Ok(DecodedChunk::UnsupportedVersion("XacChunkMesh", Vec::new())),
+            },
+            id if id == XacChunk::XacChunkSkinninginfo as u32 => {
+                // Skinning tables are sized by the original vertex count of their mesh
+                let node_index = u32::read_options(&mut reader.reader, self.header.endian(), ())?;
+                reader.skip_bytes(-4)?;
+                let num_org_verts = mesh_num_org_verts(node_index).unwrap_or(0);
+                match chunk.version {
+                    1 => XacChunkData::XacSkinningInfo(self.read_xac_skinning_info(reader)?),
+                    2 => XacChunkData::XacSkinningInfo2(
+                        self.read_xac_skinning_info2(reader, num_org_verts)?,
+                    ),
+                    3 => XacChunkData::XacSkinningInfo3(
+                        self.read_xac_skinning_info3(reader, num_org_verts)?,
+                    ),
+                    4 => XacChunkData::XacSkinningInfo4(
+                        self.read_xac_skinning_info4(reader, num_org_verts)?,
+                    ),
+                    _ => {
+                        return Ok(DecodedChunk::UnsupportedVersion(
+                            "XacChunkSkinninginfo",
+                            Vec::new(),
                         ));
                     }
+                }
+            }
+            id if id == XacChunk::XacChunkStdmaterial as u32 => match chunk.version {
+                1 => XacChunkData::XacStandardMaterial(self.read_xac_standard_material(reader)?),
+                2 => XacChunkData::XacStandardMaterial2(self.read_xac_standard_material2(reader)?),
+                3 => XacChunkData::XacStandardMaterial3(self.read_xac_standard_material3(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkStdmaterial",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkStdmateriallayer as u32 => match chunk.version {
+                1 => XacChunkData::XACStandardMaterialLayer(
+                    self.read_xac_standard_material_layer(reader)?,
+                ),
+                2 => XacChunkData::XACStandardMaterialLayer2(
+                    self.read_xac_standard_material_layer2(reader)?,
+                ),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkStdmateriallayer",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkFxmaterial as u32 => match chunk.version {
+                1 => XacChunkData::XACFXMaterial(self.read_xac_fx_material(reader)?),
+                2 => XacChunkData::XACFXMaterial2(self.read_xac_fx_material2(reader)?),
+                3 => XacChunkData::XACFXMaterial3(self.read_xac_fx_material3(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkFxmaterial",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkMaterialinfo as u32 => match chunk.version {
+                1 => XacChunkData::XACMaterialInfo(self.read_xac_material_info(reader)?),
+                2 => XacChunkData::XACMaterialInfo2(self.read_xac_material_info2(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkMaterialinfo",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkNodes as u32 => match chunk.version {
+                1 => XacChunkData::XACNodes(self.read_xac_nodes(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkNodes",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkNodegroups as u32 => match chunk.version {
+                1 => XacChunkData::XACNodeGroup(self.read_xac_node_group(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkNodegroups",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkMeshlodlevels as u32 => match chunk.version {
+                1 => XacChunkData::XACMeshLodLevel(self.read_xac_mesh_lod_level(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkMeshlodlevels",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacLimit as u32 => match chunk.version {
+                1 => XacChunkData::XACLimit(self.read_xac_limit(reader)?),
+                _ => return Ok(DecodedChunk::UnsupportedVersion("XacLimit", Vec::new())),
+            },
+            id if id == XacChunk::XacChunkInfo as u32 => match chunk.version {
+                1 => XacChunkData::XacInfo(self.read_xac_info(reader)?),
+                2 => XacChunkData::XacInfo2(self.read_xac_info2(reader)?),
+                3 => XacChunkData::XacInfo3(self.read_xac_info3(reader)?),
+                4 => XacChunkData::XacInfo4(self.read_xac_info4(reader)?),
+                _ => return Ok(DecodedChunk::UnsupportedVersion("XacChunkInfo", Vec::new())),
+            },
+            id if id == XacChunk::XacChunkStdprogmorphtarget as u32 => match chunk.version {
+                1 => XacChunkData::XACPMorphTarget(self.read_xac_pmorph_target(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkStdprogmorphtarget",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkStdpmorphtargets as u32 => match chunk.version {
+                1 => XacChunkData::XACPMorphTargets(self.read_xac_pmorph_targets(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkStdpmorphtargets",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkNodemotionsources as u32 => match chunk.version {
+                1 => XacChunkData::XACNodeMotionSources(self.read_xac_node_motion_sources(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkNodemotionsources",
+                        Vec::new(),
+                    ));
+                }
+            },
+            id if id == XacChunk::XacChunkAttachmentnodes as u32 => match chunk.version {
+                1 => XacChunkData::XACAttachmentNodes(self.read_xac_attachment_nodes(reader)?),
+                _ => {
+                    return Ok(DecodedChunk::UnsupportedVersion(
+                        "XacChunkAttachmentnodes",
+                        Vec::new(),
+                    ));
+                }
+            },
+            _ => return Ok(DecodedChunk::Unknown(Vec::new())),
+        };
+        Ok(DecodedChunk::Data(data))
+    }
+
+    fn read_xac_info<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacInfo> {
+        // Implement parsing logic
+        Ok(XacInfo::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_info2<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacInfo2> {
+        Ok(XacInfo2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_info3<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacInfo3> {
+        Ok(XacInfo3::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_info4<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacInfo4> {
+        Ok(XacInfo4::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacNode> {
+        Ok(XacNode::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node2<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacNode2> {
+        Ok(XacNode2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node3<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacNode3> {
+        Ok(XacNode3::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node4<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XacNode4> {
+        Ok(XacNode4::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_skinning_info<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XacSkinningInfo> {
+        Ok(XacSkinningInfo::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    /// Original vertex count of the mesh attached to `node_index`, if there is one.
+    fn mesh_num_org_verts(&self, node_index: u32) -> Option<u32> {
+        find_mesh_num_org_verts(&self.chunk_data, node_index)
+    }
+
+    fn read_xac_skinning_info2<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        num_org_verts: u32,
+    ) -> Result<XacSkinningInfo2> {
+        Ok(XacSkinningInfo2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (num_org_verts,),
+        )?)
+    }
+
+    fn read_xac_skinning_info3<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        num_org_verts: u32,
+    ) -> Result<XacSkinningInfo3> {
+        Ok(XacSkinningInfo3::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (num_org_verts,),
+        )?)
+    }
+
+    fn read_xac_skinning_info4<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+        num_org_verts: u32,
+    ) -> Result<XacSkinningInfo4> {
+        Ok(XacSkinningInfo4::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (num_org_verts,),
+        )?)
+    }
+
+    fn read_xac_standard_material<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XacStandardMaterial> {
+        Ok(XacStandardMaterial::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_standard_material2<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XacStandardMaterial2> {
+        Ok(XacStandardMaterial2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_standard_material3<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XacStandardMaterial3> {
+        Ok(XacStandardMaterial3::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
 
-                    let nx = f32::from_le_bytes(normals[offset..offset + 4].try_into().unwrap());
-                    let ny =
-                        f32::from_le_bytes(normals[offset + 4..offset + 8].try_into().unwrap());
-                    let nz =
-                        f32::from_le_bytes(normals[offset + 8..offset + 12].try_into().unwrap());
+    fn read_xac_standard_material_layer<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACStandardMaterialLayer> {
+        Ok(XACStandardMaterialLayer::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_standard_material_layer2<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACStandardMaterialLayer2> {
+        Ok(XACStandardMaterialLayer2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_sub_mesh<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACSubMesh> {
+        Ok(XACSubMesh::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_mesh<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XACMesh> {
+        Ok(XACMesh::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_mesh2<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XACMesh2> {
+        Ok(XACMesh2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_limit<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XACLimit> {
+        Ok(XACLimit::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_pmorph_target<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACPMorphTarget> {
+        Ok(XACPMorphTarget::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_pmorph_targets<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACPMorphTargets> {
+        Ok(XACPMorphTargets::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_fx_material<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACFXMaterial> {
+        Ok(XACFXMaterial::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_fx_material2<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACFXMaterial2> {
+        Ok(XACFXMaterial2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_fx_material3<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACFXMaterial3> {
+        Ok(XACFXMaterial3::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node_group<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACNodeGroup> {
+        Ok(XACNodeGroup::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_nodes<R: Read + Seek>(&self, reader: &mut BinaryReader<R>) -> Result<XACNodes> {
+        Ok(XACNodes::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
 
-                    writeln!(writer, "vn {} {} {}", -nx, ny, nz)?;
+    fn read_xac_material_info<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACMaterialInfo> {
+        Ok(XACMaterialInfo::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_material_info2<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACMaterialInfo2> {
+        Ok(XACMaterialInfo2::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_mesh_lod_level<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACMeshLodLevel> {
+        Ok(XACMeshLodLevel::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_node_motion_sources<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACNodeMotionSources> {
+        Ok(XACNodeMotionSources::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    fn read_xac_attachment_nodes<R: Read + Seek>(
+        &self,
+        reader: &mut BinaryReader<R>,
+    ) -> Result<XACAttachmentNodes> {
+        Ok(XACAttachmentNodes::read_options(
+            &mut reader.reader,
+            self.header.endian(),
+            (),
+        )?)
+    }
+
+    /// Per material index of level of detail `lod`, the texture the material shows,
+    /// see `material_textures`.
+    fn get_texture_names(&self, lod: u32) -> Vec<&str> {
+        self.material_textures(lod)
+            .into_iter()
+            .map(|(_, texture)| texture)
+            .collect()
+    }
+
+    /// `material_table` borrowed: per material, its name and the texture picked as in
+    /// `Material::texture_name`, or the name for materials without one.
+    fn material_textures(&self, lod: u32) -> Vec<(&str, &str)> {
+        fn layers(layers: &[XACStandardMaterialLayer2]) -> Vec<(&str, u8)> {
+            layers
+                .iter()
+                .map(|layer| (&*layer.texture_name, layer.map_type))
+                .collect()
+        }
+        fn bitmaps(bitmaps: &Option<Vec<XACFXBitmapParameter>>) -> Vec<(&str, u8)> {
+            bitmaps
+                .iter()
+                .flatten()
+                .map(|bitmap| (&*bitmap.value_name, DIFFUSE_MAP))
+                .collect()
+        }
+
+        // The level, name and textures with their map types of each material
+        let lods = self.material_lods();
+        let mut materials = Vec::new();
+        for (chunk, &lod) in self.chunk_data.iter().zip(&lods) {
+            let (name, textures) = match chunk {
+                XacChunkData::XacStandardMaterial(m) => (&*m.material_name, Vec::new()),
+                XacChunkData::XacStandardMaterial2(m) => {
+                    (&*m.material_name, layers(&m.standard_material_layer2))
+                }
+                XacChunkData::XacStandardMaterial3(m) => {
+                    (&*m.material_name, layers(&m.standard_material_layer2))
                 }
+                XacChunkData::XACFXMaterial(m) => (&*m.name, bitmaps(&m.xac_fx_bitmap_parameter)),
+                XacChunkData::XACFXMaterial2(m) => (&*m.name, bitmaps(&m.xac_fx_bitmap_parameter)),
+                XacChunkData::XACFXMaterial3(m) => (&*m.name, bitmaps(&m.xac_fx_bitmap_parameter)),
+                _ => continue,
+            };
+            materials.push((lod, name, textures));
+        }
+        for (chunk, &lod) in self.chunk_data.iter().zip(&lods) {
+            let (material_number, layer) = match chunk {
+                XacChunkData::XACStandardMaterialLayer(layer) => (
+                    layer.material_number,
+                    (&*layer.texture_name, layer.map_type),
+                ),
+                XacChunkData::XACStandardMaterialLayer2(layer) => (
+                    layer.material_number,
+                    (&*layer.texture_name, layer.map_type),
+                ),
+                _ => continue,
+            };
+            let material = materials
+                .iter_mut()
+                .filter(|(level, ..)| *level == lod)
+                .nth(material_number as usize);
+            if let Some((_, _, layers)) = material {
+                layers.push(layer);
             }
+        }
 
-            // Write texture coordinates
-            if let Some(uvs) = uvs_data {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 8) as usize;
+        let lod = if materials.iter().any(|(level, ..)| *level == lod) {
+            lod
+        } else {
+            0
+        };
+        materials
+            .into_iter()
+            .filter(|(level, ..)| *level == lod)
+            .map(|(_, name, layers)| {
+                let texture = layers
+                    .iter()
+                    .find(|(_, map_type)| *map_type == DIFFUSE_MAP)
+                    .or(layers.first())
+                    .map_or(name, |(texture, _)| texture);
+                (name, texture)
+            })
+            .collect()
+    }
 
-                    if offset + 8 > uvs.len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "UV data out of bounds",
-                        ));
-                    }
+    pub fn export_all_meshes(&self, output_prefix: &str) -> Result<()> {
+        self.export_all_meshes_with_collision(output_prefix, CollisionMeshes::Include)
+    }
 
-                    let u = f32::from_le_bytes(uvs[offset..offset + 4].try_into().unwrap());
-                    let v = f32::from_le_bytes(uvs[offset + 4..offset + 8].try_into().unwrap());
+    /// Like `export_all_meshes`, writing only the meshes `collision` selects, e.g.
+    /// `CollisionMeshes::Only` for hitbox geometry.
+    pub fn export_all_meshes_with_collision(
+        &self,
+        output_prefix: &str,
+        collision: CollisionMeshes,
+    ) -> Result<()> {
+        instrument::timed(Phase::Export, 0, || {
+            self.export_all_meshes_to_obj(output_prefix, &HashMap::new(), collision)
+        })
+    }
 
-                    writeln!(writer, "vt {} {}", u, 1.0 - v)?;
+    /// Writes every mesh into the one OBJ file `path`, an object per mesh named after
+    /// its node and a group per submesh, with the materials in one MTL file next to it.
+    pub fn export_to_obj_single<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let export = || -> Result<()> {
+            let node_names = self.node_names();
+            let mtl_path = path.with_extension("mtl");
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let mut writer = BufWriter::new(File::create(path)?);
+            let mut materials = Vec::new();
+            writeln!(
+                writer,
+                "mtllib {}",
+                relative_path(path.parent().unwrap_or(Path::new("")), &mtl_path)
+            )?;
+
+            let mut written = [0; 3];
+            for chunk in self.mesh_chunks() {
+                let layers = ObjLayers::new(chunk.layers)?;
+                let material_textures = self.material_textures(chunk.lod);
+                let mut object = node_names
+                    .get(chunk.node_index as usize)
+                    .map_or_else(|| format!("mesh_{}", chunk.index), |name| name.to_string());
+                if chunk.lod > 0 {
+                    object = format!("{}_lod{}", object, chunk.lod);
+                }
+                writeln!(writer, "o {}", object)?;
+
+                let mut vertex_offset: usize = 0;
+                for (i, submesh) in chunk.sub_meshes.iter().enumerate() {
+                    let range = vertex_offset..vertex_offset + submesh.num_verts as usize;
+                    // Material 0 is the default material, which has no texture
+                    let (material, texture) = match submesh.material_index as usize {
+                        0 => ("default", None),
+                        index => material_textures
+                            .get(index)
+                            .map_or(("default", None), |&(name, texture)| (name, Some(texture))),
+                    };
+                    if !materials.iter().any(|&(name, _)| name == material) {
+                        materials.push((material, texture));
+                    }
+                    writeln!(writer, "g {}_submesh_{}", object, i)?;
+                    writeln!(writer, "usemtl {}", material)?;
+                    layers.write_submesh(&mut writer, &range, &submesh.indices, &mut written)?;
+                    vertex_offset = range.end;
                 }
             }
+            writer.flush()?;
 
-            // Write faces
-            for i in (0..submesh.num_indices).step_by(3) {
-                let idx1 = submesh.indices[i as usize] + 1;
-                let idx2 = submesh.indices[i as usize + 1] + 1;
-                let idx3 = submesh.indices[i as usize + 2] + 1;
-
-                if normals_data.is_some() && uvs_data.is_some() {
-                    writeln!(
-                        writer,
-                        "f {}/{}/{} {}/{}/{} {}/{}/{}",
-                        idx3, idx3, idx3, idx2, idx2, idx2, idx1, idx1, idx1
-                    )?;
-                } else if normals_data.is_some() {
-                    writeln!(
-                        writer,
-                        "f {}//{} {}//{} {}//{}",
-                        idx3, idx3, idx2, idx2, idx1, idx1
-                    )?;
-                } else {
-                    writeln!(writer, "f {} {} {}", idx3, idx2, idx1)?;
+            let mut mtl_writer = BufWriter::new(File::create(&mtl_path)?);
+            for (material, texture) in materials {
+                writeln!(mtl_writer, "newmtl {}", material)?;
+                writeln!(mtl_writer, "Kd 1.0 1.0 1.0")?;
+                if let Some(texture) = texture {
+                    writeln!(mtl_writer, "map_Kd {}", texture)?;
                 }
             }
+            mtl_writer.flush()?;
+            Ok(())
+        };
+        instrument::timed(Phase::Export, 0, export).map_err(|err| err.in_file(path))
+    }
 
-            // println!("✅ Saved submesh {} to {}", i, obj_filename);
+    /// Like `export_all_meshes`, also converting the textures the materials use to PNG
+    /// files next to the OBJ files, which the MTL files then reference.
+    ///
+    /// `load_texture` returns the DDS bytes for a texture name as stored in the
+    /// materials, or `None` when it is not available; those materials keep referring
+    /// to the bare texture name.
+    pub fn export_all_meshes_with_textures(
+        &self,
+        output_prefix: &str,
+        load_texture: impl FnMut(&str) -> Option<Vec<u8>>,
+    ) -> Result<()> {
+        instrument::timed(Phase::Export, 0, || {
+            let texture_files = self.export_textures(
+                &mut |stem| PathBuf::from(format!("{}_{}.png", output_prefix, stem)),
+                load_texture,
+            )?;
+            self.export_all_meshes_to_obj(output_prefix, &texture_files, CollisionMeshes::Include)
+        })
+    }
 
-            vertex_offset += submesh.num_verts;
+    /// Like `export_all_meshes_with_textures`, naming every OBJ, MTL and PNG file
+    /// through `layout`. `actor` fills the `{actor}` placeholder; textures get their
+    /// stem as `{name}` and `{material}`. Returns the files written.
+    pub fn export_all_meshes_to_layout(
+        &self,
+        layout: &mut OutputLayout,
+        actor: &str,
+        load_texture: impl FnMut(&str) -> Option<Vec<u8>>,
+    ) -> Result<Vec<PathBuf>> {
+        let progress = Task::start(Operation::Convert, actor, None);
+        let export = || {
+            let mut written = Vec::new();
+            let texture_files = self.export_textures(
+                &mut |stem| {
+                    let path = layout.path(&NameFields {
+                        actor,
+                        material: stem,
+                        name: stem,
+                        ext: "png",
+                        ..Default::default()
+                    });
+                    written.push(path.clone());
+                    progress.progress(written.len() as u64);
+                    path
+                },
+                load_texture,
+            )?;
+            self.export_meshes_to_obj(
+                &mut |fields| {
+                    let path = layout.path(&NameFields { actor, ..fields });
+                    written.push(path.clone());
+                    progress.progress(written.len() as u64);
+                    path
+                },
+                &texture_files,
+                CollisionMeshes::Include,
+            )?;
+            Ok(written)
+        };
+        progress.finish(instrument::timed(Phase::Export, 0, export))
+    }
+
+    /// Converts the textures `load_texture` provides to PNG files named by
+    /// `name_texture` from the texture stem. Returns the file written per texture name.
+    fn export_textures(
+        &self,
+        name_texture: &mut dyn FnMut(&str) -> PathBuf,
+        mut load_texture: impl FnMut(&str) -> Option<Vec<u8>>,
+    ) -> Result<HashMap<String, PathBuf>> {
+        let mut texture_files = HashMap::new();
+        let names = self.lod_levels().into_iter();
+        for name in names.flat_map(|lod| self.get_texture_names(lod)) {
+            if texture_files.contains_key(name) {
+                continue;
+            }
+            let Some(dds) = load_texture(name) else {
+                continue;
+            };
+            let png_path = name_texture(&texture_stem(name));
+            if let Some(dir) = png_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            texture::Image::from_dds(&dds)
+                .and_then(|image| image.save_png(&png_path))
+                .map_err(|err| err.in_entry(name))?;
+            texture_files.insert(name.to_string(), png_path);
         }
+        Ok(texture_files)
+    }
 
+    /// `texture_files` maps texture names to the file written for them, if any.
+    fn export_all_meshes_to_obj(
+        &self,
+        output_prefix: &str,
+        texture_files: &HashMap<String, PathBuf>,
+        collision: CollisionMeshes,
+    ) -> Result<()> {
+        self.export_meshes_to_obj(
+            &mut |fields| {
+                PathBuf::from(format!(
+                    "{}_mesh_{}_submesh_{}.{}",
+                    output_prefix, fields.mesh, fields.index, fields.ext
+                ))
+            },
+            texture_files,
+            collision,
+        )
+    }
+
+    /// Writes an OBJ file, and an MTL file when it has a material, per submesh of the
+    /// meshes `collision` selects, named by `name_file`.
+    fn export_meshes_to_obj(
+        &self,
+        name_file: &mut dyn for<'n> FnMut(NameFields<'n>) -> PathBuf,
+        texture_files: &HashMap<String, PathBuf>,
+        collision: CollisionMeshes,
+    ) -> Result<()> {
+        for chunk in self
+            .mesh_chunks()
+            .filter(|chunk| collision.keeps(chunk.is_collision_mesh))
+        {
+            let mut name_submesh = |index, material: Option<&str>, ext: &str| {
+                let material = material.map(texture_stem);
+                name_file(NameFields {
+                    lod: chunk.lod,
+                    mesh: chunk.index,
+                    index,
+                    material: material.as_deref().unwrap_or("default"),
+                    ext,
+                    ..Default::default()
+                })
+            };
+            self.export_to_obj(&chunk, &mut name_submesh, texture_files)?;
+        }
         Ok(())
     }
 
-    fn export_to_struct(&self, mesh: &XACMesh) -> io::Result<Mesh> {
-        let texture_name = self.get_texture_names();
+    /// Owned copies of every mesh, see `mesh_views` for the borrowed form.
+    pub fn export_all_meshes_into_struct(&self) -> Result<Vec<Mesh>> {
+        self.export_all_meshes_into_struct_with(&ExportOptions::default())
+    }
 
-        // Find layers by their layer_type_id
-        let positions_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribPositions as u32);
+    /// Like `export_all_meshes_into_struct`, converting the geometry for a target engine,
+    /// e.g. with `ExportOptions::preset(EnginePreset::Unreal)`.
+    pub fn export_all_meshes_into_struct_with(&self, options: &ExportOptions) -> Result<Vec<Mesh>> {
+        self.export_meshes(self.mesh_chunks(), options)
+    }
 
-        let normals_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribNormals as u32);
+    /// The axis-aligned box around the meshes of level of detail 0 as `(min, max)`,
+    /// exported with `options` as `export_all_meshes_into_struct_with` does, so it
+    /// encloses the `Mesh::aabb` of each of them. `None` without any vertices.
+    pub fn actor_bounds(&self, options: &ExportOptions) -> Result<Option<([f32; 3], [f32; 3])>> {
+        let meshes =
+            self.export_meshes(self.mesh_chunks().filter(|chunk| chunk.lod == 0), options)?;
+        Ok(aabb(
+            meshes
+                .iter()
+                .filter_map(Mesh::aabb)
+                .flat_map(|(min, max)| [min, max]),
+        ))
+    }
 
-        let tangents_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribTangents as u32);
+    /// The levels of detail the actor has meshes for, ascending; 0 is the most
+    /// detailed.
+    pub fn lod_levels(&self) -> Vec<u32> {
+        let mut levels: Vec<u32> = self
+            .mesh_chunks()
+            .map(|chunk| chunk.lod)
+            .chain(self.chunk_data.iter().filter_map(|chunk| match chunk {
+                XacChunkData::XACMeshLodLevel(level) => Some(level.lod_level),
+                _ => None,
+            }))
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels
+    }
 
-        let uvs_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribUvcoords as u32);
+    /// The meshes of level of detail `lod`, see `meshes_for_lod_with`.
+    pub fn meshes_for_lod(&self, lod: u32) -> Result<Vec<Mesh>> {
+        self.meshes_for_lod_with(lod, &ExportOptions::default())
+    }
 
-        let colors32_layer = mesh
-            .vertex_attribute_layer
+    /// Like `export_all_meshes_into_struct_with`, but only the meshes of level of
+    /// detail `lod`. Actors store lower levels either as mesh chunks tagged with
+    /// their level or as a whole actor embedded in a mesh LOD level chunk, which is
+    /// parsed when no mesh chunk has the level. Fails for levels the actor lacks.
+    pub fn meshes_for_lod_with(&self, lod: u32, options: &ExportOptions) -> Result<Vec<Mesh>> {
+        if lod == 0 || self.mesh_chunks().any(|chunk| chunk.lod == lod) {
+            return self
+                .export_meshes(self.mesh_chunks().filter(|chunk| chunk.lod == lod), options);
+        }
+        let level = self
+            .chunk_data
             .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribColors32 as u32);
+            .find_map(|chunk| match chunk {
+                XacChunkData::XACMeshLodLevel(level) if level.lod_level == lod => Some(level),
+                _ => None,
+            })
+            .ok_or_else(|| Error::Xac(format!("Actor has no level of detail {}", lod)))?;
+        let actor = XACFile::load_from_slice(&level.lod_model)
+            .map_err(|err| err.in_chunk(XacChunk::XacChunkMeshlodlevels as u32, 1))?;
+        actor.export_all_meshes_into_struct_with(options)
+    }
 
-        let original_vertex_numbers_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribOrgvtxnumbers as u32);
+    fn export_meshes<'a>(
+        &self,
+        chunks: impl Iterator<Item = MeshChunk<'a>>,
+        options: &ExportOptions,
+    ) -> Result<Vec<Mesh>> {
+        instrument::timed(Phase::Export, 0, || {
+            let world_transforms = if options.world_space {
+                self.world_transforms(options)
+            } else {
+                Vec::new()
+            };
+            chunks
+                .filter(|chunk| options.collision.keeps(chunk.is_collision_mesh))
+                .map(|chunk| {
+                    let texture_names = self.get_texture_names(chunk.lod);
+                    let view = mesh_view(chunk.layers, chunk.sub_meshes, &texture_names)?;
+                    let mut mesh = view.to_mesh_with(options);
+                    mesh.is_collision = chunk.is_collision_mesh;
+                    self.skin_mesh(&chunk, &mut mesh);
+                    self.morph_mesh(&chunk, &view, &mut mesh, options);
+                    if let Some(world) = world_transforms.get(chunk.node_index as usize) {
+                        mesh.transform(world);
+                    }
+                    if options.compute_tangents {
+                        mesh.compute_tangents();
+                    }
+                    Ok(mesh)
+                })
+                .collect()
+        })
+    }
 
-        let colors128_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribColors128 as u32);
+    fn export_to_obj(
+        &self,
+        mesh: &MeshChunk,
+        name_file: &mut dyn FnMut(usize, Option<&str>, &str) -> PathBuf,
+        texture_files: &HashMap<String, PathBuf>,
+    ) -> Result<()> {
+        let materials = self.material_textures(mesh.lod);
 
-        let bitangents_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribBitangents as u32);
+        let layers = ObjLayers::new(mesh.layers)?;
 
-        let positions_data = if let Some(l) = positions_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+        let mut vertex_offset: usize = 0;
 
-        let normals_data = if let Some(l) = normals_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+        for (i, submesh) in mesh.sub_meshes.iter().enumerate() {
+            let range = vertex_offset..vertex_offset + submesh.num_verts as usize;
+            let material_index = submesh.material_index as usize;
 
-        let tangents_data = if let Some(l) = tangents_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+            let material = match material_index {
+                0 => None,
+                index => materials.get(index).copied(),
+            };
+            let texture_name = material.map(|(_, texture)| texture);
+            let obj_path = name_file(i, texture_name, "obj");
+            let obj_dir = obj_path.parent().unwrap_or(Path::new(""));
+            std::fs::create_dir_all(obj_dir)?;
+            let file = File::create(&obj_path)?;
+            let mut writer = BufWriter::new(file);
 
-        let uvs_data = if let Some(l) = uvs_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+            writeln!(writer, "o Submesh_{}", i)?;
 
-        let colors32_data = if let Some(l) = colors32_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+            if let Some((material_name, texture_name)) = material {
+                let mtl_path = name_file(i, Some(texture_name), "mtl");
+                writeln!(writer, "mtllib {}", relative_path(obj_dir, &mtl_path))?;
+                let mtl_dir = mtl_path.parent().unwrap_or(Path::new(""));
+                std::fs::create_dir_all(mtl_dir)?;
+                let mtl_file = File::create(&mtl_path)?;
+                let mut mtl_writer = BufWriter::new(mtl_file);
 
-        let original_vertex_numbers_data = if let Some(l) = original_vertex_numbers_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+                writeln!(mtl_writer, "newmtl {}", material_name)?;
+                writeln!(mtl_writer, "Kd 1.0 1.0 1.0")?;
+                let texture_file = texture_files
+                    .get(texture_name)
+                    .map(|path| relative_path(mtl_dir, path));
+                writeln!(
+                    mtl_writer,
+                    "map_Kd {}",
+                    texture_file.as_deref().unwrap_or(texture_name)
+                )?;
+                writeln!(writer, "usemtl {}", material_name)?;
+            }
 
-        let colors128_data = if let Some(l) = colors128_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+            layers.write_submesh(&mut writer, &range, &submesh.indices, &mut [0; 3])?;
 
-        let bitangents_data = if let Some(l) = bitangents_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
-        };
+            vertex_offset = range.end;
+        }
 
-        let mut vertex_offset: u32 = 0;
-        let mut submeshes = Vec::new();
+        Ok(())
+    }
 
-        for (i, submesh) in mesh.sub_meshes.iter().enumerate() {
-            let material_index = submesh.material_index as usize;
+    /// Writes the actor as a binary glTF 2.0 file, see `to_glb`.
+    pub fn export_gltf<P: AsRef<Path>>(&self, path: P, options: &GltfOptions) -> Result<()> {
+        let path = path.as_ref();
+        let glb = self.to_glb(options)?;
+        std::fs::write(path, glb).map_err(|err| Error::from(err).in_file(path))
+    }
 
-            let mut submesh_data = SubMesh {
-                texture_name: String::new(),
-                position_count: 0,
-                positions: Vec::new(),
-                normal_count: 0,
-                normals: Vec::new(),
-                tangent_count: 0,
-                tangents: Vec::new(),
-                uvcoord_count: 0,
-                uvcoords: Vec::new(),
-                color32_count: 0,
-                colors32: Vec::new(),
-                original_vertex_numbers_count: 0,
-                original_vertex_numbers: Vec::new(),
-                color128_count: 0,
-                colors128: Vec::new(),
-                bitangent_count: 0,
-                bitangents: Vec::new(),
-                indices_count: submesh.num_indices as usize,
-                indices: submesh.indices.clone(),
-            };
+    /// The actor as binary glTF 2.0 (`.glb`): the node hierarchy in its bind pose, the
+    /// meshes of `options.lod` with one primitive per submesh and a material per
+    /// texture, UVs, vertex colors, and with `options.skinning` a skin over every node
+    /// with up to four weights per vertex. Collision meshes are left out. Textures are
+    /// referenced as `<prefix><texture stem>.png` next to the file, the names
+    /// `export_all_meshes_with_textures` writes them under.
+    pub fn to_glb(&self, options: &GltfOptions) -> Result<Vec<u8>> {
+        instrument::timed(Phase::Export, 0, || {
+            gltf::write_glb(&self.gltf_scene(options)?)
+        })
+    }
 
-            // Process texture name if material_index is valid
-            if material_index != 0 {
-                if let Some(material_name) = texture_name.get(material_index) {
-                    submesh_data.texture_name = material_name.to_string();
-                }
-            }
+    /// Writes the actor as a COLLADA document, see `to_collada`.
+    pub fn export_collada<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let dae = self.to_collada()?;
+        std::fs::write(path, dae).map_err(|err| Error::from(err).in_file(path))
+    }
 
-            // Write vertex positions if data exists
-            if let Some(positions_layer) = positions_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
+    /// The actor as COLLADA 1.4.1 (`.dae`) with the same content as `to_glb` with
+    /// default options, for tools that predate glTF: the skeleton as joint nodes, a
+    /// geometry per mesh of LOD 0 and a skin controller for every skinned mesh. Lengths
+    /// stay in centimeters, which the document declares as its unit.
+    pub fn to_collada(&self) -> Result<String> {
+        instrument::timed(Phase::Export, 0, || {
+            let options = GltfOptions::default().with_scale(1.0);
+            collada::write_dae(&self.gltf_scene(&options)?)
+        })
+    }
 
-                    if offset + 12 > positions_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Vertex data out of bounds",
-                        ));
+    fn gltf_scene(&self, options: &GltfOptions) -> Result<GltfScene> {
+        let convert = options.export_options();
+        let mut scene = GltfScene::default();
+        let mut push_node = |quat: &FileQuaternion,
+                             position: &FileVector3,
+                             scale: &FileVector3,
+                             parent_index: u32,
+                             name: &str| {
+            scene.nodes.push(GltfNode {
+                name: name.to_string(),
+                parent: (parent_index != u32::MAX).then_some(parent_index as usize),
+                translation: convert.position([position.axis_x, position.axis_y, position.axis_z]),
+                rotation: convert.rotation([quat.axis_x, quat.axis_y, quat.axis_z, quat.axis_w]),
+                scale: [scale.axis_x, scale.axis_y, scale.axis_z],
+            })
+        };
+        for chunk in &self.chunk_data {
+            match chunk {
+                XacChunkData::XacNode(node) => push_node(
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    node.parent_index,
+                    &node.node_name,
+                ),
+                XacChunkData::XacNode2(node) => push_node(
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    node.parent_index,
+                    &node.node_name,
+                ),
+                XacChunkData::XacNode3(node) => push_node(
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    node.parent_index,
+                    &node.node_name,
+                ),
+                XacChunkData::XacNode4(node) => push_node(
+                    &node.local_quat,
+                    &node.local_pos,
+                    &node.local_scale,
+                    node.parent_index,
+                    &node.node_name,
+                ),
+                XacChunkData::XACNodes(nodes) => {
+                    for node in &nodes.xac_node {
+                        push_node(
+                            &node.local_quat,
+                            &node.local_pos,
+                            &node.local_scale,
+                            node.parent_index,
+                            &node.node_name,
+                        );
                     }
-
-                    let px = f32::from_le_bytes(
-                        positions_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let py = f32::from_le_bytes(
-                        positions_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let pz = f32::from_le_bytes(
-                        positions_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.positions.push([-px, py, pz]);
                 }
-                submesh_data.position_count = submesh_data.positions.len();
+                _ => {}
             }
+        }
 
-            // Write normals if data exists
-            if let Some(normals_layer) = normals_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
-
-                    if offset + 12 > normals_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Normal data out of bounds",
-                        ));
-                    }
+        // Diffuse color and sidedness of the standard materials, by the texture name
+        // submeshes know them by
+        let mut standard: HashMap<String, ([f32; 4], bool)> = HashMap::new();
+        for material in self.material_table(options.lod) {
+            let Material::Standard(m) = &material else {
+                continue;
+            };
+            let [red, green, blue, _] = m.diffuse;
+            let color = [red, green, blue, m.opacity.clamp(0.0, 1.0)];
+            let name = material.texture_name().unwrap_or(&m.name).to_string();
+            standard.entry(name).or_insert((color, m.double_sided));
+        }
+        let mut materials: HashMap<&str, usize> = HashMap::new();
 
-                    let nx = f32::from_le_bytes(
-                        normals_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let ny = f32::from_le_bytes(
-                        normals_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let nz = f32::from_le_bytes(
-                        normals_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.normals.push([-nx, ny, nz]);
-                }
-                submesh_data.normal_count = submesh_data.normals.len();
+        let texture_names = self.get_texture_names(options.lod);
+        for chunk in self.mesh_chunks() {
+            if chunk.lod != options.lod || chunk.is_collision_mesh {
+                continue;
+            }
+            let view = mesh_view(chunk.layers, chunk.sub_meshes, &texture_names)?;
+            let mut mesh = view.to_mesh_with(&convert);
+            if options.skinning {
+                self.skin_mesh(&chunk, &mut mesh);
             }
+            self.morph_mesh(&chunk, &view, &mut mesh, &convert);
+            let morph_targets = std::mem::take(&mut mesh.morph_targets);
+            let primitives = view
+                .submeshes
+                .iter()
+                .zip(mesh.submeshes)
+                .map(|(view, submesh)| {
+                    let name = view.texture_name;
+                    let material = *materials.entry(name).or_insert_with(|| {
+                        let (base_color, double_sided) =
+                            standard.get(name).copied().unwrap_or(([1.0; 4], false));
+                        scene.materials.push(GltfMaterial {
+                            name: if name.is_empty() {
+                                "default".to_string()
+                            } else {
+                                texture_stem(name)
+                            },
+                            texture: (!name.is_empty()).then(|| {
+                                format!("{}{}.png", options.texture_uri_prefix, texture_stem(name))
+                            }),
+                            base_color,
+                            double_sided,
+                        });
+                        scene.materials.len() - 1
+                    });
+                    GltfPrimitive { submesh, material }
+                })
+                .collect();
+
+            let node_index = chunk.node_index as usize;
+            let node = scene.nodes.get(node_index);
+            scene.meshes.push(GltfMesh {
+                name: node
+                    .map_or_else(|| format!("mesh_{}", chunk.index), |node| node.name.clone()),
+                node: node.map(|_| node_index),
+                primitives,
+                morph_targets,
+            });
+        }
+        Ok(scene)
+    }
 
-            // Write tangents if data exists
-            if let Some(tangents_layer) = tangents_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 16) as usize; // 16 bytes for tangent (4 components)
+    /// Influences and the per original vertex table of the skinning info of the mesh
+    /// on node `node_index`.
+    fn skinning_table(
+        &self,
+        node_index: u32,
+        lod: u32,
+    ) -> Option<(&[XacSkinInfluence], &[XacSkinningInfoTableEntry])> {
+        self.chunk_data.iter().find_map(|chunk| {
+            let (node, info_lod, collision, influences, table) = match chunk {
+                XacChunkData::XacSkinningInfo2(info) => (
+                    info.node_index,
+                    0,
+                    info.is_for_collision_mesh,
+                    &info.skinning_influence,
+                    &info.skinning_info_table_entry,
+                ),
+                XacChunkData::XacSkinningInfo3(info) => (
+                    info.node_index,
+                    0,
+                    info.is_for_collision_mesh,
+                    &info.skinning_influence,
+                    &info.skinning_info_table_entry,
+                ),
+                XacChunkData::XacSkinningInfo4(info) => (
+                    info.node_index,
+                    info.lod,
+                    info.is_for_collision_mesh,
+                    &info.skinning_influence,
+                    &info.skinning_info_table_entry,
+                ),
+                _ => return None,
+            };
+            (node == node_index && info_lod == lod && collision == 0)
+                .then_some((influences.as_slice(), table.as_slice()))
+        })
+    }
 
-                    if offset + 16 > tangents_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Tangent data out of bounds",
-                        ));
-                    }
+    /// Fills the bone indices and weights of every submesh of `mesh`, which was made
+    /// from `chunk`, from the skinning info of its node. Left empty when the mesh has
+    /// no skinning info or a submesh lacks original vertex numbers to look it up by.
+    fn skin_mesh(&self, chunk: &MeshChunk, mesh: &mut Mesh) {
+        let Some((influences, table)) = self.skinning_table(chunk.node_index, chunk.lod) else {
+            return;
+        };
+        let resolvable = mesh
+            .submeshes
+            .iter()
+            .all(|submesh| submesh.original_vertex_numbers.len() == submesh.positions.len());
+        let node_count = self.node_count();
+        if !resolvable || node_count == 0 {
+            return;
+        }
+        let fallback = u16::try_from(chunk.node_index)
+            .ok()
+            .filter(|&node| (node as usize) < node_count)
+            .unwrap_or(0);
+        for submesh in &mut mesh.submeshes {
+            (submesh.bone_indices, submesh.bone_weights) = submesh
+                .original_vertex_numbers
+                .iter()
+                .map(|&vertex| {
+                    vertex_weights(influences, table.get(vertex as usize), node_count, fallback)
+                })
+                .unzip();
+        }
+    }
+
+    /// Morph targets of `lod`, from the single morph target chunks and the morph
+    /// target list.
+    fn morph_targets(&self, lod: u32) -> impl Iterator<Item = &XACPMorphTarget> {
+        self.chunk_data
+            .iter()
+            .flat_map(|chunk| match chunk {
+                XacChunkData::XACPMorphTarget(target) => std::slice::from_ref(target),
+                XacChunkData::XACPMorphTargets(targets) => targets.morph_targets.as_slice(),
+                _ => &[],
+            })
+            .filter(move |target| target.lod == lod)
+    }
 
-                    let tx = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let ty = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let tz = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let tw = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 12..offset + 16]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.tangents.push([tx, ty, tz, tw]);
+    /// Adds the morph targets that deform `chunk` to `mesh`, which was made from
+    /// `view` with `options`. Delta vertex numbers index the vertex layers of the mesh;
+    /// positions are decompressed into the range of their deltas, normals and tangents
+    /// into -2..2.
+    fn morph_mesh(
+        &self,
+        chunk: &MeshChunk,
+        view: &MeshView,
+        mesh: &mut Mesh,
+        options: &ExportOptions,
+    ) {
+        if chunk.is_collision_mesh {
+            return;
+        }
+        for target in self.morph_targets(chunk.lod) {
+            let mut deltas = target
+                .morph_target_mesh_deltas
+                .iter()
+                .filter(|deltas| deltas.node_index == chunk.node_index)
+                .peekable();
+            if deltas.peek().is_none() {
+                continue;
+            }
+            let zeros: Vec<Vec<[f32; 3]>> = view
+                .submeshes
+                .iter()
+                .map(|submesh| vec![[0.0; 3]; submesh.vertices.len()])
+                .collect();
+            let mut morph = MorphTarget {
+                name: target.name.clone(),
+                range_min: target.range_min,
+                range_max: target.range_max,
+                position_deltas: zeros.clone(),
+                normal_deltas: zeros.clone(),
+                tangent_deltas: zeros,
+            };
+            for deltas in deltas {
+                for (i, &vertex) in deltas.vertex_numbers.iter().enumerate() {
+                    let vertex = vertex as usize;
+                    let submesh = view
+                        .submeshes
+                        .partition_point(|submesh| submesh.vertices.end <= vertex);
+                    let Some(local) = view
+                        .submeshes
+                        .get(submesh)
+                        .filter(|submesh| submesh.vertices.contains(&vertex))
+                        .map(|submesh| vertex - submesh.vertices.start)
+                    else {
+                        continue;
+                    };
+                    if let Some(delta) = deltas.delta_position_values.get(i) {
+                        morph.position_deltas[submesh][local] =
+                            options.position(delta.decompress(deltas.min_value, deltas.max_value));
+                    }
+                    if let Some(delta) = deltas.delta_normal_values.get(i) {
+                        morph.normal_deltas[submesh][local] =
+                            options.direction(delta.decompress(-2.0, 2.0));
+                    }
+                    if let Some(delta) = deltas.delta_tangent_values.get(i) {
+                        morph.tangent_deltas[submesh][local] =
+                            options.direction(delta.decompress(-2.0, 2.0));
+                    }
                 }
-                submesh_data.tangent_count = submesh_data.tangents.len();
             }
+            mesh.morph_targets.push(morph);
+        }
+    }
 
-            // Write UVs if data exists
-            if let Some(uvs_layer) = uvs_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 8) as usize; // 8 bytes for UV (2 components)
-
-                    if offset + 8 > uvs_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "UV data out of bounds",
-                        ));
-                    }
+    /// The bind pose transform of every node relative to the actor, composed along
+    /// the parent chain and converted to the space `options` export to.
+    fn world_transforms(&self, options: &ExportOptions) -> Vec<[f32; 16]> {
+        let nodes: Vec<GltfNode> = self
+            .nodes()
+            .into_iter()
+            .map(|node| GltfNode {
+                name: node.name,
+                parent: node.parent.map(|parent| parent as usize),
+                translation: options.position(node.position),
+                rotation: options.rotation(node.rotation),
+                scale: options.axis_scale(node.scale),
+            })
+            .collect();
+        gltf::global_transforms(&nodes, &gltf::acyclic_parents(&nodes))
+    }
 
-                    let u = f32::from_le_bytes(
-                        uvs_data.unwrap()[offset..offset + 4].try_into().unwrap(),
-                    );
-                    let v = f32::from_le_bytes(
-                        uvs_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
+    /// Number of nodes over all node chunks, the range of bone indices.
+    fn node_count(&self) -> usize {
+        self.node_names().len()
+    }
 
-                    submesh_data.uvcoords.push([u, v]);
+    /// Node names by node index.
+    fn node_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for chunk in &self.chunk_data {
+            match chunk {
+                XacChunkData::XacNode(node) => names.push(&*node.node_name),
+                XacChunkData::XacNode2(node) => names.push(&*node.node_name),
+                XacChunkData::XacNode3(node) => names.push(&*node.node_name),
+                XacChunkData::XacNode4(node) => names.push(&*node.node_name),
+                XacChunkData::XACNodes(nodes) => {
+                    names.extend(nodes.xac_node.iter().map(|node| &*node.node_name))
                 }
-                submesh_data.uvcoord_count = submesh_data.uvcoords.len();
+                _ => {}
             }
+        }
+        names
+    }
 
-            // Write Colors32 if data exists
-            if let Some(colors32_layer) = colors32_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 4) as usize; // 4 bytes for color32
+    /// The mesh chunks of either version, in file order.
+    fn mesh_chunks(&self) -> impl Iterator<Item = MeshChunk<'_>> {
+        self.chunk_data
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| match chunk {
+                XacChunkData::XACMesh(mesh) => Some(MeshChunk {
+                    index,
+                    node_index: mesh.node_index,
+                    lod: 0,
+                    is_collision_mesh: mesh.is_collision_mesh != 0,
+                    layers: &mesh.vertex_attribute_layer,
+                    sub_meshes: &mesh.sub_meshes,
+                }),
+                XacChunkData::XACMesh2(mesh) => Some(MeshChunk {
+                    index,
+                    node_index: mesh.node_index,
+                    lod: mesh.lod,
+                    is_collision_mesh: mesh.is_collision_mesh != 0,
+                    layers: &mesh.vertex_attribute_layer,
+                    sub_meshes: &mesh.sub_meshes,
+                }),
+                _ => None,
+            })
+    }
 
-                    if offset + 4 > colors32_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Color32 data out of bounds",
-                        ));
-                    }
+    /// Borrowed views of every mesh, straight over the parsed layer data.
+    pub fn mesh_views(&self) -> Result<Vec<MeshView<'_>>> {
+        self.mesh_chunks()
+            .map(|chunk| {
+                let texture_names = self.get_texture_names(chunk.lod);
+                mesh_view(chunk.layers, chunk.sub_meshes, &texture_names)
+            })
+            .collect()
+    }
+}
 
-                    let r = u32::from_le_bytes(
-                        colors32_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
+/// The vertex layers an OBJ file carries, the first of each kind.
+struct ObjLayers<'a> {
+    positions: &'a [[f32; 3]],
+    normals: Option<&'a [[f32; 3]]>,
+    uvs: Option<&'a [[f32; 2]]>,
+}
 
-                    submesh_data.colors32.push(r);
+impl<'a> ObjLayers<'a> {
+    fn new(layers: &'a [XACVertexAttributeLayer]) -> Result<Self> {
+        let mut positions = None;
+        let mut normals = None;
+        let mut uvs = None;
+        for layer in layers {
+            match &layer.data {
+                VertexLayerData::Positions(values) => {
+                    positions.get_or_insert(values.as_slice());
+                }
+                VertexLayerData::Normals(values) => {
+                    normals.get_or_insert(values.as_slice());
+                }
+                VertexLayerData::Uvs(values) => {
+                    uvs.get_or_insert(values.as_slice());
                 }
-                submesh_data.color32_count = submesh_data.colors32.len();
+                _ => {}
             }
+        }
+        let Some(positions) = positions else {
+            return Err(Error::Xac("No vertex positions found".to_string()));
+        };
+        Ok(Self {
+            positions,
+            normals,
+            uvs,
+        })
+    }
 
-            // Write Original Vertex Numbers if data exists
-            if let Some(original_vertex_numbers_layer) = original_vertex_numbers_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 4) as usize; // 4 bytes for vertex number
+    /// Writes the vertices in `range` and the faces over them, mirrored on the x axis.
+    /// `written` counts the positions, normals and UVs already in the file, which the
+    /// face indices continue from, and is advanced past the ones written here.
+    fn write_submesh(
+        &self,
+        writer: &mut impl Write,
+        range: &std::ops::Range<usize>,
+        indices: &[u32],
+        written: &mut [usize; 3],
+    ) -> Result<()> {
+        let out_of_bounds = |what: &str| Error::Xac(format!("{} data out of bounds", what));
+        let positions = self
+            .positions
+            .get(range.clone())
+            .ok_or_else(|| out_of_bounds("Vertex"))?;
+        let normals = self
+            .normals
+            .map(|normals| {
+                normals
+                    .get(range.clone())
+                    .ok_or_else(|| out_of_bounds("Normal"))
+            })
+            .transpose()?;
+        let uvs = self
+            .uvs
+            .map(|uvs| uvs.get(range.clone()).ok_or_else(|| out_of_bounds("UV")))
+            .transpose()?;
+
+        for [px, py, pz] in positions {
+            writeln!(writer, "v {} {} {}", -px, py, pz)?;
+        }
+        for [nx, ny, nz] in normals.unwrap_or_default() {
+            writeln!(writer, "vn {} {} {}", -nx, ny, nz)?;
+        }
+        for [u, v] in uvs.unwrap_or_default() {
+            writeln!(writer, "vt {} {}", u, 1.0 - v)?;
+        }
 
-                    if offset + 4 > original_vertex_numbers_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Original vertex numbers data out of bounds",
-                        ));
+        let [position_base, normal_base, uv_base] = *written;
+        // The x mirror flips the winding, so faces are written in reverse
+        for face in indices.chunks_exact(3) {
+            let corner = |index: u32| {
+                let index = index as usize + 1;
+                let position = position_base + index;
+                match (normals.is_some(), uvs.is_some()) {
+                    (true, true) => {
+                        format!("{}/{}/{}", position, uv_base + index, normal_base + index)
                     }
+                    (true, false) => format!("{}//{}", position, normal_base + index),
+                    (false, true) => format!("{}/{}", position, uv_base + index),
+                    (false, false) => position.to_string(),
+                }
+            };
+            writeln!(
+                writer,
+                "f {} {} {}",
+                corner(face[2]),
+                corner(face[1]),
+                corner(face[0])
+            )?;
+        }
 
-                    let vertex_number = u32::from_le_bytes(
-                        original_vertex_numbers_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
+        written[0] += positions.len();
+        written[1] += normals.map_or(0, <[_]>::len);
+        written[2] += uvs.map_or(0, <[_]>::len);
+        Ok(())
+    }
+}
 
-                    submesh_data.original_vertex_numbers.push(vertex_number);
-                }
-                submesh_data.original_vertex_numbers_count =
-                    submesh_data.original_vertex_numbers.len();
-            }
+/// The fields of `XACMesh` and `XACMesh2` the exports need.
+struct MeshChunk<'a> {
+    /// Index in `chunk_data`
+    index: usize,
+    node_index: u32,
+    lod: u32,
+    is_collision_mesh: bool,
+    layers: &'a [XACVertexAttributeLayer],
+    sub_meshes: &'a [XACSubMesh],
+}
 
-            // Write Color128 if data exists
-            if let Some(colors128_layer) = colors128_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 16) as usize; // 16 bytes for Color128 (4 components)
+// Rust function to extract xac data
+/// File stem of a texture name as stored in the materials, which may carry a
+/// directory and extension.
+fn texture_stem(name: &str) -> String {
+    Path::new(name).file_stem().map_or_else(
+        || name.to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
 
-                    if offset + 16 > colors128_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Color128 data out of bounds",
-                        ));
-                    }
+/// The four strongest influences of a vertex as joints and weights summing to one.
+/// Influences on nodes past `joint_count` or the `u16` range are dropped; a vertex
+/// without influences follows `fallback` fully.
+fn vertex_weights(
+    influences: &[XacSkinInfluence],
+    entry: Option<&XacSkinningInfoTableEntry>,
+    joint_count: usize,
+    fallback: u16,
+) -> ([u16; 4], [f32; 4]) {
+    let mut strongest: Vec<(u16, f32)> = entry
+        .and_then(|entry| {
+            let start = entry.start_index as usize;
+            influences.get(start..start.checked_add(entry.num_elements as usize)?)
+        })
+        .unwrap_or_default()
+        .iter()
+        .filter(|influence| (influence.node_number as usize) < joint_count.min(1 << 16))
+        .filter(|influence| influence.weight > 0.0)
+        .map(|influence| (influence.node_number as u16, influence.weight))
+        .collect();
+    strongest.sort_by(|a, b| b.1.total_cmp(&a.1));
+    strongest.truncate(4);
+    let total: f32 = strongest.iter().map(|(_, weight)| weight).sum();
+    if strongest.is_empty() || total <= 0.0 {
+        return ([fallback, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]);
+    }
+    let mut joints = [0; 4];
+    let mut weights = [0.0; 4];
+    for (slot, (joint, weight)) in strongest.into_iter().enumerate() {
+        joints[slot] = joint;
+        weights[slot] = weight / total;
+    }
+    (joints, weights)
+}
 
-                    let r = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let g = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let b = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let a = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 12..offset + 16]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.colors128.push([r, g, b, a]);
-                }
-                submesh_data.color128_count = submesh_data.colors128.len();
-            }
+pub fn extract_xac_data(ipf_path: &str, xac_filename: &str) -> Result<Vec<Mesh>> {
+    // Open the IPF file
+    let file = File::open(ipf_path).map_err(|err| Error::from(err).in_file(ipf_path))?;
+    let mut reader = BinaryReader::new(BufReader::new(file));
 
-            // Write Bitangents if data exists
-            if let Some(bitangents_layer) = bitangents_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize; // 12 bytes for bitangent (3 components)
+    // Load the IPF file
+    let ipf = IPFFile::load_from_reader(&mut reader).map_err(|err| err.in_file(ipf_path))?;
 
-                    if offset + 12 > bitangents_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Bitangent data out of bounds",
-                        ));
-                    }
+    let mut result_mesh: Vec<Mesh> = Vec::new();
+    for file_entry in ipf.file_table() {
+        let filename = file_entry.directory_name_str();
 
-                    let bx = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let by = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let bz = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.bitangents.push([bx, by, bz]);
-                }
-                submesh_data.bitangent_count = submesh_data.bitangents.len();
-            }
+        // Extract only the filename part (without the directory)
+        let file_name_only = Path::new(filename.as_ref())
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or("");
 
-            // Add submesh to the list if it has valid data
-            if !submesh_data.positions.is_empty()
-                || !submesh_data.normals.is_empty()
-                || !submesh_data.tangents.is_empty()
-                || !submesh_data.uvcoords.is_empty()
-                || !submesh_data.colors32.is_empty()
-                || !submesh_data.original_vertex_numbers.is_empty()
-                || !submesh_data.colors128.is_empty()
-                || !submesh_data.bitangents.is_empty()
-            {
-                submeshes.push(submesh_data);
-            }
+        // Check if the extracted filename matches the target
+        if file_name_only == xac_filename {
+            let result = file_entry
+                .extract(&mut reader)
+                .map_err(|err| err.in_file(ipf_path))?;
+            // Only meshes and materials are exported
+            let options = ParseOptions::default().with_chunks(ChunkSet::GEOMETRY);
+            let xac_data = XACFile::load_from_slice_with_options(&result, &options)
+                .map_err(|err| err.in_entry(&filename).in_file(ipf_path))?;
 
-            vertex_offset += submesh.num_verts;
+            result_mesh = xac_data.export_all_meshes_into_struct()?;
+            break; // Stop after extracting the target file
         }
+    }
 
-        // Return the Mesh struct with the submeshes and their count
-        Ok(Mesh {
-            submesh_count: submeshes.len(),
-            submeshes,
-        })
+    Ok(result_mesh)
+}
+
+/// The FX material of an FX material chunk of any version, at level of detail `lod`.
+fn fx_material(chunk: &XacChunkData, lod: u32) -> Option<FxMaterial> {
+    fn parameters<'a, T: 'a>(
+        parameters: &'a Option<Vec<T>>,
+        parameter: impl Fn(&T) -> FxParameter + 'a,
+    ) -> impl Iterator<Item = FxParameter> + 'a {
+        parameters.iter().flatten().map(parameter)
     }
+    let int = |p: &XACFXIntParameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Int(p.value),
+    };
+    let float = |p: &XACFXFloatParameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Float(p.value),
+    };
+    let color = |p: &XACFXColorParameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Color(p.value.to_array()),
+    };
+    let boolean = |p: &XACFXBoolParameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Bool(p.value != 0),
+    };
+    let vector3 = |p: &XACFXVector3Parameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Vector3(p.value.to_array()),
+    };
+    let bitmap = |p: &XACFXBitmapParameter| FxParameter {
+        name: p.name.clone(),
+        value: FxValue::Bitmap(p.value_name.to_string()),
+    };
+
+    let (material, parameters) = match chunk {
+        XacChunkData::XACFXMaterial(m) => (
+            (&m.name, &m.effect_file, &m.shader_technique),
+            parameters(&m.xac_fx_int_parameter, int)
+                .chain(parameters(&m.xac_fx_float_parameter, float))
+                .chain(parameters(&m.xac_fx_color_parameter, color))
+                .chain(parameters(&m.xac_fx_bitmap_parameter, bitmap))
+                .collect(),
+        ),
+        XacChunkData::XACFXMaterial2(m) => (
+            (&m.name, &m.effect_file, &m.shader_technique),
+            parameters(&m.xac_fx_int_parameter, int)
+                .chain(parameters(&m.xac_fx_float_parameter, float))
+                .chain(parameters(&m.xac_fx_color_parameter, color))
+                .chain(parameters(&m.xac_fx_bool_parameter, boolean))
+                .chain(parameters(&m.xac_fx_vector3_parameter, vector3))
+                .chain(parameters(&m.xac_fx_bitmap_parameter, bitmap))
+                .collect(),
+        ),
+        XacChunkData::XACFXMaterial3(m) => (
+            (&m.name, &m.effect_file, &m.shader_technique),
+            parameters(&m.xac_fx_int_parameter, int)
+                .chain(parameters(&m.xac_fx_float_parameter, float))
+                .chain(parameters(&m.xac_fx_color_parameter, color))
+                .chain(parameters(&m.xac_fx_bool_parameter, boolean))
+                .chain(parameters(&m.xac_fx_vector3_parameter, vector3))
+                .chain(parameters(&m.xac_fx_bitmap_parameter, bitmap))
+                .collect(),
+        ),
+        _ => return None,
+    };
+    let (name, effect_file, shader_technique) = material;
+    Some(FxMaterial {
+        name: name.clone(),
+        lod,
+        effect_file: effect_file.clone(),
+        shader_technique: shader_technique.clone(),
+        parameters,
+    })
+}
 
-    fn export_to_struct2(&self, mesh: &XACMesh2) -> io::Result<Mesh> {
-        let texture_name = self.get_texture_names();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{Handedness, UpAxis};
+
+    #[test]
+    fn mesh_views_split_layers_per_submesh() {
+        let positions = (0..3).map(|i| [0.0, 1.0, 2.0].map(|v| v + 3.0 * i as f32));
+        let layers = vec![XACVertexAttributeLayer {
+            layer_type_id: XacAttribute::AttribPositions as u32,
+            attrib_size_in_bytes: 12,
+            data: VertexLayerData::Positions(positions.collect()),
+            ..Default::default()
+        }];
+        let sub_meshes = vec![
+            XACSubMesh {
+                num_verts: 1,
+                material_index: 1,
+                ..Default::default()
+            },
+            XACSubMesh {
+                num_verts: 2,
+                indices: vec![0, 1, 0],
+                ..Default::default()
+            },
+        ];
+
+        let view = mesh_view(&layers, &sub_meshes, &["default", "skin.dds"]).unwrap();
+        assert_eq!(view.submeshes.len(), 2);
+        assert_eq!(view.submeshes[0].texture_name, "skin.dds");
+        let second = view.submeshes[1].positions.unwrap();
+        assert_eq!(second.get(1), Some([6.0, 7.0, 8.0]));
+
+        let mesh = view.to_mesh();
+        assert_eq!(
+            mesh.submeshes[1].positions,
+            vec![[-3.0, 4.0, 5.0], [-6.0, 7.0, 8.0]]
+        );
+        assert_eq!(mesh.submeshes[1].indices, vec![0, 1, 0]);
+
+        let too_many = [XACSubMesh {
+            num_verts: 4,
+            ..Default::default()
+        }];
+        assert!(mesh_view(&layers, &too_many, &[]).is_err());
+
+        // An element size that does not fit the layer type keeps the bytes as they are
+        let odd = VertexLayerData::decode(
+            XacAttribute::AttribUvcoords as u32,
+            12,
+            vec![1; 24],
+            Endian::Little,
+        );
+        assert!(matches!(&odd, VertexLayerData::Other(bytes) if bytes.len() == 24));
+        assert_eq!(
+            layers[0].data.to_bytes(Endian::Little)[4..8],
+            1.0f32.to_le_bytes()
+        );
+    }
 
-        // Find layers by their layer_type_id
-        let positions_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribPositions as u32);
+    #[test]
+    fn single_obj_groups_every_submesh() {
+        let triangle = |z: f32| [[0.0, 0.0, z], [1.0, 0.0, z], [0.0, 1.0, z]];
+        let sub_mesh = |material_index| XACSubMesh {
+            num_indices: 3,
+            num_verts: 3,
+            material_index,
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        };
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XACNodes(XACNodes {
+                    num_nodes: 1,
+                    num_root_nodes: 1,
+                    xac_node: vec![XacNode4 {
+                        parent_index: u32::MAX,
+                        node_name: "body".into(),
+                        ..Default::default()
+                    }],
+                }),
+                XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                    material_name: "default".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                    material_name: "char/skin.dds".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACMesh(XACMesh {
+                    vertex_attribute_layer: vec![
+                        XACVertexAttributeLayer {
+                            layer_type_id: XacAttribute::AttribPositions as u32,
+                            attrib_size_in_bytes: 12,
+                            data: VertexLayerData::Positions(
+                                [triangle(0.0), triangle(1.0)].concat(),
+                            ),
+                            ..Default::default()
+                        },
+                        XACVertexAttributeLayer {
+                            layer_type_id: XacAttribute::AttribUvcoords as u32,
+                            attrib_size_in_bytes: 8,
+                            data: VertexLayerData::Uvs(vec![[0.0, 0.0]; 6]),
+                            ..Default::default()
+                        },
+                    ],
+                    sub_meshes: vec![sub_mesh(1), sub_mesh(0)],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-        let normals_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribNormals as u32);
+        let dir = std::env::temp_dir().join(format!("toslib-obj-single-{}", std::process::id()));
+        xac.export_to_obj_single(dir.join("body.obj")).unwrap();
+        let obj = std::fs::read_to_string(dir.join("body.obj")).unwrap();
+        let mtl = std::fs::read_to_string(dir.join("body.mtl")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let lines: Vec<&str> = obj
+            .lines()
+            .filter(|line| !line.starts_with(['v', 'f']))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "mtllib body.mtl",
+                "o body",
+                "g body_submesh_0",
+                "usemtl char/skin.dds",
+                "g body_submesh_1",
+                "usemtl default",
+            ]
+        );
+        let faces: Vec<&str> = obj.lines().filter(|line| line.starts_with('f')).collect();
+        assert_eq!(faces, ["f 3/3 2/2 1/1", "f 6/6 5/5 4/4"]);
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 6);
+        assert_eq!(
+            mtl,
+            "newmtl char/skin.dds\nKd 1.0 1.0 1.0\nmap_Kd char/skin.dds\nnewmtl default\nKd 1.0 1.0 1.0\n"
+        );
+    }
 
-        let tangents_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribTangents as u32);
+    #[test]
+    fn load_info_skips_other_chunks() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&[0; 8]);
+        body.extend_from_slice(&[2, 34, 0, 0]);
+        for text in ["3ds Max", "bow.max", "2014-01-01", "bow"] {
+            body.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            body.extend_from_slice(text.as_bytes());
+        }
 
-        let uvs_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribUvcoords as u32);
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        // A mesh chunk with a body that would not parse, which must not be touched
+        for (chunk_id, body) in [
+            (XacChunk::XacChunkMesh as u32, &[0xFF; 4][..]),
+            (XacChunk::XacChunkInfo as u32, &body),
+        ] {
+            bytes.extend_from_slice(&chunk_id.to_le_bytes());
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(body);
+        }
 
-        let colors32_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribColors32 as u32);
+        let info = XACFile::load_info_from_slice(&bytes).unwrap();
+        assert_eq!(info.version, (1, 0));
+        assert_eq!(info.exporter_version, (2, 34));
+        assert_eq!(info.actor_name, "bow");
+        assert_eq!(info.source_app, "3ds Max");
+        assert_eq!(info.num_lods, 1);
+    }
 
-        let original_vertex_numbers_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribOrgvtxnumbers as u32);
+    #[test]
+    fn excluded_chunks_are_skipped_undecoded() {
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&ChunkSet::MESH.to_le_bytes());
+        bytes.extend_from_slice(&4u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0xFF; 4]);
+
+        assert!(XACFile::load_from_slice_with_options(&bytes, &ParseOptions::strict()).is_err());
+        for parallel in [false, true] {
+            let options = ParseOptions::strict()
+                .with_chunks(ChunkSet::ALL.without(ChunkSet::MESH))
+                .with_parallel(parallel);
+            let xac = XACFile::load_from_slice_with_options(&bytes, &options).unwrap();
+            assert!(xac.chunk_data.is_empty());
+            assert!(xac.warnings().is_empty());
+        }
+    }
 
-        let colors128_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribColors128 as u32);
+    #[test]
+    fn malformed_input_returns_error() {
+        // Header followed by a mesh chunk header whose body is missing.
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&(XacChunk::XacChunkMesh as u32).to_le_bytes());
+        bytes.extend_from_slice(&1000u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0xFF; 16]);
+
+        let err = XACFile::load_from_bytes(bytes).unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.chunk, Some((XacChunk::XacChunkMesh as u32, 1)));
+        assert_eq!(context.offset, Some(8));
+        assert!(XACFile::load_from_bytes(Vec::new()).is_err());
+    }
 
-        let bitangents_layer = mesh
-            .vertex_attribute_layer
-            .iter()
-            .find(|layer| layer.layer_type_id == XacAttribute::AttribBitangents as u32);
+    #[test]
+    fn strict_mode_rejects_what_lenient_mode_records() {
+        // Valid header, an unknown chunk with an empty body, then trailing garbage.
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&9999u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0xAB; 3]);
+
+        let lenient = XACFile::load_from_slice(&bytes).unwrap();
+        assert!(matches!(
+            lenient.warnings(),
+            [
+                ParseWarning::UnknownChunk { chunk_id: 9999, .. },
+                ParseWarning::SkippedBytes {
+                    offset: 20,
+                    count: 3
+                }
+            ]
+        ));
+        assert!(XACFile::load_from_slice_with_options(&bytes, &ParseOptions::strict()).is_err());
+    }
 
-        let positions_data = if let Some(l) = positions_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+    #[test]
+    fn accessors_merge_chunk_versions() {
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XacNode2(XacNode2 {
+                    local_pos: FileVector3 {
+                        axis_x: 1.0,
+                        ..Default::default()
+                    },
+                    parent_index: u32::MAX,
+                    node_flags: 1,
+                    node_name: "root".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACNodes(XACNodes {
+                    num_nodes: 1,
+                    xac_node: vec![XacNode4 {
+                        parent_index: 0,
+                        node_name: "arm".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                    opacity: 0.5,
+                    double_sided: 1,
+                    material_name: "skin".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACStandardMaterialLayer(XACStandardMaterialLayer {
+                    map_type: 2,
+                    texture_name: "char/skin.dds".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACLimit(XACLimit {
+                    limit_flags: [1, 0, 0, 0, 0, 0, 0, 0, 1],
+                    node_number: 1,
+                    ..Default::default()
+                }),
+                XacChunkData::XACFXMaterial2(XACFXMaterial2 {
+                    name: "glow".into(),
+                    xac_fx_float_parameter: Some(vec![XACFXFloatParameter {
+                        value: 0.25,
+                        name: "power".into(),
+                    }]),
+                    xac_fx_bitmap_parameter: Some(vec![XACFXBitmapParameter {
+                        name: "diffuse".into(),
+                        value_name: "char/glow.dds".into(),
+                    }]),
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
         };
 
-        let normals_data = if let Some(l) = normals_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+        let nodes = xac.nodes();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].parent, None);
+        assert_eq!(nodes[0].position, [1.0, 0.0, 0.0]);
+        assert_eq!(nodes[0].flags, 1);
+        assert_eq!((nodes[1].name.as_str(), nodes[1].parent), ("arm", Some(0)));
+
+        let materials = xac.materials();
+        assert_eq!(materials.len(), 2);
+        let Material::Standard(standard) = &materials[0] else {
+            panic!("expected the standard material first");
         };
-
-        let tangents_data = if let Some(l) = tangents_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+        assert_eq!(standard.opacity, 0.5);
+        assert!(standard.double_sided);
+        assert_eq!(standard.layers[0].texture_name, "char/skin.dds");
+        assert_eq!(materials[0].texture_name(), Some("char/skin.dds"));
+        // The OBJ export names the material but references its layer's texture
+        assert_eq!(
+            xac.material_textures(0),
+            [("skin", "char/skin.dds"), ("glow", "char/glow.dds")]
+        );
+
+        let limits = xac.limits();
+        assert_eq!(limits[0].node_index, 1);
+        assert!(limits[0].enabled[0] && limits[0].enabled[8] && !limits[0].enabled[4]);
+
+        let Material::Fx(fx) = &materials[1] else {
+            panic!("expected the FX material second");
         };
+        assert_eq!(fx.name, "glow");
+        assert_eq!(
+            fx.parameters,
+            [
+                FxParameter {
+                    name: "power".into(),
+                    value: FxValue::Float(0.25),
+                },
+                FxParameter {
+                    name: "diffuse".into(),
+                    value: FxValue::Bitmap("char/glow.dds".into()),
+                },
+            ]
+        );
+    }
 
-        let uvs_data = if let Some(l) = uvs_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+    #[test]
+    fn material_tables_follow_material_info() {
+        let info = |lod| {
+            XacChunkData::XACMaterialInfo2(XACMaterialInfo2 {
+                lod,
+                ..Default::default()
+            })
         };
-
-        let colors32_data = if let Some(l) = colors32_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+        let standard = |name: &str| {
+            XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                material_name: name.into(),
+                ..Default::default()
+            })
         };
-
-        let original_vertex_numbers_data = if let Some(l) = original_vertex_numbers_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+        let xac = XACFile {
+            chunk_data: vec![
+                info(0),
+                standard("default"),
+                XacChunkData::XACFXMaterial(XACFXMaterial {
+                    name: "glow".into(),
+                    ..Default::default()
+                }),
+                standard("skin"),
+                info(1),
+                standard("default"),
+                standard("skin_low"),
+            ],
+            ..Default::default()
         };
 
-        let colors128_data = if let Some(l) = colors128_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+        let names = |lod| {
+            xac.material_table(lod)
+                .iter()
+                .map(|material| material.name().to_string())
+                .collect::<Vec<_>>()
         };
+        assert_eq!(names(0), ["default", "glow", "skin"]);
+        assert_eq!(names(1), ["default", "skin_low"]);
+        assert_eq!(names(2), names(0));
+        // Index 2 of level 0 is past the FX material, not a texture of it
+        assert_eq!(xac.get_texture_names(0)[2], "skin");
+        assert_eq!(xac.get_texture_names(1), ["default", "skin_low"]);
+    }
 
-        let bitangents_data = if let Some(l) = bitangents_layer {
-            Some(&l.mesh_data)
-        } else {
-            None
+    #[test]
+    fn lower_lods_come_from_embedded_actors() {
+        let mesh = |x: f32| {
+            let positions = [x, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            XacChunkData::XACMesh(XACMesh {
+                total_verts: 3,
+                total_indices: 3,
+                num_sub_meshes: 1,
+                num_layers: 1,
+                vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                    layer_type_id: XacAttribute::AttribPositions as u32,
+                    attrib_size_in_bytes: 12,
+                    data: VertexLayerData::decode(
+                        XacAttribute::AttribPositions as u32,
+                        12,
+                        positions,
+                        Endian::Little,
+                    ),
+                    ..Default::default()
+                }],
+                sub_meshes: vec![XACSubMesh {
+                    num_indices: 3,
+                    num_verts: 3,
+                    indices: vec![0, 1, 2],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+        };
+        let header = || XacHeader {
+            fourcc: XAC_FOURCC,
+            hi_version: 1,
+            ..Default::default()
+        };
+        let lod = XACFile {
+            header: header(),
+            chunk_data: vec![mesh(2.0)],
+            ..Default::default()
+        };
+        let actor = XACFile {
+            header: header(),
+            chunk_data: vec![
+                mesh(1.0),
+                XacChunkData::XACMeshLodLevel(XACMeshLodLevel {
+                    lod_level: 1,
+                    lod_model: lod.to_bytes().unwrap(),
+                }),
+            ],
+            ..Default::default()
         };
 
-        let mut vertex_offset: u32 = 0;
-        let mut submeshes = Vec::new();
-
-        for (i, submesh) in mesh.sub_meshes.iter().enumerate() {
-            let material_index = submesh.material_index as usize;
-
-            let mut submesh_data = SubMesh {
-                texture_name: String::new(),
-                position_count: 0,
-                positions: Vec::new(),
-                normal_count: 0,
-                normals: Vec::new(),
-                tangent_count: 0,
-                tangents: Vec::new(),
-                uvcoord_count: 0,
-                uvcoords: Vec::new(),
-                color32_count: 0,
-                colors32: Vec::new(),
-                original_vertex_numbers_count: 0,
-                original_vertex_numbers: Vec::new(),
-                color128_count: 0,
-                colors128: Vec::new(),
-                bitangent_count: 0,
-                bitangents: Vec::new(),
-                indices_count: submesh.num_indices as usize,
-                indices: submesh.indices.clone(),
-            };
-
-            // Process texture name if material_index is valid
-            if material_index != 0 {
-                if let Some(material_name) = texture_name.get(material_index) {
-                    submesh_data.texture_name = material_name.to_string();
-                }
-            }
-
-            // Write vertex positions if data exists
-            if let Some(positions_layer) = positions_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
-
-                    if offset + 12 > positions_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Vertex data out of bounds",
-                        ));
-                    }
+        let actor = XACFile::load_from_slice(&actor.to_bytes().unwrap()).unwrap();
+        assert!(actor.warnings().is_empty());
+        assert_eq!(actor.lod_levels(), [0, 1]);
+        let x = |meshes: Vec<Mesh>| meshes[0].submeshes[0].positions[0][0].abs();
+        assert_eq!(x(actor.meshes_for_lod(0).unwrap()), 1.0);
+        assert_eq!(x(actor.meshes_for_lod(1).unwrap()), 2.0);
+        assert!(actor.meshes_for_lod(2).is_err());
+    }
 
-                    let px = f32::from_le_bytes(
-                        positions_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let py = f32::from_le_bytes(
-                        positions_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let pz = f32::from_le_bytes(
-                        positions_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.positions.push([-px, py, pz]);
-                }
-                submesh_data.position_count = submesh_data.positions.len();
-            }
+    #[test]
+    fn world_space_moves_meshes_by_their_node() {
+        let vector = |axis_x, axis_y, axis_z| FileVector3 {
+            axis_x,
+            axis_y,
+            axis_z,
+        };
+        let half = 0.5f32.sqrt();
+        let xac = XACFile {
+            chunk_data: vec![
+                // A quarter turn about Y, which takes +X to -Z
+                XacChunkData::XacNode2(XacNode2 {
+                    local_quat: FileQuaternion {
+                        axis_y: half,
+                        axis_w: half,
+                        ..Default::default()
+                    },
+                    local_pos: vector(0.0, 10.0, 0.0),
+                    local_scale: vector(1.0, 1.0, 1.0),
+                    parent_index: u32::MAX,
+                    node_name: "root".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XacNode2(XacNode2 {
+                    local_quat: FileQuaternion {
+                        axis_w: 1.0,
+                        ..Default::default()
+                    },
+                    local_pos: vector(5.0, 0.0, 0.0),
+                    local_scale: vector(2.0, 1.0, 1.0),
+                    parent_index: 0,
+                    node_name: "lid".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACMesh(XACMesh {
+                    node_index: 1,
+                    total_verts: 1,
+                    num_sub_meshes: 1,
+                    num_layers: 2,
+                    vertex_attribute_layer: [
+                        XacAttribute::AttribPositions as u32,
+                        XacAttribute::AttribNormals as u32,
+                    ]
+                    .map(|layer_type_id| XACVertexAttributeLayer {
+                        layer_type_id,
+                        attrib_size_in_bytes: 12,
+                        data: VertexLayerData::decode(
+                            layer_type_id,
+                            12,
+                            [1.0f32, 0.0, 0.0]
+                                .iter()
+                                .flat_map(|v| v.to_le_bytes())
+                                .collect(),
+                            Endian::Little,
+                        ),
+                        ..Default::default()
+                    })
+                    .into(),
+                    sub_meshes: vec![XACSubMesh {
+                        num_verts: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-            // Write normals if data exists
-            if let Some(normals_layer) = normals_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize;
+        let vertex = |options: ExportOptions| {
+            let meshes = xac.export_all_meshes_into_struct_with(&options).unwrap();
+            let submesh = &meshes[0].submeshes[0];
+            let round = |value: [f32; 3]| value.map(|v| (v * 1000.0).round() / 1000.0 + 0.0);
+            (round(submesh.positions[0]), round(submesh.normals[0]))
+        };
+        let left = ExportOptions::default().with_handedness(Handedness::Left);
+        assert_eq!(vertex(left), ([1.0, 0.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_eq!(
+            vertex(left.with_world_space(true)),
+            ([0.0, 10.0, -7.0], [0.0, 0.0, -1.0])
+        );
+        // The same place once mirrored and turned Z-up
+        assert_eq!(
+            vertex(
+                ExportOptions::default()
+                    .with_up_axis(UpAxis::Z)
+                    .with_world_space(true)
+            ),
+            ([0.0, 7.0, 10.0], [0.0, 1.0, 0.0])
+        );
+    }
 
-                    if offset + 12 > normals_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Normal data out of bounds",
-                        ));
-                    }
+    #[test]
+    fn world_space_morph_normals_follow_the_normal_matrix() {
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XacNode2(XacNode2 {
+                    local_quat: FileQuaternion {
+                        axis_w: 1.0,
+                        ..Default::default()
+                    },
+                    local_scale: FileVector3 {
+                        axis_x: 2.0,
+                        axis_y: 1.0,
+                        axis_z: 1.0,
+                    },
+                    parent_index: u32::MAX,
+                    node_name: "root".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACMesh(XACMesh {
+                    node_index: 0,
+                    vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                        layer_type_id: XacAttribute::AttribPositions as u32,
+                        attrib_size_in_bytes: 12,
+                        data: VertexLayerData::decode(
+                            XacAttribute::AttribPositions as u32,
+                            12,
+                            vec![0; 12],
+                            Endian::Little,
+                        ),
+                        ..Default::default()
+                    }],
+                    sub_meshes: vec![XACSubMesh {
+                        num_verts: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XACPMorphTarget(XACPMorphTarget {
+                    range_max: 1.0,
+                    name: "stretch".into(),
+                    morph_target_mesh_deltas: vec![XACPMorphTargetMeshDeltas {
+                        min_value: -1.0,
+                        max_value: 1.0,
+                        num_vertices: 1,
+                        delta_position_values: vec![File16BitVector3 {
+                            axis_x: u16::MAX,
+                            axis_y: 0,
+                            axis_z: u16::MAX,
+                        }],
+                        delta_normal_values: vec![File8BitVector3 {
+                            axis_x: 0,
+                            axis_y: u8::MAX,
+                            axis_z: 0,
+                        }],
+                        delta_tangent_values: vec![File8BitVector3::default()],
+                        vertex_numbers: vec![0],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-                    let nx = f32::from_le_bytes(
-                        normals_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let ny = f32::from_le_bytes(
-                        normals_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let nz = f32::from_le_bytes(
-                        normals_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.normals.push([-nx, ny, nz]);
-                }
-                submesh_data.normal_count = submesh_data.normals.len();
-            }
+        let options = ExportOptions::default()
+            .with_handedness(Handedness::Left)
+            .with_world_space(true);
+        let meshes = xac.export_all_meshes_into_struct_with(&options).unwrap();
+        let target = &meshes[0].morph_targets[0];
+        // Scaling X by 2 stretches position deltas along X and shrinks normal deltas
+        assert_eq!(target.position_deltas[0][0], [2.0, -1.0, 1.0]);
+        assert_eq!(target.normal_deltas[0][0], [-1.0, 2.0, -2.0]);
+        assert_eq!(target.tangent_deltas[0][0], [-4.0, -2.0, -2.0]);
+    }
 
-            // Write tangents if data exists
-            if let Some(tangents_layer) = tangents_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 16) as usize; // 16 bytes for tangent (4 components)
+    #[test]
+    fn bounds_enclose_the_exported_meshes() {
+        let mesh = |lod, positions: [f32; 9]| {
+            XacChunkData::XACMesh2(XACMesh2 {
+                lod,
+                total_verts: 3,
+                num_sub_meshes: 1,
+                num_layers: 1,
+                vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                    layer_type_id: XacAttribute::AttribPositions as u32,
+                    attrib_size_in_bytes: 12,
+                    data: VertexLayerData::decode(
+                        XacAttribute::AttribPositions as u32,
+                        12,
+                        positions.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                        Endian::Little,
+                    ),
+                    ..Default::default()
+                }],
+                sub_meshes: vec![XACSubMesh {
+                    num_verts: 3,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+        };
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XacNode2(XacNode2 {
+                    parent_index: u32::MAX,
+                    node_name: "root".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACNodes(XACNodes {
+                    num_nodes: 1,
+                    xac_node: vec![XacNode4 {
+                        parent_index: 0,
+                        obb: [2.0; 16],
+                        node_name: "arm".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                mesh(0, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]),
+                mesh(0, [0.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 0.0]),
+                // Other levels of detail are left out
+                mesh(1, [9.0; 9]),
+            ],
+            ..Default::default()
+        };
 
-                    if offset + 16 > tangents_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Tangent data out of bounds",
-                        ));
-                    }
+        let options = ExportOptions::default();
+        let meshes = xac.export_all_meshes_into_struct_with(&options).unwrap();
+        // Mirrored on X like the exported vertices
+        assert_eq!(meshes[0].aabb(), Some(([-1.0, 0.0, 0.0], [0.0, 1.0, 1.0])));
+        let (center, radius) = meshes[0].bounding_sphere().unwrap();
+        assert_eq!(center, [-0.5, 0.5, 0.5]);
+        assert!((radius - 0.75f32.sqrt()).abs() < 1e-6);
+        assert_eq!(Mesh::default().aabb(), None);
+
+        assert_eq!(
+            xac.actor_bounds(&options).unwrap(),
+            Some(([-1.0, 0.0, 0.0], [0.0, 3.0, 1.0]))
+        );
+        assert_eq!(
+            xac.actor_bounds(&options.with_scale(2.0)).unwrap(),
+            Some(([-2.0, 0.0, 0.0], [0.0, 6.0, 2.0]))
+        );
+
+        let nodes = xac.nodes();
+        assert_eq!((nodes[0].obb, nodes[1].obb), (None, Some([2.0; 16])));
+    }
 
-                    let tx = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let ty = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let tz = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let tw = f32::from_le_bytes(
-                        tangents_data.unwrap()[offset + 12..offset + 16]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.tangents.push([tx, ty, tz, tw]);
-                }
-                submesh_data.tangent_count = submesh_data.tangents.len();
-            }
+    #[test]
+    fn metadata_loads_skip_meshes() {
+        let actor = XACFile {
+            header: XacHeader {
+                fourcc: XAC_FOURCC,
+                hi_version: 1,
+                ..Default::default()
+            },
+            chunk_data: vec![
+                XacChunkData::XacNode2(XacNode2 {
+                    parent_index: u32::MAX,
+                    node_name: "root".into(),
+                    ..Default::default()
+                }),
+                XacChunkData::XACMesh(XACMesh {
+                    total_verts: 1,
+                    num_sub_meshes: 1,
+                    num_layers: 1,
+                    vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                        layer_type_id: XacAttribute::AttribPositions as u32,
+                        attrib_size_in_bytes: 12,
+                        data: VertexLayerData::decode(
+                            XacAttribute::AttribPositions as u32,
+                            12,
+                            vec![0; 12],
+                            Endian::Little,
+                        ),
+                        ..Default::default()
+                    }],
+                    sub_meshes: vec![XACSubMesh {
+                        num_verts: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                    material_name: "skin".into(),
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-            // Write UVs if data exists
-            if let Some(uvs_layer) = uvs_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 8) as usize; // 8 bytes for UV (2 components)
+        let bytes = actor.to_bytes().unwrap();
+        let (metadata, report) = XACFile::load_metadata_only(&bytes)
+            .map(XACFile::with_report)
+            .unwrap();
+        assert_eq!(metadata.nodes()[0].name, "root");
+        assert_eq!(metadata.materials()[0].name(), "skin");
+        assert_eq!(metadata.mesh_chunks().count(), 0);
+        let outcomes: Vec<_> = report.chunks.iter().map(|chunk| chunk.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                ChunkOutcome::Parsed,
+                ChunkOutcome::Skipped,
+                ChunkOutcome::Parsed
+            ]
+        );
+    }
 
-                    if offset + 8 > uvs_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "UV data out of bounds",
-                        ));
-                    }
+    #[test]
+    fn collision_meshes_can_be_filtered() {
+        let mesh = |is_collision_mesh: u8| {
+            XacChunkData::XACMesh(XACMesh {
+                total_verts: 3,
+                total_indices: 3,
+                num_sub_meshes: 1,
+                num_layers: 1,
+                is_collision_mesh,
+                vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                    layer_type_id: XacAttribute::AttribPositions as u32,
+                    attrib_size_in_bytes: 12,
+                    data: VertexLayerData::decode(
+                        XacAttribute::AttribPositions as u32,
+                        12,
+                        vec![0; 36],
+                        Endian::Little,
+                    ),
+                    ..Default::default()
+                }],
+                sub_meshes: vec![XACSubMesh {
+                    num_indices: 3,
+                    num_verts: 3,
+                    indices: vec![0, 1, 2],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+        };
+        let actor = XACFile {
+            chunk_data: vec![mesh(0), mesh(1)],
+            ..Default::default()
+        };
 
-                    let u = f32::from_le_bytes(
-                        uvs_data.unwrap()[offset..offset + 4].try_into().unwrap(),
-                    );
-                    let v = f32::from_le_bytes(
-                        uvs_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
+        let flags = |collision| {
+            actor
+                .export_all_meshes_into_struct_with(
+                    &ExportOptions::default().with_collision(collision),
+                )
+                .unwrap()
+                .iter()
+                .map(Mesh::is_collision)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(flags(CollisionMeshes::Include), [false, true]);
+        assert_eq!(flags(CollisionMeshes::Exclude), [false]);
+        assert_eq!(flags(CollisionMeshes::Only), [true]);
+
+        let dir = std::env::temp_dir().join(format!("toslib-collision-{}", std::process::id()));
+        let prefix = dir.join("actor");
+        let prefix = prefix.to_str().unwrap();
+        actor
+            .export_all_meshes_with_collision(prefix, CollisionMeshes::Only)
+            .unwrap();
+        assert!(!dir.join("actor_mesh_0_submesh_0.obj").exists());
+        assert!(dir.join("actor_mesh_1_submesh_0.obj").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-                    submesh_data.uvcoords.push([u, v]);
-                }
-                submesh_data.uvcoord_count = submesh_data.uvcoords.len();
-            }
+    #[test]
+    fn unknown_chunks_survive_a_round_trip() {
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        for (chunk_id, version, body) in [
+            (9999u32, 2u32, &[1u8, 2, 3, 4, 5][..]),
+            (XacChunk::XacChunkNode as u32, 9, &[0xAB; 4][..]),
+        ] {
+            bytes.extend_from_slice(&chunk_id.to_le_bytes());
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&version.to_le_bytes());
+            bytes.extend_from_slice(body);
+        }
 
-            // Write Colors32 if data exists
-            if let Some(colors32_layer) = colors32_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 4) as usize; // 4 bytes for color32
+        let xac = XACFile::load_from_slice(&bytes).unwrap();
+        assert_eq!(xac.warnings().len(), 2);
+        assert_eq!(
+            xac.unknown_chunks().collect::<Vec<_>>(),
+            [
+                (9999, 2, &[1, 2, 3, 4, 5][..]),
+                (XacChunk::XacChunkNode as u32, 9, &[0xAB; 4][..])
+            ]
+        );
+        assert_eq!(xac.to_bytes().unwrap(), bytes);
+        let json = XACFile::from_json(&xac.to_json().unwrap()).unwrap();
+        assert_eq!(json.unknown_chunks().count(), 2);
+    }
 
-                    if offset + 4 > colors32_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Color32 data out of bounds",
-                        ));
-                    }
+    #[test]
+    fn under_read_chunk_is_reported() {
+        // A limit chunk is 85 bytes but this one declares 4 bytes of padding more.
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&(XacChunk::XacLimit as u32).to_le_bytes());
+        bytes.extend_from_slice(&89u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 89]);
+
+        let xac = XACFile::load_from_slice(&bytes).unwrap();
+        assert_eq!(
+            xac.warnings(),
+            [ParseWarning::SizeMismatch {
+                offset: 8,
+                chunk_id: XacChunk::XacLimit as u32,
+                version: 1,
+                declared: 89,
+                consumed: 85,
+            }]
+        );
+
+        bytes.extend_from_slice(&9999u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        let (_, report) =
+            XACFile::load_from_slice_with_report(&bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(report.under_reads().count(), 1);
+        assert_eq!(
+            report
+                .chunks
+                .iter()
+                .map(|chunk| (chunk.offset, chunk.outcome))
+                .collect::<Vec<_>>(),
+            [(8, ChunkOutcome::Parsed), (109, ChunkOutcome::Raw)]
+        );
+    }
 
-                    let r = u32::from_le_bytes(
-                        colors32_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
+    #[test]
+    fn truncated_actor_keeps_complete_chunks() {
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&(XacChunk::XacLimit as u32).to_le_bytes());
+        bytes.extend_from_slice(&85u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 85]);
+        // A second limit chunk cut off halfway through its body
+        bytes.extend_from_slice(&(XacChunk::XacLimit as u32).to_le_bytes());
+        bytes.extend_from_slice(&85u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 40]);
+
+        let parsed = XACFile::load_partial_from_slice(&bytes, &ParseOptions::lenient()).unwrap();
+        assert!(!parsed.is_complete());
+        assert_eq!(parsed.value().chunk_data.len(), 1);
+        assert!(XACFile::load_partial_from_slice(&bytes, &ParseOptions::strict()).is_err());
+    }
 
-                    submesh_data.colors32.push(r);
-                }
-                submesh_data.color32_count = submesh_data.colors32.len();
+    #[test]
+    fn duplicate_singleton_chunks_follow_the_conflict_policy() {
+        // Two attachment node chunks listing one and two nodes respectively
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        for nodes in [&[7u16][..], &[8, 9]] {
+            bytes.extend_from_slice(&(XacChunk::XacChunkAttachmentnodes as u32).to_le_bytes());
+            bytes.extend_from_slice(&(4 + 2 * nodes.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+            for node in nodes {
+                bytes.extend_from_slice(&node.to_le_bytes());
             }
+        }
+        let attachments = |xac: &XACFile| match &xac.chunk_data[..] {
+            [XacChunkData::XACAttachmentNodes(data)] => data.attachment_indices.clone(),
+            _ => panic!("expected a single attachment chunk"),
+        };
 
-            // Write Original Vertex Numbers if data exists
-            if let Some(original_vertex_numbers_layer) = original_vertex_numbers_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 4) as usize; // 4 bytes for vertex number
-
-                    if offset + 4 > original_vertex_numbers_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Original vertex numbers data out of bounds",
-                        ));
-                    }
-
-                    let vertex_number = u32::from_le_bytes(
-                        original_vertex_numbers_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.original_vertex_numbers.push(vertex_number);
-                }
-                submesh_data.original_vertex_numbers_count =
-                    submesh_data.original_vertex_numbers.len();
-            }
+        let first = XACFile::load_from_slice(&bytes).unwrap();
+        assert_eq!(attachments(&first), [7]);
+        assert!(matches!(
+            first.warnings(),
+            [ParseWarning::DuplicateChunk { .. }]
+        ));
+
+        let options = ParseOptions::default();
+        let last = XACFile::load_from_slice_with_options(
+            &bytes,
+            &options.with_conflicts(ConflictPolicy::LastWins),
+        )
+        .unwrap();
+        assert_eq!(attachments(&last), [8, 9]);
+        assert!(
+            XACFile::load_from_slice_with_options(
+                &bytes,
+                &options.with_conflicts(ConflictPolicy::Error)
+            )
+            .is_err()
+        );
+    }
 
-            // Write Color128 if data exists
-            if let Some(colors128_layer) = colors128_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 16) as usize; // 16 bytes for Color128 (4 components)
+    #[test]
+    fn parallel_load_matches_sequential_load() {
+        let mut bytes = b"XAC \x01\x00\x00\x00".to_vec();
+        for (chunk, body) in [
+            (XacChunk::XacLimit, vec![1u8; 85]),
+            (XacChunk::XacChunkAttachmentnodes, vec![1, 0, 0, 0, 7, 0]),
+            (XacChunk::XacLimit, vec![2u8; 89]),
+            (XacChunk::XacChunkAttachmentnodes, vec![0; 4]),
+        ] {
+            bytes.extend_from_slice(&(chunk as u32).to_le_bytes());
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(&body);
+        }
+        bytes.extend_from_slice(&[0xAB; 5]);
+
+        let sequential = XACFile::load_from_slice(&bytes).unwrap();
+        let options = ParseOptions::default().with_parallel(true);
+        let parallel = XACFile::load_from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(
+            serde_json::to_string(&sequential).unwrap(),
+            serde_json::to_string(&parallel).unwrap()
+        );
+        assert_eq!(sequential.warnings(), parallel.warnings());
+        assert_eq!(parallel.warnings().len(), 3);
+    }
 
-                    if offset + 16 > colors128_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Color128 data out of bounds",
-                        ));
-                    }
+    #[test]
+    fn big_endian_actors_decode() {
+        // Header with endian type 1, then a limit and a mesh chunk in big endian
+        let mut bytes = b"XAC \x01\x00\x01\x00".to_vec();
+        let words = |values: &[u32]| values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let floats = |values: &[f32]| values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let mut chunk = |chunk_id: u32, version: u32, body: Vec<u8>| {
+            bytes.extend_from_slice(&chunk_id.to_be_bytes());
+            bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&version.to_be_bytes());
+            bytes.extend_from_slice(&body);
+        };
 
-                    let r = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let g = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let b = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let a = f32::from_le_bytes(
-                        colors128_data.unwrap()[offset + 12..offset + 16]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.colors128.push([r, g, b, a]);
-                }
-                submesh_data.color128_count = submesh_data.colors128.len();
-            }
+        let mut limit: Vec<u8> = floats(&[-1.0, -2.0, -3.0, 1.0, 2.0, 3.0]);
+        limit.extend(vec![0; 48]);
+        limit.extend([1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        limit.extend(words(&[7]));
+        chunk(XacChunk::XacLimit as u32, 1, limit);
+
+        // One vertex and an empty submesh
+        let mut mesh: Vec<u8> = words(&[0, 1, 1, 0, 1, 1]);
+        mesh.extend([0; 4]);
+        mesh.extend(words(&[XacAttribute::AttribPositions as u32, 12]));
+        mesh.extend([0; 4]);
+        mesh.extend(floats(&[1.5, -2.0, 3.25]));
+        mesh.extend(words(&[0, 1, 0, 0]));
+        chunk(XacChunk::XacChunkMesh as u32, 1, mesh);
+
+        let xac = XACFile::load_from_slice(&bytes).unwrap();
+        assert!(xac.warnings().is_empty());
+        let limits = xac.limits();
+        assert_eq!(limits[0].node_index, 7);
+        assert_eq!(limits[0].translation_min, [-1.0, -2.0, -3.0]);
+        assert_eq!(limits[0].translation_max, [1.0, 2.0, 3.0]);
+        assert!(limits[0].enabled[0] && limits[0].enabled[8] && !limits[0].enabled[1]);
+
+        let options = ExportOptions::default().with_handedness(Handedness::Left);
+        let meshes = xac.export_all_meshes_into_struct_with(&options).unwrap();
+        assert_eq!(meshes[0].submeshes[0].positions, [[1.5, -2.0, 3.25]]);
+    }
 
-            // Write Bitangents if data exists
-            if let Some(bitangents_layer) = bitangents_layer {
-                for v in 0..submesh.num_verts {
-                    let actual_index = vertex_offset + v;
-                    let offset = (actual_index * 12) as usize; // 12 bytes for bitangent (3 components)
+    #[test]
+    fn written_actor_reads_back_unchanged() {
+        let mut xac = XACFile {
+            header: XacHeader {
+                fourcc: XAC_FOURCC,
+                hi_version: 1,
+                ..Default::default()
+            },
+            chunk_data: vec![
+                XacChunkData::XACNodes(XACNodes {
+                    num_nodes: 1,
+                    num_root_nodes: 1,
+                    xac_node: vec![XacNode4 {
+                        parent_index: u32::MAX,
+                        node_name: "Bip01".into(),
+                        ..Default::default()
+                    }],
+                }),
+                XacChunkData::XacStandardMaterial2(XacStandardMaterial2 {
+                    num_layers: 1,
+                    material_name: "char/skin.dds".into(),
+                    standard_material_layer2: vec![XACStandardMaterialLayer2 {
+                        map_type: 2,
+                        texture_name: "char/skin.dds".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XACMesh2(XACMesh2 {
+                    num_org_verts: 1,
+                    total_verts: 1,
+                    total_indices: 3,
+                    num_sub_meshes: 1,
+                    num_layers: 1,
+                    vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                        layer_type_id: XacAttribute::AttribPositions as u32,
+                        attrib_size_in_bytes: 12,
+                        data: VertexLayerData::Positions(vec![[7.0, 8.0, 9.0]]),
+                        ..Default::default()
+                    }],
+                    sub_meshes: vec![XACSubMesh {
+                        num_indices: 3,
+                        num_verts: 1,
+                        indices: vec![0; 3],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XacSkinningInfo4(XacSkinningInfo4 {
+                    num_total_influences: 1,
+                    skinning_influence: vec![XacSkinInfluence {
+                        weight: 1.0,
+                        node_number: 0,
+                    }],
+                    skinning_info_table_entry: vec![XacSkinningInfoTableEntry {
+                        start_index: 0,
+                        num_elements: 1,
+                    }],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-                    if offset + 12 > bitangents_data.unwrap().len() {
-                        return Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            "Bitangent data out of bounds",
-                        ));
-                    }
+        let bytes = xac.to_bytes().unwrap();
+        let read = XACFile::load_from_slice(&bytes).unwrap();
+        assert!(read.warnings().is_empty());
+        assert_eq!(
+            serde_json::to_value(&read.chunk_data).unwrap(),
+            serde_json::to_value(&xac.chunk_data).unwrap()
+        );
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+
+        assert_eq!(xac.rename_node("Bip01", "root"), 1);
+        assert_eq!(xac.rename_texture("char/skin.dds", "char/skin_red.dds"), 2);
+        let read = XACFile::load_from_slice(&xac.to_bytes().unwrap()).unwrap();
+        assert!(read.warnings().is_empty());
+        assert_eq!(read.get_texture_names(0), ["char/skin_red.dds"]);
+        let XacChunkData::XACNodes(nodes) = &read.chunk_data[0] else {
+            panic!("expected the nodes chunk");
+        };
+        assert_eq!(&*nodes.xac_node[0].node_name, "root");
 
-                    let bx = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset..offset + 4]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let by = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset + 4..offset + 8]
-                            .try_into()
-                            .unwrap(),
-                    );
-                    let bz = f32::from_le_bytes(
-                        bitangents_data.unwrap()[offset + 8..offset + 12]
-                            .try_into()
-                            .unwrap(),
-                    );
-
-                    submesh_data.bitangents.push([bx, by, bz]);
-                }
-                submesh_data.bitangent_count = submesh_data.bitangents.len();
-            }
+        xac.chunk_data
+            .push(XacChunkData::XacSkinningInfo(XacSkinningInfo::default()));
+        let err = xac.to_bytes().unwrap_err();
+        assert!(err.to_string().contains("cannot be written"), "{err}");
+    }
 
-            // Add submesh to the list if it has valid data
-            if !submesh_data.positions.is_empty()
-                || !submesh_data.normals.is_empty()
-                || !submesh_data.tangents.is_empty()
-                || !submesh_data.uvcoords.is_empty()
-                || !submesh_data.colors32.is_empty()
-                || !submesh_data.original_vertex_numbers.is_empty()
-                || !submesh_data.colors128.is_empty()
-                || !submesh_data.bitangents.is_empty()
-            {
-                submeshes.push(submesh_data);
+    #[test]
+    fn gltf_export_carries_skeleton_and_weights() {
+        let floats = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let layer = |attribute: XacAttribute, size: u32, mesh_data: Vec<u8>| {
+            let layer_type_id = attribute as u32;
+            XACVertexAttributeLayer {
+                layer_type_id,
+                attrib_size_in_bytes: size,
+                data: VertexLayerData::decode(layer_type_id, size, mesh_data, Endian::Little),
+                ..Default::default()
             }
+        };
+        let node = |name: &str, parent_index: u32, x: f32| XacNode4 {
+            local_quat: FileQuaternion {
+                axis_w: 1.0,
+                ..Default::default()
+            },
+            local_pos: FileVector3 {
+                axis_x: x,
+                ..Default::default()
+            },
+            local_scale: FileVector3 {
+                axis_x: 1.0,
+                axis_y: 1.0,
+                axis_z: 1.0,
+            },
+            parent_index,
+            node_name: name.into(),
+            ..Default::default()
+        };
+        let material = |name: &str| {
+            XacChunkData::XacStandardMaterial(XacStandardMaterial {
+                opacity: 1.0,
+                double_sided: 1,
+                material_name: name.into(),
+                ..Default::default()
+            })
+        };
+        let influence = |node_number, weight| XacSkinInfluence {
+            weight,
+            node_number,
+        };
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XACNodes(XACNodes {
+                    num_nodes: 2,
+                    num_root_nodes: 1,
+                    xac_node: vec![node("root", u32::MAX, 0.0), node("arm", 0, 100.0)],
+                }),
+                material("default"),
+                material("char/skin.dds"),
+                XacChunkData::XACMesh(XACMesh {
+                    node_index: 0,
+                    vertex_attribute_layer: vec![
+                        layer(
+                            XacAttribute::AttribPositions,
+                            12,
+                            floats(&[0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 100.0, 0.0]),
+                        ),
+                        layer(XacAttribute::AttribUvcoords, 8, floats(&[0.0; 6])),
+                        layer(
+                            XacAttribute::AttribOrgvtxnumbers,
+                            4,
+                            [0u32, 1, 1].iter().flat_map(|v| v.to_le_bytes()).collect(),
+                        ),
+                    ],
+                    sub_meshes: vec![XACSubMesh {
+                        num_verts: 3,
+                        material_index: 1,
+                        indices: vec![0, 1, 2],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                XacChunkData::XacSkinningInfo3(XacSkinningInfo3 {
+                    skinning_influence: vec![
+                        influence(0, 1.0),
+                        influence(1, 3.0),
+                        influence(0, 1.0),
+                    ],
+                    skinning_info_table_entry: vec![
+                        XacSkinningInfoTableEntry {
+                            start_index: 0,
+                            num_elements: 1,
+                        },
+                        XacSkinningInfoTableEntry {
+                            start_index: 1,
+                            num_elements: 2,
+                        },
+                    ],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-            vertex_offset += submesh.num_verts;
-        }
-
-        // Return the Mesh struct with the submeshes and their count
-        Ok(Mesh {
-            submesh_count: submeshes.len(),
-            submeshes,
-        })
-    }
-}
-
-// Rust function to extract xac data
-pub fn extract_xac_data(ipf_path: &str, xac_filename: &str) -> io::Result<Vec<Mesh>> {
-    // Check if the IPF file exists
-    if !Path::new(ipf_path).exists() {
-        println!("Error: IPF file '{}' not found!", ipf_path);
+        let glb = xac.to_glb(&GltfOptions::default()).unwrap();
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let json: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let bin = &glb[20 + json_length + 8..];
+
+        assert_eq!(json["nodes"][1]["name"], "arm");
+        assert_eq!(
+            json["nodes"][1]["translation"],
+            serde_json::json!([-1.0, 0.0, 0.0])
+        );
+        assert_eq!(json["nodes"][0]["children"], serde_json::json!([1]));
+        assert_eq!(json["nodes"][2]["skin"], 0);
+        assert_eq!(json["scenes"][0]["nodes"], serde_json::json!([0, 2]));
+        assert_eq!(json["skins"][0]["joints"], serde_json::json!([0, 1]));
+        assert_eq!(json["materials"][0]["name"], "skin");
+        assert_eq!(json["materials"][0]["doubleSided"], true);
+        assert_eq!(json["images"][0]["uri"], "skin.png");
+
+        let attributes = &json["meshes"][0]["primitives"][0]["attributes"];
+        assert!(attributes["TEXCOORD_0"].is_u64());
+        let accessor = |index: &serde_json::Value| {
+            let accessor = &json["accessors"][index.as_u64().unwrap() as usize];
+            let view = &json["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+            let start = view["byteOffset"].as_u64().unwrap() as usize;
+            &bin[start..start + view["byteLength"].as_u64().unwrap() as usize]
+        };
+        let weights: Vec<f32> = accessor(&attributes["WEIGHTS_0"])
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(&weights[4..8], &[0.75, 0.25, 0.0, 0.0]);
+        assert_eq!(&accessor(&attributes["JOINTS_0"])[8..12], &[1, 0, 0, 0]);
+        let indices = accessor(&json["meshes"][0]["primitives"][0]["indices"]);
+        assert_eq!(indices[0], 2);
+
+        let still = xac
+            .to_glb(&GltfOptions::default().with_skinning(false))
+            .unwrap();
+        let json_length = u32::from_le_bytes(still[12..16].try_into().unwrap()) as usize;
+        let json: serde_json::Value = serde_json::from_slice(&still[20..20 + json_length]).unwrap();
+        assert!(json.get("skins").is_none());
+        assert_eq!(json["nodes"][0]["children"], serde_json::json!([1, 2]));
+
+        let meshes = xac.export_all_meshes_into_struct().unwrap();
+        let submesh = &meshes[0].submeshes[0];
+        assert_eq!(
+            submesh.bone_indices,
+            vec![[0, 0, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0]]
+        );
+        assert_eq!(submesh.bone_weights[1], [0.75, 0.25, 0.0, 0.0]);
     }
 
-    // Open the IPF file
-    let file = File::open(ipf_path)?;
-    let mut reader = BinaryReader::new(BufReader::new(file));
-
-    // Load the IPF file
-    let ipf = IPFFile::load_from_reader(&mut reader)?;
-
-    let mut result_mesh: Vec<Mesh> = Vec::new();
-    for file_entry in ipf.file_table() {
-        let filename = file_entry.directory_name();
-
-        // Extract only the filename part (without the directory)
-        let file_name_only = Path::new(&filename)
-            .file_name()
-            .and_then(|f| f.to_str())
-            .unwrap_or("");
-
-        // Check if the extracted filename matches the target
-        if file_name_only == xac_filename {
-            let result = file_entry.extract(&mut reader)?;
-            let mut xac_data = XACFile::load_from_bytes(result)?;
+    #[test]
+    fn morph_targets_become_blend_shapes() {
+        let positions: Vec<u8> = (0..18).flat_map(|i| (i as f32).to_le_bytes()).collect();
+        let submesh = |material_index| XACSubMesh {
+            num_verts: 3,
+            material_index,
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        };
+        let xac = XACFile {
+            chunk_data: vec![
+                XacChunkData::XACMesh(XACMesh {
+                    node_index: 0,
+                    vertex_attribute_layer: vec![XACVertexAttributeLayer {
+                        layer_type_id: XacAttribute::AttribPositions as u32,
+                        attrib_size_in_bytes: 12,
+                        data: VertexLayerData::decode(
+                            XacAttribute::AttribPositions as u32,
+                            12,
+                            positions,
+                            Endian::Little,
+                        ),
+                        ..Default::default()
+                    }],
+                    sub_meshes: vec![submesh(0), submesh(1)],
+                    ..Default::default()
+                }),
+                XacChunkData::XACPMorphTarget(XACPMorphTarget {
+                    range_max: 1.0,
+                    name: "smile".into(),
+                    morph_target_mesh_deltas: vec![XACPMorphTargetMeshDeltas {
+                        min_value: -1.0,
+                        max_value: 1.0,
+                        num_vertices: 1,
+                        delta_position_values: vec![File16BitVector3 {
+                            axis_x: u16::MAX,
+                            axis_y: 0,
+                            axis_z: u16::MAX,
+                        }],
+                        delta_normal_values: vec![File8BitVector3 {
+                            axis_x: 0,
+                            axis_y: u8::MAX,
+                            axis_z: 0,
+                        }],
+                        delta_tangent_values: vec![File8BitVector3::default()],
+                        vertex_numbers: vec![4],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
 
-            result_mesh = xac_data.export_all_meshes_into_struct()?;
-            break; // Stop after extracting the target file
+        let meshes = xac.export_all_meshes_into_struct().unwrap();
+        let options = ExportOptions::default();
+        let target = &meshes[0].morph_targets[0];
+        assert_eq!(target.name, "smile");
+        assert_eq!(target.position_deltas[0], vec![[0.0; 3]; 3]);
+        assert_eq!(
+            target.position_deltas[1][1],
+            options.position([1.0, -1.0, 1.0])
+        );
+        assert_eq!(
+            target.normal_deltas[1][1],
+            options.direction([-2.0, 2.0, -2.0])
+        );
+        assert_eq!(target.position_deltas[1][0], [0.0; 3]);
+
+        let glb = xac.to_glb(&GltfOptions::default()).unwrap();
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let json: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let mesh = &json["meshes"][0];
+        assert_eq!(mesh["weights"], serde_json::json!([0.0]));
+        assert_eq!(mesh["extras"]["targetNames"], serde_json::json!(["smile"]));
+        for primitive in mesh["primitives"].as_array().unwrap() {
+            let targets = primitive["targets"].as_array().unwrap();
+            assert_eq!(targets.len(), 1);
+            assert!(targets[0]["POSITION"].is_u64());
+            assert!(targets[0].get("NORMAL").is_none());
         }
     }
-
-    Ok(result_mesh)
 }